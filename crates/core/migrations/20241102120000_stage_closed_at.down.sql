@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE stages DROP COLUMN IF EXISTS closed_at;
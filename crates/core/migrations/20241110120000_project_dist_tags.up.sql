@@ -0,0 +1,17 @@
+-- Add up migration script here
+create TABLE IF NOT EXISTS project_dist_tags
+(
+    id         serial
+        constraint project_dist_tags_pk
+            primary key,
+    project_id UUID                                               not null
+        constraint fk_project
+            references projects
+            on delete cascade,
+    tag        TEXT                                               not null,
+    version    TEXT                                               not null,
+    updated_at TIMESTAMP WITH TIME ZONE default CURRENT_TIMESTAMP not null,
+    created_at TIMESTAMP WITH TIME ZONE default CURRENT_TIMESTAMP not null,
+    constraint project_dist_tags_project_tag_unique
+        unique (project_id, tag)
+);
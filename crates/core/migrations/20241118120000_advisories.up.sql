@@ -0,0 +1,24 @@
+-- Add up migration script here
+create TABLE IF NOT EXISTS advisories
+(
+    id                  serial
+        constraint advisories_pk
+            primary key,
+    -- The id of the advisory in its source. Such as GHSA-xxxx-xxxx-xxxx
+    source_id           TEXT                                               not null,
+    ecosystem           TEXT                     default 'npm'             not null,
+    package_name        TEXT                                               not null,
+    title               TEXT                                               not null,
+    overview            TEXT,
+    url                 TEXT,
+    severity            TEXT                     default 'moderate'        not null,
+    vulnerable_versions TEXT                                               not null,
+    patched_versions    TEXT,
+    cwe                 TEXT[]                   default array []::text[]  not null,
+    updated_at          TIMESTAMP WITH TIME ZONE default CURRENT_TIMESTAMP not null,
+    created_at          TIMESTAMP WITH TIME ZONE default CURRENT_TIMESTAMP not null,
+    constraint advisories_source_package_unique
+        unique (ecosystem, source_id, package_name)
+);
+create INDEX IF NOT EXISTS advisories_package_name_index
+    on advisories (ecosystem, package_name);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE stages ADD COLUMN IF NOT EXISTS promoting_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+
+use crate::database::prelude::*;
+/// Severity of an advisory. Uses the same levels as npm
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Display,
+    EnumString,
+    Type,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum AdvisorySeverity {
+    Info,
+    Low,
+    #[default]
+    Moderate,
+    High,
+    Critical,
+}
+/// A known vulnerability in a package.
+///
+/// Advisories are shared by every repository of the ecosystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
+pub struct DBAdvisory {
+    pub id: i32,
+    /// The id of the advisory in its source. Such as `GHSA-xxxx-xxxx-xxxx`
+    pub source_id: String,
+    /// Such as `npm`
+    pub ecosystem: String,
+    pub package_name: String,
+    pub title: String,
+    pub overview: Option<String>,
+    pub url: Option<String>,
+    pub severity: AdvisorySeverity,
+    /// The range of affected versions. Using the ecosystems range syntax
+    pub vulnerable_versions: String,
+    pub patched_versions: Option<String>,
+    /// Such as `CWE-79`
+    pub cwe: Vec<String>,
+    pub updated_at: DateTime<FixedOffset>,
+    pub created_at: DateTime<FixedOffset>,
+}
+impl DBAdvisory {
+    #[instrument(skip(database))]
+    pub async fn get_all(
+        ecosystem: Option<&str>,
+        database: &PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let advisories = if let Some(ecosystem) = ecosystem {
+            sqlx::query_as::<_, Self>(
+                "SELECT * FROM advisories WHERE ecosystem = $1 ORDER BY package_name, id",
+            )
+            .bind(ecosystem)
+            .fetch_all(database)
+            .await?
+        } else {
+            sqlx::query_as::<_, Self>("SELECT * FROM advisories ORDER BY package_name, id")
+                .fetch_all(database)
+                .await?
+        };
+        Ok(advisories)
+    }
+    #[instrument(skip(database))]
+    pub async fn get_by_id(id: i32, database: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        let advisory = sqlx::query_as::<_, Self>("SELECT * FROM advisories WHERE id = $1")
+            .bind(id)
+            .fetch_optional(database)
+            .await?;
+        Ok(advisory)
+    }
+    /// All advisories for the packages. Package names are case sensitive
+    #[instrument(skip(database, packages))]
+    pub async fn get_for_packages(
+        ecosystem: &str,
+        packages: &[String],
+        database: &PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        if packages.is_empty() {
+            return Ok(vec![]);
+        }
+        let advisories = sqlx::query_as::<_, Self>(
+            "SELECT * FROM advisories WHERE ecosystem = $1 AND package_name = ANY($2) ORDER BY id",
+        )
+        .bind(ecosystem)
+        .bind(packages)
+        .fetch_all(database)
+        .await?;
+        Ok(advisories)
+    }
+    /// Returns true if the advisory existed
+    #[instrument(skip(database))]
+    pub async fn delete_by_id(id: i32, database: &PgPool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM advisories WHERE id = $1")
+            .bind(id)
+            .execute(database)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct NewAdvisory {
+    pub source_id: String,
+    #[serde(default = "default_ecosystem")]
+    pub ecosystem: String,
+    pub package_name: String,
+    pub title: String,
+    #[serde(default)]
+    pub overview: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub severity: AdvisorySeverity,
+    pub vulnerable_versions: String,
+    #[serde(default)]
+    pub patched_versions: Option<String>,
+    #[serde(default)]
+    pub cwe: Vec<String>,
+}
+fn default_ecosystem() -> String {
+    "npm".to_owned()
+}
+impl NewAdvisory {
+    /// Inserts the advisory. If the source already has an advisory for the package it is replaced
+    #[instrument(skip(database))]
+    pub async fn upsert(self, database: &PgPool) -> Result<DBAdvisory, sqlx::Error> {
+        let Self {
+            source_id,
+            ecosystem,
+            package_name,
+            title,
+            overview,
+            url,
+            severity,
+            vulnerable_versions,
+            patched_versions,
+            cwe,
+        } = self;
+        let advisory = sqlx::query_as::<_, DBAdvisory>(
+            r#"
+            INSERT INTO advisories (source_id, ecosystem, package_name, title, overview, url, severity, vulnerable_versions, patched_versions, cwe)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (ecosystem, source_id, package_name) DO UPDATE SET
+                title = EXCLUDED.title,
+                overview = EXCLUDED.overview,
+                url = EXCLUDED.url,
+                severity = EXCLUDED.severity,
+                vulnerable_versions = EXCLUDED.vulnerable_versions,
+                patched_versions = EXCLUDED.patched_versions,
+                cwe = EXCLUDED.cwe,
+                updated_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(source_id)
+        .bind(ecosystem)
+        .bind(package_name)
+        .bind(title)
+        .bind(overview)
+        .bind(url)
+        .bind(severity)
+        .bind(vulnerable_versions)
+        .bind(patched_versions)
+        .bind(cwe)
+        .fetch_one(database)
+        .await?;
+        Ok(advisory)
+    }
+}
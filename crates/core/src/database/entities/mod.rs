@@ -1,3 +1,4 @@
+pub mod advisories;
 pub mod project;
 pub mod repository;
 pub mod stages;
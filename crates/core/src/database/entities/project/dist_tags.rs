@@ -0,0 +1,100 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::database::prelude::*;
+/// A named pointer to a version of a project. Such as `latest` or `next`
+///
+/// Used by NPM. Each tag is unique per project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
+pub struct DBProjectDistTag {
+    pub id: i32,
+    pub project_id: Uuid,
+    pub tag: String,
+    pub version: String,
+    pub updated_at: DateTime<FixedOffset>,
+    pub created_at: DateTime<FixedOffset>,
+}
+impl DBProjectDistTag {
+    #[instrument(skip(database))]
+    pub async fn get_all_for_project(
+        project_id: Uuid,
+        database: &PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let tags = sqlx::query_as::<_, Self>(
+            "SELECT * FROM project_dist_tags WHERE project_id = $1 ORDER BY tag",
+        )
+        .bind(project_id)
+        .fetch_all(database)
+        .await?;
+        Ok(tags)
+    }
+    #[instrument(skip(database))]
+    pub async fn get_tag(
+        project_id: Uuid,
+        tag: &str,
+        database: &PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let tag = sqlx::query_as::<_, Self>(
+            "SELECT * FROM project_dist_tags WHERE project_id = $1 AND tag = $2",
+        )
+        .bind(project_id)
+        .bind(tag)
+        .fetch_optional(database)
+        .await?;
+        Ok(tag)
+    }
+    /// Points the tag at the version. Creating the tag if it does not exist
+    #[instrument(skip(database))]
+    pub async fn set_tag(
+        project_id: Uuid,
+        tag: &str,
+        version: &str,
+        database: &PgPool,
+    ) -> Result<Self, sqlx::Error> {
+        let tag = sqlx::query_as::<_, Self>(
+            r#"
+            INSERT INTO project_dist_tags (project_id, tag, version)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (project_id, tag) DO UPDATE SET version = $3, updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+        )
+        .bind(project_id)
+        .bind(tag)
+        .bind(version)
+        .fetch_one(database)
+        .await?;
+        Ok(tag)
+    }
+    /// Returns true if the tag existed
+    #[instrument(skip(database))]
+    pub async fn delete_tag(
+        project_id: Uuid,
+        tag: &str,
+        database: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM project_dist_tags WHERE project_id = $1 AND tag = $2")
+                .bind(project_id)
+                .bind(tag)
+                .execute(database)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+    /// Removes every tag that points at the version
+    #[instrument(skip(database))]
+    pub async fn delete_tags_for_version(
+        project_id: Uuid,
+        version: &str,
+        database: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM project_dist_tags WHERE project_id = $1 AND version = $2")
+            .bind(project_id)
+            .bind(version)
+            .execute(database)
+            .await?;
+        Ok(())
+    }
+}
@@ -8,7 +8,9 @@ mod new;
 pub mod utils;
 use crate::database::prelude::*;
 pub use new::*;
+pub mod dist_tags;
 pub mod info;
+pub mod search;
 pub mod update;
 pub mod versions;
 /// Implemented on different types of Project query result. Such as ProjectLookupResult
@@ -51,6 +53,23 @@ pub trait ProjectDBType: for<'r> FromRow<'r, PgRow> + Unpin + Send + Sync {
         .await?;
         Ok(project)
     }
+    /// Same as [find_by_project_key](ProjectDBType::find_by_project_key) but the key is case sensitive
+    async fn find_by_exact_project_key(
+        project_key: &str,
+        repository: Uuid,
+        database: &PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let columns = Self::format_columns(None);
+        let project = sqlx::query_as::<_, Self>(&format!(
+            "SELECT {} FROM projects WHERE repository_id = $1 AND project_key = $2",
+            columns
+        ))
+        .bind(repository)
+        .bind(project_key)
+        .fetch_optional(database)
+        .await?;
+        Ok(project)
+    }
     #[instrument(skip(database))]
     async fn find_by_project_directory(
         directory: &str,
@@ -148,6 +167,61 @@ impl ProjectDBType for DBProject {
         vec!["*"]
     }
 }
+impl DBProject {
+    /// Every project in the repository. Ordered by the project key
+    #[instrument(skip(database))]
+    pub async fn get_all_for_repository(
+        repository: Uuid,
+        database: &PgPool,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let projects = sqlx::query_as::<_, Self>(
+            "SELECT * FROM projects WHERE repository_id = $1 ORDER BY project_key",
+        )
+        .bind(repository)
+        .fetch_all(database)
+        .await?;
+        Ok(projects)
+    }
+    /// Deletes the project. Versions, members, and dist-tags are deleted with it
+    #[instrument(skip(database))]
+    pub async fn delete_by_id(id: Uuid, database: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM projects WHERE id = $1")
+            .bind(id)
+            .execute(database)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(database))]
+    pub async fn update_latest_release(
+        id: Uuid,
+        latest_release: Option<&str>,
+        database: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE projects SET latest_release = $1, updated_at = NOW() WHERE id = $2")
+            .bind(latest_release)
+            .bind(id)
+            .execute(database)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(database))]
+    pub async fn update_description_and_tags(
+        id: Uuid,
+        description: Option<&str>,
+        tags: &[String],
+        database: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE projects SET description = $1, tags = $2, updated_at = NOW() WHERE id = $3",
+        )
+        .bind(description)
+        .bind(tags)
+        .bind(id)
+        .execute(database)
+        .await?;
+        Ok(())
+    }
+}
 /// On the first push. The pusher will be added as a project member with write and manage permissions
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
 pub struct DBProjectMember {
@@ -158,7 +232,73 @@ pub struct DBProjectMember {
     pub can_manage: bool,
     pub added: chrono::DateTime<chrono::FixedOffset>,
 }
-impl DBProjectMember {}
+impl DBProjectMember {
+    pub async fn get_member(
+        project_id: Uuid,
+        user_id: i32,
+        database: &PgPool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let member = sqlx::query_as::<_, Self>(
+            "SELECT * FROM project_members WHERE project_id = $1 AND user_id = $2",
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_optional(database)
+        .await?;
+        Ok(member)
+    }
+    /// Checks if the user is a member of the project that can push new versions
+    pub async fn can_user_write(
+        project_id: Uuid,
+        user_id: i32,
+        database: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let member = Self::get_member(project_id, user_id, database).await?;
+        Ok(member.is_some_and(|member| member.can_write))
+    }
+    /// Every member of the project with their username
+    #[instrument(skip(database))]
+    pub async fn get_members_with_users(
+        project_id: Uuid,
+        database: &PgPool,
+    ) -> Result<Vec<ProjectMemberUser>, sqlx::Error> {
+        let members = sqlx::query_as::<_, ProjectMemberUser>(
+            r#"SELECT project_members.user_id, users.username, users.name,
+                COALESCE(project_members.can_write, false) as can_write,
+                COALESCE(project_members.can_manage, false) as can_manage
+            FROM project_members INNER JOIN users ON users.id = project_members.user_id
+            WHERE project_members.project_id = $1 ORDER BY project_members.id"#,
+        )
+        .bind(project_id)
+        .fetch_all(database)
+        .await?;
+        Ok(members)
+    }
+    /// Returns true if the user was a member of the project
+    #[instrument(skip(database))]
+    pub async fn delete_member(
+        project_id: Uuid,
+        user_id: i32,
+        database: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
+                .bind(project_id)
+                .bind(user_id)
+                .execute(database)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+/// A project member joined with the user. Such as the owners of a crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
+pub struct ProjectMemberUser {
+    pub user_id: i32,
+    pub username: String,
+    pub name: String,
+    pub can_write: bool,
+    pub can_manage: bool,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
 pub struct ProjectIds {
@@ -0,0 +1,100 @@
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::DBProject;
+/// Filters for searching the projects of a repository
+///
+/// Every filter must match for a project to be returned
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProjectSearchQuery {
+    /// Matched against the project key, description, and tags. Case insensitive
+    pub terms: Vec<String>,
+    /// The project must have every tag. Case insensitive
+    pub tags: Vec<String>,
+    /// The scope of the project. Case insensitive
+    pub scope: Option<String>,
+}
+/// Escapes `%`, `_`, and `\` so the term is matched literally by `ILIKE`
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+impl ProjectSearchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.tags.is_empty() && self.scope.is_none()
+    }
+    fn push_where(&self, repository: Uuid, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push(" WHERE repository_id = ");
+        query.push_bind(repository);
+        for term in &self.terms {
+            let pattern = like_pattern(term);
+            query.push(" AND (project_key ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR description ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR EXISTS (SELECT 1 FROM unnest(tags) AS tag WHERE tag ILIKE ");
+            query.push_bind(pattern);
+            query.push("))");
+        }
+        for tag in &self.tags {
+            query.push(" AND EXISTS (SELECT 1 FROM unnest(tags) AS tag WHERE LOWER(tag) = ");
+            query.push_bind(tag.to_lowercase());
+            query.push(")");
+        }
+        if let Some(scope) = &self.scope {
+            query.push(" AND LOWER(scope) = ");
+            query.push_bind(scope.to_lowercase());
+        }
+    }
+    /// Returns a page of the matching projects and the total number of matches
+    #[instrument(skip(database))]
+    pub async fn search(
+        &self,
+        repository: Uuid,
+        limit: i64,
+        offset: i64,
+        database: &PgPool,
+    ) -> Result<(Vec<DBProject>, i64), sqlx::Error> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM projects");
+        self.push_where(repository, &mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(database).await?;
+
+        let mut query = QueryBuilder::new("SELECT * FROM projects");
+        self.push_where(repository, &mut query);
+        // Exact matches of the project key come first
+        query.push(" ORDER BY LOWER(project_key) = ANY(");
+        query.push_bind(
+            self.terms
+                .iter()
+                .map(|term| term.to_lowercase())
+                .collect::<Vec<_>>(),
+        );
+        query.push(") DESC, updated_at DESC LIMIT ");
+        query.push_bind(limit);
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+        let projects = query
+            .build_query_as::<DBProject>()
+            .fetch_all(database)
+            .await?;
+        Ok((projects, total))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("react"), "%react%");
+        assert_eq!(like_pattern("100%_sure\\"), "%100\\%\\_sure\\\\%");
+    }
+}
@@ -99,4 +99,12 @@ impl DBProjectVersion {
                 .await?;
         Ok(versions)
     }
+    #[instrument(skip(database))]
+    pub async fn delete_by_id(id: i32, database: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM project_versions WHERE id = $1"#)
+            .bind(id)
+            .execute(database)
+            .await?;
+        Ok(())
+    }
 }
@@ -8,20 +8,30 @@ use uuid::Uuid;
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
 pub struct DBStage {
     pub id: Uuid,
-    pub repository: Uuid,
+    pub repository_id: Uuid,
     #[schema(value_type = crate::utils::utopia::AnyType)]
     pub stage_state: Json<Value>,
     pub created_by: i32,
     pub created_at: chrono::DateTime<chrono::FixedOffset>,
+    /// Once closed no more files can be added and the stage is ready to be promoted
+    pub closed_at: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// Set while the stage is being promoted. Only one promote can hold the stage at a time
+    pub promoting_at: Option<chrono::DateTime<chrono::FixedOffset>>,
 }
 
 impl DBStage {
+    pub fn is_closed(&self) -> bool {
+        self.closed_at.is_some()
+    }
+    pub fn is_promoting(&self) -> bool {
+        self.promoting_at.is_some()
+    }
     pub async fn get_stage_by_id(
         id: Uuid,
         repository: Uuid,
         database: &sqlx::PgPool,
     ) -> Result<Option<DBStage>, sqlx::Error> {
-        let query = "SELECT * FROM stages WHERE id = $1 AND repository = $2".to_string();
+        let query = "SELECT * FROM stages WHERE id = $1 AND repository_id = $2".to_string();
         let stage = sqlx::query_as(&query)
             .bind(id)
             .bind(repository)
@@ -40,6 +50,70 @@ impl DBStage {
             .await?;
         Ok(files)
     }
+    pub async fn get_file_by_name(
+        &self,
+        file_name: &str,
+        database: &sqlx::PgPool,
+    ) -> Result<Option<DBStageFile>, sqlx::Error> {
+        let query = "SELECT * FROM stage_files WHERE stage = $1 AND file_name = $2".to_string();
+        let file = sqlx::query_as(&query)
+            .bind(self.id)
+            .bind(file_name)
+            .fetch_optional(database)
+            .await?;
+        Ok(file)
+    }
+    /// Closes the stage. Returns None if it was already closed
+    pub async fn close(&self, database: &sqlx::PgPool) -> Result<Option<DBStage>, sqlx::Error> {
+        let query = "UPDATE stages SET closed_at = CURRENT_TIMESTAMP WHERE id = $1 AND closed_at IS NULL RETURNING *".to_string();
+        let stage = sqlx::query_as(&query)
+            .bind(self.id)
+            .fetch_optional(database)
+            .await?;
+        Ok(stage)
+    }
+    /// Claims a closed stage for promotion.
+    ///
+    /// Returns None if the stage does not exist, is not closed or another promote already claimed it
+    pub async fn claim_for_promotion(
+        id: Uuid,
+        repository: Uuid,
+        database: &sqlx::PgPool,
+    ) -> Result<Option<DBStage>, sqlx::Error> {
+        let query = "UPDATE stages SET promoting_at = CURRENT_TIMESTAMP WHERE id = $1 AND repository_id = $2 AND closed_at IS NOT NULL AND promoting_at IS NULL RETURNING *".to_string();
+        let stage = sqlx::query_as(&query)
+            .bind(id)
+            .bind(repository)
+            .fetch_optional(database)
+            .await?;
+        Ok(stage)
+    }
+    /// Releases the claim of a promote that did not publish the stage
+    pub async fn release_promotion(&self, database: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = "UPDATE stages SET promoting_at = NULL WHERE id = $1".to_string();
+        sqlx::query(&query).bind(self.id).execute(database).await?;
+        Ok(())
+    }
+    pub async fn does_stage_exist(id: Uuid, database: &sqlx::PgPool) -> Result<bool, sqlx::Error> {
+        let query = "SELECT EXISTS(SELECT 1 FROM stages WHERE id = $1)".to_string();
+        let exists: bool = sqlx::query_scalar(&query)
+            .bind(id)
+            .fetch_one(database)
+            .await?;
+        Ok(exists)
+    }
+    /// Stages that were never closed and were created before the cutoff
+    pub async fn get_open_stages_created_before(
+        cutoff: chrono::DateTime<chrono::FixedOffset>,
+        database: &sqlx::PgPool,
+    ) -> Result<Vec<DBStage>, sqlx::Error> {
+        let query = "SELECT * FROM stages WHERE closed_at IS NULL AND created_at < $1".to_string();
+        let stages = sqlx::query_as(&query)
+            .bind(cutoff)
+            .fetch_all(database)
+            .await?;
+        Ok(stages)
+    }
     pub async fn delete_stage(&self, database: &sqlx::PgPool) -> Result<(), sqlx::Error> {
         let query = "DELETE FROM stages WHERE id = $1".to_string();
         sqlx::query(&query).bind(self.id).execute(database).await?;
@@ -49,7 +123,7 @@ impl DBStage {
         repository: Uuid,
         database: &sqlx::PgPool,
     ) -> Result<Vec<DBStage>, sqlx::Error> {
-        let query = "SELECT * FROM stages WHERE repository = $1".to_string();
+        let query = "SELECT * FROM stages WHERE repository_id = $1".to_string();
         let stages = sqlx::query_as(&query)
             .bind(repository)
             .fetch_all(database)
@@ -66,15 +140,15 @@ pub struct DBStageFile {
 }
 #[derive(Debug, Clone, PartialEq, Eq, Builder)]
 pub struct NewDBStage {
-    pub repository: Uuid,
+    pub repository_id: Uuid,
     pub stage_state: Value,
     pub created_by: i32,
 }
 impl NewDBStage {
     pub async fn insert(&self, database: &sqlx::PgPool) -> Result<DBStage, sqlx::Error> {
-        let query = "INSERT INTO stages (repository, stage_state, created_by) VALUES ($1, $2, $3) RETURNING *".to_string();
+        let query = "INSERT INTO stages (repository_id, stage_state, created_by) VALUES ($1, $2, $3) RETURNING *".to_string();
         let stage = sqlx::query_as(&query)
-            .bind(self.repository)
+            .bind(self.repository_id)
             .bind(Json(self.stage_state.clone()))
             .bind(self.created_by)
             .fetch_one(database)
@@ -41,14 +41,26 @@ impl ReferencesUser for AuthToken {
     }
 }
 impl AuthToken {
+    /// Expired tokens are not returned
     pub async fn get_by_token(token: &str, database: &PgPool) -> sqlx::Result<Option<Self>> {
-        let token =
-            sqlx::query_as(r#"SELECT * FROM user_auth_tokens WHERE token = $1 AND active = true"#)
-                .bind(hash_token(token))
-                .fetch_optional(database)
-                .await?;
+        let token = sqlx::query_as(
+            r#"SELECT * FROM user_auth_tokens WHERE token = $1 AND active = true AND (expires_at IS NULL OR expires_at > NOW())"#,
+        )
+        .bind(hash_token(token))
+        .fetch_optional(database)
+        .await?;
         Ok(token)
     }
+    /// Deletes every token that has expired. Returns the number of tokens deleted
+    #[instrument(skip(database))]
+    pub async fn delete_expired(database: &PgPool) -> sqlx::Result<u64> {
+        let result = sqlx::query(
+            r#"DELETE FROM user_auth_tokens WHERE expires_at IS NOT NULL AND expires_at <= NOW()"#,
+        )
+        .execute(database)
+        .await?;
+        Ok(result.rows_affected())
+    }
     pub async fn has_scope(&self, scope: NRScope, database: &PgPool) -> sqlx::Result<bool> {
         let can_read: i64 = sqlx::query_scalar(
             r#"SELECT COUNT(id) FROM user_auth_token_scopes WHERE user_auth_token_id = $1 AND scope = $2"#,
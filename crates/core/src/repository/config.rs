@@ -52,6 +52,17 @@ pub trait RepositoryConfigType: Send + Sync + Debug {
     fn sanitize_for_public_view(&self, _: Value) -> Result<Option<Value>, RepositoryConfigError> {
         Ok(None)
     }
+    /// Replaces any secrets in the config before it is returned from the API.
+    ///
+    /// This is applied for every user. Including ones that can edit the config
+    fn redact_secrets(&self, config: Value) -> Result<Value, RepositoryConfigError> {
+        Ok(config)
+    }
+    /// Called when a config is updated. Secrets that were redacted by [RepositoryConfigType::redact_secrets]
+    /// should be replaced with the values from the old config
+    fn restore_secrets(&self, _old: Value, new: Value) -> Result<Value, RepositoryConfigError> {
+        Ok(new)
+    }
     /// Validate the config. If the config is invalid this function should return an error
     fn validate_config(&self, config: Value) -> Result<(), RepositoryConfigError>;
     /// If part of the config cannot be changed this function should return an error
@@ -1,12 +1,19 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
 use derive_more::derive::{AsRef, Deref};
+use http::{
+    HeaderMap, HeaderName, HeaderValue,
+    header::{AUTHORIZATION, InvalidHeaderName, InvalidHeaderValue},
+};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tracing::{instrument, trace};
 use url::Url;
 
-use crate::storage::StoragePath;
+use crate::{repository::config::RepositoryConfigError, storage::StoragePath, utils::base64_utils};
+/// The value secrets are replaced with when a config is returned from the API
+pub static REDACTED_SECRET: &str = "**REDACTED**";
 
 #[derive(Debug, Clone, PartialEq, Eq, JsonSchema, Deref, AsRef)]
 pub struct ProxyURL(String);
@@ -68,3 +75,240 @@ impl ProxyURL {
         Ok(url)
     }
 }
+#[derive(Debug, Error)]
+pub enum InvalidProxyHeader {
+    #[error("Invalid Header Name: {0}")]
+    Name(#[from] InvalidHeaderName),
+    #[error("Invalid Header Value: {0}")]
+    Value(#[from] InvalidHeaderValue),
+    /// A secret could not be restored from the old config. Such as after the URL was changed
+    #[error("A secret is still **REDACTED**")]
+    RedactedSecret,
+}
+impl From<InvalidProxyHeader> for RepositoryConfigError {
+    fn from(err: InvalidProxyHeader) -> Self {
+        match err {
+            InvalidProxyHeader::RedactedSecret => RepositoryConfigError::InvalidConfig(
+                "A proxy secret is still **REDACTED**. Secrets must be entered again when the URL changes",
+            ),
+            _ => RepositoryConfigError::InvalidConfig("Proxy has an invalid header"),
+        }
+    }
+}
+/// An upstream with its own credentials. Such as a route of a proxy config
+pub trait ProxyRoute {
+    fn url(&self) -> &ProxyURL;
+    fn credentials(&self) -> &ProxyCredentials;
+    fn credentials_mut(&mut self) -> &mut ProxyCredentials;
+}
+/// How to authenticate with an upstream repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "config")]
+pub enum ProxyAuthentication {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+impl ProxyAuthentication {
+    pub fn header_value(&self) -> String {
+        match self {
+            ProxyAuthentication::Basic { username, password } => {
+                format!(
+                    "Basic {}",
+                    base64_utils::encode_basic_header(username, password)
+                )
+            }
+            ProxyAuthentication::Bearer { token } => format!("Bearer {}", token),
+        }
+    }
+}
+/// Credentials and headers that are sent to an upstream repository
+///
+/// Passwords, tokens, and header values are treated as secrets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ProxyCredentials {
+    pub authentication: Option<ProxyAuthentication>,
+    /// Extra headers to send with every request
+    pub headers: BTreeMap<String, String>,
+}
+impl ProxyCredentials {
+    pub fn header_map(&self) -> Result<HeaderMap, InvalidProxyHeader> {
+        let mut headers = HeaderMap::with_capacity(self.headers.len() + 1);
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::try_from(name.as_str())?,
+                HeaderValue::try_from(value.as_str())?,
+            );
+        }
+        if let Some(authentication) = &self.authentication {
+            let mut value = HeaderValue::try_from(authentication.header_value())?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+    /// [ProxyCredentials::validate] for every route
+    pub fn validate_routes(routes: &[impl ProxyRoute]) -> Result<(), InvalidProxyHeader> {
+        for route in routes {
+            route.credentials().validate()?;
+        }
+        Ok(())
+    }
+    /// [ProxyCredentials::redact] for every route
+    pub fn redact_routes(routes: &mut [impl ProxyRoute]) {
+        for route in routes {
+            route.credentials_mut().redact();
+        }
+    }
+    /// [ProxyCredentials::restore_redacted] for every route. Routes are matched to the old routes by URL.
+    ///
+    /// Secrets of routes with a new URL are not restored. [ProxyCredentials::validate] rejects them
+    pub fn restore_redacted_routes<R: ProxyRoute>(routes: &mut [R], old: &[R]) {
+        for route in routes {
+            if let Some(old_route) = old.iter().find(|old| old.url() == route.url()) {
+                route
+                    .credentials_mut()
+                    .restore_redacted(old_route.credentials());
+            }
+        }
+    }
+    /// Checks that the headers are valid and no secret is still [REDACTED_SECRET]
+    pub fn validate(&self) -> Result<(), InvalidProxyHeader> {
+        if self.has_redacted_secret() {
+            return Err(InvalidProxyHeader::RedactedSecret);
+        }
+        self.header_map()?;
+        Ok(())
+    }
+    pub fn has_redacted_secret(&self) -> bool {
+        let authentication_redacted = match &self.authentication {
+            Some(ProxyAuthentication::Basic { password, .. }) => password == REDACTED_SECRET,
+            Some(ProxyAuthentication::Bearer { token }) => token == REDACTED_SECRET,
+            None => false,
+        };
+        authentication_redacted || self.headers.values().any(|value| value == REDACTED_SECRET)
+    }
+    /// Replaces all secrets with [REDACTED_SECRET]
+    pub fn redact(&mut self) {
+        match &mut self.authentication {
+            Some(ProxyAuthentication::Basic { password, .. }) => {
+                *password = REDACTED_SECRET.to_owned();
+            }
+            Some(ProxyAuthentication::Bearer { token }) => {
+                *token = REDACTED_SECRET.to_owned();
+            }
+            None => {}
+        }
+        for value in self.headers.values_mut() {
+            *value = REDACTED_SECRET.to_owned();
+        }
+    }
+    /// Any secret that is still [REDACTED_SECRET] will be replaced with the value from the old credentials
+    pub fn restore_redacted(&mut self, old: &ProxyCredentials) {
+        match (&mut self.authentication, &old.authentication) {
+            (
+                Some(ProxyAuthentication::Basic { password, .. }),
+                Some(ProxyAuthentication::Basic {
+                    password: old_password,
+                    ..
+                }),
+            ) if password.as_str() == REDACTED_SECRET => {
+                *password = old_password.clone();
+            }
+            (
+                Some(ProxyAuthentication::Bearer { token }),
+                Some(ProxyAuthentication::Bearer { token: old_token }),
+            ) if token.as_str() == REDACTED_SECRET => {
+                *token = old_token.clone();
+            }
+            _ => {}
+        }
+        for (name, value) in self.headers.iter_mut() {
+            if value.as_str() != REDACTED_SECRET {
+                continue;
+            }
+            if let Some(old_value) = old.headers.get(name) {
+                *value = old_value.clone();
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn redact_and_restore() {
+        let original = ProxyCredentials {
+            authentication: Some(ProxyAuthentication::Basic {
+                username: "user".to_owned(),
+                password: "password".to_owned(),
+            }),
+            headers: BTreeMap::from([("X-Api-Key".to_owned(), "secret".to_owned())]),
+        };
+        let mut redacted = original.clone();
+        redacted.redact();
+        assert_eq!(
+            redacted.authentication,
+            Some(ProxyAuthentication::Basic {
+                username: "user".to_owned(),
+                password: REDACTED_SECRET.to_owned(),
+            })
+        );
+        assert_eq!(redacted.headers["X-Api-Key"], REDACTED_SECRET);
+
+        assert!(redacted.validate().is_err());
+
+        // The old credentials do not have the header
+        let mut unresolved = redacted.clone();
+        unresolved.restore_redacted(&ProxyCredentials::default());
+        assert!(matches!(
+            unresolved.validate(),
+            Err(InvalidProxyHeader::RedactedSecret)
+        ));
+
+        redacted.restore_redacted(&original);
+        assert_eq!(redacted, original);
+        assert!(redacted.validate().is_ok());
+    }
+    struct TestRoute {
+        url: ProxyURL,
+        credentials: ProxyCredentials,
+    }
+    impl ProxyRoute for TestRoute {
+        fn url(&self) -> &ProxyURL {
+            &self.url
+        }
+        fn credentials(&self) -> &ProxyCredentials {
+            &self.credentials
+        }
+        fn credentials_mut(&mut self) -> &mut ProxyCredentials {
+            &mut self.credentials
+        }
+    }
+    #[test]
+    fn restore_routes_by_url() {
+        let route = |url: &str| TestRoute {
+            url: ProxyURL::try_from(url.to_owned()).unwrap(),
+            credentials: ProxyCredentials {
+                authentication: Some(ProxyAuthentication::Bearer {
+                    token: "token".to_owned(),
+                }),
+                headers: BTreeMap::new(),
+            },
+        };
+        let old = vec![route("https://repo1.maven.org/maven2")];
+        let mut new = vec![
+            route("https://repo1.maven.org/maven2"),
+            route("https://example.com/maven2"),
+        ];
+        ProxyCredentials::redact_routes(&mut new);
+        ProxyCredentials::restore_redacted_routes(&mut new, &old);
+        assert_eq!(new[0].credentials, old[0].credentials);
+        // The URL changed. So the secret is not sent to the new URL
+        assert!(new[1].credentials.has_redacted_secret());
+        assert!(matches!(
+            ProxyCredentials::validate_routes(&new),
+            Err(InvalidProxyHeader::RedactedSecret)
+        ));
+    }
+}
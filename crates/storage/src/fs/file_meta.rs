@@ -32,54 +32,55 @@ pub fn is_hidden_file(path: &Path) -> bool {
     }
 }
 
+/// Files are hashed in chunks of this size
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
 pub fn generate_hashes_from_path(path: impl AsRef<Path>) -> Result<FileHashes, io::Error> {
-    let mut buffer = Vec::new();
-    {
-        let mut file = std::fs::File::open(path)?;
-        file.read_to_end(&mut buffer)?;
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = FileHasher::default();
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
     }
-    Ok(generate_from_bytes(&buffer))
+    Ok(hasher.finalize())
 }
 #[instrument(skip(buffer))]
 pub fn generate_from_bytes(buffer: &[u8]) -> FileHashes {
-    FileHashes {
-        md5: Some(generate_md5(buffer)),
-        sha1: Some(generate_sha1(buffer)),
-        sha2_256: Some(generate_sha2_256(buffer)),
-        sha3_256: Some(generate_sha3_256(buffer)),
-    }
-}
-fn generate_md5(buffer: &[u8]) -> String {
-    use md5::Md5;
-
-    let mut hasher = Md5::new();
+    let mut hasher = FileHasher::default();
     hasher.update(buffer);
-    let hash = hasher.finalize();
-    base64_utils::encode(hash)
+    hasher.finalize()
 }
-fn generate_sha1(buffer: &[u8]) -> String {
-    use sha1::Sha1;
-
-    let mut hasher = Sha1::new();
-    hasher.update(buffer);
-    let hash = hasher.finalize();
-    base64_utils::encode(hash)
+/// Generates the [FileHashes] of content that is read in chunks. Such as a download or a large file
+#[derive(Clone, Default)]
+pub struct FileHasher {
+    md5: md5::Md5,
+    sha1: sha1::Sha1,
+    sha2_256: sha2::Sha256,
+    sha3_256: sha3::Sha3_256,
 }
-fn generate_sha2_256(buffer: &[u8]) -> String {
-    use sha2::Sha256;
-
-    let mut hasher = Sha256::new();
-    hasher.update(buffer);
-    let hash = hasher.finalize();
-    base64_utils::encode(hash)
+impl FileHasher {
+    pub fn update(&mut self, buffer: &[u8]) {
+        self.md5.update(buffer);
+        self.sha1.update(buffer);
+        self.sha2_256.update(buffer);
+        self.sha3_256.update(buffer);
+    }
+    pub fn finalize(self) -> FileHashes {
+        FileHashes {
+            md5: Some(base64_utils::encode(self.md5.finalize())),
+            sha1: Some(base64_utils::encode(self.sha1.finalize())),
+            sha2_256: Some(base64_utils::encode(self.sha2_256.finalize())),
+            sha3_256: Some(base64_utils::encode(self.sha3_256.finalize())),
+        }
+    }
 }
-fn generate_sha3_256(buffer: &[u8]) -> String {
-    use sha3::Sha3_256;
-
-    let mut hasher = Sha3_256::new();
-    hasher.update(buffer);
-    let hash = hasher.finalize();
-    base64_utils::encode(hash)
+impl std::fmt::Debug for FileHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileHasher").finish_non_exhaustive()
+    }
 }
 
 pub const FILE_META_MIME: Mime = mime::APPLICATION_JSON;
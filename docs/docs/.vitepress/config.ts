@@ -111,5 +111,25 @@ function repositoryTypesBar() {
         },
       ],
     },
+    {
+      text: "Cargo",
+      link: "/repositoryTypes/cargo",
+    },
+    {
+      text: "Python",
+      link: "/repositoryTypes/python",
+    },
+    {
+      text: "Docker",
+      link: "/repositoryTypes/docker",
+    },
+    {
+      text: "Go",
+      link: "/repositoryTypes/go",
+    },
+    {
+      text: "Helm",
+      link: "/repositoryTypes/helm",
+    },
   ];
 }
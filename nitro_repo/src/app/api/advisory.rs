@@ -0,0 +1,204 @@
+//! Advisories are used by `npm audit`. They can be created one at a time or imported from OSV files
+use std::path::PathBuf;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use nr_core::{
+    database::entities::advisories::{AdvisorySeverity, DBAdvisory, NewAdvisory},
+    user::permissions::HasPermissions,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+mod osv;
+use crate::{
+    app::{NitroRepo, authentication::Authentication, responses::MissingPermission},
+    error::InternalError,
+    utils::response_builder::ResponseBuilder,
+};
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_advisories,
+        new_advisory,
+        get_advisory,
+        delete_advisory,
+        import_osv
+    ),
+    components(schemas(
+        DBAdvisory,
+        NewAdvisory,
+        AdvisorySeverity,
+        ImportOSVRequest,
+        ImportOSVResponse
+    ))
+)]
+pub struct AdvisoryAPI;
+pub fn advisory_routes() -> axum::Router<NitroRepo> {
+    axum::Router::new()
+        .route("/list", get(list_advisories))
+        .route("/new", post(new_advisory))
+        .route("/import/osv", post(import_osv))
+        .route("/{id}", get(get_advisory).delete(delete_advisory))
+}
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct AdvisoryListRequest {
+    /// Only include advisories for the ecosystem. Such as `npm`
+    pub ecosystem: Option<String>,
+}
+#[utoipa::path(
+    get,
+    path = "/list",
+    params(AdvisoryListRequest),
+    responses(
+        (status = 200, description = "All advisories", body = [DBAdvisory]),
+    )
+)]
+#[instrument]
+pub async fn list_advisories(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Query(request): Query<AdvisoryListRequest>,
+) -> Result<Response, InternalError> {
+    let advisories = DBAdvisory::get_all(request.ecosystem.as_deref(), &site.database).await?;
+    Ok(ResponseBuilder::ok().json(&advisories))
+}
+#[utoipa::path(
+    post,
+    path = "/new",
+    request_body = NewAdvisory,
+    responses(
+        (status = 200, description = "The created advisory. Replaces the advisory with the same source id and package", body = DBAdvisory),
+        (status = 403, description = "Missing permission"),
+    )
+)]
+#[instrument]
+pub async fn new_advisory(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Json(request): Json<NewAdvisory>,
+) -> Result<Response, InternalError> {
+    if !auth.is_admin_or_system_manager() {
+        return Ok(MissingPermission::SystemManager.into_response());
+    }
+    let advisory = request.upsert(&site.database).await?;
+    info!(?advisory.id, ?advisory.source_id, "Saved advisory");
+    Ok(ResponseBuilder::ok().json(&advisory))
+}
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    params(
+        ("id" = i32, Path, description = "The Advisory ID"),
+    ),
+    responses(
+        (status = 200, description = "The advisory", body = DBAdvisory),
+        (status = 404, description = "Advisory not found"),
+    )
+)]
+#[instrument]
+pub async fn get_advisory(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path(id): Path<i32>,
+) -> Result<Response, InternalError> {
+    let advisory = DBAdvisory::get_by_id(id, &site.database).await?;
+    match advisory {
+        Some(advisory) => Ok(ResponseBuilder::ok().json(&advisory)),
+        None => Ok(ResponseBuilder::not_found().body("Advisory not found")),
+    }
+}
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    params(
+        ("id" = i32, Path, description = "The Advisory ID"),
+    ),
+    responses(
+        (status = 204, description = "Advisory deleted"),
+        (status = 403, description = "Missing permission"),
+        (status = 404, description = "Advisory not found"),
+    )
+)]
+#[instrument]
+pub async fn delete_advisory(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path(id): Path<i32>,
+) -> Result<Response, InternalError> {
+    if !auth.is_admin_or_system_manager() {
+        return Ok(MissingPermission::SystemManager.into_response());
+    }
+    if !DBAdvisory::delete_by_id(id, &site.database).await? {
+        return Ok(ResponseBuilder::not_found().body("Advisory not found"));
+    }
+    Ok(ResponseBuilder::no_content().empty())
+}
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportOSVRequest {
+    /// A JSON file or a directory of JSON files on the server. Each file can contain one OSV entry or an array of entries
+    #[schema(value_type = String)]
+    pub path: PathBuf,
+    /// Only entries affecting this ecosystem are imported
+    #[serde(default = "default_ecosystem")]
+    pub ecosystem: String,
+}
+fn default_ecosystem() -> String {
+    "npm".to_owned()
+}
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportOSVResponse {
+    /// Number of advisories created or updated
+    pub imported: usize,
+    /// Files that could not be parsed
+    pub invalid_files: usize,
+}
+#[utoipa::path(
+    post,
+    path = "/import/osv",
+    request_body = ImportOSVRequest,
+    responses(
+        (status = 200, description = "Import finished", body = ImportOSVResponse),
+        (status = 400, description = "The path could not be read"),
+        (status = 403, description = "Missing permission"),
+    )
+)]
+#[instrument]
+pub async fn import_osv(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Json(request): Json<ImportOSVRequest>,
+) -> Result<Response, InternalError> {
+    if !auth.is_admin_or_system_manager() {
+        return Ok(MissingPermission::SystemManager.into_response());
+    }
+    let (entries, invalid_files) = match osv::read_osv_entries(&request.path).await {
+        Ok(ok) => ok,
+        Err(err) => {
+            warn!(?err, path = ?request.path, "Unable to read OSV path");
+            return Ok(ResponseBuilder::bad_request().body(format!(
+                "Unable to read {}: {}",
+                request.path.display(),
+                err
+            )));
+        }
+    };
+    let mut imported = 0;
+    for entry in entries {
+        for advisory in entry.into_advisories(&request.ecosystem) {
+            advisory.upsert(&site.database).await?;
+            imported += 1;
+        }
+    }
+    info!(?imported, ?invalid_files, "Imported OSV advisories");
+    Ok(ResponseBuilder::ok().json(&ImportOSVResponse {
+        imported,
+        invalid_files,
+    }))
+}
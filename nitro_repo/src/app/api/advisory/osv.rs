@@ -0,0 +1,280 @@
+//! Converts [OSV](https://ossf.github.io/osv-schema/) entries into advisories
+use std::{path::Path, str::FromStr};
+
+use nr_core::database::entities::advisories::{AdvisorySeverity, NewAdvisory};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OSVEntry {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub details: Option<String>,
+    /// Set if the entry has been withdrawn
+    #[serde(default)]
+    pub withdrawn: Option<String>,
+    #[serde(default)]
+    pub affected: Vec<OSVAffected>,
+    #[serde(default)]
+    pub references: Vec<OSVReference>,
+    #[serde(default)]
+    pub database_specific: Option<Value>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct OSVAffected {
+    pub package: OSVPackage,
+    #[serde(default)]
+    pub ranges: Vec<OSVRange>,
+    #[serde(default)]
+    pub versions: Vec<String>,
+    #[serde(default)]
+    pub database_specific: Option<Value>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct OSVPackage {
+    pub ecosystem: String,
+    pub name: String,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct OSVRange {
+    #[serde(rename = "type")]
+    pub range_type: String,
+    #[serde(default)]
+    pub events: Vec<OSVEvent>,
+}
+/// Each event only has one of the fields set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OSVEvent {
+    pub introduced: Option<String>,
+    pub fixed: Option<String>,
+    pub last_affected: Option<String>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct OSVReference {
+    #[serde(rename = "type")]
+    pub reference_type: String,
+    pub url: String,
+}
+/// Reads the `severity` field of a `database_specific` object. GitHub uses `LOW`, `MODERATE`, `HIGH`, and `CRITICAL`
+fn database_specific_severity(database_specific: Option<&Value>) -> Option<AdvisorySeverity> {
+    let severity = database_specific?.get("severity")?.as_str()?;
+    if severity.eq_ignore_ascii_case("medium") {
+        return Some(AdvisorySeverity::Moderate);
+    }
+    AdvisorySeverity::from_str(severity).ok()
+}
+/// Converts the events of the ranges into a npm range. Such as `>=1.0.0 <1.2.3 || >=2.0.0 <2.0.4`
+fn npm_range(affected: &OSVAffected) -> Option<String> {
+    let mut parts = Vec::new();
+    for range in &affected.ranges {
+        if range.range_type != "SEMVER" && range.range_type != "ECOSYSTEM" {
+            continue;
+        }
+        let mut introduced: Option<&str> = None;
+        for event in &range.events {
+            if let Some(version) = &event.introduced {
+                introduced = Some(version);
+            } else if let Some(version) = &event.fixed {
+                parts.push(range_part(introduced.take(), Some(format!("<{}", version))));
+            } else if let Some(version) = &event.last_affected {
+                parts.push(range_part(
+                    introduced.take(),
+                    Some(format!("<={}", version)),
+                ));
+            }
+        }
+        if let Some(introduced) = introduced {
+            parts.push(range_part(Some(introduced), None));
+        }
+    }
+    if parts.is_empty() {
+        parts = affected.versions.clone();
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" || "))
+    }
+}
+fn range_part(introduced: Option<&str>, upper: Option<String>) -> String {
+    let lower = introduced
+        .filter(|introduced| *introduced != "0")
+        .map(|introduced| format!(">={}", introduced));
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => format!("{} {}", lower, upper),
+        (Some(lower), None) => lower,
+        (None, Some(upper)) => upper,
+        (None, None) => "*".to_owned(),
+    }
+}
+/// The highest fixed version. As a npm range
+fn patched_versions(affected: &OSVAffected) -> Option<String> {
+    affected
+        .ranges
+        .iter()
+        .flat_map(|range| range.events.iter())
+        .filter_map(|event| event.fixed.as_deref())
+        .max_by(
+            |a, b| match (semver::Version::parse(a), semver::Version::parse(b)) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            },
+        )
+        .map(|fixed| format!(">={}", fixed))
+}
+impl OSVEntry {
+    /// Returns an advisory for every affected package of the ecosystem
+    pub fn into_advisories(self, ecosystem: &str) -> Vec<NewAdvisory> {
+        if self.withdrawn.is_some() {
+            debug!(id = ?self.id, "Skipping withdrawn entry");
+            return vec![];
+        }
+        let url = ["ADVISORY", "WEB"]
+            .iter()
+            .find_map(|reference_type| {
+                self.references
+                    .iter()
+                    .find(|reference| reference.reference_type == *reference_type)
+            })
+            .or(self.references.first())
+            .map(|reference| reference.url.clone());
+        let cwe: Vec<String> = self
+            .database_specific
+            .as_ref()
+            .and_then(|database_specific| database_specific.get("cwe_ids"))
+            .and_then(Value::as_array)
+            .map(|cwe_ids| {
+                cwe_ids
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut advisories = Vec::new();
+        for affected in &self.affected {
+            if !affected.package.ecosystem.eq_ignore_ascii_case(ecosystem) {
+                continue;
+            }
+            let Some(vulnerable_versions) = npm_range(affected) else {
+                warn!(id = ?self.id, package = ?affected.package.name, "No affected versions");
+                continue;
+            };
+            let severity = database_specific_severity(affected.database_specific.as_ref())
+                .or_else(|| database_specific_severity(self.database_specific.as_ref()))
+                .unwrap_or_default();
+            advisories.push(NewAdvisory {
+                source_id: self.id.clone(),
+                ecosystem: ecosystem.to_owned(),
+                package_name: affected.package.name.clone(),
+                title: self.summary.clone().unwrap_or_else(|| self.id.clone()),
+                overview: self.details.clone(),
+                url: url.clone(),
+                severity,
+                vulnerable_versions,
+                patched_versions: patched_versions(affected),
+                cwe: cwe.clone(),
+            });
+        }
+        advisories
+    }
+}
+/// A file can contain a single entry or an array of entries
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OSVFile {
+    Single(Box<OSVEntry>),
+    Many(Vec<OSVEntry>),
+}
+/// Reads the OSV entries from a JSON file or every JSON file in a directory
+pub async fn read_osv_entries(path: &Path) -> std::io::Result<(Vec<OSVEntry>, usize)> {
+    let files = if tokio::fs::metadata(path).await?.is_dir() {
+        let mut files = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let file = entry.path();
+            if file
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                files.push(file);
+            }
+        }
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut entries = Vec::new();
+    let mut invalid_files = 0;
+    for file in files {
+        let content = tokio::fs::read(&file).await?;
+        match serde_json::from_slice::<OSVFile>(&content) {
+            Ok(OSVFile::Single(entry)) => entries.push(*entry),
+            Ok(OSVFile::Many(many)) => entries.extend(many),
+            Err(err) => {
+                warn!(?err, ?file, "Invalid OSV file");
+                invalid_files += 1;
+            }
+        }
+    }
+    Ok((entries, invalid_files))
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_osv_into_advisories() {
+        let entry: OSVEntry = serde_json::from_value(json!({
+            "id": "GHSA-xxxx-yyyy-zzzz",
+            "summary": "Prototype Pollution in mylib",
+            "details": "mylib is vulnerable to prototype pollution",
+            "affected": [
+                {
+                    "package": { "ecosystem": "npm", "name": "mylib" },
+                    "ranges": [
+                        {
+                            "type": "ECOSYSTEM",
+                            "events": [
+                                { "introduced": "0" },
+                                { "fixed": "1.2.3" },
+                                { "introduced": "2.0.0" },
+                                { "fixed": "2.0.4" }
+                            ]
+                        }
+                    ]
+                },
+                {
+                    "package": { "ecosystem": "PyPI", "name": "mylib" },
+                    "versions": ["1.0.0"]
+                }
+            ],
+            "references": [
+                { "type": "WEB", "url": "https://example.com" },
+                { "type": "ADVISORY", "url": "https://github.com/advisories/GHSA-xxxx-yyyy-zzzz" }
+            ],
+            "database_specific": {
+                "severity": "HIGH",
+                "cwe_ids": ["CWE-1321"]
+            }
+        }))
+        .unwrap();
+        let advisories = entry.into_advisories("npm");
+        assert_eq!(advisories.len(), 1);
+        let advisory = &advisories[0];
+        assert_eq!(advisory.package_name, "mylib");
+        assert_eq!(advisory.vulnerable_versions, "<1.2.3 || >=2.0.0 <2.0.4");
+        assert_eq!(advisory.patched_versions.as_deref(), Some(">=2.0.4"));
+        assert_eq!(advisory.severity, AdvisorySeverity::High);
+        assert_eq!(
+            advisory.url.as_deref(),
+            Some("https://github.com/advisories/GHSA-xxxx-yyyy-zzzz")
+        );
+        assert_eq!(advisory.cwe, vec!["CWE-1321".to_owned()]);
+    }
+}
@@ -13,6 +13,7 @@ use strum::IntoEnumIterator;
 use tower_http::cors::CorsLayer;
 use tracing::{error, instrument};
 use utoipa::ToSchema;
+pub mod advisory;
 pub mod project;
 pub mod repository;
 pub mod storage;
@@ -37,6 +38,7 @@ pub fn api_routes() -> axum::Router<NitroRepo> {
         )
         .nest("/repository", repository::repository_routes())
         .nest("/project", project::project_routes())
+        .nest("/advisory", advisory::advisory_routes())
         .fallback(route_not_found)
         .layer(CorsLayer::very_permissive())
 }
@@ -5,9 +5,12 @@ use axum::{
 };
 use management::NewRepositoryRequest;
 use nr_core::{
-    database::entities::repository::{
-        DBRepository, DBRepositoryNames, DBRepositoryNamesWithVisibility,
-        DBRepositoryWithStorageName,
+    database::entities::{
+        repository::{
+            DBRepository, DBRepositoryNames, DBRepositoryNamesWithVisibility,
+            DBRepositoryWithStorageName,
+        },
+        stages::{DBStage, DBStageFile},
     },
     repository::{
         Visibility,
@@ -37,7 +40,9 @@ use crate::{
 mod browse;
 mod config;
 mod management;
+mod npm_login;
 mod page;
+mod stages;
 mod types;
 #[derive(OpenApi)]
 #[openapi(
@@ -57,6 +62,13 @@ mod types;
         management::get_configs_for_repository,
         management::delete_repository,
         browse::browse,
+        stages::list_stages,
+        stages::get_stage,
+        stages::close_stage,
+        stages::promote_stage,
+        stages::drop_stage,
+        npm_login::get_web_login,
+        npm_login::approve_web_login,
     ),
     components(schemas(
         DBRepository,
@@ -69,7 +81,12 @@ mod types;
         BrowseResponse,
         ProjectResolution,
         DBRepositoryNames,
-        DBRepositoryNamesWithVisibility
+        DBRepositoryNamesWithVisibility,
+        DBStage,
+        DBStageFile,
+        stages::StageResponse,
+        stages::PromoteStageRequest,
+        npm_login::WebLoginSessionResponse
     )),
     nest(
         (path = "/page", api = RepositoryPageRoutes, tags=["repository", "page"]),
@@ -90,6 +107,8 @@ pub fn repository_routes() -> axum::Router<NitroRepo> {
         .merge(browse::browse_routes())
         .merge(management::management_routes())
         .merge(config::config_routes())
+        .merge(stages::stage_routes())
+        .merge(npm_login::npm_login_routes())
 }
 #[derive(Debug, Serialize, ToSchema)]
 pub struct RepositoryIdResponse {
@@ -218,6 +218,7 @@ pub async fn get_config(
         }
     };
     if let Some(config) = config {
+        let config = config_type.redact_secrets(config)?;
         Ok(Response::builder()
             .status(StatusCode::OK)
             .json_body(&config)?)
@@ -275,25 +276,28 @@ pub async fn update_config(
         }
         .into_response());
     }
-    match GenericDBRepositoryConfig::get_config(repository.id(), &config_key, site.as_ref()).await?
-    {
-        Some(old) => {
-            if let Err(error) = config_type.validate_change(old.value.0, config.clone()) {
-                error!("Error validating config: {}", error);
-                return Ok(
-                    InvalidRepositoryConfig::InvalidConfig { config_key, error }.into_response()
-                );
+    let config =
+        match GenericDBRepositoryConfig::get_config(repository.id(), &config_key, site.as_ref())
+            .await?
+        {
+            Some(old) => {
+                let config = config_type.restore_secrets(old.value.0.clone(), config)?;
+                if let Err(error) = config_type.validate_change(old.value.0, config.clone()) {
+                    error!("Error validating config: {}", error);
+                    return Ok(InvalidRepositoryConfig::InvalidConfig { config_key, error }
+                        .into_response());
+                }
+                config
             }
-        }
-        None => {
-            if let Err(error) = config_type.validate_config(config.clone()) {
-                error!("Error validating config: {}", error);
-                return Ok(
-                    InvalidRepositoryConfig::InvalidConfig { config_key, error }.into_response()
-                );
+            None => {
+                if let Err(error) = config_type.validate_config(config.clone()) {
+                    error!("Error validating config: {}", error);
+                    return Ok(InvalidRepositoryConfig::InvalidConfig { config_key, error }
+                        .into_response());
+                }
+                config
             }
-        }
-    };
+        };
 
     GenericDBRepositoryConfig::add_or_update(db_repository.id, config_key, config, site.as_ref())
         .await?;
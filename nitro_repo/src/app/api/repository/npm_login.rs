@@ -0,0 +1,131 @@
+//! Approving `npm login --auth-type=web` sessions.
+//!
+//! The npm CLI opens the login page in the browser. The page uses these routes to approve the login
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, FixedOffset};
+use nr_core::{
+    database::entities::user::auth_token::{AuthToken, NewRepositoryToken},
+    user::permissions::RepositoryActions,
+};
+use serde::Serialize;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    app::{NitroRepo, authentication::Authentication, responses::RepositoryNotFound},
+    error::InternalError,
+    repository::{
+        DynRepository,
+        npm::{NPMRegistry, hosted::NPMHostedRegistry},
+    },
+    utils::response_builder::ResponseBuilder,
+};
+pub fn npm_login_routes() -> Router<NitroRepo> {
+    Router::new().route(
+        "/{repository_id}/npm/web-login/{session_id}",
+        get(get_web_login).post(approve_web_login),
+    )
+}
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebLoginSessionResponse {
+    pub repository_id: Uuid,
+    pub repository_name: String,
+    /// The client that started the login. Such as `NPM CLI (npm/10.8.2 node/v20.17.0)`
+    pub source: String,
+    pub created: DateTime<FixedOffset>,
+}
+/// Web logins are only supported by NPM hosted repositories
+fn get_npm_hosted(site: &NitroRepo, repository: Uuid) -> Result<NPMHostedRegistry, Response> {
+    match site.get_repository(repository) {
+        Some(DynRepository::NPM(NPMRegistry::Hosted(hosted))) => Ok(hosted),
+        Some(_) => {
+            Err(ResponseBuilder::bad_request().body("Repository does not support web login"))
+        }
+        None => Err(RepositoryNotFound::Uuid(repository).into_response()),
+    }
+}
+#[utoipa::path(
+    get,
+    path = "/{repository_id}/npm/web-login/{session_id}",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("session_id" = String, Path, description = "The Login Session ID"),
+    ),
+    responses(
+        (status = 200, description = "The pending login", body = WebLoginSessionResponse),
+        (status = 404, description = "Login session not found or expired"),
+    )
+)]
+#[instrument]
+pub async fn get_web_login(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, session_id)): Path<(Uuid, String)>,
+) -> Result<Response, InternalError> {
+    let hosted = match get_npm_hosted(&site, repository) {
+        Ok(ok) => ok,
+        Err(response) => return Ok(response),
+    };
+    let Some(session) = hosted.web_logins.get(&session_id) else {
+        return Ok(ResponseBuilder::not_found().body("Login session not found or expired"));
+    };
+    let response = WebLoginSessionResponse {
+        repository_id: repository,
+        repository_name: hosted.repository.name.to_string(),
+        source: session.source,
+        created: session.created,
+    };
+    Ok(ResponseBuilder::ok().json(&response))
+}
+#[utoipa::path(
+    post,
+    path = "/{repository_id}/npm/web-login/{session_id}",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("session_id" = String, Path, description = "The Login Session ID"),
+    ),
+    responses(
+        (status = 204, description = "Login approved. The npm CLI will receive a token"),
+        (status = 404, description = "Login session not found or expired"),
+    )
+)]
+#[instrument]
+pub async fn approve_web_login(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, session_id)): Path<(Uuid, String)>,
+) -> Result<Response, InternalError> {
+    let hosted = match get_npm_hosted(&site, repository) {
+        Ok(ok) => ok,
+        Err(response) => return Ok(response),
+    };
+    let Some(session) = hosted.web_logins.get(&session_id) else {
+        return Ok(ResponseBuilder::not_found().body("Login session not found or expired"));
+    };
+    let (token_id, token) = NewRepositoryToken::new(
+        auth.id,
+        session.source,
+        repository,
+        RepositoryActions::all(),
+    )
+    .insert(&site.database)
+    .await?;
+    if !hosted.web_logins.approve(&session_id, token) {
+        // The session expired or was approved by another request while the token was being created.
+        // The token was never given out
+        if let Some(token) =
+            AuthToken::get_by_id_and_user_id(token_id, auth.id, &site.database).await?
+        {
+            token.delete(&site.database).await?;
+        }
+        return Ok(ResponseBuilder::not_found().body("Login session not found or expired"));
+    }
+    info!(?token_id, user = auth.id, "Approved npm web login");
+    Ok(ResponseBuilder::no_content().empty())
+}
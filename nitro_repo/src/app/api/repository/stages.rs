@@ -0,0 +1,290 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use nr_core::{
+    database::entities::stages::{DBStage, DBStageFile},
+    user::permissions::{HasPermissions, RepositoryActions},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        NitroRepo,
+        authentication::Authentication,
+        responses::{MissingPermission, RepositoryNotFound},
+    },
+    error::InternalError,
+    repository::{
+        DynRepository, StagingManager,
+        maven::{
+            MavenRepository,
+            hosted::MavenHosted,
+            nitro_deploy::{NRMavenPublishSuccessResponse, NewNRMavenDeploy},
+        },
+    },
+    utils::response_builder::ResponseBuilder,
+};
+pub fn stage_routes() -> Router<NitroRepo> {
+    Router::new()
+        .route("/{repository_id}/stages", get(list_stages))
+        .route(
+            "/{repository_id}/stages/{stage_id}",
+            get(get_stage).delete(drop_stage),
+        )
+        .route(
+            "/{repository_id}/stages/{stage_id}/close",
+            post(close_stage),
+        )
+        .route(
+            "/{repository_id}/stages/{stage_id}/promote",
+            post(promote_stage),
+        )
+}
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StageResponse {
+    #[serde(flatten)]
+    pub stage: DBStage,
+    /// The files that have been uploaded to the stage
+    pub files: Vec<DBStageFile>,
+}
+#[derive(Debug, Deserialize, Default, ToSchema)]
+#[serde(default)]
+pub struct PromoteStageRequest {
+    /// The repository to publish the stage into. Defaults to the repository the stage was created in
+    pub target_repository: Option<Uuid>,
+}
+/// Stages are only supported by Maven hosted repositories
+fn get_maven_hosted(site: &NitroRepo, repository: Uuid) -> Result<MavenHosted, Response> {
+    match site.get_repository(repository) {
+        Some(DynRepository::Maven(MavenRepository::Hosted(hosted))) => Ok(hosted),
+        Some(_) => Err(ResponseBuilder::bad_request().body("Repository does not support stages")),
+        None => Err(RepositoryNotFound::Uuid(repository).into_response()),
+    }
+}
+#[utoipa::path(
+    get,
+    path = "/{repository_id}/stages",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+    ),
+    responses(
+        (status = 200, description = "Stages of the repository", body = [DBStage]),
+        (status = 403, description = "Missing permission"),
+    )
+)]
+#[instrument]
+pub async fn list_stages(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path(repository): Path<Uuid>,
+) -> Result<Response, InternalError> {
+    if !auth
+        .has_action(RepositoryActions::Write, repository, &site.database)
+        .await?
+    {
+        return Ok(MissingPermission::EditRepository(repository).into_response());
+    }
+    let stages = DBStage::get_all_stages_for_repository(repository, &site.database).await?;
+    Ok(ResponseBuilder::ok().json(&stages))
+}
+#[utoipa::path(
+    get,
+    path = "/{repository_id}/stages/{stage_id}",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("stage_id" = Uuid, Path, description = "The Stage ID"),
+    ),
+    responses(
+        (status = 200, description = "The stage and its files", body = StageResponse),
+        (status = 403, description = "Missing permission"),
+        (status = 404, description = "Stage not found"),
+    )
+)]
+#[instrument]
+pub async fn get_stage(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, stage_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, InternalError> {
+    if !auth
+        .has_action(RepositoryActions::Write, repository, &site.database)
+        .await?
+    {
+        return Ok(MissingPermission::EditRepository(repository).into_response());
+    }
+    let Some(stage) = DBStage::get_stage_by_id(stage_id, repository, &site.database).await? else {
+        return Ok(ResponseBuilder::not_found().body("Stage not found"));
+    };
+    let files = stage.get_files(&site.database).await?;
+    Ok(ResponseBuilder::ok().json(&StageResponse { stage, files }))
+}
+/// Closes the stage. The files are verified and no more files can be uploaded.
+///
+/// Closed stages are not removed by the cleaner
+#[utoipa::path(
+    post,
+    path = "/{repository_id}/stages/{stage_id}/close",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("stage_id" = Uuid, Path, description = "The Stage ID"),
+    ),
+    responses(
+        (status = 200, description = "The closed stage", body = DBStage),
+        (status = 400, description = "The stage is missing files or a file does not match its hashes"),
+        (status = 403, description = "Missing permission"),
+        (status = 404, description = "Stage not found"),
+        (status = 409, description = "Stage is already closed"),
+    )
+)]
+#[instrument]
+pub async fn close_stage(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, stage_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, InternalError> {
+    if !auth
+        .has_action(RepositoryActions::Write, repository, &site.database)
+        .await?
+    {
+        return Ok(MissingPermission::EditRepository(repository).into_response());
+    }
+    let hosted = match get_maven_hosted(&site, repository) {
+        Ok(ok) => ok,
+        Err(response) => return Ok(response),
+    };
+    let Some(stage) = hosted.staging.get_stage(stage_id).await? else {
+        return Ok(ResponseBuilder::not_found().body("Stage not found"));
+    };
+    if stage.is_closed() {
+        return Ok(ResponseBuilder::conflict().body("Stage is already closed"));
+    }
+    let deploy = NewNRMavenDeploy::try_from(&stage)?;
+    if let Err(err) = hosted.verify_stage(&stage, &deploy).await? {
+        return Ok(ResponseBuilder::bad_request().body(err.to_string()));
+    }
+    let Some(stage) = stage.close(&site.database).await? else {
+        return Ok(ResponseBuilder::conflict().body("Stage is already closed"));
+    };
+    info!(?stage.id, "Stage closed");
+    Ok(ResponseBuilder::ok().json(&stage))
+}
+/// Publishes a closed stage. Optionally into a different Maven hosted repository
+#[utoipa::path(
+    post,
+    path = "/{repository_id}/stages/{stage_id}/promote",
+    request_body(content = Option<PromoteStageRequest>, description = "Optional. Without a body the stage is published into its own repository"),
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("stage_id" = Uuid, Path, description = "The Stage ID"),
+    ),
+    responses(
+        (status = 200, description = "The published files", body = NRMavenPublishSuccessResponse),
+        (status = 400, description = "The stage can not be published"),
+        (status = 403, description = "Missing permission or not a member of the project in the target repository"),
+        (status = 404, description = "Stage not found"),
+        (status = 409, description = "Stage is not closed, is already being promoted or the target repository does not allow overwriting the release"),
+    )
+)]
+#[instrument]
+pub async fn promote_stage(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, stage_id)): Path<(Uuid, Uuid)>,
+    request: Option<Json<PromoteStageRequest>>,
+) -> Result<Response, InternalError> {
+    let target = request
+        .and_then(|Json(request)| request.target_repository)
+        .unwrap_or(repository);
+    for repository in [repository, target] {
+        if !auth
+            .has_action(RepositoryActions::Write, repository, &site.database)
+            .await?
+        {
+            return Ok(MissingPermission::EditRepository(repository).into_response());
+        }
+    }
+    let target = match get_maven_hosted(&site, target) {
+        Ok(ok) => ok,
+        Err(response) => return Ok(response),
+    };
+    // Claimed before anything is published so concurrent promotes can not publish the same stage
+    let Some(stage) = DBStage::claim_for_promotion(stage_id, repository, &site.database).await?
+    else {
+        let Some(stage) = DBStage::get_stage_by_id(stage_id, repository, &site.database).await?
+        else {
+            return Ok(ResponseBuilder::not_found().body("Stage not found"));
+        };
+        if stage.is_promoting() {
+            return Ok(ResponseBuilder::conflict().body("Stage is already being promoted"));
+        }
+        return Ok(ResponseBuilder::conflict().body("Stage must be closed before it is promoted"));
+    };
+    let result = match NewNRMavenDeploy::try_from(&stage) {
+        Ok(deploy) => target
+            .publish_stage(&stage, &deploy, auth.id)
+            .await
+            .map_err(InternalError::from),
+        Err(err) => Err(err.into()),
+    };
+    match result {
+        Ok(Ok(published)) => {
+            info!(?stage.id, target = ?target.id, "Stage promoted");
+            Ok(ResponseBuilder::ok().json(&published))
+        }
+        Ok(Err(err)) => {
+            stage.release_promotion(&site.database).await?;
+            Ok(ResponseBuilder::default()
+                .status(err.status_code())
+                .body(err.to_string()))
+        }
+        Err(err) => {
+            stage.release_promotion(&site.database).await?;
+            Err(err)
+        }
+    }
+}
+/// Drops the stage and deletes its files
+#[utoipa::path(
+    delete,
+    path = "/{repository_id}/stages/{stage_id}",
+    params(
+        ("repository_id" = Uuid, Path, description = "The Repository ID"),
+        ("stage_id" = Uuid, Path, description = "The Stage ID"),
+    ),
+    responses(
+        (status = 204, description = "Stage dropped"),
+        (status = 403, description = "Missing permission"),
+        (status = 404, description = "Stage not found"),
+        (status = 409, description = "Stage is being promoted"),
+    )
+)]
+#[instrument]
+pub async fn drop_stage(
+    State(site): State<NitroRepo>,
+    auth: Authentication,
+    Path((repository, stage_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, InternalError> {
+    if !auth
+        .has_action(RepositoryActions::Write, repository, &site.database)
+        .await?
+    {
+        return Ok(MissingPermission::EditRepository(repository).into_response());
+    }
+    let Some(stage) = DBStage::get_stage_by_id(stage_id, repository, &site.database).await? else {
+        return Ok(ResponseBuilder::not_found().body("Stage not found"));
+    };
+    if stage.is_promoting() {
+        return Ok(ResponseBuilder::conflict().body("Stage is being promoted"));
+    }
+    StagingManager::new(repository, site.clone())
+        .delete_stage(&stage)
+        .await?;
+    info!(?stage.id, "Stage dropped");
+    Ok(ResponseBuilder::no_content().empty())
+}
@@ -44,9 +44,14 @@ use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 pub mod open_api;
 use crate::repository::{
-    DynRepository, RepositoryType, StagingConfig,
+    DynRepository, RepositoryType, StagingConfig, StagingManager,
+    cargo::CargoRegistryType,
+    docker::DockerRegistryType,
+    go::{GoRepositoryConfigType, GoRepositoryType},
+    helm::HelmRepositoryType,
     maven::{MavenPushRulesConfigType, MavenRepositoryConfigType, MavenRepositoryType},
-    npm::{NPMRegistryConfigType, NpmRegistryType},
+    npm::{NPMPublishRulesConfigType, NPMRegistryConfigType, NpmRegistryType},
+    python::PythonRepositoryType,
     repo_tracing::RepositoryMetricsMeter,
 };
 pub mod api;
@@ -107,6 +112,7 @@ impl From<(String, String)> for RepositoryStorageName {
 #[derive(Debug, Default)]
 pub struct InternalServices {
     pub session_cleaner: Option<JoinHandle<()>>,
+    pub stage_cleaner: Option<JoinHandle<()>>,
     pub email: Option<EmailService>,
 }
 pub struct NitroRepoInner {
@@ -136,6 +142,7 @@ macro_rules! take_service {
 impl NitroRepoInner {
     take_service! {
         take_session_cleaner => session_cleaner -> JoinHandle<()>,
+        take_stage_cleaner => stage_cleaner -> JoinHandle<()>,
         take_email => email -> EmailService
     }
     /// Notifies services that have waiters that the application is shutting down
@@ -368,6 +375,10 @@ impl NitroRepo {
         if let Some(handle) = session_cleaner {
             handle.abort();
         }
+        let stage_cleaner = self.inner.take_stage_cleaner();
+        if let Some(handle) = stage_cleaner {
+            handle.abort();
+        }
     }
     pub fn get_repository_config_type(
         &self,
@@ -478,6 +489,14 @@ impl NitroRepo {
             info!("Session cleaner started");
         }
     }
+    fn start_stage_cleaner(&self) {
+        let result = StagingManager::start_cleaner(self.clone());
+        if let Some(handle) = result {
+            let mut services = self.inner.services.lock();
+            services.stage_cleaner = Some(handle);
+            info!("Stage cleaner started");
+        }
+    }
 }
 
 pub type NitroRepoState = State<NitroRepo>;
@@ -488,5 +507,15 @@ pub static REPOSITORY_CONFIG_TYPES: &[&dyn RepositoryConfigType] = &[
     &MavenRepositoryConfigType,
     &MavenPushRulesConfigType,
     &NPMRegistryConfigType,
+    &NPMPublishRulesConfigType,
+    &GoRepositoryConfigType,
+];
+pub static REPOSITORY_TYPES: &[&dyn RepositoryType] = &[
+    &MavenRepositoryType,
+    &NpmRegistryType,
+    &CargoRegistryType,
+    &PythonRepositoryType,
+    &DockerRegistryType,
+    &GoRepositoryType,
+    &HelmRepositoryType,
 ];
-pub static REPOSITORY_TYPES: &[&dyn RepositoryType] = &[&MavenRepositoryType, &NpmRegistryType];
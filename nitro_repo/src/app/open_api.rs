@@ -2,6 +2,7 @@ use crate::app::api::project::ProjectRoutes;
 use crate::app::badge::BadgeRoutes;
 
 use super::api;
+use super::api::advisory::AdvisoryAPI;
 use super::api::repository::RepositoryAPI;
 use super::api::storage::StorageAPI;
 use super::api::user::UserAPI;
@@ -31,6 +32,7 @@ use utoipa::{Modify, OpenApi};
         (path = "/api/repository", api = RepositoryAPI, tags=["repository"]),
         (path="/badge", api = BadgeRoutes),
         (path="/api/project", api = ProjectRoutes, tags= ["project", "repository"]),
+        (path = "/api/advisory", api = AdvisoryAPI, tags=["advisory"]),
     ),
     paths(
         api::info,
@@ -55,6 +57,7 @@ use utoipa::{Modify, OpenApi};
         (name="repository",description= "Repository Management"),
         (name="config", description = "Repository Config Types"),
         (name="project", description = "Project Access"),
+        (name="advisory", description = "Security Advisories used by npm audit"),
     )
 )]
 pub struct ApiDoc;
@@ -45,6 +45,7 @@ pub enum MissingPermission {
     EditRepository(uuid::Uuid),
     ReadRepository(uuid::Uuid),
     StorageManager,
+    SystemManager,
 }
 impl IntoResponse for MissingPermission {
     #[inline(always)]
@@ -77,6 +78,10 @@ impl IntoResponse for MissingPermission {
                 .status(StatusCode::FORBIDDEN)
                 .body(Body::from("You are not a storage manager or admin"))
                 .unwrap(),
+            Self::SystemManager => Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::from("You are not a system manager or admin"))
+                .unwrap(),
         }
     }
 }
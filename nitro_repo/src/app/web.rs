@@ -64,6 +64,7 @@ pub(crate) async fn start(config_path: Option<PathBuf>) -> anyhow::Result<()> {
     .context("Unable to Initialize Website Core")?;
 
     site.start_session_cleaner();
+    site.start_stage_cleaner();
 
     let cloned_site = site.clone();
     let auth_layer = AuthenticationLayer::from(site.clone());
@@ -72,6 +73,7 @@ pub(crate) async fn start(config_path: Option<PathBuf>) -> anyhow::Result<()> {
         .nest("/storages", crate::repository::repository_router())
         .nest("/api", api::api_routes())
         .nest("/badge", super::badge::badge_routes())
+        .merge(crate::repository::docker::v2_router())
         .fallback(super::frontend::frontend_request)
         .with_state(site.clone());
 
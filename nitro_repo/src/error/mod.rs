@@ -76,6 +76,7 @@ default_internal_errors!(
     "database" -> sqlx::Error,
     "database" -> DBError,
     "json" -> serde_json::Error,
+    "yaml" -> serde_yaml::Error,
     "argon2" -> argon2::Error,
     "argon2" -> argon2::password_hash::Error,
     "repository-config" -> RepositoryConfigError,
@@ -0,0 +1,605 @@
+use super::{
+    CargoRegistryError,
+    request::{CargoPath, CargoSearchRequest},
+    types::{
+        CargoErrors, CargoUser, IndexConfig, IndexEntry, OkResponse, OwnersRequest, OwnersResponse,
+        PublishResponse, PublishWarnings, SearchCrate, SearchMeta, SearchResponse,
+        normalize_crate_name, read_publish_body, validate_crate_name,
+    },
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        maven::get_release_type,
+        utils::{RepositoryExt, json_response, registry_url},
+    },
+};
+use axum::response::Response;
+use derive_more::derive::Deref;
+use http::{StatusCode, header::CONTENT_TYPE};
+use nr_core::{
+    database::entities::{
+        project::{
+            DBProject, DBProjectMember, NewProject, NewProjectMember, ProjectMemberUser,
+            update::UpdateProjectVersion,
+            versions::{DBProjectVersion, NewVersion},
+        },
+        repository::DBRepository,
+        user::{UserSafeData, UserType},
+    },
+    repository::Visibility,
+    storage::StoragePath,
+    user::permissions::{HasPermissions, RepositoryActions},
+};
+use nr_storage::{DynStorage, FileContent, Storage};
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+use tracing::{debug, error, info, instrument, warn};
+
+#[derive(derive_more::Debug)]
+pub struct CargoHostedInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: uuid::Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+}
+/// A Cargo Registry that hosts its own crates
+///
+/// Crates are saved at `crates/{name}/{version}/{name}-{version}.crate`.
+/// The index is built from the versions in the database
+#[derive(Debug, Clone, Deref)]
+pub struct CargoHosted(Arc<CargoHostedInner>);
+/// Cargo shows the `detail` of errors in this format to the user
+fn error_response(status: StatusCode, detail: impl Into<String>) -> RepoResponse {
+    let body = serde_json::to_string(&CargoErrors::new(detail)).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .into()
+}
+fn crate_not_found(name: &str) -> RepoResponse {
+    error_response(
+        StatusCode::NOT_FOUND,
+        format!("Crate {} not found in repository", name),
+    )
+}
+fn version_not_found(name: &str, version: &str) -> RepoResponse {
+    error_response(
+        StatusCode::NOT_FOUND,
+        format!("Version {} not found in crate {}", version, name),
+    )
+}
+impl CargoHosted {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(CargoHostedInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+        })))
+    }
+    /// Checks that the user can change the crate.
+    ///
+    /// Admins and system managers can change every crate. Everyone else must be an owner of the crate
+    async fn can_modify_crate(
+        &self,
+        user: &UserSafeData,
+        project: &DBProject,
+        manage: bool,
+    ) -> Result<bool, CargoRegistryError> {
+        if user.is_admin_or_system_manager() {
+            return Ok(true);
+        }
+        let members =
+            DBProjectMember::get_members_with_users(project.id, self.site.as_ref()).await?;
+        let member = members.iter().find(|member| member.user_id == user.id);
+        Ok(member.is_some_and(|member| {
+            if manage {
+                member.can_manage
+            } else {
+                member.can_write
+            }
+        }))
+    }
+    /// Finds the crate. `-` and `_` are treated as the same character like crates.io
+    async fn get_crate(&self, name: &str) -> Result<Option<DBProject>, CargoRegistryError> {
+        Ok(self
+            .get_project_from_key(&normalize_crate_name(name))
+            .await?)
+    }
+    fn index_config(&self, request: &RepositoryRequest) -> IndexConfig {
+        let storage_config = self.storage.storage_config();
+        let registry_url = registry_url(
+            &self.site,
+            &request.parts,
+            &storage_config.storage_config.storage_name,
+            &self.repository.name,
+        );
+        IndexConfig {
+            dl: format!("{}/api/v1/crates", registry_url),
+            api: registry_url,
+            auth_required: self.visibility().is_private(),
+        }
+    }
+    /// The index file of the crate. One JSON line per version. Ordered by when the version was published
+    #[instrument]
+    async fn get_index_file(&self, name: &str) -> Result<Option<String>, CargoRegistryError> {
+        let Some(project) = self.get_crate(name).await? else {
+            return Ok(None);
+        };
+        let mut versions =
+            DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        versions.sort_by_key(|version| version.created_at);
+        let mut lines = Vec::with_capacity(versions.len());
+        for version in versions {
+            let Some(extra) = version.extra.0.extra else {
+                warn!(?version, "Invalid Cargo Crate");
+                continue;
+            };
+            match serde_json::from_value::<IndexEntry>(extra) {
+                Ok(entry) => lines.push(serde_json::to_string(&entry)?),
+                Err(err) => warn!(?err, ?version.version, "Invalid Cargo Crate"),
+            }
+        }
+        if lines.is_empty() {
+            return Ok(None);
+        }
+        let mut index = lines.join("\n");
+        index.push('\n');
+        Ok(Some(index))
+    }
+    #[instrument]
+    async fn handle_download(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let Some(project) = self.get_crate(name).await? else {
+            return Ok(crate_not_found(name));
+        };
+        let Some(version) = self.get_project_version(project.id, version).await? else {
+            return Ok(version_not_found(name, version));
+        };
+        let mut storage_path = StoragePath::from(version.version_path.as_str());
+        storage_path.push_mut(&format!("{}-{}.crate", project.name, version.version));
+        debug!(?storage_path, "Getting crate");
+        let file = self.storage.open_file(self.id, &storage_path).await?;
+        Ok(RepoResponse::from(file))
+    }
+    /// `cargo search`
+    #[instrument]
+    async fn handle_search(
+        &self,
+        request: &RepositoryRequest,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let search_request = CargoSearchRequest::from_query(request.parts.uri.query());
+        let (projects, total) = search_request
+            .project_search()
+            .search(
+                self.id,
+                search_request.per_page,
+                search_request.offset(),
+                self.site.as_ref(),
+            )
+            .await?;
+        let crates = projects
+            .into_iter()
+            .filter_map(|project| {
+                let max_version = project.latest_release.or(project.latest_pre_release)?;
+                Some(SearchCrate {
+                    name: project.name,
+                    max_version,
+                    description: project.description,
+                })
+            })
+            .collect();
+        json_response(
+            StatusCode::OK,
+            &SearchResponse {
+                crates,
+                meta: SearchMeta { total },
+            },
+        )
+    }
+    /// `cargo publish`
+    #[instrument]
+    async fn handle_publish(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        let body = request.body.body_as_bytes().await?;
+        let (metadata, crate_file) = read_publish_body(body)?;
+        validate_crate_name(&metadata.name)?;
+        if semver::Version::parse(&metadata.vers).is_err() {
+            return Err(CargoRegistryError::InvalidVersion(metadata.vers));
+        }
+        let existing = self.get_crate(&metadata.name).await?;
+        if let Some(project) = &existing {
+            if !self.can_modify_crate(user, project, false).await? {
+                return Ok(error_response(
+                    StatusCode::FORBIDDEN,
+                    format!("You are not an owner of {}", project.name),
+                ));
+            }
+            if project.name != metadata.name {
+                return Ok(error_response(
+                    StatusCode::CONFLICT,
+                    format!("Crate was previously named {}", project.name),
+                ));
+            }
+            if self
+                .get_project_version(project.id, &metadata.vers)
+                .await?
+                .is_some()
+            {
+                return Ok(error_response(
+                    StatusCode::CONFLICT,
+                    format!(
+                        "Crate {}@{} has already been published",
+                        project.name, metadata.vers
+                    ),
+                ));
+            }
+        }
+        let cksum = format!("{:x}", Sha256::digest(&crate_file));
+        let entry = metadata.index_entry(cksum);
+        let version_data = metadata.version_data(&entry)?;
+
+        let project_path = StoragePath::from(format!("crates/{}", metadata.name.to_lowercase()));
+        let mut version_path = project_path.clone();
+        version_path.push_mut(&metadata.vers);
+        let mut crate_path = version_path.clone();
+        crate_path.push_mut(&format!("{}-{}.crate", metadata.name, metadata.vers));
+        self.storage
+            .save_file(self.id, FileContent::Bytes(crate_file), &crate_path)
+            .await?;
+
+        let project = match existing {
+            Some(project) => {
+                if project.description != metadata.description || project.tags != metadata.keywords
+                {
+                    DBProject::update_description_and_tags(
+                        project.id,
+                        metadata.description.as_deref(),
+                        &metadata.keywords,
+                        self.site.as_ref(),
+                    )
+                    .await?;
+                }
+                project
+            }
+            None => {
+                let project = NewProject {
+                    scope: None,
+                    project_key: normalize_crate_name(&metadata.name),
+                    name: metadata.name.clone(),
+                    latest_release: None,
+                    latest_pre_release: None,
+                    description: metadata.description.clone(),
+                    tags: metadata.keywords.clone(),
+                    repository: self.id,
+                    storage_path: project_path.to_string(),
+                }
+                .insert(self.site.as_ref())
+                .await?;
+                NewProjectMember::new_owner(user.id, project.id)
+                    .insert_no_return(self.site.as_ref())
+                    .await?;
+                info!(?project, "Created new crate");
+                project
+            }
+        };
+        NewVersion {
+            project_id: project.id,
+            version: metadata.vers.clone(),
+            release_type: get_release_type(&metadata.vers),
+            version_path: version_path.to_string(),
+            publisher: Some(user.id),
+            version_page: None,
+            extra: version_data,
+        }
+        .insert(self.site.as_ref())
+        .await?;
+        info!(name = ?metadata.name, version = ?metadata.vers, "Published crate");
+        // Nitro Repo does not have a fixed list of categories or badges
+        json_response(
+            StatusCode::OK,
+            &PublishResponse {
+                warnings: PublishWarnings::default(),
+            },
+        )
+    }
+    /// `cargo yank` and `cargo yank --undo`
+    #[instrument]
+    async fn handle_yank(
+        &self,
+        request: RepositoryRequest,
+        name: &str,
+        version: &str,
+        yanked: bool,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        let Some(project) = self.get_crate(name).await? else {
+            return Ok(crate_not_found(name));
+        };
+        if !self.can_modify_crate(user, &project, false).await? {
+            return Ok(error_response(
+                StatusCode::FORBIDDEN,
+                format!("You are not an owner of {}", project.name),
+            ));
+        }
+        let Some(db_version) = self.get_project_version(project.id, version).await? else {
+            return Ok(version_not_found(name, version));
+        };
+        let mut version_data = db_version.extra.0.clone();
+        let Some(extra) = version_data.extra.take() else {
+            warn!(?version, "Invalid Cargo Crate");
+            return Ok(version_not_found(name, version));
+        };
+        let mut entry: IndexEntry = serde_json::from_value(extra)?;
+        if entry.yanked != yanked {
+            entry.yanked = yanked;
+            version_data.extra = Some(serde_json::to_value(&entry)?);
+            UpdateProjectVersion {
+                extra: Some(version_data),
+                ..Default::default()
+            }
+            .update(db_version.id, self.site.as_ref())
+            .await?;
+            info!(?name, ?version, ?yanked, "Updated yanked");
+        }
+        json_response(StatusCode::OK, &OkResponse::new(None))
+    }
+    /// `cargo owner --list`
+    #[instrument]
+    async fn handle_list_owners(&self, name: &str) -> Result<RepoResponse, CargoRegistryError> {
+        let Some(project) = self.get_crate(name).await? else {
+            return Ok(crate_not_found(name));
+        };
+        let users = DBProjectMember::get_members_with_users(project.id, self.site.as_ref())
+            .await?
+            .into_iter()
+            .filter(|member| member.can_manage)
+            .map(|member| CargoUser {
+                id: member.user_id,
+                login: member.username,
+                name: Some(member.name),
+            })
+            .collect();
+        json_response(StatusCode::OK, &OwnersResponse { users })
+    }
+    /// `cargo owner --add` and `cargo owner --remove`
+    ///
+    /// The last owner of a crate can not be removed
+    #[instrument]
+    async fn handle_change_owners(
+        &self,
+        request: RepositoryRequest,
+        name: &str,
+        add: bool,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        let Some(project) = self.get_crate(name).await? else {
+            return Ok(crate_not_found(name));
+        };
+        if !self.can_modify_crate(user, &project, true).await? {
+            return Ok(error_response(
+                StatusCode::FORBIDDEN,
+                format!("You are not an owner of {}", project.name),
+            ));
+        }
+        let body = request.body.body_as_bytes().await?;
+        let OwnersRequest { users } = serde_json::from_slice(&body)?;
+        let members: Vec<ProjectMemberUser> =
+            DBProjectMember::get_members_with_users(project.id, self.site.as_ref()).await?;
+        let mut changed = Vec::with_capacity(users.len());
+        let mut owners = members.iter().filter(|member| member.can_manage).count();
+        for username in users {
+            let Some(owner) =
+                UserSafeData::get_by_username_or_email(&username, self.site.as_ref()).await?
+            else {
+                return Ok(error_response(
+                    StatusCode::NOT_FOUND,
+                    format!("User {} not found", username),
+                ));
+            };
+            let is_member = members.iter().any(|member| member.user_id == owner.id);
+            if add {
+                if is_member {
+                    continue;
+                }
+                NewProjectMember::new_owner(owner.id, project.id)
+                    .insert_no_return(self.site.as_ref())
+                    .await?;
+            } else {
+                if !is_member {
+                    continue;
+                }
+                if owners <= 1 {
+                    return Ok(error_response(
+                        StatusCode::BAD_REQUEST,
+                        format!("Can not remove the last owner of {}", project.name),
+                    ));
+                }
+                DBProjectMember::delete_member(project.id, owner.id, self.site.as_ref()).await?;
+                owners -= 1;
+            }
+            changed.push(username);
+        }
+        info!(?name, ?changed, ?add, "Changed owners");
+        let msg = if add {
+            format!("Added {} as owners of {}", changed.join(", "), project.name)
+        } else {
+            format!(
+                "Removed {} as owners of {}",
+                changed.join(", "),
+                project.name
+            )
+        };
+        json_response(StatusCode::OK, &OkResponse::new(Some(msg)))
+    }
+}
+impl RepositoryExt for CargoHosted {}
+impl Repository for CargoHosted {
+    type Error = CargoRegistryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "cargo"
+    }
+    fn full_type(&self) -> &'static str {
+        "cargo/hosted"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "cargo/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        let path_as_string = request.path.to_string();
+        debug!(?path_as_string, "Handling Cargo GET request");
+        // Private repositories require authentication for everything. Including the index.
+        if let Some(err) = self.check_read(&request.authentication, "Cargo").await? {
+            return Ok(err);
+        }
+        let Some(path) = CargoPath::parse(&path_as_string) else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            ));
+        };
+        match path {
+            CargoPath::Config => json_response(StatusCode::OK, &self.index_config(&request)),
+            CargoPath::Index { name } => {
+                let Some(index) = self.get_index_file(&name).await? else {
+                    return Ok(crate_not_found(&name));
+                };
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "text/plain")
+                    .body(index.into())
+                    .into())
+            }
+            CargoPath::Download { name, version } => self.handle_download(&name, &version).await,
+            CargoPath::Search => {
+                if let Some(err) = self.indexing_check(&request.authentication).await? {
+                    return Ok(err);
+                }
+                self.handle_search(&request).await
+            }
+            CargoPath::Owners { name } => self.handle_list_owners(&name).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_put(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        match CargoPath::parse(&request.path.to_string()) {
+            Some(CargoPath::Publish) => self.handle_publish(request).await,
+            Some(CargoPath::Unyank { name, version }) => {
+                self.handle_yank(request, &name, &version, false).await
+            }
+            Some(CargoPath::Owners { name }) => {
+                self.handle_change_owners(request, &name, true).await
+            }
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_delete(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, CargoRegistryError> {
+        match CargoPath::parse(&request.path.to_string()) {
+            Some(CargoPath::Yank { name, version }) => {
+                self.handle_yank(request, &name, &version, true).await
+            }
+            Some(CargoPath::Owners { name }) => {
+                self.handle_change_owners(request, &name, false).await
+            }
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Cargo Registry Implementation
+//!
+//! Implements the sparse index and the Web API used by `cargo publish`, `cargo yank`, `cargo owner`, and `cargo search`.
+//!
+//! Documentation for Cargo: https://doc.rust-lang.org/cargo/reference/registries.html
+
+use ahash::HashMap;
+use futures::future::BoxFuture;
+use hosted::CargoHosted;
+use nr_core::database::entities::repository::DBRepository;
+use nr_macros::DynRepositoryHandler;
+use nr_storage::DynStorage;
+use tracing::debug;
+use types::{CargoErrors, InvalidCrateName};
+
+pub mod hosted;
+pub mod request;
+pub mod types;
+use crate::{
+    app::authentication::AuthenticationError,
+    error::{BadRequestErrors, IntoErrorResponse},
+};
+
+pub use super::prelude::*;
+use super::{DynRepository, NewRepository, RepositoryType, RepositoryTypeDescription};
+
+#[derive(Debug, Clone, DynRepositoryHandler)]
+#[repository_handler(error=CargoRegistryError)]
+pub enum CargoRegistry {
+    Hosted(CargoHosted),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CargoRegistryError {
+    #[error(transparent)]
+    InvalidCrateName(#[from] InvalidCrateName),
+    #[error("Invalid publish request. {0}")]
+    InvalidPublishBody(&'static str),
+    #[error("Invalid version {0}. Versions must be valid semver")]
+    InvalidVersion(String),
+    #[error("{0}")]
+    Other(Box<dyn IntoErrorResponse>),
+}
+impl From<CargoRegistryError> for RepositoryHandlerError {
+    fn from(err: CargoRegistryError) -> Self {
+        RepositoryHandlerError::Other(Box::new(err))
+    }
+}
+macro_rules! impl_from_error_for_other {
+    ($t:ty) => {
+        impl From<$t> for CargoRegistryError {
+            fn from(e: $t) -> Self {
+                CargoRegistryError::Other(Box::new(e))
+            }
+        }
+    };
+}
+impl_from_error_for_other!(BadRequestErrors);
+impl_from_error_for_other!(sqlx::Error);
+impl_from_error_for_other!(serde_json::Error);
+impl_from_error_for_other!(std::io::Error);
+impl_from_error_for_other!(AuthenticationError);
+impl_from_error_for_other!(RepositoryHandlerError);
+impl_from_error_for_other!(nr_storage::StorageError);
+
+impl IntoErrorResponse for CargoRegistryError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
+
+impl From<CargoRegistryError> for DynRepositoryHandlerError {
+    fn from(err: CargoRegistryError) -> Self {
+        DynRepositoryHandlerError(Box::new(err))
+    }
+}
+
+impl IntoResponse for CargoRegistryError {
+    fn into_response(self) -> Response {
+        match self {
+            CargoRegistryError::Other(other) => other.into_response_boxed(),
+            bad_request => {
+                debug!("Bad Request: {:?}", bad_request);
+                // Cargo only shows the error to the user if it is in this format
+                let body = serde_json::to_string(&CargoErrors::new(bad_request.to_string()))
+                    .unwrap_or_default();
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(body.into())
+                    .unwrap()
+            }
+        }
+    }
+}
+#[derive(Debug, Default)]
+pub struct CargoRegistryType;
+
+impl RepositoryType for CargoRegistryType {
+    fn get_type(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn get_description(&self) -> RepositoryTypeDescription {
+        RepositoryTypeDescription {
+            type_name: "cargo",
+            name: "Cargo",
+            description: "A Cargo Registry using the sparse index",
+            documentation_url: Some("https://nitro-repo.kingtux.dev/repositoryTypes/cargo/"),
+            is_stable: false,
+            required_configs: vec![],
+        }
+    }
+
+    fn create_new(
+        &self,
+        name: String,
+        uuid: uuid::Uuid,
+        configs: HashMap<String, serde_json::Value>,
+        storage: nr_storage::DynStorage,
+    ) -> BoxFuture<'static, Result<NewRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            Ok(NewRepository {
+                name,
+                uuid,
+                repository_type: "cargo".to_string(),
+                configs,
+            })
+        })
+    }
+
+    fn load_repo(
+        &self,
+        repo: DBRepository,
+        storage: DynStorage,
+        website: NitroRepo,
+    ) -> BoxFuture<'static, Result<DynRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let hosted = CargoHosted::load(website, storage, repo).await?;
+            Ok(CargoRegistry::Hosted(hosted).into())
+        })
+    }
+}
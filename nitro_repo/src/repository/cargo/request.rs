@@ -0,0 +1,166 @@
+//! Routes of the Cargo Registry
+//!
+//! The index is at `index/` and the Web API is at `api/v1/`
+use nr_core::database::entities::project::search::ProjectSearchQuery;
+
+/// The max number of crates returned by a search. Same as crates.io
+const MAX_PER_PAGE: i64 = 100;
+const DEFAULT_PER_PAGE: i64 = 10;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CargoPath {
+    /// `index/config.json`
+    Config,
+    /// `index/{prefix}/{name}`. The prefix is not checked
+    Index { name: String },
+    /// `api/v1/crates/{name}/{version}/download`
+    Download { name: String, version: String },
+    /// `api/v1/crates`
+    Search,
+    /// `api/v1/crates/new`
+    Publish,
+    /// `api/v1/crates/{name}/{version}/yank`
+    Yank { name: String, version: String },
+    /// `api/v1/crates/{name}/{version}/unyank`
+    Unyank { name: String, version: String },
+    /// `api/v1/crates/{name}/owners`
+    Owners { name: String },
+}
+impl CargoPath {
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path.trim_matches('/');
+        if let Some(index) = path.strip_prefix("index/") {
+            if index == "config.json" {
+                return Some(Self::Config);
+            }
+            let name = index.rsplit('/').next()?;
+            if name.is_empty() {
+                return None;
+            }
+            return Some(Self::Index {
+                name: name.to_owned(),
+            });
+        }
+        let crates = path.strip_prefix("api/v1/crates")?;
+        let parts: Vec<&str> = crates.split('/').filter(|part| !part.is_empty()).collect();
+        let path = match parts.as_slice() {
+            [] => Self::Search,
+            ["new"] => Self::Publish,
+            [name, "owners"] => Self::Owners {
+                name: (*name).to_owned(),
+            },
+            [name, version, action] => {
+                let (name, version) = ((*name).to_owned(), (*version).to_owned());
+                match *action {
+                    "download" => Self::Download { name, version },
+                    "yank" => Self::Yank { name, version },
+                    "unyank" => Self::Unyank { name, version },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(path)
+    }
+}
+/// `cargo search {query} --limit {per_page}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoSearchRequest {
+    pub q: String,
+    pub per_page: i64,
+    pub page: i64,
+}
+impl Default for CargoSearchRequest {
+    fn default() -> Self {
+        Self {
+            q: String::new(),
+            per_page: DEFAULT_PER_PAGE,
+            page: 1,
+        }
+    }
+}
+impl CargoSearchRequest {
+    pub fn from_query(query: Option<&str>) -> Self {
+        let mut request = Self::default();
+        let Some(query) = query else {
+            return request;
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "q" => request.q = value.into_owned(),
+                "per_page" => {
+                    if let Ok(per_page) = value.parse::<i64>() {
+                        request.per_page = per_page.clamp(1, MAX_PER_PAGE);
+                    }
+                }
+                "page" => {
+                    if let Ok(page) = value.parse::<i64>() {
+                        request.page = page.max(1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        request
+    }
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+    pub fn project_search(&self) -> ProjectSearchQuery {
+        ProjectSearchQuery {
+            terms: self.q.split_whitespace().map(str::to_owned).collect(),
+            ..Default::default()
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_paths() {
+        assert_eq!(
+            CargoPath::parse("index/config.json"),
+            Some(CargoPath::Config)
+        );
+        assert_eq!(
+            CargoPath::parse("index/se/rd/serde"),
+            Some(CargoPath::Index {
+                name: "serde".to_owned()
+            })
+        );
+        assert_eq!(CargoPath::parse("api/v1/crates"), Some(CargoPath::Search));
+        assert_eq!(
+            CargoPath::parse("api/v1/crates/new"),
+            Some(CargoPath::Publish)
+        );
+        assert_eq!(
+            CargoPath::parse("api/v1/crates/serde/1.0.0/download"),
+            Some(CargoPath::Download {
+                name: "serde".to_owned(),
+                version: "1.0.0".to_owned()
+            })
+        );
+        assert_eq!(
+            CargoPath::parse("api/v1/crates/serde/1.0.0/unyank"),
+            Some(CargoPath::Unyank {
+                name: "serde".to_owned(),
+                version: "1.0.0".to_owned()
+            })
+        );
+        assert_eq!(
+            CargoPath::parse("api/v1/crates/serde/owners"),
+            Some(CargoPath::Owners {
+                name: "serde".to_owned()
+            })
+        );
+        assert_eq!(CargoPath::parse("api/v1/crates/serde/1.0.0/other"), None);
+        assert_eq!(CargoPath::parse("other"), None);
+    }
+    #[test]
+    fn test_search_request() {
+        let request = CargoSearchRequest::from_query(Some("q=serde+json&per_page=500&page=3"));
+        assert_eq!(request.q, "serde json");
+        assert_eq!(request.per_page, MAX_PER_PAGE);
+        assert_eq!(request.offset(), 200);
+        assert_eq!(request.project_search().terms, vec!["serde", "json"]);
+    }
+}
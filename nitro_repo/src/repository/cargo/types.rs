@@ -0,0 +1,440 @@
+//! Types for the sparse index and the Web API
+//!
+//! https://doc.rust-lang.org/cargo/reference/registry-index.html
+//! https://doc.rust-lang.org/cargo/reference/registry-web-api.html
+use ahash::HashMap;
+use bytes::Bytes;
+use nr_core::repository::project::{Author, Licence, ProjectSource, VersionData};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::CargoRegistryError;
+/// The max length of a crate name. Same as crates.io
+const MAX_CRATE_NAME_LENGTH: usize = 64;
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InvalidCrateName {
+    #[error("Crate names can not be empty")]
+    Empty,
+    #[error("Crate names can not be longer than {MAX_CRATE_NAME_LENGTH} characters")]
+    TooLong,
+    #[error("Crate names must start with a letter")]
+    InvalidStart,
+    #[error("Crate names can only contain letters, numbers, `-`, and `_`. Found `{0}`")]
+    InvalidCharacter(char),
+}
+/// Checks the name follows the same rules as crates.io
+pub fn validate_crate_name(name: &str) -> Result<(), InvalidCrateName> {
+    let Some(first) = name.chars().next() else {
+        return Err(InvalidCrateName::Empty);
+    };
+    if name.len() > MAX_CRATE_NAME_LENGTH {
+        return Err(InvalidCrateName::TooLong);
+    }
+    if !first.is_ascii_alphabetic() {
+        return Err(InvalidCrateName::InvalidStart);
+    }
+    if let Some(invalid) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(InvalidCrateName::InvalidCharacter(invalid));
+    }
+    Ok(())
+}
+/// The key of the crate's project. Lowercased with `-` replaced by `_`
+///
+/// crates.io treats `my-crate` and `My_Crate` as the same crate
+pub fn normalize_crate_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+/// The path of the crate's file in the index. Relative to the index root
+///
+/// - `1/{name}` for names with one character
+/// - `2/{name}` for names with two characters
+/// - `3/{first-char}/{name}` for names with three characters
+/// - `{first-two}/{second-two}/{name}` for everything else
+pub fn index_file_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+/// `index/config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// Where crates are downloaded from. Cargo appends `/{crate}/{version}/download`
+    pub dl: String,
+    /// The root of the Web API
+    pub api: String,
+    /// If cargo must send the token with every request. Set for private registries
+    #[serde(rename = "auth-required")]
+    pub auth_required: bool,
+}
+/// A line of an index file. One per version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub vers: String,
+    pub deps: Vec<IndexDependency>,
+    /// Hex encoded sha256 of the `.crate` file
+    pub cksum: String,
+    pub features: HashMap<String, Vec<String>>,
+    /// Features using the `dep:` or `?` syntax. Older versions of cargo can not read them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features2: Option<HashMap<String, Vec<String>>>,
+    pub yanked: bool,
+    #[serde(default)]
+    pub links: Option<String>,
+    /// Set to 2 if `features2` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_version: Option<String>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexDependency {
+    /// The name used in the `Cargo.toml`. If the dependency was renamed this is the new name
+    pub name: String,
+    pub req: String,
+    pub features: Vec<String>,
+    pub optional: bool,
+    pub default_features: bool,
+    pub target: Option<String>,
+    pub kind: Option<String>,
+    /// The index the dependency is from. None if it is from this registry
+    pub registry: Option<String>,
+    /// The actual name of the crate if the dependency was renamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+}
+/// The JSON sent by `cargo publish`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishMetadata {
+    pub name: String,
+    pub vers: String,
+    #[serde(default)]
+    pub deps: Vec<PublishDependency>,
+    #[serde(default)]
+    pub features: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub documentation: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub readme: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub links: Option<String>,
+    #[serde(default)]
+    pub rust_version: Option<String>,
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishDependency {
+    /// The actual name of the crate
+    pub name: String,
+    pub version_req: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default = "default_true")]
+    pub default_features: bool,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub registry: Option<String>,
+    /// Set if the dependency was renamed in the `Cargo.toml`
+    #[serde(default)]
+    pub explicit_name_in_toml: Option<String>,
+}
+fn default_true() -> bool {
+    true
+}
+impl From<&PublishDependency> for IndexDependency {
+    fn from(dependency: &PublishDependency) -> Self {
+        let (name, package) = match &dependency.explicit_name_in_toml {
+            Some(renamed) => (renamed.clone(), Some(dependency.name.clone())),
+            None => (dependency.name.clone(), None),
+        };
+        Self {
+            name,
+            req: dependency.version_req.clone(),
+            features: dependency.features.clone(),
+            optional: dependency.optional,
+            default_features: dependency.default_features,
+            target: dependency.target.clone(),
+            kind: dependency.kind.clone(),
+            registry: dependency.registry.clone(),
+            package,
+        }
+    }
+}
+impl PublishMetadata {
+    /// Builds the index entry for the published version
+    pub fn index_entry(&self, cksum: String) -> IndexEntry {
+        // Same as crates.io. Features with the new syntax are moved to `features2`
+        let (features2, features): (HashMap<_, _>, HashMap<_, _>) =
+            self.features.clone().into_iter().partition(|(_, values)| {
+                values
+                    .iter()
+                    .any(|value| value.starts_with("dep:") || value.contains("?/"))
+            });
+        let features2 = (!features2.is_empty()).then_some(features2);
+        IndexEntry {
+            name: self.name.clone(),
+            vers: self.vers.clone(),
+            deps: self.deps.iter().map(IndexDependency::from).collect(),
+            cksum,
+            features,
+            v: features2.as_ref().map(|_| 2),
+            features2,
+            yanked: false,
+            links: self.links.clone(),
+            rust_version: self.rust_version.clone(),
+        }
+    }
+    /// The version data saved to the database. The index entry is kept in `extra`
+    pub fn version_data(&self, entry: &IndexEntry) -> Result<VersionData, serde_json::Error> {
+        Ok(VersionData {
+            documentation_url: self.documentation.clone(),
+            website: self.homepage.clone(),
+            authors: self
+                .authors
+                .iter()
+                .map(String::as_str)
+                .map(parse_author)
+                .collect(),
+            description: self.description.clone(),
+            source: self.repository.clone().map(|url| ProjectSource::Git {
+                url,
+                branch: None,
+                commit: None,
+            }),
+            licence: self.license.clone().map(Licence::Simple),
+            extra: Some(serde_json::to_value(entry)?),
+        })
+    }
+}
+/// Parses the `authors` of a `Cargo.toml`. Such as `Name <email@example.com>`
+pub fn parse_author(author: &str) -> Author {
+    let (name, email) = match author.split_once('<') {
+        Some((name, email)) => (name.trim(), Some(email.trim_end_matches('>').trim())),
+        None => (author.trim(), None),
+    };
+    Author {
+        name: (!name.is_empty()).then(|| name.to_owned()),
+        email: email.filter(|email| !email.is_empty()).map(str::to_owned),
+        website: None,
+    }
+}
+/// Reads the body of `cargo publish`.
+///
+/// The body is the length of the JSON metadata as a u32 (little endian), the metadata, the length of the `.crate` file, and the `.crate` file
+pub fn read_publish_body(body: Bytes) -> Result<(PublishMetadata, Bytes), CargoRegistryError> {
+    fn read_length(body: &Bytes, offset: usize) -> Result<usize, CargoRegistryError> {
+        let bytes = body
+            .get(offset..offset + 4)
+            .ok_or(CargoRegistryError::InvalidPublishBody("Missing length"))?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    }
+    let metadata_length = read_length(&body, 0)?;
+    let metadata_end = 4 + metadata_length;
+    let metadata = body
+        .get(4..metadata_end)
+        .ok_or(CargoRegistryError::InvalidPublishBody(
+            "Metadata is too short",
+        ))?;
+    let metadata: PublishMetadata = serde_json::from_slice(metadata)?;
+    let crate_length = read_length(&body, metadata_end)?;
+    let crate_start = metadata_end + 4;
+    if body.len() < crate_start + crate_length {
+        return Err(CargoRegistryError::InvalidPublishBody(
+            "Crate file is too short",
+        ));
+    }
+    let crate_file = body.slice(crate_start..crate_start + crate_length);
+    Ok((metadata, crate_file))
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishResponse {
+    pub warnings: PublishWarnings,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishWarnings {
+    pub invalid_categories: Vec<String>,
+    pub invalid_badges: Vec<String>,
+    pub other: Vec<String>,
+}
+/// Response of yank, unyank, and changing owners
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OkResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg: Option<String>,
+}
+impl OkResponse {
+    pub fn new(msg: Option<String>) -> Self {
+        Self { ok: true, msg }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnersResponse {
+    pub users: Vec<CargoUser>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CargoUser {
+    pub id: i32,
+    pub login: String,
+    pub name: Option<String>,
+}
+/// Body of adding or removing owners. The users are usernames
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnersRequest {
+    pub users: Vec<String>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResponse {
+    pub crates: Vec<SearchCrate>,
+    pub meta: SearchMeta,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchCrate {
+    pub name: String,
+    pub max_version: String,
+    pub description: Option<String>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMeta {
+    pub total: i64,
+}
+/// Cargo shows the `detail` of every error to the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CargoErrors {
+    pub errors: Vec<CargoErrorDetail>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CargoErrorDetail {
+    pub detail: String,
+}
+impl CargoErrors {
+    pub fn new(detail: impl Into<String>) -> Self {
+        Self {
+            errors: vec![CargoErrorDetail {
+                detail: detail.into(),
+            }],
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_index_file_path() {
+        assert_eq!(index_file_path("a"), "1/a");
+        assert_eq!(index_file_path("ab"), "2/ab");
+        assert_eq!(index_file_path("abc"), "3/a/abc");
+        assert_eq!(index_file_path("Serde_JSON"), "se/rd/serde_json");
+    }
+    #[test]
+    fn test_validate_crate_name() {
+        assert!(validate_crate_name("serde_json").is_ok());
+        assert!(validate_crate_name("my-crate2").is_ok());
+        assert_eq!(validate_crate_name(""), Err(InvalidCrateName::Empty));
+        assert_eq!(
+            validate_crate_name("2crate"),
+            Err(InvalidCrateName::InvalidStart)
+        );
+        assert_eq!(
+            validate_crate_name("my.crate"),
+            Err(InvalidCrateName::InvalidCharacter('.'))
+        );
+    }
+    #[test]
+    fn test_normalize_crate_name() {
+        assert_eq!(normalize_crate_name("My-Crate"), "my_crate");
+        assert_eq!(
+            normalize_crate_name("my_crate"),
+            normalize_crate_name("my-crate")
+        );
+    }
+    #[test]
+    fn test_parse_author() {
+        let author = parse_author("Example <example@example.com>");
+        assert_eq!(author.name.as_deref(), Some("Example"));
+        assert_eq!(author.email.as_deref(), Some("example@example.com"));
+        let author = parse_author("Example");
+        assert_eq!(author.name.as_deref(), Some("Example"));
+        assert_eq!(author.email, None);
+    }
+    #[test]
+    fn test_read_publish_body() {
+        let metadata = json!({
+            "name": "mycrate",
+            "vers": "0.1.0",
+            "deps": [
+                {
+                    "name": "serde",
+                    "version_req": "^1.0",
+                    "features": ["derive"],
+                    "optional": true,
+                    "default_features": true,
+                    "target": null,
+                    "kind": "normal",
+                    "registry": "https://github.com/rust-lang/crates.io-index",
+                    "explicit_name_in_toml": "serde1"
+                }
+            ],
+            "features": {
+                "default": ["std"],
+                "std": [],
+                "derive": ["dep:serde1"]
+            },
+            "authors": ["Example <example@example.com>"],
+            "description": "An example crate",
+            "license": "MIT",
+            "keywords": ["example"]
+        });
+        let metadata = serde_json::to_vec(&metadata).unwrap();
+        let crate_file = b"not really a crate";
+        let mut body = Vec::new();
+        body.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        body.extend_from_slice(&metadata);
+        body.extend_from_slice(&(crate_file.len() as u32).to_le_bytes());
+        body.extend_from_slice(crate_file);
+
+        let (metadata, file) = read_publish_body(Bytes::from(body.clone())).unwrap();
+        assert_eq!(metadata.name, "mycrate");
+        assert_eq!(file.as_ref(), crate_file);
+
+        let entry = metadata.index_entry("abc".to_owned());
+        assert_eq!(entry.deps[0].name, "serde1");
+        assert_eq!(entry.deps[0].package.as_deref(), Some("serde"));
+        assert_eq!(entry.features.len(), 2);
+        assert!(entry.features2.unwrap().contains_key("derive"));
+        assert_eq!(entry.v, Some(2));
+
+        body.truncate(body.len() - 1);
+        assert!(read_publish_body(Bytes::from(body)).is_err());
+    }
+}
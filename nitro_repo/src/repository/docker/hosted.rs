@@ -0,0 +1,862 @@
+use super::{
+    DockerRegistryError,
+    types::{
+        DOCKER_CONTENT_DIGEST, DOCKER_UPLOAD_UUID, Digest, DockerErrorCode, DockerErrors,
+        DockerPath, DockerTag, Manifest, ManifestLink, Reference, TagsList, TagsListQuery,
+        UploadQuery, parse_content_range, upload_range,
+    },
+    upload::BlobUploads,
+    v2::unauthorized_response,
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        maven::get_release_type, utils::RepositoryExt,
+    },
+};
+use axum::{body::Body, response::Response};
+use derive_more::derive::Deref;
+use http::{
+    StatusCode,
+    header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LINK, LOCATION, RANGE},
+};
+use nr_core::{
+    database::entities::{
+        project::{
+            NewProject, NewProjectMember,
+            update::UpdateProjectVersion,
+            versions::{DBProjectVersion, NewVersion},
+        },
+        repository::DBRepository,
+    },
+    repository::{Visibility, project::VersionData},
+    storage::StoragePath,
+    user::permissions::RepositoryActions,
+};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile};
+use parking_lot::RwLock;
+use std::{
+    borrow::Cow,
+    sync::{
+        Arc,
+        atomic::{self, AtomicBool},
+    },
+};
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
+/// Manifests larger than this are rejected
+const MAX_MANIFEST_SIZE: usize = 4 * 1024 * 1024;
+#[derive(derive_more::Debug)]
+pub struct DockerHostedInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: uuid::Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    #[debug(skip)]
+    pub uploads: BlobUploads,
+}
+/// A Docker Registry that hosts its own images
+///
+/// Blobs are saved at `blobs/sha256/{hex}` and shared by every image in the repository.
+/// Manifests are saved as blobs and linked to the image at `{name}/_manifests/revisions/sha256/{hex}`.
+/// Each image is a project and each tag is a version of the project.
+/// Uploads in progress are kept in the staging directory. See [BlobUploads]
+#[derive(Debug, Clone, Deref)]
+pub struct DockerHosted(Arc<DockerHostedInner>);
+impl DockerHosted {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+    ) -> Result<Self, RepositoryFactoryError> {
+        let uploads = BlobUploads::new(
+            site.staging_config
+                .staging_dir
+                .join("docker_uploads")
+                .join(repository.id.to_string()),
+        );
+        Ok(Self(Arc::new(DockerHostedInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+            uploads,
+        })))
+    }
+    /// `{storage}/{repository}/{name}`. The name clients use for the image
+    fn full_name(&self, name: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.storage.storage_config().storage_config.storage_name,
+            self.repository.name,
+            name
+        )
+    }
+    /// `/v2/{storage}/{repository}/{name}`
+    fn image_path(&self, name: &str) -> String {
+        format!("/v2/{}", self.full_name(name))
+    }
+    /// Public and hidden repositories can be pulled from without authentication.
+    ///
+    /// Requests without credentials are sent a challenge for the scope they need
+    async fn check_access(
+        &self,
+        request: &RepositoryRequest,
+        name: &str,
+        action: RepositoryActions,
+    ) -> Result<Option<RepoResponse>, DockerRegistryError> {
+        if action == RepositoryActions::Read && !self.visibility().is_private() {
+            return Ok(None);
+        }
+        if request
+            .authentication
+            .can_access_repository(action, self.id, self.site.as_ref())
+            .await?
+        {
+            return Ok(None);
+        }
+        if request.authentication.is_no_identification() {
+            let actions = match action {
+                RepositoryActions::Read => "pull",
+                _ => "pull,push",
+            };
+            let scope = format!("repository:{}:{}", self.full_name(name), actions);
+            return Ok(Some(
+                unauthorized_response(&self.site, &request.parts, Some(&scope)).into(),
+            ));
+        }
+        let body = serde_json::to_string(&DockerErrors::new(
+            DockerErrorCode::Denied,
+            "Requested access to the resource is denied",
+        ))?;
+        Ok(Some(
+            Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.into())
+                .into(),
+        ))
+    }
+    /// Hidden repositories require read access to list tags
+    async fn indexing_check(
+        &self,
+        request: &RepositoryRequest,
+    ) -> Result<Option<RepoResponse>, DockerRegistryError> {
+        if self.visibility().is_hidden()
+            && !request
+                .authentication
+                .can_access_repository(RepositoryActions::Read, self.id, self.site.as_ref())
+                .await?
+        {
+            return Ok(Some(RepoResponse::indexing_not_allowed()));
+        }
+        Ok(None)
+    }
+    async fn read_file(&self, path: &StoragePath) -> Result<Option<Vec<u8>>, DockerRegistryError> {
+        let Some(StorageFile::File { meta, content }) =
+            self.storage.open_file(self.id, path).await?
+        else {
+            return Ok(None);
+        };
+        let content = content
+            .read_to_vec(meta.file_type.file_size as usize)
+            .await?;
+        Ok(Some(content))
+    }
+    async fn read_manifest_link(
+        &self,
+        name: &str,
+        digest: &Digest,
+    ) -> Result<Option<ManifestLink>, DockerRegistryError> {
+        let Some(content) = self.read_file(&digest.revision_path(name)).await? else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+    /// The tag saved in the project version
+    async fn get_tag(
+        &self,
+        name: &str,
+        tag: &str,
+    ) -> Result<Option<(DBProjectVersion, DockerTag)>, DockerRegistryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(None);
+        };
+        let Some(version) = self.get_project_version(project.id, tag).await? else {
+            return Ok(None);
+        };
+        let Some(extra) = version.extra.0.extra.clone() else {
+            warn!(?version, "Tag is missing the manifest digest");
+            return Ok(None);
+        };
+        let tag: DockerTag = serde_json::from_value(extra)?;
+        Ok(Some((version, tag)))
+    }
+    async fn resolve_reference(
+        &self,
+        name: &str,
+        reference: &Reference,
+    ) -> Result<Digest, DockerRegistryError> {
+        match reference {
+            Reference::Digest(digest) => Ok(digest.clone()),
+            Reference::Tag(tag) => {
+                let Some((_, tag)) = self.get_tag(name, tag).await? else {
+                    return Err(DockerRegistryError::ManifestUnknown(tag.clone()));
+                };
+                Digest::parse(&tag.digest)
+            }
+        }
+    }
+    fn upload_response(&self, name: &str, uuid: &Uuid, size: u64, status: StatusCode) -> Response {
+        Response::builder()
+            .status(status)
+            .header(
+                LOCATION,
+                format!("{}/blobs/uploads/{}", self.image_path(name), uuid),
+            )
+            .header(RANGE, upload_range(size))
+            .header(DOCKER_UPLOAD_UUID, uuid.to_string())
+            .header(CONTENT_LENGTH, "0")
+            .body(Body::empty())
+            .unwrap()
+    }
+    fn created_response(&self, location: String, digest: &Digest) -> Response {
+        Response::builder()
+            .status(StatusCode::CREATED)
+            .header(LOCATION, location)
+            .header(DOCKER_CONTENT_DIGEST, digest.to_string())
+            .header(CONTENT_LENGTH, "0")
+            .body(Body::empty())
+            .unwrap()
+    }
+    /// Ends the upload and saves it as a blob if the content matches the digest
+    async fn save_upload(
+        &self,
+        name: &str,
+        uuid: &Uuid,
+        expected: &Digest,
+    ) -> Result<Response, DockerRegistryError> {
+        let digest = self.uploads.finish(uuid)?;
+        let result = if &digest != expected {
+            Err(DockerRegistryError::DigestMismatch(expected.to_string()))
+        } else {
+            self.storage
+                .save_file(
+                    self.id,
+                    FileContent::Path(self.uploads.file_path(uuid)),
+                    &expected.blob_path(),
+                )
+                .await
+                .map_err(DockerRegistryError::from)
+        };
+        self.uploads.remove_file(uuid).await;
+        result?;
+        debug!(digest = %expected, "Saved blob");
+        Ok(self.created_response(
+            format!("{}/blobs/{}", self.image_path(name), expected),
+            &expected,
+        ))
+    }
+    #[instrument]
+    async fn handle_get_blob(
+        &self,
+        digest: &Digest,
+        head: bool,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(StorageFile::File { meta, content }) =
+            self.storage.open_file(self.id, &digest.blob_path()).await?
+        else {
+            return Err(DockerRegistryError::BlobUnknown(digest.to_string()));
+        };
+        let file_size = meta.file_type.file_size;
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_LENGTH, file_size.to_string())
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(DOCKER_CONTENT_DIGEST, digest.to_string())
+            .header(ETAG, format!(r#""{}""#, digest));
+        let body = if head {
+            Body::empty()
+        } else {
+            Body::new(content.into_body(file_size as usize))
+        };
+        Ok(response.body(body).into())
+    }
+    /// `POST {name}/blobs/uploads/`. Starts an upload, uploads the whole blob if `digest` is set, or mounts an existing blob
+    #[instrument]
+    async fn handle_start_upload(
+        &self,
+        name: &str,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let query = UploadQuery::from_query(request.parts.uri.query());
+        if let Some(mount) = &query.mount {
+            // Blobs are shared by every image in the repository. So any blob in this repository can be mounted
+            if let Ok(digest) = Digest::parse(mount) {
+                if self
+                    .storage
+                    .file_exists(self.id, &digest.blob_path())
+                    .await?
+                {
+                    let location = format!("{}/blobs/{}", self.image_path(name), digest);
+                    return Ok(self.created_response(location, &digest).into());
+                }
+            }
+        }
+        let digest = query.digest.as_deref().map(Digest::parse).transpose()?;
+        let uuid = self.uploads.start().await?;
+        let size = self
+            .uploads
+            .append(&uuid, None, request.body.into_data_stream())
+            .await?;
+        if let Some(digest) = &digest {
+            return Ok(self.save_upload(name, &uuid, digest).await?.into());
+        }
+        debug!(?uuid, "Started upload");
+        Ok(self
+            .upload_response(name, &uuid, size, StatusCode::ACCEPTED)
+            .into())
+    }
+    /// Appends the body to the upload. Chunks must be sent in order. Returns the new size of the upload
+    async fn append_chunk(
+        &self,
+        uuid: &Uuid,
+        request: RepositoryRequest,
+    ) -> Result<u64, DockerRegistryError> {
+        let start = request
+            .parts
+            .headers
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range)
+            .map(|(start, _)| start);
+        self.uploads
+            .append(uuid, start, request.body.into_data_stream())
+            .await
+    }
+    /// `PATCH {name}/blobs/uploads/{uuid}`
+    #[instrument]
+    async fn handle_upload_chunk(
+        &self,
+        name: &str,
+        uuid: Uuid,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let size = self.append_chunk(&uuid, request).await?;
+        Ok(self
+            .upload_response(name, &uuid, size, StatusCode::ACCEPTED)
+            .into())
+    }
+    /// `PUT {name}/blobs/uploads/{uuid}?digest={digest}`. The body is the last chunk
+    #[instrument]
+    async fn handle_finish_upload(
+        &self,
+        name: &str,
+        uuid: Uuid,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(digest) = UploadQuery::from_query(request.parts.uri.query()).digest else {
+            return Err(DockerRegistryError::BlobUploadInvalid(Cow::Borrowed(
+                "The digest query parameter is required to finish an upload",
+            )));
+        };
+        let digest = Digest::parse(&digest)?;
+        self.append_chunk(&uuid, request).await?;
+        Ok(self.save_upload(name, &uuid, &digest).await?.into())
+    }
+    /// `GET {name}/blobs/uploads/{uuid}`. The progress of the upload
+    async fn handle_upload_status(
+        &self,
+        name: &str,
+        uuid: Uuid,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(size) = self.uploads.size(&uuid) else {
+            return Err(DockerRegistryError::BlobUploadUnknown(uuid.to_string()));
+        };
+        Ok(self
+            .upload_response(name, &uuid, size, StatusCode::NO_CONTENT)
+            .into())
+    }
+    /// `GET {name}/manifests/{reference}`
+    #[instrument]
+    async fn handle_get_manifest(
+        &self,
+        name: &str,
+        reference: &Reference,
+        head: bool,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let digest = self.resolve_reference(name, reference).await?;
+        let Some(link) = self.read_manifest_link(name, &digest).await? else {
+            return Err(DockerRegistryError::ManifestUnknown(digest.to_string()));
+        };
+        let Some(content) = self.read_file(&digest.blob_path()).await? else {
+            error!(%digest, "Manifest is linked but the blob is missing");
+            return Err(DockerRegistryError::ManifestUnknown(digest.to_string()));
+        };
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, link.media_type)
+            .header(CONTENT_LENGTH, content.len().to_string())
+            .header(DOCKER_CONTENT_DIGEST, digest.to_string())
+            .header(ETAG, format!(r#""{}""#, digest));
+        let body = if head {
+            Body::empty()
+        } else {
+            Body::from(content)
+        };
+        Ok(response.body(body).into())
+    }
+    /// `PUT {name}/manifests/{reference}`
+    ///
+    /// Every blob or manifest the manifest references must already be pushed
+    #[instrument]
+    async fn handle_put_manifest(
+        &self,
+        name: &str,
+        reference: Reference,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let content_type = request
+            .parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let publisher = request.authentication.get_user_id();
+        let content = request.body.body_as_bytes().await?;
+        if content.len() > MAX_MANIFEST_SIZE {
+            return Err(DockerRegistryError::ManifestInvalid(Cow::Borrowed(
+                "Manifest is too large",
+            )));
+        }
+        let manifest: Manifest = serde_json::from_slice(&content)
+            .map_err(|err| DockerRegistryError::ManifestInvalid(Cow::Owned(err.to_string())))?;
+        if manifest.schema_version != 2 {
+            return Err(DockerRegistryError::ManifestInvalid(Cow::Borrowed(
+                "Only schema version 2 is supported",
+            )));
+        }
+        let digest = Digest::from_content(&content);
+        if let Reference::Digest(expected) = &reference {
+            if *expected != digest {
+                return Err(DockerRegistryError::DigestMismatch(expected.to_string()));
+            }
+        }
+        if manifest.is_index() {
+            for child in &manifest.manifests {
+                let child_digest = Digest::parse(&child.digest)?;
+                if self
+                    .read_manifest_link(name, &child_digest)
+                    .await?
+                    .is_none()
+                {
+                    return Err(DockerRegistryError::ManifestBlobUnknown(
+                        child.digest.clone(),
+                    ));
+                }
+            }
+        } else {
+            // Foreign layers are downloaded from their URLs and never pushed
+            let blobs = manifest
+                .config
+                .iter()
+                .chain(manifest.layers.iter())
+                .filter(|descriptor| !descriptor.media_type.contains("foreign"));
+            for blob in blobs {
+                let blob_digest = Digest::parse(&blob.digest)?;
+                if !self
+                    .storage
+                    .file_exists(self.id, &blob_digest.blob_path())
+                    .await?
+                {
+                    return Err(DockerRegistryError::ManifestBlobUnknown(
+                        blob.digest.clone(),
+                    ));
+                }
+            }
+        }
+        let link = ManifestLink {
+            digest: digest.to_string(),
+            media_type: manifest.resolve_media_type(content_type.as_deref()),
+            size: content.len() as u64,
+        };
+        self.storage
+            .save_file(self.id, FileContent::Bytes(content), &digest.blob_path())
+            .await?;
+        self.storage
+            .save_file(
+                self.id,
+                FileContent::Content(serde_json::to_vec(&link)?),
+                &digest.revision_path(name),
+            )
+            .await?;
+        if let Reference::Tag(tag) = &reference {
+            self.save_tag(name, tag, &link, publisher).await?;
+        }
+        info!(?name, ?reference, %digest, "Pushed manifest");
+        let location = format!("{}/manifests/{}", self.image_path(name), digest);
+        Ok(self.created_response(location, &digest).into())
+    }
+    /// Creates the project on the first push. The tag is created or moved to the new manifest
+    async fn save_tag(
+        &self,
+        name: &str,
+        tag: &str,
+        link: &ManifestLink,
+        publisher: Option<i32>,
+    ) -> Result<(), DockerRegistryError> {
+        let docker_tag = serde_json::to_value(DockerTag {
+            digest: link.digest.clone(),
+            media_type: link.media_type.clone(),
+        })?;
+        let project = match self.get_project_from_key(name).await? {
+            Some(project) => project,
+            None => {
+                let project = NewProject {
+                    scope: None,
+                    project_key: name.to_owned(),
+                    name: name.to_owned(),
+                    latest_release: None,
+                    latest_pre_release: None,
+                    description: None,
+                    tags: vec![],
+                    repository: self.id,
+                    storage_path: format!("{}/", name),
+                }
+                .insert(self.site.as_ref())
+                .await?;
+                if let Some(publisher) = publisher {
+                    NewProjectMember::new_owner(publisher, project.id)
+                        .insert_no_return(self.site.as_ref())
+                        .await?;
+                }
+                info!(?project, "Created new project");
+                project
+            }
+        };
+        match self.get_project_version(project.id, tag).await? {
+            Some(version) => {
+                let mut version_data = version.extra.0;
+                version_data.extra = Some(docker_tag);
+                UpdateProjectVersion {
+                    publisher: Some(publisher),
+                    extra: Some(version_data),
+                    ..Default::default()
+                }
+                .update(version.id, self.site.as_ref())
+                .await?;
+            }
+            None => {
+                NewVersion {
+                    project_id: project.id,
+                    version: tag.to_owned(),
+                    release_type: get_release_type(tag),
+                    version_path: format!("{}/_manifests/tags/{}", name, tag),
+                    publisher,
+                    version_page: None,
+                    extra: VersionData {
+                        extra: Some(docker_tag),
+                        ..Default::default()
+                    },
+                }
+                .insert(self.site.as_ref())
+                .await?;
+            }
+        }
+        Ok(())
+    }
+    /// `DELETE {name}/manifests/{reference}`
+    ///
+    /// Deleting a tag only removes the tag. Deleting a digest removes the manifest and every tag pointing to it
+    #[instrument]
+    async fn handle_delete_manifest(
+        &self,
+        name: &str,
+        reference: &Reference,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        match reference {
+            Reference::Tag(tag) => {
+                let Some((version, _)) = self.get_tag(name, tag).await? else {
+                    return Err(DockerRegistryError::ManifestUnknown(tag.clone()));
+                };
+                DBProjectVersion::delete_by_id(version.id, self.site.as_ref()).await?;
+            }
+            Reference::Digest(digest) => {
+                if !self
+                    .storage
+                    .delete_file(self.id, &digest.revision_path(name))
+                    .await?
+                {
+                    return Err(DockerRegistryError::ManifestUnknown(digest.to_string()));
+                }
+                if let Some(project) = self.get_project_from_key(name).await? {
+                    let digest = digest.to_string();
+                    let versions =
+                        DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+                    for version in versions {
+                        let points_to_digest = version
+                            .extra
+                            .0
+                            .extra
+                            .and_then(|extra| serde_json::from_value::<DockerTag>(extra).ok())
+                            .is_some_and(|tag| tag.digest == digest);
+                        if points_to_digest {
+                            DBProjectVersion::delete_by_id(version.id, self.site.as_ref()).await?;
+                        }
+                    }
+                }
+            }
+        }
+        info!(?name, ?reference, "Deleted manifest");
+        Ok(RepoResponse::basic_text_response(StatusCode::ACCEPTED, ""))
+    }
+    /// `GET {name}/tags/list`. Tags are sorted and paginated with `n` and `last`
+    #[instrument]
+    async fn handle_tags_list(
+        &self,
+        name: &str,
+        request: &RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Err(DockerRegistryError::NameUnknown(self.full_name(name)));
+        };
+        let mut tags: Vec<String> =
+            DBProjectVersion::get_all_versions(project.id, self.site.as_ref())
+                .await?
+                .into_iter()
+                .map(|version| version.version)
+                .collect();
+        tags.sort();
+        let query = TagsListQuery::from_query(request.parts.uri.query());
+        let (tags, has_more) = query.page(tags);
+        let mut response = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json");
+        if let (true, Some(n), Some(last)) = (has_more, query.n, tags.last()) {
+            let next = format!(
+                r#"<{}/tags/list?n={}&last={}>; rel="next""#,
+                self.image_path(name),
+                n,
+                last
+            );
+            response = response.header(LINK, next);
+        }
+        let body = serde_json::to_string(&TagsList {
+            name: self.full_name(name),
+            tags,
+        })?;
+        Ok(response.body(body.into()).into())
+    }
+    fn parse_path(request: &RepositoryRequest) -> Result<Option<DockerPath>, DockerRegistryError> {
+        DockerPath::parse(&request.path.to_string())
+    }
+    fn not_found() -> RepoResponse {
+        RepoResponse::basic_text_response(StatusCode::NOT_FOUND, "Not Found")
+    }
+}
+impl RepositoryExt for DockerHosted {}
+impl Repository for DockerHosted {
+    type Error = DockerRegistryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "docker"
+    }
+    fn full_type(&self) -> &'static str {
+        "docker/hosted"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "docker/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(path) = Self::parse_path(&request)? else {
+            return Ok(Self::not_found());
+        };
+        debug!(?path, "Handling Docker GET request");
+        if let Some(err) = self
+            .check_access(&request, path.name(), RepositoryActions::Read)
+            .await?
+        {
+            return Ok(err);
+        }
+        match path {
+            DockerPath::Manifest { name, reference } => {
+                self.handle_get_manifest(&name, &reference, false).await
+            }
+            DockerPath::Blob { digest, .. } => self.handle_get_blob(&digest, false).await,
+            DockerPath::Upload { name, uuid } => self.handle_upload_status(&name, uuid).await,
+            DockerPath::TagsList { name } => {
+                if let Some(err) = self.indexing_check(&request).await? {
+                    return Ok(err);
+                }
+                self.handle_tags_list(&name, &request).await
+            }
+            DockerPath::StartUpload { .. } => Ok(Self::not_found()),
+        }
+    }
+    async fn handle_head(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(path) = Self::parse_path(&request)? else {
+            return Ok(Self::not_found());
+        };
+        if let Some(err) = self
+            .check_access(&request, path.name(), RepositoryActions::Read)
+            .await?
+        {
+            return Ok(err);
+        }
+        match path {
+            DockerPath::Manifest { name, reference } => {
+                self.handle_get_manifest(&name, &reference, true).await
+            }
+            DockerPath::Blob { digest, .. } => self.handle_get_blob(&digest, true).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(DockerPath::StartUpload { name }) = Self::parse_path(&request)? else {
+            return Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            ));
+        };
+        if let Some(err) = self
+            .check_access(&request, &name, RepositoryActions::Write)
+            .await?
+        {
+            return Ok(err);
+        }
+        self.handle_start_upload(&name, request).await
+    }
+    async fn handle_patch(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(DockerPath::Upload { name, uuid }) = Self::parse_path(&request)? else {
+            return Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            ));
+        };
+        if let Some(err) = self
+            .check_access(&request, &name, RepositoryActions::Write)
+            .await?
+        {
+            return Ok(err);
+        }
+        self.handle_upload_chunk(&name, uuid, request).await
+    }
+    async fn handle_put(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(path) = Self::parse_path(&request)? else {
+            return Ok(Self::not_found());
+        };
+        if let Some(err) = self
+            .check_access(&request, path.name(), RepositoryActions::Write)
+            .await?
+        {
+            return Ok(err);
+        }
+        match path {
+            DockerPath::Upload { name, uuid } => {
+                self.handle_finish_upload(&name, uuid, request).await
+            }
+            DockerPath::Manifest { name, reference } => {
+                self.handle_put_manifest(&name, reference, request).await
+            }
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_delete(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, DockerRegistryError> {
+        let Some(path) = Self::parse_path(&request)? else {
+            return Ok(Self::not_found());
+        };
+        if let Some(err) = self
+            .check_access(&request, path.name(), RepositoryActions::Write)
+            .await?
+        {
+            return Ok(err);
+        }
+        match path {
+            DockerPath::Manifest { name, reference } => {
+                self.handle_delete_manifest(&name, &reference).await
+            }
+            DockerPath::Upload { uuid, .. } => {
+                if !self.uploads.cancel(&uuid).await {
+                    return Err(DockerRegistryError::BlobUploadUnknown(uuid.to_string()));
+                }
+                Ok(RepoResponse::basic_text_response(
+                    StatusCode::NO_CONTENT,
+                    "",
+                ))
+            }
+            DockerPath::Blob { .. } => Err(DockerRegistryError::Unsupported(Cow::Borrowed(
+                "Deleting blobs is not supported",
+            ))),
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Docker Registry Implementation
+//!
+//! Implements the OCI Distribution Spec (Docker Registry HTTP API V2). Images are pushed and pulled from
+//! `/v2/{storage}/{repository}/{name}`
+//!
+//! Documentation for the Distribution Spec: https://github.com/opencontainers/distribution-spec/blob/main/spec.md
+
+use std::borrow::Cow;
+
+use ahash::HashMap;
+use futures::future::BoxFuture;
+use hosted::DockerHosted;
+use nr_core::database::entities::repository::DBRepository;
+use nr_macros::DynRepositoryHandler;
+use nr_storage::DynStorage;
+use tracing::debug;
+use types::{DockerErrorCode, DockerErrors};
+
+pub mod hosted;
+pub mod types;
+pub mod upload;
+pub mod v2;
+use crate::{
+    app::authentication::AuthenticationError,
+    error::{BadRequestErrors, IntoErrorResponse},
+};
+
+pub use super::prelude::*;
+use super::{DynRepository, NewRepository, RepositoryType, RepositoryTypeDescription};
+pub use v2::v2_router;
+
+#[derive(Debug, Clone, DynRepositoryHandler)]
+#[repository_handler(error=DockerRegistryError)]
+pub enum DockerRegistry {
+    Hosted(DockerHosted),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DockerRegistryError {
+    #[error("Invalid repository name {0}")]
+    NameInvalid(String),
+    #[error("Repository {0} is not known to the registry")]
+    NameUnknown(String),
+    #[error("Invalid tag {0}")]
+    TagInvalid(String),
+    #[error("Invalid digest {0}. Only sha256 digests are supported")]
+    DigestInvalid(String),
+    #[error("Blob {0} is not known to the registry")]
+    BlobUnknown(String),
+    #[error("Blob upload {0} is not known to the registry")]
+    BlobUploadUnknown(String),
+    #[error("Invalid blob upload. {0}")]
+    BlobUploadInvalid(Cow<'static, str>),
+    #[error("Chunk does not start at the end of the upload. Expected offset {expected}")]
+    RangeInvalid { expected: u64 },
+    #[error("Content does not match the digest {0}")]
+    DigestMismatch(String),
+    #[error("Manifest {0} is not known to the registry")]
+    ManifestUnknown(String),
+    #[error("Invalid manifest. {0}")]
+    ManifestInvalid(Cow<'static, str>),
+    #[error("Manifest references blob {0} that is not known to the registry")]
+    ManifestBlobUnknown(String),
+    #[error("{0}")]
+    Unsupported(Cow<'static, str>),
+    #[error("{0}")]
+    Other(Box<dyn IntoErrorResponse>),
+}
+impl DockerRegistryError {
+    fn code_and_status(&self) -> (DockerErrorCode, StatusCode) {
+        match self {
+            Self::NameInvalid(_) => (DockerErrorCode::NameInvalid, StatusCode::BAD_REQUEST),
+            Self::NameUnknown(_) => (DockerErrorCode::NameUnknown, StatusCode::NOT_FOUND),
+            Self::TagInvalid(_) => (DockerErrorCode::TagInvalid, StatusCode::BAD_REQUEST),
+            Self::DigestInvalid(_) | Self::DigestMismatch(_) => {
+                (DockerErrorCode::DigestInvalid, StatusCode::BAD_REQUEST)
+            }
+            Self::BlobUnknown(_) => (DockerErrorCode::BlobUnknown, StatusCode::NOT_FOUND),
+            Self::BlobUploadUnknown(_) => {
+                (DockerErrorCode::BlobUploadUnknown, StatusCode::NOT_FOUND)
+            }
+            Self::BlobUploadInvalid(_) => {
+                (DockerErrorCode::BlobUploadInvalid, StatusCode::BAD_REQUEST)
+            }
+            Self::RangeInvalid { .. } => (
+                DockerErrorCode::BlobUploadInvalid,
+                StatusCode::RANGE_NOT_SATISFIABLE,
+            ),
+            Self::ManifestUnknown(_) => (DockerErrorCode::ManifestUnknown, StatusCode::NOT_FOUND),
+            Self::ManifestInvalid(_) => (DockerErrorCode::ManifestInvalid, StatusCode::BAD_REQUEST),
+            Self::ManifestBlobUnknown(_) => (
+                DockerErrorCode::ManifestBlobUnknown,
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::Unsupported(_) | Self::Other(_) => {
+                (DockerErrorCode::Unsupported, StatusCode::METHOD_NOT_ALLOWED)
+            }
+        }
+    }
+}
+impl From<DockerRegistryError> for RepositoryHandlerError {
+    fn from(err: DockerRegistryError) -> Self {
+        RepositoryHandlerError::Other(Box::new(err))
+    }
+}
+macro_rules! impl_from_error_for_other {
+    ($t:ty) => {
+        impl From<$t> for DockerRegistryError {
+            fn from(e: $t) -> Self {
+                DockerRegistryError::Other(Box::new(e))
+            }
+        }
+    };
+}
+impl_from_error_for_other!(BadRequestErrors);
+impl_from_error_for_other!(sqlx::Error);
+impl_from_error_for_other!(serde_json::Error);
+impl_from_error_for_other!(std::io::Error);
+impl_from_error_for_other!(AuthenticationError);
+impl_from_error_for_other!(RepositoryHandlerError);
+impl_from_error_for_other!(nr_storage::StorageError);
+
+impl IntoErrorResponse for DockerRegistryError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
+
+impl From<DockerRegistryError> for DynRepositoryHandlerError {
+    fn from(err: DockerRegistryError) -> Self {
+        DynRepositoryHandlerError(Box::new(err))
+    }
+}
+
+impl IntoResponse for DockerRegistryError {
+    fn into_response(self) -> Response {
+        match self {
+            DockerRegistryError::Other(other) => other.into_response_boxed(),
+            bad_request => {
+                debug!("Bad Request: {:?}", bad_request);
+                let (code, status) = bad_request.code_and_status();
+                // Docker and other clients display the message of the first error
+                let body = serde_json::to_string(&DockerErrors::new(code, bad_request.to_string()))
+                    .unwrap_or_default();
+                Response::builder()
+                    .status(status)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(body.into())
+                    .unwrap()
+            }
+        }
+    }
+}
+#[derive(Debug, Default)]
+pub struct DockerRegistryType;
+
+impl RepositoryType for DockerRegistryType {
+    fn get_type(&self) -> &'static str {
+        "docker"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn get_description(&self) -> RepositoryTypeDescription {
+        RepositoryTypeDescription {
+            type_name: "docker",
+            name: "Docker",
+            description: "A Docker and OCI container registry",
+            documentation_url: Some("https://nitro-repo.kingtux.dev/repositoryTypes/docker/"),
+            is_stable: false,
+            required_configs: vec![],
+        }
+    }
+
+    fn create_new(
+        &self,
+        name: String,
+        uuid: uuid::Uuid,
+        configs: HashMap<String, serde_json::Value>,
+        storage: nr_storage::DynStorage,
+    ) -> BoxFuture<'static, Result<NewRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            Ok(NewRepository {
+                name,
+                uuid,
+                repository_type: "docker".to_string(),
+                configs,
+            })
+        })
+    }
+
+    fn load_repo(
+        &self,
+        repo: DBRepository,
+        storage: DynStorage,
+        website: NitroRepo,
+    ) -> BoxFuture<'static, Result<DynRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let hosted = DockerHosted::load(website, storage, repo).await?;
+            Ok(DockerRegistry::Hosted(hosted).into())
+        })
+    }
+}
@@ -0,0 +1,477 @@
+use std::fmt::Display;
+
+use http::HeaderName;
+use nr_core::storage::StoragePath;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
+
+use super::DockerRegistryError;
+
+pub const DOCKER_DISTRIBUTION_API_VERSION: HeaderName =
+    HeaderName::from_static("docker-distribution-api-version");
+pub const DOCKER_CONTENT_DIGEST: HeaderName = HeaderName::from_static("docker-content-digest");
+pub const DOCKER_UPLOAD_UUID: HeaderName = HeaderName::from_static("docker-upload-uuid");
+/// Value of the `Docker-Distribution-API-Version` header
+pub const API_VERSION: &str = "registry/2.0";
+
+pub const OCI_IMAGE_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub const OCI_IMAGE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+pub const DOCKER_MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+pub const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+/// Manifest types that can be pushed
+pub const SUPPORTED_MANIFEST_TYPES: [&str; 4] = [
+    OCI_IMAGE_MANIFEST,
+    OCI_IMAGE_INDEX,
+    DOCKER_MANIFEST_V2,
+    DOCKER_MANIFEST_LIST,
+];
+/// Error codes defined by the distribution spec
+///
+/// https://github.com/opencontainers/distribution-spec/blob/main/spec.md#error-codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DockerErrorCode {
+    BlobUnknown,
+    BlobUploadInvalid,
+    BlobUploadUnknown,
+    DigestInvalid,
+    ManifestBlobUnknown,
+    ManifestInvalid,
+    ManifestUnknown,
+    NameInvalid,
+    NameUnknown,
+    TagInvalid,
+    Unauthorized,
+    Denied,
+    Unsupported,
+}
+#[derive(Debug, Serialize)]
+pub struct DockerError {
+    pub code: DockerErrorCode,
+    pub message: String,
+}
+/// The body of every error response. `{"errors": [{"code": "...", "message": "..."}]}`
+#[derive(Debug, Serialize)]
+pub struct DockerErrors {
+    pub errors: Vec<DockerError>,
+}
+impl DockerErrors {
+    pub fn new(code: DockerErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            errors: vec![DockerError {
+                code,
+                message: message.into(),
+            }],
+        }
+    }
+}
+/// A sha256 digest. Such as `sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b`
+///
+/// Other algorithms are rejected
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Digest {
+    hex: String,
+}
+impl Digest {
+    pub fn parse(value: &str) -> Result<Self, DockerRegistryError> {
+        let Some((algorithm, hex)) = value.split_once(':') else {
+            return Err(DockerRegistryError::DigestInvalid(value.to_owned()));
+        };
+        if algorithm != "sha256"
+            || hex.len() != 64
+            || !hex
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, 'a'..='f'))
+        {
+            return Err(DockerRegistryError::DigestInvalid(value.to_owned()));
+        }
+        Ok(Self {
+            hex: hex.to_owned(),
+        })
+    }
+    pub fn from_content(content: &[u8]) -> Self {
+        Self {
+            hex: format!("{:x}", Sha256::digest(content)),
+        }
+    }
+    pub fn from_hasher(hasher: Sha256) -> Self {
+        Self {
+            hex: format!("{:x}", hasher.finalize()),
+        }
+    }
+    /// Blobs are shared by every image in the repository. `blobs/sha256/{hex}`
+    pub fn blob_path(&self) -> StoragePath {
+        StoragePath::from(format!("blobs/sha256/{}", self.hex))
+    }
+    /// Marks the manifest as part of the image. `{name}/_manifests/revisions/sha256/{hex}`
+    pub fn revision_path(&self, name: &str) -> StoragePath {
+        StoragePath::from(format!("{}/_manifests/revisions/sha256/{}", name, self.hex))
+    }
+}
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sha256:{}", self.hex)
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    Tag(String),
+    Digest(Digest),
+}
+impl Reference {
+    pub fn parse(value: &str) -> Result<Self, DockerRegistryError> {
+        if value.contains(':') {
+            return Digest::parse(value).map(Reference::Digest);
+        }
+        if !is_valid_tag(value) {
+            return Err(DockerRegistryError::TagInvalid(value.to_owned()));
+        }
+        Ok(Reference::Tag(value.to_owned()))
+    }
+}
+/// `[a-zA-Z0-9_][a-zA-Z0-9._-]{0,127}`
+pub fn is_valid_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    tag.len() <= 128
+        && (first.is_ascii_alphanumeric() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+/// Each component must match `[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*`
+pub fn is_valid_name_component(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    let (Some(first), Some(last)) = (bytes.first(), bytes.last()) else {
+        return false;
+    };
+    if !first.is_ascii_alphanumeric() || !last.is_ascii_alphanumeric() {
+        return false;
+    }
+    let mut previous_separator: Option<u8> = None;
+    let mut separator_length = 0;
+    for &byte in bytes {
+        match byte {
+            b'a'..=b'z' | b'0'..=b'9' => {
+                previous_separator = None;
+                separator_length = 0;
+            }
+            b'.' | b'_' | b'-' => {
+                separator_length += 1;
+                match previous_separator {
+                    None => previous_separator = Some(byte),
+                    // `__` and `--...` are allowed. Anything else is not
+                    Some(b'_') if byte == b'_' && separator_length == 2 => {}
+                    Some(b'-') if byte == b'-' => {}
+                    Some(_) => return false,
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+/// The name of the image inside of the repository. `{storage}/{repository}/{name}`
+pub fn validate_image_name(name: &str) -> Result<(), DockerRegistryError> {
+    if name.is_empty() || name.len() > 255 || !name.split('/').all(is_valid_name_component) {
+        return Err(DockerRegistryError::NameInvalid(name.to_owned()));
+    }
+    Ok(())
+}
+/// The routes of the distribution spec after `/v2/{storage}/{repository}/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DockerPath {
+    /// `{name}/manifests/{reference}`
+    Manifest { name: String, reference: Reference },
+    /// `{name}/blobs/{digest}`
+    Blob { name: String, digest: Digest },
+    /// `{name}/blobs/uploads/`
+    StartUpload { name: String },
+    /// `{name}/blobs/uploads/{uuid}`
+    Upload { name: String, uuid: Uuid },
+    /// `{name}/tags/list`
+    TagsList { name: String },
+}
+impl DockerPath {
+    /// Returns None if the path is not a route of the distribution spec
+    pub fn parse(path: &str) -> Result<Option<Self>, DockerRegistryError> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let name_of = |length: usize| -> Result<String, DockerRegistryError> {
+            let name = components[..components.len() - length].join("/");
+            validate_image_name(&name)?;
+            Ok(name)
+        };
+        let path = match components.as_slice() {
+            [.., "tags", "list"] => DockerPath::TagsList { name: name_of(2)? },
+            [.., "blobs", "uploads"] => DockerPath::StartUpload { name: name_of(2)? },
+            [.., "blobs", "uploads", uuid] => {
+                let uuid = Uuid::parse_str(uuid)
+                    .map_err(|_| DockerRegistryError::BlobUploadUnknown(uuid.to_string()))?;
+                DockerPath::Upload {
+                    name: name_of(3)?,
+                    uuid,
+                }
+            }
+            [.., "blobs", digest] => DockerPath::Blob {
+                digest: Digest::parse(digest)?,
+                name: name_of(2)?,
+            },
+            [.., "manifests", reference] => DockerPath::Manifest {
+                reference: Reference::parse(reference)?,
+                name: name_of(2)?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(path))
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            DockerPath::Manifest { name, .. }
+            | DockerPath::Blob { name, .. }
+            | DockerPath::StartUpload { name }
+            | DockerPath::Upload { name, .. }
+            | DockerPath::TagsList { name } => name,
+        }
+    }
+}
+/// Query of the blob upload routes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadQuery {
+    /// Completes the upload. Required when finishing an upload or when uploading in a single POST
+    pub digest: Option<String>,
+    /// Mounts an existing blob instead of uploading it
+    pub mount: Option<String>,
+}
+impl UploadQuery {
+    pub fn from_query(query: Option<&str>) -> Self {
+        let mut request = Self::default();
+        let Some(query) = query else {
+            return request;
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "digest" => request.digest = Some(value.into_owned()),
+                "mount" => request.mount = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        request
+    }
+}
+/// The `Range` header of an upload. Always starts at 0 and the end is inclusive
+pub fn upload_range(size: u64) -> String {
+    format!("0-{}", size.max(1) - 1)
+}
+/// Parses a `Content-Range` header of a chunk. `{start}-{end}`
+pub fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let value = value
+        .trim()
+        .trim_start_matches("bytes")
+        .trim_start_matches([' ', '=']);
+    let (start, end) = value.split_once('-')?;
+    let start = start.trim().parse().ok()?;
+    let end = end.trim().parse().ok()?;
+    (start <= end).then_some((start, end))
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+}
+/// The fields of an image manifest or image index that are checked on push
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub media_type: Option<String>,
+    /// Image Manifests only
+    #[serde(default)]
+    pub config: Option<Descriptor>,
+    /// Image Manifests only
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+    /// Image Indexes only
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+impl Manifest {
+    pub fn is_index(&self) -> bool {
+        matches!(
+            self.media_type.as_deref(),
+            Some(OCI_IMAGE_INDEX | DOCKER_MANIFEST_LIST)
+        ) || (self.config.is_none() && !self.manifests.is_empty())
+    }
+    /// The media type is taken from the `Content-Type` header. Falling back to the `mediaType` field
+    pub fn resolve_media_type(&self, content_type: Option<&str>) -> String {
+        if let Some(content_type) = content_type {
+            let content_type = content_type.split(';').next().unwrap_or_default().trim();
+            if SUPPORTED_MANIFEST_TYPES.contains(&content_type) {
+                return content_type.to_owned();
+            }
+        }
+        if let Some(media_type) = &self.media_type {
+            return media_type.clone();
+        }
+        if self.is_index() {
+            OCI_IMAGE_INDEX.to_owned()
+        } else {
+            OCI_IMAGE_MANIFEST.to_owned()
+        }
+    }
+}
+/// Saved at the revision path of a manifest. The manifest itself is saved as a blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestLink {
+    pub digest: String,
+    pub media_type: String,
+    pub size: u64,
+}
+/// Saved in the `extra` of the project version for each tag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DockerTag {
+    pub digest: String,
+    pub media_type: String,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagsList {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+/// Query of `tags/list`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagsListQuery {
+    pub n: Option<usize>,
+    pub last: Option<String>,
+}
+impl TagsListQuery {
+    pub fn from_query(query: Option<&str>) -> Self {
+        let mut request = Self::default();
+        let Some(query) = query else {
+            return request;
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "n" => request.n = value.parse().ok(),
+                "last" => request.last = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        request
+    }
+    /// Returns the page of tags and if there are more tags after it
+    ///
+    /// Tags must be sorted
+    pub fn page(&self, tags: Vec<String>) -> (Vec<String>, bool) {
+        let mut tags: Vec<String> = match &self.last {
+            Some(last) => tags.into_iter().filter(|tag| tag > last).collect(),
+            None => tags,
+        };
+        let Some(n) = self.n else {
+            return (tags, false);
+        };
+        let has_more = tags.len() > n;
+        tags.truncate(n);
+        (tags, has_more)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const HEX: &str = "6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b";
+    #[test]
+    fn test_name_components() {
+        for valid in ["nginx", "my-app", "my__app", "my.app", "a--b", "app2"] {
+            assert!(is_valid_name_component(valid), "{}", valid);
+        }
+        for invalid in ["", "My-App", "-app", "app-", "a___b", "a._b", "a..b", "a:b"] {
+            assert!(!is_valid_name_component(invalid), "{}", invalid);
+        }
+        assert!(validate_image_name("library/nginx").is_ok());
+        assert!(validate_image_name("").is_err());
+        assert!(validate_image_name("library//nginx").is_err());
+    }
+    #[test]
+    fn test_tags_and_digests() {
+        assert!(is_valid_tag("latest"));
+        assert!(is_valid_tag("_v1.0-rc.1"));
+        assert!(!is_valid_tag(".hidden"));
+        assert!(!is_valid_tag(&"a".repeat(129)));
+        let digest = Digest::parse(&format!("sha256:{}", HEX)).unwrap();
+        assert_eq!(digest.to_string(), format!("sha256:{}", HEX));
+        assert!(Digest::parse("sha512:abc").is_err());
+        assert!(Digest::parse(&format!("sha256:{}", HEX.to_uppercase())).is_err());
+        assert_eq!(Digest::from_content(b"").hex.len(), 64);
+    }
+    #[test]
+    fn test_parse_path() {
+        let digest = Digest::parse(&format!("sha256:{}", HEX)).unwrap();
+        let uuid = Uuid::new_v4();
+        let cases = [
+            (
+                "library/nginx/manifests/latest".to_owned(),
+                DockerPath::Manifest {
+                    name: "library/nginx".to_owned(),
+                    reference: Reference::Tag("latest".to_owned()),
+                },
+            ),
+            (
+                format!("app/manifests/sha256:{}", HEX),
+                DockerPath::Manifest {
+                    name: "app".to_owned(),
+                    reference: Reference::Digest(digest.clone()),
+                },
+            ),
+            (
+                format!("app/blobs/sha256:{}", HEX),
+                DockerPath::Blob {
+                    name: "app".to_owned(),
+                    digest,
+                },
+            ),
+            (
+                "app/blobs/uploads/".to_owned(),
+                DockerPath::StartUpload {
+                    name: "app".to_owned(),
+                },
+            ),
+            (
+                format!("app/blobs/uploads/{}", uuid),
+                DockerPath::Upload {
+                    name: "app".to_owned(),
+                    uuid,
+                },
+            ),
+            (
+                "a/b/tags/list".to_owned(),
+                DockerPath::TagsList {
+                    name: "a/b".to_owned(),
+                },
+            ),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                DockerPath::parse(&path).unwrap(),
+                Some(expected),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(DockerPath::parse("app/other").unwrap(), None);
+        assert!(DockerPath::parse("manifests/latest").is_err());
+        assert!(DockerPath::parse("App/manifests/latest").is_err());
+    }
+    #[test]
+    fn test_content_range_and_tags_page() {
+        assert_eq!(parse_content_range("0-1023"), Some((0, 1023)));
+        assert_eq!(parse_content_range("bytes=10-20"), Some((10, 20)));
+        assert_eq!(parse_content_range("20-10"), None);
+        let tags = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let query = TagsListQuery::from_query(Some("n=1&last=a"));
+        assert_eq!(query.page(tags.clone()), (vec!["b".to_owned()], true));
+        assert_eq!(TagsListQuery::default().page(tags.clone()), (tags, false));
+    }
+}
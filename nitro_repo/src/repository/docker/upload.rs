@@ -0,0 +1,216 @@
+//! Blob uploads that are in progress
+//!
+//! Chunks are appended to a local file in the staging directory and hashed while they are received.
+//! So finishing an upload does not have to read the blob again.
+//! Uploads that have not received a chunk for [UPLOAD_EXPIRATION] are removed when the next upload is started
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use ahash::{HashMap, HashMapExt};
+use axum::body::BodyDataStream;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use sha2::{Digest as _, Sha256};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::{DockerRegistryError, types::Digest};
+use crate::error::BadRequestErrors;
+/// Uploads that have not received a chunk for this long are removed
+pub const UPLOAD_EXPIRATION: Duration = Duration::from_secs(60 * 60);
+#[derive(Debug)]
+struct BlobUpload {
+    hasher: Sha256,
+    size: u64,
+    last_updated: Instant,
+}
+#[derive(Debug)]
+pub struct BlobUploads {
+    directory: PathBuf,
+    uploads: Mutex<HashMap<Uuid, BlobUpload>>,
+}
+impl BlobUploads {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            uploads: Mutex::new(HashMap::new()),
+        }
+    }
+    /// The local file the upload is written to
+    pub fn file_path(&self, uuid: &Uuid) -> PathBuf {
+        self.directory.join(uuid.to_string())
+    }
+    /// Starts a new empty upload. Expired uploads are removed first
+    pub async fn start(&self) -> Result<Uuid, DockerRegistryError> {
+        self.remove_expired().await;
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let uuid = Uuid::new_v4();
+        tokio::fs::File::create(self.file_path(&uuid)).await?;
+        self.uploads.lock().insert(
+            uuid,
+            BlobUpload {
+                hasher: Sha256::new(),
+                size: 0,
+                last_updated: Instant::now(),
+            },
+        );
+        Ok(uuid)
+    }
+    /// The number of bytes received. None if the upload does not exist
+    pub fn size(&self, uuid: &Uuid) -> Option<u64> {
+        self.uploads.lock().get(uuid).map(|upload| upload.size)
+    }
+    /// Appends the body to the upload. If `start` is provided it must be the current size of the upload.
+    ///
+    /// If the body can not be read or written the upload is removed. Returns the new size of the upload
+    pub async fn append(
+        &self,
+        uuid: &Uuid,
+        start: Option<u64>,
+        body: BodyDataStream,
+    ) -> Result<u64, DockerRegistryError> {
+        // Taken out of the map while the chunk is written. So concurrent chunks for the same upload are rejected
+        let Some(mut upload) = self.uploads.lock().remove(uuid) else {
+            return Err(DockerRegistryError::BlobUploadUnknown(uuid.to_string()));
+        };
+        if let Some(start) = start {
+            if start != upload.size {
+                let expected = upload.size;
+                self.uploads.lock().insert(*uuid, upload);
+                return Err(DockerRegistryError::RangeInvalid { expected });
+            }
+        }
+        if let Err(err) = Self::write_body(&self.file_path(uuid), &mut upload, body).await {
+            self.remove_file(uuid).await;
+            return Err(err);
+        }
+        upload.last_updated = Instant::now();
+        let size = upload.size;
+        self.uploads.lock().insert(*uuid, upload);
+        Ok(size)
+    }
+    async fn write_body(
+        path: &Path,
+        upload: &mut BlobUpload,
+        mut body: BodyDataStream,
+    ) -> Result<(), DockerRegistryError> {
+        let mut file = OpenOptions::new().append(true).open(path).await?;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(BadRequestErrors::from)?;
+            file.write_all(&chunk).await?;
+            upload.hasher.update(&chunk);
+            upload.size += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    /// Ends the upload and returns the digest of the content.
+    ///
+    /// The file stays at [BlobUploads::file_path] until [BlobUploads::remove_file] is called
+    pub fn finish(&self, uuid: &Uuid) -> Result<Digest, DockerRegistryError> {
+        let Some(upload) = self.uploads.lock().remove(uuid) else {
+            return Err(DockerRegistryError::BlobUploadUnknown(uuid.to_string()));
+        };
+        Ok(Digest::from_hasher(upload.hasher))
+    }
+    /// Removes the upload. Returns false if the upload does not exist
+    pub async fn cancel(&self, uuid: &Uuid) -> bool {
+        if self.uploads.lock().remove(uuid).is_none() {
+            return false;
+        }
+        self.remove_file(uuid).await;
+        true
+    }
+    pub async fn remove_file(&self, uuid: &Uuid) {
+        if let Err(err) = tokio::fs::remove_file(self.file_path(uuid)).await {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!(?err, ?uuid, "Failed to remove upload file");
+            }
+        }
+    }
+    /// Removes uploads that have not received a chunk for [UPLOAD_EXPIRATION].
+    ///
+    /// Files without an upload are left over from a restart. They are removed once they are expired
+    pub async fn remove_expired(&self) {
+        let expired: Vec<Uuid> = {
+            let mut uploads = self.uploads.lock();
+            let expired: Vec<Uuid> = uploads
+                .iter()
+                .filter(|(_, upload)| upload.last_updated.elapsed() > UPLOAD_EXPIRATION)
+                .map(|(uuid, _)| *uuid)
+                .collect();
+            for uuid in &expired {
+                uploads.remove(uuid);
+            }
+            expired
+        };
+        for uuid in expired {
+            debug!(?uuid, "Removing expired upload");
+            self.remove_file(&uuid).await;
+        }
+        let Ok(mut entries) = tokio::fs::read_dir(&self.directory).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(uuid) = Uuid::parse_str(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            if self.uploads.lock().contains_key(&uuid) {
+                continue;
+            }
+            let is_expired = entry
+                .metadata()
+                .await
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|elapsed| elapsed > UPLOAD_EXPIRATION);
+            if is_expired {
+                warn!(?uuid, "Removing upload file without an upload");
+                self.remove_file(&uuid).await;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        let directory = tempfile::tempdir().unwrap();
+        let uploads = BlobUploads::new(directory.path().to_path_buf());
+        let uuid = uploads.start().await.unwrap();
+        let size = uploads
+            .append(&uuid, None, Body::from("hello ").into_data_stream())
+            .await
+            .unwrap();
+        assert_eq!(size, 6);
+        assert!(matches!(
+            uploads
+                .append(&uuid, Some(0), Body::from("world").into_data_stream())
+                .await,
+            Err(DockerRegistryError::RangeInvalid { expected: 6 })
+        ));
+        let size = uploads
+            .append(&uuid, Some(6), Body::from("world").into_data_stream())
+            .await
+            .unwrap();
+        assert_eq!(size, 11);
+        assert_eq!(uploads.size(&uuid), Some(11));
+
+        let digest = uploads.finish(&uuid).unwrap();
+        assert_eq!(digest, Digest::from_content(b"hello world"));
+        assert_eq!(
+            tokio::fs::read(uploads.file_path(&uuid)).await.unwrap(),
+            b"hello world"
+        );
+        uploads.remove_file(&uuid).await;
+        assert!(!uploads.file_path(&uuid).exists());
+        assert!(uploads.finish(&uuid).is_err());
+    }
+}
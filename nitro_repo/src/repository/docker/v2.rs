@@ -0,0 +1,353 @@
+//! Routes under `/v2/`
+//!
+//! Docker only sends credentials after `/v2/` responds with a `WWW-Authenticate` challenge.
+//! The credentials are exchanged at `/v2/token` for a bearer token that is sent with every other request.
+//!
+//! https://distribution.github.io/distribution/spec/auth/token/
+use axum::{
+    Json, Router,
+    extract::{FromRequestParts, Path, Request, State},
+    response::{IntoResponse, Response},
+    routing::{any, get},
+};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use http::{
+    StatusCode,
+    header::{CONTENT_TYPE, WWW_AUTHENTICATE},
+    request::Parts,
+};
+use nr_core::{
+    database::entities::user::{
+        UserSafeData, UserType,
+        auth_token::{AuthToken, NewRepositoryToken},
+    },
+    user::permissions::{HasPermissions, RepositoryActions},
+};
+use serde::Serialize;
+use tracing::{debug, instrument};
+use uuid::Uuid;
+
+use super::{
+    DockerRegistryError,
+    types::{API_VERSION, DOCKER_DISTRIBUTION_API_VERSION, DockerErrorCode, DockerErrors},
+};
+use crate::{
+    app::{
+        NitroRepo, RepositoryStorageName,
+        authentication::{
+            AuthenticationError, AuthenticationRaw, get_user_and_auth_token, verify_login,
+        },
+        logging::request_logging::RequestSpan,
+    },
+    error::InternalError,
+    repository::{
+        DynRepository, RepoRequestPath, Repository, RepositoryAuthentication,
+        RepositoryHandlerError, handle_repo_request, utils::site_base_url,
+    },
+};
+/// Returned by the token endpoint when no credentials are sent. It is treated as no identification
+pub const ANONYMOUS_TOKEN: &str = "anonymous";
+/// How long tokens created by the token endpoint are valid for in seconds
+const TOKEN_EXPIRES_IN: i64 = 900;
+
+pub fn v2_router() -> Router<NitroRepo> {
+    Router::new()
+        .route("/v2", get(base))
+        .route("/v2/", get(base))
+        .route("/v2/token", get(token))
+        .route("/v2/{storage}/{repository}/{*path}", any(handle_v2_request))
+}
+/// The `WWW-Authenticate` header pointing the client to the token endpoint
+pub fn bearer_challenge(site: &NitroRepo, parts: &Parts, scope: Option<&str>) -> String {
+    let base_url = site_base_url(site, parts);
+    let service = base_url
+        .split_once("://")
+        .map(|(_, host)| host)
+        .unwrap_or(&base_url);
+    let mut challenge = format!(
+        r#"Bearer realm="{}/v2/token",service="{}""#,
+        base_url, service
+    );
+    if let Some(scope) = scope {
+        challenge.push_str(&format!(r#",scope="{}""#, scope));
+    }
+    challenge
+}
+pub fn unauthorized_response(site: &NitroRepo, parts: &Parts, scope: Option<&str>) -> Response {
+    let body = serde_json::to_string(&DockerErrors::new(
+        DockerErrorCode::Unauthorized,
+        "Authentication required",
+    ))
+    .unwrap_or_default();
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(WWW_AUTHENTICATE, bearer_challenge(site, parts, scope))
+        .header(DOCKER_DISTRIBUTION_API_VERSION, API_VERSION)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+fn is_anonymous(parts: &Parts) -> bool {
+    matches!(
+        parts.extensions.get::<AuthenticationRaw>(),
+        Some(AuthenticationRaw::AuthToken(token)) if token == ANONYMOUS_TOKEN
+    )
+}
+/// Same as [RepositoryAuthentication] except the anonymous token is treated as no identification
+async fn docker_authentication(
+    parts: &mut Parts,
+    site: &NitroRepo,
+) -> Result<RepositoryAuthentication, AuthenticationError> {
+    if is_anonymous(parts) {
+        parts.extensions.insert(AuthenticationRaw::NoIdentification);
+    }
+    RepositoryAuthentication::from_request_parts(parts, site).await
+}
+/// `GET /v2/`. Clients check this route to find out if they need to authenticate
+#[instrument(skip(site, request))]
+async fn base(State(site): State<NitroRepo>, request: Request) -> Response {
+    let (mut parts, _) = request.into_parts();
+    let is_anonymous = is_anonymous(&parts);
+    match docker_authentication(&mut parts, &site).await {
+        Ok(RepositoryAuthentication::NoIdentification) if !is_anonymous => {
+            unauthorized_response(&site, &parts, None)
+        }
+        Ok(RepositoryAuthentication::Other(..)) | Err(AuthenticationError::Unauthorized) => {
+            unauthorized_response(&site, &parts, None)
+        }
+        Ok(_) => Response::builder()
+            .status(StatusCode::OK)
+            .header(DOCKER_DISTRIBUTION_API_VERSION, API_VERSION)
+            .header(CONTENT_TYPE, "application/json")
+            .body("{}".into())
+            .unwrap(),
+        Err(err) => err.into_response(),
+    }
+}
+/// Requests to a repository. Only Docker repositories are served under `/v2/`
+async fn handle_v2_request(
+    State(site): State<NitroRepo>,
+    Path(request_path): Path<RepoRequestPath>,
+    parent_span: Option<RequestSpan>,
+    request: Request,
+) -> Result<Response, RepositoryHandlerError> {
+    let names = RepositoryStorageName::from((
+        request_path.storage.as_str(),
+        request_path.repository.as_str(),
+    ));
+    let repository = site.get_repository_from_names(&names).await?;
+    if !matches!(repository, Some(DynRepository::Docker(_))) {
+        return Ok(DockerRegistryError::NameUnknown(format!(
+            "{}/{}",
+            request_path.storage, request_path.repository
+        ))
+        .into_response());
+    }
+    let (mut parts, body) = request.into_parts();
+    let authentication = match docker_authentication(&mut parts, &site).await {
+        Ok(authentication) => authentication,
+        Err(AuthenticationError::Unauthorized) => {
+            // Expired tokens end up here. The challenge makes the client request a new one
+            return Ok(unauthorized_response(&site, &parts, None));
+        }
+        Err(err) => return Ok(err.into_response()),
+    };
+    handle_repo_request(
+        State(site),
+        Path(request_path),
+        parent_span,
+        authentication,
+        Request::from_parts(parts, body),
+    )
+    .await
+}
+/// A scope requested from the token endpoint. `repository:{storage}/{repository}/{name}:{actions}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenScope {
+    pub storage: String,
+    pub repository: String,
+    pub actions: Vec<RepositoryActions>,
+}
+impl TokenScope {
+    pub fn parse(scope: &str) -> Option<Self> {
+        let scope = scope.strip_prefix("repository:")?;
+        let (name, actions) = scope.rsplit_once(':')?;
+        let mut components = name.splitn(3, '/');
+        let storage = components.next()?.to_owned();
+        let repository = components.next()?.to_owned();
+        let mut scope_actions = Vec::new();
+        for action in actions.split(',') {
+            let action = match action.trim() {
+                "pull" => RepositoryActions::Read,
+                "push" | "delete" | "*" => RepositoryActions::Write,
+                _ => continue,
+            };
+            if !scope_actions.contains(&action) {
+                scope_actions.push(action);
+            }
+        }
+        Some(Self {
+            storage,
+            repository,
+            actions: scope_actions,
+        })
+    }
+    /// Every `scope` parameter of the query. A parameter can contain multiple scopes separated by spaces
+    pub fn from_query(query: Option<&str>) -> Vec<Self> {
+        let Some(query) = query else {
+            return vec![];
+        };
+        url::form_urlencoded::parse(query.as_bytes())
+            .filter(|(key, _)| key == "scope")
+            .flat_map(|(_, value)| {
+                value
+                    .split_whitespace()
+                    .filter_map(TokenScope::parse)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+    /// Same as `token`. OAuth2 clients read this field
+    pub access_token: String,
+    pub expires_in: i64,
+    pub issued_at: DateTime<FixedOffset>,
+}
+impl TokenResponse {
+    pub fn new(token: String) -> Self {
+        Self {
+            access_token: token.clone(),
+            token,
+            expires_in: TOKEN_EXPIRES_IN,
+            issued_at: Local::now().fixed_offset(),
+        }
+    }
+}
+/// `GET /v2/token`. Exchanges the credentials for a bearer token
+///
+/// Auth Tokens are returned as is. Logins with a password create a short lived token limited to the requested scopes.
+/// Requests without credentials get the anonymous token
+#[instrument(skip(site, request))]
+async fn token(State(site): State<NitroRepo>, request: Request) -> Result<Response, InternalError> {
+    let (parts, _) = request.into_parts();
+    let raw_auth = parts
+        .extensions
+        .get::<AuthenticationRaw>()
+        .cloned()
+        .unwrap_or(AuthenticationRaw::NoIdentification);
+    let user = match raw_auth {
+        AuthenticationRaw::NoIdentification => {
+            return Ok(Json(TokenResponse::new(ANONYMOUS_TOKEN.to_owned())).into_response());
+        }
+        AuthenticationRaw::AuthToken(token) => {
+            return auth_token_response(&site, &parts, token).await;
+        }
+        AuthenticationRaw::Basic { username, password } => {
+            match verify_login(username, &password, &site.database).await {
+                Ok(user) => user,
+                // `docker login` with an Auth Token as the password
+                Err(AuthenticationError::Unauthorized) => {
+                    return auth_token_response(&site, &parts, password).await;
+                }
+                Err(err) => return Ok(err.into_response()),
+            }
+        }
+        AuthenticationRaw::Session(session) => {
+            match UserSafeData::get_by_id(session.user_id, &site.database).await? {
+                Some(user) => user,
+                None => return Ok(unauthorized_response(&site, &parts, None)),
+            }
+        }
+        AuthenticationRaw::AuthorizationHeaderUnknown(..) => {
+            return Ok(unauthorized_response(&site, &parts, None));
+        }
+    };
+    let scopes = TokenScope::from_query(parts.uri.query());
+    let token = create_scoped_token(&site, &user, scopes).await?;
+    Ok(Json(TokenResponse::new(token)).into_response())
+}
+async fn auth_token_response(
+    site: &NitroRepo,
+    parts: &Parts,
+    token: String,
+) -> Result<Response, InternalError> {
+    match get_user_and_auth_token(&token, &site.database).await {
+        Ok(_) => Ok(Json(TokenResponse::new(token)).into_response()),
+        Err(AuthenticationError::Unauthorized) => Ok(unauthorized_response(site, parts, None)),
+        Err(err) => Ok(err.into_response()),
+    }
+}
+/// Creates a token that expires after [TOKEN_EXPIRES_IN] seconds.
+///
+/// Only the actions the user has are granted. A token without any repositories is still valid for `/v2/`
+async fn create_scoped_token(
+    site: &NitroRepo,
+    user: &UserSafeData,
+    scopes: Vec<TokenScope>,
+) -> Result<String, sqlx::Error> {
+    let mut repositories: Vec<(Uuid, Vec<RepositoryActions>)> = Vec::new();
+    for scope in scopes {
+        let names =
+            RepositoryStorageName::from((scope.storage.as_str(), scope.repository.as_str()));
+        let Some(DynRepository::Docker(repository)) =
+            site.get_repository_from_names(&names).await?
+        else {
+            debug!(?scope, "Scope is not a Docker repository");
+            continue;
+        };
+        let repository_id = repository.id();
+        let mut actions = Vec::new();
+        for action in scope.actions {
+            if user
+                .has_action(action, repository_id, &site.database)
+                .await?
+            {
+                actions.push(action);
+            }
+        }
+        match repositories.iter_mut().find(|(id, _)| *id == repository_id) {
+            Some((_, existing)) => {
+                for action in actions {
+                    if !existing.contains(&action) {
+                        existing.push(action);
+                    }
+                }
+            }
+            None if !actions.is_empty() => repositories.push((repository_id, actions)),
+            None => {}
+        }
+    }
+    AuthToken::delete_expired(&site.database).await?;
+    let (_, token) = NewRepositoryToken {
+        user_id: user.id,
+        source: "Docker Registry Token".to_owned(),
+        repositories,
+        expires_at: Some(Local::now().fixed_offset() + Duration::seconds(TOKEN_EXPIRES_IN)),
+    }
+    .insert(&site.database)
+    .await?;
+    Ok(token)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_token_scope() {
+        let scope = TokenScope::parse("repository:local/docker/library/nginx:pull,push").unwrap();
+        assert_eq!(scope.storage, "local");
+        assert_eq!(scope.repository, "docker");
+        assert_eq!(
+            scope.actions,
+            vec![RepositoryActions::Read, RepositoryActions::Write]
+        );
+        assert!(TokenScope::parse("registry:catalog:*").is_none());
+        let scopes = TokenScope::from_query(Some(
+            "service=localhost&scope=repository%3Alocal%2Fdocker%2Fapp%3Apull&scope=repository:a/b/c:push",
+        ));
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes[1].actions, vec![RepositoryActions::Write]);
+    }
+}
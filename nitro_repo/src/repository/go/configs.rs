@@ -0,0 +1,88 @@
+use nr_core::repository::config::{ConfigDescription, RepositoryConfigError, RepositoryConfigType};
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::proxy::GoProxyConfig;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "config")]
+pub enum GoRepositoryConfig {
+    Hosted,
+    Proxy(GoProxyConfig),
+}
+impl GoRepositoryConfig {
+    pub fn validate(&self) -> Result<(), RepositoryConfigError> {
+        if let GoRepositoryConfig::Proxy(proxy) = self {
+            proxy.validate()?;
+        }
+        Ok(())
+    }
+    pub fn is_same_type(&self, other: &GoRepositoryConfig) -> bool {
+        matches!(
+            (self, other),
+            (GoRepositoryConfig::Hosted, GoRepositoryConfig::Hosted)
+                | (GoRepositoryConfig::Proxy(_), GoRepositoryConfig::Proxy(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GoRepositoryConfigType;
+impl RepositoryConfigType for GoRepositoryConfigType {
+    fn get_type(&self) -> &'static str {
+        "go"
+    }
+
+    fn get_type_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "go"
+    }
+    fn schema(&self) -> Option<schemars::Schema> {
+        Some(schema_for!(GoRepositoryConfig))
+    }
+    fn validate_config(&self, config: Value) -> Result<(), RepositoryConfigError> {
+        let config: GoRepositoryConfig = serde_json::from_value(config)?;
+        config.validate()
+    }
+    fn redact_secrets(&self, config: Value) -> Result<Value, RepositoryConfigError> {
+        let mut config: GoRepositoryConfig = serde_json::from_value(config)?;
+        if let GoRepositoryConfig::Proxy(proxy) = &mut config {
+            proxy.credentials.redact();
+        }
+        Ok(serde_json::to_value(config)?)
+    }
+    fn restore_secrets(&self, old: Value, new: Value) -> Result<Value, RepositoryConfigError> {
+        let old: GoRepositoryConfig = serde_json::from_value(old)?;
+        let mut new: GoRepositoryConfig = serde_json::from_value(new)?;
+        if let (GoRepositoryConfig::Proxy(old), GoRepositoryConfig::Proxy(new)) = (&old, &mut new) {
+            new.credentials.restore_redacted(&old.credentials);
+        }
+        Ok(serde_json::to_value(new)?)
+    }
+    fn validate_change(&self, old: Value, new: Value) -> Result<(), RepositoryConfigError> {
+        let new: GoRepositoryConfig = serde_json::from_value(new)?;
+        let old: GoRepositoryConfig = serde_json::from_value(old)?;
+        if !old.is_same_type(&new) {
+            return Err(RepositoryConfigError::InvalidChange(
+                "go",
+                "Cannot change the type of Go Repository",
+            ));
+        }
+        new.validate()
+    }
+    fn default(&self) -> Result<Value, RepositoryConfigError> {
+        let config = GoRepositoryConfig::Hosted;
+        Ok(serde_json::to_value(config).unwrap())
+    }
+    fn get_description(&self) -> ConfigDescription {
+        ConfigDescription {
+            name: "Go Repository Config",
+            description: Some("Handles the type of Go Repository"),
+            documentation_link: None,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use super::{
+    GoRepositoryConfigType, GoRepositoryError,
+    module_zip::validate_module_zip,
+    types::{
+        GoFile, GoPath, GoVersionInfo, compare_versions, is_pseudo_version, latest_version,
+        synthesized_go_mod, validate_version, version_file_path,
+    },
+    utils::{GoRepositoryExt, go_file_response, module_not_found},
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest, utils::RepositoryExt,
+    },
+};
+use axum::body::Body;
+use chrono::Utc;
+use derive_more::derive::Deref;
+use http::StatusCode;
+use nr_core::{
+    database::entities::{project::versions::DBProjectVersion, repository::DBRepository},
+    repository::{
+        Visibility,
+        config::{RepositoryConfigType, project::ProjectConfigType},
+        project::ProjectResolution,
+    },
+    storage::StoragePath,
+    user::permissions::RepositoryActions,
+};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile};
+use parking_lot::RwLock;
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+use tracing::{debug, error, info, instrument};
+
+#[derive(derive_more::Debug)]
+pub struct GoHostedInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: uuid::Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+}
+/// A Go Module Proxy that hosts its own modules
+///
+/// Module zips are uploaded with `PUT {module}/@v/{version}.zip`. The `.mod` and `.info` files are created from the zip.
+/// Files are saved at `{module}/@v/{version}.{extension}` using the case encoded module path and version
+#[derive(Debug, Clone, Deref)]
+pub struct GoHosted(Arc<GoHostedInner>);
+impl GoHosted {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(GoHostedInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+        })))
+    }
+    async fn get_versions(&self, module: &str) -> Result<Option<Vec<String>>, GoRepositoryError> {
+        let Some(project) = self.get_module_project(module).await? else {
+            return Ok(None);
+        };
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref())
+            .await?
+            .into_iter()
+            .map(|version| version.version)
+            .collect();
+        Ok(Some(versions))
+    }
+    /// `{module}/@v/list`. Every version except pseudo-versions
+    #[instrument]
+    async fn handle_list(&self, module: &str) -> Result<RepoResponse, GoRepositoryError> {
+        let Some(mut versions) = self.get_versions(module).await? else {
+            return Ok(module_not_found(module));
+        };
+        versions.retain(|version| !is_pseudo_version(version));
+        versions.sort_by(|a, b| compare_versions(a, b));
+        let mut body = versions.join("\n");
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        Ok(RepoResponse::basic_text_response(StatusCode::OK, body))
+    }
+    /// `{module}/@latest`. The `.info` of the latest version
+    #[instrument]
+    async fn handle_latest(&self, module: &str) -> Result<RepoResponse, GoRepositoryError> {
+        let Some(versions) = self.get_versions(module).await? else {
+            return Ok(module_not_found(module));
+        };
+        let Some(latest) = latest_version(versions.iter().map(String::as_str)) else {
+            return Ok(module_not_found(module));
+        };
+        self.handle_file(module, latest, GoFile::Info).await
+    }
+    /// `{module}/@v/{version}.info`, `.mod`, or `.zip`
+    async fn handle_file(
+        &self,
+        module: &str,
+        version: &str,
+        file: GoFile,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        let path = version_file_path(module, version, file);
+        match self.storage.open_file(self.id, &path).await? {
+            Some(StorageFile::File { meta, content }) => {
+                let size = meta.file_type.file_size as usize;
+                Ok(go_file_response(file, Body::new(content.into_body(size))))
+            }
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("{}@{} not found", module, version),
+            )),
+        }
+    }
+    /// `PUT {module}/@v/{version}.zip`. Versions can not be replaced
+    #[instrument]
+    async fn handle_upload(
+        &self,
+        request: RepositoryRequest,
+        module: String,
+        version: String,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        validate_version(&module, &version)?;
+        let zip_path = version_file_path(&module, &version, GoFile::Zip);
+        if self.storage.file_exists(self.id, &zip_path).await? {
+            return Err(GoRepositoryError::VersionAlreadyExists { module, version });
+        }
+        let body = request.body.body_as_bytes().await?;
+        let module_zip = validate_module_zip(&body, &module, &version)?;
+        let go_mod = module_zip
+            .go_mod
+            .unwrap_or_else(|| synthesized_go_mod(&module));
+        let info = GoVersionInfo {
+            version: version.clone(),
+            time: Utc::now(),
+        };
+        self.storage
+            .save_file(
+                self.id,
+                FileContent::Content(go_mod.into_bytes()),
+                &version_file_path(&module, &version, GoFile::Mod),
+            )
+            .await?;
+        self.storage
+            .save_file(
+                self.id,
+                FileContent::Content(serde_json::to_vec(&info)?),
+                &version_file_path(&module, &version, GoFile::Info),
+            )
+            .await?;
+        // The zip is saved last. It marks the version as uploaded
+        self.storage
+            .save_file(self.id, FileContent::Bytes(body), &zip_path)
+            .await?;
+        self.record_module_version(&module, &info, Some(user.id))
+            .await?;
+        info!(?module, ?version, "Uploaded module");
+        Ok(RepoResponse::put_response(true, zip_path.to_string()))
+    }
+}
+impl RepositoryExt for GoHosted {}
+impl GoRepositoryExt for GoHosted {}
+impl Repository for GoHosted {
+    type Error = GoRepositoryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "go"
+    }
+    fn full_type(&self) -> &'static str {
+        "go/hosted"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![
+            GoRepositoryConfigType::get_type_static(),
+            ProjectConfigType::get_type_static(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "go/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        Ok(())
+    }
+    async fn resolve_project_and_version_for_path(
+        &self,
+        path: &StoragePath,
+    ) -> Result<ProjectResolution, GoRepositoryError> {
+        self.resolve_go_path(path).await
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        let path_as_string = request.path.to_string();
+        debug!(?path_as_string, "Handling Go GET request");
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match GoPath::parse(&path_as_string)? {
+            Some(GoPath::List { module }) => self.handle_list(&module).await,
+            Some(GoPath::Latest { module }) => self.handle_latest(&module).await,
+            Some(GoPath::File {
+                module,
+                version,
+                file,
+            }) => self.handle_file(&module, &version, file).await,
+            None => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+    async fn handle_put(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        match GoPath::parse(&request.path.to_string())? {
+            Some(GoPath::File {
+                module,
+                version,
+                file: GoFile::Zip,
+            }) => self.handle_upload(request, module, version).await,
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Only module zips can be uploaded. PUT {module}/@v/{version}.zip",
+            )),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Go Module Proxy Implementation
+//!
+//! Implements the GOPROXY protocol. Modules are downloaded with `GOPROXY={app_url}/repositories/{storage}/{repository}`
+//!
+//! Documentation for the GOPROXY protocol: https://go.dev/ref/mod#goproxy-protocol
+
+use ahash::HashMap;
+use futures::future::BoxFuture;
+use hosted::GoHosted;
+use module_zip::InvalidModuleZip;
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::config::{RepositoryConfigType, project::ProjectConfigType},
+};
+use nr_macros::DynRepositoryHandler;
+use nr_storage::DynStorage;
+use proxy::GoProxy;
+use tracing::debug;
+use types::InvalidGoModule;
+
+pub mod hosted;
+pub mod module_zip;
+pub mod proxy;
+pub mod types;
+pub mod utils;
+use crate::{
+    app::authentication::AuthenticationError,
+    error::{BadRequestErrors, IntoErrorResponse},
+};
+
+pub use super::prelude::*;
+mod configs;
+use super::{DynRepository, NewRepository, RepositoryType, RepositoryTypeDescription};
+pub use configs::*;
+
+#[derive(Debug, Clone, DynRepositoryHandler)]
+#[repository_handler(error=GoRepositoryError)]
+pub enum GoRepository {
+    Hosted(GoHosted),
+    Proxy(GoProxy),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoRepositoryError {
+    #[error(transparent)]
+    InvalidModule(#[from] InvalidGoModule),
+    #[error(transparent)]
+    InvalidModuleZip(#[from] InvalidModuleZip),
+    #[error("{module}@{version} already exists. Module versions can not be replaced")]
+    VersionAlreadyExists { module: String, version: String },
+    #[error("{0}")]
+    Other(Box<dyn IntoErrorResponse>),
+}
+impl From<GoRepositoryError> for RepositoryHandlerError {
+    fn from(err: GoRepositoryError) -> Self {
+        RepositoryHandlerError::Other(Box::new(err))
+    }
+}
+macro_rules! impl_from_error_for_other {
+    ($t:ty) => {
+        impl From<$t> for GoRepositoryError {
+            fn from(e: $t) -> Self {
+                GoRepositoryError::Other(Box::new(e))
+            }
+        }
+    };
+}
+impl_from_error_for_other!(BadRequestErrors);
+impl_from_error_for_other!(sqlx::Error);
+impl_from_error_for_other!(serde_json::Error);
+impl_from_error_for_other!(std::io::Error);
+impl_from_error_for_other!(AuthenticationError);
+impl_from_error_for_other!(RepositoryHandlerError);
+impl_from_error_for_other!(nr_storage::StorageError);
+impl_from_error_for_other!(reqwest::Error);
+
+impl IntoErrorResponse for GoRepositoryError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
+
+impl From<GoRepositoryError> for DynRepositoryHandlerError {
+    fn from(err: GoRepositoryError) -> Self {
+        DynRepositoryHandlerError(Box::new(err))
+    }
+}
+
+impl IntoResponse for GoRepositoryError {
+    fn into_response(self) -> Response {
+        match self {
+            GoRepositoryError::Other(other) => other.into_response_boxed(),
+            GoRepositoryError::VersionAlreadyExists { .. } => Response::builder()
+                .status(StatusCode::CONFLICT)
+                .body(self.to_string().into())
+                .unwrap(),
+            bad_request => {
+                debug!("Bad Request: {:?}", bad_request);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(bad_request.to_string().into())
+                    .unwrap()
+            }
+        }
+    }
+}
+#[derive(Debug, Default)]
+pub struct GoRepositoryType;
+
+impl RepositoryType for GoRepositoryType {
+    fn get_type(&self) -> &'static str {
+        "go"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![
+            GoRepositoryConfigType::get_type_static(),
+            ProjectConfigType::get_type_static(),
+        ]
+    }
+
+    fn get_description(&self) -> RepositoryTypeDescription {
+        RepositoryTypeDescription {
+            type_name: "go",
+            name: "Go",
+            description: "A Go Module Proxy. Hosts modules or proxies another module proxy",
+            documentation_url: Some("https://nitro-repo.kingtux.dev/repositoryTypes/go/"),
+            is_stable: false,
+            required_configs: vec![GoRepositoryConfigType::get_type_static()],
+        }
+    }
+
+    fn create_new(
+        &self,
+        name: String,
+        uuid: uuid::Uuid,
+        configs: HashMap<String, serde_json::Value>,
+        storage: nr_storage::DynStorage,
+    ) -> BoxFuture<'static, Result<NewRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let sub_type = configs
+                .get(GoRepositoryConfigType::get_type_static())
+                .ok_or(RepositoryFactoryError::MissingConfig(
+                    GoRepositoryConfigType::get_type_static(),
+                ))?
+                .clone();
+            if let Err(err) = serde_json::from_value::<GoRepositoryConfig>(sub_type) {
+                return Err(RepositoryFactoryError::InvalidConfig(
+                    GoRepositoryConfigType::get_type_static(),
+                    err.to_string(),
+                ));
+            }
+            Ok(NewRepository {
+                name,
+                uuid,
+                repository_type: "go".to_string(),
+                configs,
+            })
+        })
+    }
+
+    fn load_repo(
+        &self,
+        repo: DBRepository,
+        storage: DynStorage,
+        website: NitroRepo,
+    ) -> BoxFuture<'static, Result<DynRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let Some(go_config_db) = DBRepositoryConfig::<GoRepositoryConfig>::get_config(
+                repo.id,
+                GoRepositoryConfigType::get_type_static(),
+                &website.database,
+            )
+            .await?
+            else {
+                return Err(RepositoryFactoryError::MissingConfig(
+                    GoRepositoryConfigType::get_type_static(),
+                ));
+            };
+            match go_config_db.value.0 {
+                GoRepositoryConfig::Hosted => {
+                    let hosted = GoHosted::load(website, storage, repo).await?;
+                    Ok(GoRepository::Hosted(hosted).into())
+                }
+                GoRepositoryConfig::Proxy(proxy_config) => {
+                    let proxy = GoProxy::load(website, storage, repo, proxy_config).await?;
+                    Ok(GoRepository::Proxy(proxy).into())
+                }
+            }
+        })
+    }
+}
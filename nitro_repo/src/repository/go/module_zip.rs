@@ -0,0 +1,182 @@
+//! Validates the layout of module zips
+//!
+//! Documentation for module zips: https://go.dev/ref/mod#zip-files
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    io::{Cursor, Read},
+};
+
+use thiserror::Error;
+use zip::{ZipArchive, result::ZipError};
+
+use super::types::go_mod_module_path;
+/// The largest module zip the go command will download
+pub const MAX_ZIP_SIZE: u64 = 500 << 20;
+/// The largest go.mod the go command will read
+pub const MAX_GO_MOD_SIZE: u64 = 16 << 20;
+
+#[derive(Debug, Error)]
+#[error("Invalid module zip. {0}")]
+pub struct InvalidModuleZip(pub Cow<'static, str>);
+impl From<ZipError> for InvalidModuleZip {
+    fn from(err: ZipError) -> Self {
+        InvalidModuleZip(Cow::Owned(err.to_string()))
+    }
+}
+impl From<String> for InvalidModuleZip {
+    fn from(err: String) -> Self {
+        InvalidModuleZip(Cow::Owned(err))
+    }
+}
+impl From<&'static str> for InvalidModuleZip {
+    fn from(err: &'static str) -> Self {
+        InvalidModuleZip(Cow::Borrowed(err))
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleZip {
+    /// The go.mod in the root of the module. If the module has one
+    pub go_mod: Option<String>,
+}
+/// Checks that every file is in `{module}@{version}/`, has a valid path, and is only in the zip once.
+///
+/// Nested modules are rejected. The go.mod must declare the module path
+pub fn validate_module_zip(
+    content: &[u8],
+    module: &str,
+    version: &str,
+) -> Result<ModuleZip, InvalidModuleZip> {
+    if content.len() as u64 > MAX_ZIP_SIZE {
+        return Err("The zip is larger than 500 MiB".into());
+    }
+    let prefix = format!("{}@{}/", module, version);
+    let mut archive = ZipArchive::new(Cursor::new(content))?;
+    let mut seen = HashSet::with_capacity(archive.len());
+    let mut unzipped_size = 0u64;
+    let mut go_mod = None;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_owned();
+        let Some(relative) = name.strip_prefix(&prefix) else {
+            return Err(format!("{} is not in {}", name, prefix).into());
+        };
+        if file.is_dir() {
+            continue;
+        }
+        validate_file_path(relative)?;
+        // Files are compared case insensitively so the module can be extracted on any file system
+        if !seen.insert(relative.to_lowercase()) {
+            return Err(format!("{} is in the zip more than once", relative).into());
+        }
+        unzipped_size += file.size();
+        if unzipped_size > MAX_ZIP_SIZE {
+            return Err("The unzipped module is larger than 500 MiB".into());
+        }
+        if relative == "go.mod" {
+            if file.size() > MAX_GO_MOD_SIZE {
+                return Err("go.mod is larger than 16 MiB".into());
+            }
+            let mut content = String::with_capacity(file.size() as usize);
+            file.read_to_string(&mut content)
+                .map_err(|err| format!("Failed to read go.mod. {}", err))?;
+            go_mod = Some(content);
+        } else if relative.ends_with("/go.mod") {
+            return Err(format!(
+                "{} is a nested module. Nested modules must be published separately",
+                relative
+            )
+            .into());
+        }
+    }
+    if let Some(go_mod) = &go_mod {
+        match go_mod_module_path(go_mod) {
+            Some(path) if path == module => {}
+            Some(path) => {
+                return Err(format!("go.mod declares {}. Expected {}", path, module).into());
+            }
+            None => return Err("go.mod does not have a module directive".into()),
+        }
+    }
+    Ok(ModuleZip { go_mod })
+}
+fn validate_file_path(path: &str) -> Result<(), InvalidModuleZip> {
+    if path.contains('\\') || path.chars().any(char::is_control) {
+        return Err(format!("{} is not a valid file path", path).into());
+    }
+    if path
+        .split('/')
+        .any(|element| element.is_empty() || element == "." || element == "..")
+    {
+        return Err(format!("{} is not a valid file path", path).into());
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+    fn build_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+    #[test]
+    fn test_valid_zip() {
+        let zip = build_zip(&[
+            (
+                "example.com/module@v1.0.0/go.mod",
+                "module example.com/module\n",
+            ),
+            ("example.com/module@v1.0.0/module.go", "package module\n"),
+            ("example.com/module@v1.0.0/sub/sub.go", "package sub\n"),
+        ]);
+        let module_zip = validate_module_zip(&zip, "example.com/module", "v1.0.0").unwrap();
+        assert_eq!(
+            module_zip.go_mod.as_deref(),
+            Some("module example.com/module\n")
+        );
+
+        let zip = build_zip(&[("example.com/module@v1.0.0/module.go", "package module\n")]);
+        let module_zip = validate_module_zip(&zip, "example.com/module", "v1.0.0").unwrap();
+        assert_eq!(module_zip.go_mod, None);
+    }
+    #[test]
+    fn test_invalid_zip() {
+        let wrong_prefix =
+            build_zip(&[("example.com/module@v1.0.1/module.go", "package module\n")]);
+        assert!(validate_module_zip(&wrong_prefix, "example.com/module", "v1.0.0").is_err());
+
+        let duplicate = build_zip(&[
+            ("example.com/module@v1.0.0/module.go", "package module\n"),
+            ("example.com/module@v1.0.0/Module.go", "package module\n"),
+        ]);
+        assert!(validate_module_zip(&duplicate, "example.com/module", "v1.0.0").is_err());
+
+        let nested = build_zip(&[(
+            "example.com/module@v1.0.0/nested/go.mod",
+            "module example.com/module/nested\n",
+        )]);
+        assert!(validate_module_zip(&nested, "example.com/module", "v1.0.0").is_err());
+
+        let wrong_module = build_zip(&[(
+            "example.com/module@v1.0.0/go.mod",
+            "module example.com/other\n",
+        )]);
+        assert!(validate_module_zip(&wrong_module, "example.com/module", "v1.0.0").is_err());
+
+        let traversal =
+            build_zip(&[("example.com/module@v1.0.0/../escape.go", "package escape\n")]);
+        assert!(validate_module_zip(&traversal, "example.com/module", "v1.0.0").is_err());
+
+        assert!(validate_module_zip(b"not a zip", "example.com/module", "v1.0.0").is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! A Go Module Proxy that proxies an upstream module proxy. Such as proxy.golang.org
+//!
+//! `.info`, `.mod`, and `.zip` files of canonical versions are cached in storage forever.
+//! `@v/list` and `@latest` are cached for [GoProxyConfig::list_ttl]
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+
+use axum::body::Body;
+use bytes::Bytes;
+use chrono::{Duration, Local};
+use derive_more::derive::Deref;
+use http::StatusCode;
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::{
+        Visibility,
+        config::{RepositoryConfigType, project::ProjectConfigType},
+        project::ProjectResolution,
+        proxy_url::{InvalidProxyHeader, ProxyCredentials, ProxyURL},
+    },
+    storage::StoragePath,
+};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use super::{
+    GoRepositoryConfig, GoRepositoryConfigType, GoRepositoryError,
+    module_zip::validate_module_zip,
+    types::{
+        GoFile, GoPath, GoVersionInfo, latest_path, list_path, parse_version, version_file_path,
+    },
+    utils::{GoRepositoryExt, go_file_response, module_not_found},
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        utils::{RepositoryExt, http_client},
+    },
+};
+/// The public Go module proxy. Used by default
+pub const GO_PROXY: &str = "https://proxy.golang.org";
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GoProxyConfig {
+    /// The upstream module proxy
+    #[serde(default = "default_upstream")]
+    pub url: ProxyURL,
+    /// Credentials and headers sent to the upstream
+    #[serde(default)]
+    pub credentials: ProxyCredentials,
+    /// How long `@v/list` and `@latest` are served from the cache before they are fetched again. In seconds
+    #[serde(default = "default_list_ttl")]
+    #[schemars(title = "List TTL")]
+    pub list_ttl: u64,
+}
+fn default_upstream() -> ProxyURL {
+    ProxyURL::try_from(GO_PROXY.to_owned()).expect("Invalid Go proxy URL")
+}
+fn default_list_ttl() -> u64 {
+    5 * 60
+}
+impl Default for GoProxyConfig {
+    fn default() -> Self {
+        Self {
+            url: default_upstream(),
+            credentials: ProxyCredentials::default(),
+            list_ttl: default_list_ttl(),
+        }
+    }
+}
+impl GoProxyConfig {
+    pub fn validate(&self) -> Result<(), InvalidProxyHeader> {
+        self.credentials.validate()
+    }
+}
+#[derive(derive_more::Debug)]
+pub struct GoProxyInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    pub config: RwLock<GoProxyConfig>,
+}
+#[derive(Debug, Clone, Deref)]
+pub struct GoProxy(Arc<GoProxyInner>);
+impl GoProxy {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+        config: GoProxyConfig,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(GoProxyInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+            config: RwLock::new(config),
+        })))
+    }
+    /// Requests the path from the upstream. Returns None if the upstream does not have the path
+    #[instrument(skip(self))]
+    async fn request_from_upstream(
+        &self,
+        path: &StoragePath,
+    ) -> Result<Option<Bytes>, GoRepositoryError> {
+        let (url, credentials) = {
+            let config = self.config.read();
+            (
+                format!("{}/{}", config.url, path),
+                config.credentials.clone(),
+            )
+        };
+        let mut request = http_client().get(&url);
+        match credentials.header_map() {
+            Ok(headers) => request = request.headers(headers),
+            Err(err) => warn!(?err, "Invalid credentials for upstream"),
+        }
+        let response = request.send().await?;
+        if response.status().is_success() {
+            return Ok(Some(response.bytes().await?));
+        }
+        debug!(?url, status = ?response.status(), "Upstream does not have the path");
+        Ok(None)
+    }
+    /// `@v/list` and `@latest`. Served from storage until it is older than the TTL.
+    ///
+    /// If the upstream does not respond with the path the cached response is used
+    #[instrument(skip(self))]
+    async fn get_with_ttl(&self, path: &StoragePath) -> Result<Option<Bytes>, GoRepositoryError> {
+        let ttl = Duration::seconds(self.config.read().list_ttl as i64);
+        let cached = match self.storage.open_file(self.id, path).await? {
+            Some(file) => match file.file() {
+                Some((content, meta)) => {
+                    let is_stale = Local::now().fixed_offset() - *meta.modified() > ttl;
+                    let bytes = content
+                        .read_to_vec(meta.file_type.file_size as usize)
+                        .await?;
+                    if !is_stale {
+                        return Ok(Some(Bytes::from(bytes)));
+                    }
+                    Some(bytes)
+                }
+                None => None,
+            },
+            None => None,
+        };
+        match self.request_from_upstream(path).await {
+            Ok(Some(bytes)) => {
+                self.storage
+                    .save_file(self.id, FileContent::Bytes(bytes.clone()), path)
+                    .await?;
+                Ok(Some(bytes))
+            }
+            Ok(None) => Ok(cached.map(Bytes::from)),
+            Err(err) => {
+                let Some(cached) = cached else {
+                    return Err(err);
+                };
+                warn!(
+                    ?err,
+                    ?path,
+                    "Failed to refresh from upstream. Serving cache"
+                );
+                Ok(Some(Bytes::from(cached)))
+            }
+        }
+    }
+    /// `{module}/@v/{version}.info`, `.mod`, or `.zip`.
+    ///
+    /// Only canonical versions are cached. `.info` can also be requested with a query such as `master`
+    #[instrument(skip(self))]
+    async fn get_file(
+        &self,
+        module: &str,
+        version: &str,
+        file: GoFile,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        let path = version_file_path(module, version, file);
+        if let Some(StorageFile::File { meta, content }) =
+            self.storage.open_file(self.id, &path).await?
+        {
+            let size = meta.file_type.file_size as usize;
+            return Ok(go_file_response(file, Body::new(content.into_body(size))));
+        }
+        let Some(bytes) = self.request_from_upstream(&path).await? else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("{}@{} not found", module, version),
+            ));
+        };
+        if parse_version(version).is_none() {
+            return Ok(go_file_response(file, bytes));
+        }
+        let invalid_zip = match file {
+            GoFile::Zip => validate_module_zip(&bytes, module, version).err(),
+            _ => None,
+        };
+        if let Some(err) = invalid_zip {
+            warn!(
+                ?err,
+                ?module,
+                ?version,
+                "Upstream returned an invalid module zip"
+            );
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_GATEWAY,
+                err.to_string(),
+            ));
+        }
+        self.storage
+            .save_file(self.id, FileContent::Bytes(bytes.clone()), &path)
+            .await?;
+        if file == GoFile::Zip {
+            self.record_cached_version(module, version).await;
+        }
+        Ok(go_file_response(file, bytes))
+    }
+    /// Records the module as a project once its zip is cached. Failures are only logged
+    async fn record_cached_version(&self, module: &str, version: &str) {
+        let path = version_file_path(module, version, GoFile::Info);
+        let info = match self.storage.open_file(self.id, &path).await {
+            Ok(Some(StorageFile::File { meta, content })) => content
+                .read_to_vec(meta.file_type.file_size as usize)
+                .await
+                .ok()
+                .map(Bytes::from),
+            _ => self.request_from_upstream(&path).await.ok().flatten(),
+        };
+        let info: GoVersionInfo = match info.map(|info| serde_json::from_slice(&info)) {
+            Some(Ok(info)) => info,
+            Some(Err(err)) => {
+                warn!(?err, ?module, ?version, "Invalid .info from upstream");
+                return;
+            }
+            None => {
+                warn!(
+                    ?module,
+                    ?version,
+                    "Failed to get the .info of a cached module"
+                );
+                return;
+            }
+        };
+        if let Err(err) = self.record_module_version(module, &info, None).await {
+            warn!(?err, ?module, ?version, "Failed to record module version");
+        }
+    }
+}
+impl RepositoryExt for GoProxy {}
+impl GoRepositoryExt for GoProxy {}
+impl Repository for GoProxy {
+    type Error = GoRepositoryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "go"
+    }
+    fn full_type(&self) -> &'static str {
+        "go/proxy"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![
+            GoRepositoryConfigType::get_type_static(),
+            ProjectConfigType::get_type_static(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "go/proxy"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let Some(go_config_db) = DBRepositoryConfig::<GoRepositoryConfig>::get_config(
+            self.id,
+            GoRepositoryConfigType::get_type_static(),
+            self.site.as_ref(),
+        )
+        .await?
+        else {
+            return Err(RepositoryFactoryError::MissingConfig(
+                GoRepositoryConfigType::get_type_static(),
+            ));
+        };
+        match go_config_db.value.0 {
+            GoRepositoryConfig::Proxy(proxy_config) => {
+                *self.config.write() = proxy_config;
+                Ok(())
+            }
+            _ => Err(RepositoryFactoryError::InvalidConfig(
+                GoRepositoryConfigType::get_type_static(),
+                "Expected Proxy Config".into(),
+            )),
+        }
+    }
+    async fn resolve_project_and_version_for_path(
+        &self,
+        path: &StoragePath,
+    ) -> Result<ProjectResolution, GoRepositoryError> {
+        self.resolve_go_path(path).await
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, GoRepositoryError> {
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match GoPath::parse(&request.path.to_string())? {
+            Some(GoPath::List { module }) => match self.get_with_ttl(&list_path(&module)).await? {
+                Some(list) => Ok(RepoResponse::basic_text_response(
+                    StatusCode::OK,
+                    String::from_utf8_lossy(&list),
+                )),
+                None => Ok(module_not_found(&module)),
+            },
+            Some(GoPath::Latest { module }) => {
+                match self.get_with_ttl(&latest_path(&module)).await? {
+                    Some(latest) => Ok(go_file_response(GoFile::Info, latest)),
+                    None => Ok(module_not_found(&module)),
+                }
+            }
+            Some(GoPath::File {
+                module,
+                version,
+                file,
+            }) => self.get_file(&module, &version, file).await,
+            None => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+}
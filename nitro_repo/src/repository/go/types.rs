@@ -0,0 +1,459 @@
+//! Module paths, versions, and the paths of the GOPROXY protocol
+//!
+//! Module paths and versions are case encoded in URLs. Upper case letters are replaced with `!` followed by the lower case letter.
+//! Such as `github.com/!azure/azure-sdk-for-go`
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use nr_core::{repository::project::ReleaseType, storage::StoragePath};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InvalidGoModule {
+    #[error("Invalid module path {path}. {reason}")]
+    Path { path: String, reason: &'static str },
+    #[error("Invalid version {version}. {reason}")]
+    Version {
+        version: String,
+        reason: &'static str,
+    },
+}
+/// Case encodes a module path or version
+pub fn escape_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(c.to_ascii_lowercase());
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+/// Decodes a case encoded module path or version.
+///
+/// Returns None if the value contains upper case letters or a `!` that is not followed by a lower case letter
+pub fn unescape_path(escaped: &str) -> Option<String> {
+    let mut path = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '!' => match chars.next() {
+                Some(next) if next.is_ascii_lowercase() => path.push(next.to_ascii_uppercase()),
+                _ => return None,
+            },
+            c if c.is_ascii_uppercase() => return None,
+            c => path.push(c),
+        }
+    }
+    Some(path)
+}
+/// Validates a module path. Such as `github.com/wyatt-herkamp/nitro_repo`
+///
+/// The first element must be a domain name. Elements can contain letters, digits, and `-._~`
+pub fn validate_module_path(path: &str) -> Result<(), InvalidGoModule> {
+    let invalid = |reason| {
+        Err(InvalidGoModule::Path {
+            path: path.to_owned(),
+            reason,
+        })
+    };
+    if path.is_empty() {
+        return invalid("Module paths can not be empty");
+    }
+    let mut elements = path.split('/');
+    let domain = elements.next().unwrap_or_default();
+    if !domain.contains('.') {
+        return invalid("The first element must be a domain name");
+    }
+    if domain.starts_with('-')
+        || !domain
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-')
+    {
+        return invalid(
+            "The first element can only contain lower case letters, digits, `.`, and `-`",
+        );
+    }
+    for element in std::iter::once(domain).chain(elements) {
+        if element.is_empty() {
+            return invalid("Module paths can not contain empty elements");
+        }
+        if element.starts_with('.') || element.ends_with('.') {
+            return invalid("Elements can not start or end with `.`");
+        }
+        if !element
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+        {
+            return invalid("Elements can only contain letters, digits, and `-._~`");
+        }
+    }
+    Ok(())
+}
+/// The major version required by the module path.
+///
+/// `example.com/module/v2` requires v2 and `gopkg.in/yaml.v3` requires v3
+fn path_major_version(module: &str) -> Option<u64> {
+    if module.starts_with("gopkg.in/") {
+        let (_, major) = module.rsplit_once(".v")?;
+        return major.parse().ok();
+    }
+    let (_, last) = module.rsplit_once('/')?;
+    let major = last.strip_prefix('v')?;
+    if major.starts_with('0') {
+        return None;
+    }
+    major.parse().ok().filter(|major| *major >= 2)
+}
+/// Parses a canonical Go version. Such as `v1.2.3`, `v1.2.3-beta.1` or `v2.0.0+incompatible`
+pub fn parse_version(version: &str) -> Option<semver::Version> {
+    let parsed = semver::Version::parse(version.strip_prefix('v')?).ok()?;
+    if !parsed.build.is_empty() && parsed.build.as_str() != "incompatible" {
+        return None;
+    }
+    // Rejects versions semver accepts but Go does not consider canonical
+    if format!("v{}", parsed) != version {
+        return None;
+    }
+    Some(parsed)
+}
+/// Checks the version is canonical and matches the major version of the module path
+pub fn validate_version(module: &str, version: &str) -> Result<(), InvalidGoModule> {
+    let invalid = |reason| {
+        Err(InvalidGoModule::Version {
+            version: version.to_owned(),
+            reason,
+        })
+    };
+    let Some(parsed) = parse_version(version) else {
+        return invalid("Versions must be canonical semantic versions. Such as v1.2.3");
+    };
+    let incompatible = !parsed.build.is_empty();
+    match path_major_version(module) {
+        Some(_) if incompatible => {
+            invalid("+incompatible can not be used with a major version suffix")
+        }
+        Some(major) if parsed.major != major => {
+            invalid("The major version does not match the module path")
+        }
+        Some(_) => Ok(()),
+        None if incompatible && parsed.major < 2 => {
+            invalid("+incompatible can only be used with major version 2 and above")
+        }
+        None if !incompatible && parsed.major >= 2 => {
+            invalid("Major version 2 and above require a /vN suffix on the module path")
+        }
+        None => Ok(()),
+    }
+}
+/// Pseudo-versions reference a commit. Such as `v0.0.0-20191109021931-daa7c04131f5`
+///
+/// They are not included in `@v/list`
+pub fn is_pseudo_version(version: &str) -> bool {
+    let Some(parsed) = parse_version(version) else {
+        return false;
+    };
+    let Some((rest, revision)) = parsed.pre.as_str().rsplit_once('-') else {
+        return false;
+    };
+    if revision.len() != 12
+        || !revision
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    {
+        return false;
+    }
+    let Some(timestamp_start) = rest.len().checked_sub(14) else {
+        return false;
+    };
+    let (prefix, timestamp) = rest.split_at(timestamp_start);
+    timestamp.chars().all(|c| c.is_ascii_digit()) && (prefix.is_empty() || prefix.ends_with('.'))
+}
+/// Pre-releases that are not marked as alpha, beta, or release candidates are not made the latest release
+pub fn go_release_type(version: &str) -> ReleaseType {
+    let has_pre_release = parse_version(version).is_some_and(|parsed| !parsed.pre.is_empty());
+    if !has_pre_release {
+        return ReleaseType::Stable;
+    }
+    match ReleaseType::release_type_from_version(version) {
+        ReleaseType::Stable => ReleaseType::Unknown,
+        other => other,
+    }
+}
+/// Orders versions by semantic version. Versions that are not canonical are ordered first
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (parse_version(a), parse_version(b)) {
+        (Some(a), Some(b)) => a.cmp_precedence(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+/// The version `@latest` resolves to.
+///
+/// The highest release. If there are no releases the highest pre-release. Then the highest pseudo-version
+pub fn latest_version<'a>(versions: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let rank = |version: &str| {
+        if is_pseudo_version(version) {
+            0
+        } else if parse_version(version).is_some_and(|parsed| !parsed.pre.is_empty()) {
+            1
+        } else {
+            2
+        }
+    };
+    versions
+        .into_iter()
+        .filter(|version| parse_version(version).is_some())
+        .max_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| compare_versions(a, b)))
+}
+/// The go.mod served for modules that do not have one
+pub fn synthesized_go_mod(module: &str) -> String {
+    format!("module {}\n", module)
+}
+/// The module path declared by the `module` directive of a go.mod
+pub fn go_mod_module_path(go_mod: &str) -> Option<String> {
+    go_mod.lines().find_map(|line| {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        let path = line.strip_prefix("module")?;
+        if !path.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let path = path.trim().trim_matches(|c| c == '"' || c == '`');
+        Some(path.to_owned())
+    })
+}
+/// The contents of `@v/{version}.info`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GoVersionInfo {
+    #[serde(rename = "Version")]
+    pub version: String,
+    #[serde(rename = "Time")]
+    pub time: DateTime<Utc>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoFile {
+    Info,
+    Mod,
+    Zip,
+}
+impl GoFile {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GoFile::Info => "info",
+            GoFile::Mod => "mod",
+            GoFile::Zip => "zip",
+        }
+    }
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            GoFile::Info => "application/json",
+            GoFile::Mod => "text/plain; charset=utf-8",
+            GoFile::Zip => "application/zip",
+        }
+    }
+}
+/// `{module}/@v/{version}.{extension}`
+pub fn version_file_path(module: &str, version: &str, file: GoFile) -> StoragePath {
+    StoragePath::from(format!(
+        "{}/@v/{}.{}",
+        escape_path(module),
+        escape_path(version),
+        file.extension()
+    ))
+}
+/// `{module}/@v/list`
+pub fn list_path(module: &str) -> StoragePath {
+    StoragePath::from(format!("{}/@v/list", escape_path(module)))
+}
+/// `{module}/@latest`
+pub fn latest_path(module: &str) -> StoragePath {
+    StoragePath::from(format!("{}/@latest", escape_path(module)))
+}
+/// A request of the GOPROXY protocol. Module paths and versions are decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoPath {
+    /// `{module}/@v/list`
+    List { module: String },
+    /// `{module}/@latest`
+    Latest { module: String },
+    /// `{module}/@v/{version}.info`, `.mod`, or `.zip`
+    ///
+    /// The version is not validated. `.info` can be requested with a query such as `master` when proxying
+    File {
+        module: String,
+        version: String,
+        file: GoFile,
+    },
+}
+impl GoPath {
+    pub fn parse(path: &str) -> Result<Option<Self>, InvalidGoModule> {
+        let path = path.trim_matches('/');
+        let (module, file) = if let Some(module) = path.strip_suffix("/@latest") {
+            (module, None)
+        } else if let Some((module, file)) = path.split_once("/@v/") {
+            (module, Some(file))
+        } else {
+            return Ok(None);
+        };
+        let Some(module) = unescape_path(module) else {
+            return Err(InvalidGoModule::Path {
+                path: module.to_owned(),
+                reason: "Invalid case encoding",
+            });
+        };
+        validate_module_path(&module)?;
+        let Some(file) = file else {
+            return Ok(Some(GoPath::Latest { module }));
+        };
+        if file == "list" {
+            return Ok(Some(GoPath::List { module }));
+        }
+        let Some((version, extension)) = file.rsplit_once('.') else {
+            return Ok(None);
+        };
+        let file = match extension {
+            "info" => GoFile::Info,
+            "mod" => GoFile::Mod,
+            "zip" => GoFile::Zip,
+            _ => return Ok(None),
+        };
+        if version.is_empty() || version.contains('/') {
+            return Ok(None);
+        }
+        let Some(version) = unescape_path(version) else {
+            return Err(InvalidGoModule::Version {
+                version: version.to_owned(),
+                reason: "Invalid case encoding",
+            });
+        };
+        Ok(Some(GoPath::File {
+            module,
+            version,
+            file,
+        }))
+    }
+    pub fn module(&self) -> &str {
+        match self {
+            GoPath::List { module } | GoPath::Latest { module } | GoPath::File { module, .. } => {
+                module
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_case_encoding() {
+        let module = "github.com/Azure/azure-sdk-for-go";
+        let escaped = escape_path(module);
+        assert_eq!(escaped, "github.com/!azure/azure-sdk-for-go");
+        assert_eq!(unescape_path(&escaped).as_deref(), Some(module));
+        assert_eq!(unescape_path("github.com/Azure"), None);
+        assert_eq!(unescape_path("github.com/!!azure"), None);
+    }
+    #[test]
+    fn test_validate_version() {
+        assert!(validate_version("example.com/module", "v1.2.3").is_ok());
+        assert!(validate_version("example.com/module", "v0.1.0-beta.1").is_ok());
+        assert!(validate_version("example.com/module", "v2.0.0+incompatible").is_ok());
+        assert!(validate_version("example.com/module/v2", "v2.1.0").is_ok());
+        assert!(validate_version("gopkg.in/yaml.v3", "v3.0.1").is_ok());
+
+        assert!(validate_version("example.com/module", "1.2.3").is_err());
+        assert!(validate_version("example.com/module", "v1.2").is_err());
+        assert!(validate_version("example.com/module", "v1.2.3+build").is_err());
+        assert!(validate_version("example.com/module", "v2.0.0").is_err());
+        assert!(validate_version("example.com/module", "v1.0.0+incompatible").is_err());
+        assert!(validate_version("example.com/module/v2", "v3.0.0").is_err());
+        assert!(validate_version("example.com/module/v2", "v2.0.0+incompatible").is_err());
+    }
+    #[test]
+    fn test_validate_module_path() {
+        assert!(validate_module_path("github.com/wyatt-herkamp/nitro_repo").is_ok());
+        assert!(validate_module_path("example.com").is_ok());
+        assert!(validate_module_path("").is_err());
+        assert!(validate_module_path("module").is_err());
+        assert!(validate_module_path("Example.com/module").is_err());
+        assert!(validate_module_path("example.com//module").is_err());
+        assert!(validate_module_path("example.com/../module").is_err());
+        assert!(validate_module_path("example.com/mod!ule").is_err());
+    }
+    #[test]
+    fn test_versions() {
+        assert!(is_pseudo_version("v0.0.0-20191109021931-daa7c04131f5"));
+        assert!(is_pseudo_version("v1.2.4-0.20191109021931-daa7c04131f5"));
+        assert!(is_pseudo_version(
+            "v1.2.3-pre.0.20191109021931-daa7c04131f5"
+        ));
+        assert!(!is_pseudo_version("v1.2.3-beta.1"));
+        assert!(!is_pseudo_version("v1.2.3"));
+
+        let versions = [
+            "v1.0.0",
+            "v1.10.0",
+            "v1.2.0",
+            "v2.0.0-beta.1",
+            "v0.0.0-20191109021931-daa7c04131f5",
+        ];
+        assert_eq!(latest_version(versions), Some("v1.10.0"));
+        assert_eq!(
+            latest_version(["v1.0.0-beta.1", "v0.0.0-20191109021931-daa7c04131f5"]),
+            Some("v1.0.0-beta.1")
+        );
+        assert_eq!(latest_version(["master"]), None);
+
+        assert_eq!(go_release_type("v1.0.0"), ReleaseType::Stable);
+        assert_eq!(go_release_type("v1.0.0-beta.1"), ReleaseType::Beta);
+        assert_eq!(
+            go_release_type("v0.0.0-20191109021931-daa7c04131f5"),
+            ReleaseType::Unknown
+        );
+    }
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            GoPath::parse("github.com/!azure/sdk/@v/v1.0.0.info").unwrap(),
+            Some(GoPath::File {
+                module: "github.com/Azure/sdk".to_owned(),
+                version: "v1.0.0".to_owned(),
+                file: GoFile::Info
+            })
+        );
+        assert_eq!(
+            GoPath::parse("example.com/module/@v/list").unwrap(),
+            Some(GoPath::List {
+                module: "example.com/module".to_owned()
+            })
+        );
+        assert_eq!(
+            GoPath::parse("example.com/module/@latest").unwrap(),
+            Some(GoPath::Latest {
+                module: "example.com/module".to_owned()
+            })
+        );
+        assert_eq!(GoPath::parse("example.com/module").unwrap(), None);
+        assert_eq!(
+            GoPath::parse("example.com/module/@v/v1.0.0.tar").unwrap(),
+            None
+        );
+        assert!(GoPath::parse("example.com/Module/@v/list").is_err());
+    }
+    #[test]
+    fn test_go_mod_module_path() {
+        let go_mod = "// A comment\nmodule example.com/module // trailing\n\ngo 1.22\n";
+        assert_eq!(
+            go_mod_module_path(go_mod).as_deref(),
+            Some("example.com/module")
+        );
+        assert_eq!(
+            go_mod_module_path("module \"example.com/quoted\"\n").as_deref(),
+            Some("example.com/quoted")
+        );
+        assert_eq!(go_mod_module_path("go 1.22\n"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use http::header::CONTENT_TYPE;
+use nr_core::{
+    database::entities::project::{
+        DBProject, NewProject, NewProjectMember, ProjectDBType, versions::NewVersion,
+    },
+    repository::project::{ProjectResolution, VersionData},
+    storage::StoragePath,
+};
+use tracing::info;
+
+use super::{
+    GoRepositoryError,
+    types::{GoFile, GoPath, GoVersionInfo, escape_path, go_release_type, version_file_path},
+};
+use crate::repository::{RepoResponse, utils::RepositoryExt};
+
+/// Responds with the file and the content type the go command expects
+pub fn go_file_response(file: GoFile, body: impl Into<axum::body::Body>) -> RepoResponse {
+    axum::response::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(CONTENT_TYPE, file.content_type())
+        .body(body.into())
+        .into()
+}
+pub fn module_not_found(module: &str) -> RepoResponse {
+    RepoResponse::basic_text_response(
+        http::StatusCode::NOT_FOUND,
+        format!("Module {} not found", module),
+    )
+}
+pub trait GoRepositoryExt: RepositoryExt {
+    /// Finds the project of the module.
+    ///
+    /// Module paths are case sensitive. `github.com/Foo/bar` and `github.com/foo/bar` are different modules
+    async fn get_module_project(
+        &self,
+        module: &str,
+    ) -> Result<Option<DBProject>, GoRepositoryError> {
+        let project =
+            DBProject::find_by_exact_project_key(module, self.id(), self.site().as_ref()).await?;
+        Ok(project)
+    }
+    /// Records the module as a project and the version as a project version.
+    ///
+    /// Projects are keyed by the module path. Existing versions are not changed
+    async fn record_module_version(
+        &self,
+        module: &str,
+        info: &GoVersionInfo,
+        publisher: Option<i32>,
+    ) -> Result<(), GoRepositoryError> {
+        let site = self.site();
+        let project = match self.get_module_project(module).await? {
+            Some(project) => project,
+            None => {
+                let project = NewProject {
+                    scope: None,
+                    project_key: module.to_owned(),
+                    name: module.to_owned(),
+                    latest_release: None,
+                    latest_pre_release: None,
+                    description: None,
+                    tags: vec![],
+                    repository: self.id(),
+                    storage_path: format!("{}/", escape_path(module)),
+                }
+                .insert(site.as_ref())
+                .await?;
+                if let Some(publisher) = publisher {
+                    NewProjectMember::new_owner(publisher, project.id)
+                        .insert_no_return(site.as_ref())
+                        .await?;
+                }
+                info!(?project, "Created new project");
+                project
+            }
+        };
+        if self
+            .get_project_version(project.id, &info.version)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+        NewVersion {
+            project_id: project.id,
+            version: info.version.clone(),
+            release_type: go_release_type(&info.version),
+            version_path: version_file_path(module, &info.version, GoFile::Zip).to_string(),
+            publisher,
+            version_page: None,
+            extra: VersionData {
+                extra: Some(serde_json::to_value(info)?),
+                ..Default::default()
+            },
+        }
+        .insert(site.as_ref())
+        .await?;
+        Ok(())
+    }
+    /// Resolves the module and version of a GOPROXY path
+    async fn resolve_go_path(
+        &self,
+        path: &StoragePath,
+    ) -> Result<ProjectResolution, GoRepositoryError> {
+        let Ok(Some(go_path)) = GoPath::parse(&path.to_string()) else {
+            return Ok(ProjectResolution::default());
+        };
+        let Some(project) = self.get_module_project(go_path.module()).await? else {
+            return Ok(ProjectResolution::default());
+        };
+        let version_id = match &go_path {
+            GoPath::File { version, .. } => self
+                .get_project_version(project.id, version)
+                .await?
+                .map(|version| version.id),
+            _ => None,
+        };
+        Ok(ProjectResolution {
+            project_id: Some(project.id),
+            version_id,
+        })
+    }
+}
@@ -0,0 +1,93 @@
+//! Reads `Chart.yaml` from a chart archive
+//!
+//! `helm package` creates a gzipped tarball with the chart inside of a directory named after the chart.
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use tracing::instrument;
+
+use super::types::{ChartMetadata, InvalidChart};
+/// The largest Chart.yaml that will be read from an archive
+const MAX_CHART_YAML_SIZE: u64 = 1024 * 1024;
+/// Reads and validates the `Chart.yaml` at the root of the chart.
+///
+/// Chart.yaml files of subcharts in `charts/` are ignored
+#[instrument(skip(data))]
+pub fn read_chart_metadata(data: &[u8]) -> Result<ChartMetadata, InvalidChart> {
+    let content = read_chart_yaml(data)
+        .map_err(|err| InvalidChart::Archive(err.to_string()))?
+        .ok_or_else(|| InvalidChart::Archive("Missing Chart.yaml".to_owned()))?;
+    let metadata: ChartMetadata = serde_yaml::from_slice(&content)
+        .map_err(|err| InvalidChart::Archive(format!("Invalid Chart.yaml. {}", err)))?;
+    metadata.validate()?;
+    Ok(metadata)
+}
+fn read_chart_yaml(data: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error> {
+    let mut archive = tar::Archive::new(GzDecoder::new(data));
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let is_chart_yaml = {
+            let path = entry.path()?;
+            let mut components = path.components();
+            match (components.next(), components.next(), components.next()) {
+                (Some(_), Some(file), None) => file.as_os_str() == "Chart.yaml",
+                _ => false,
+            }
+        };
+        if !is_chart_yaml {
+            continue;
+        }
+        let mut content = Vec::new();
+        entry.take(MAX_CHART_YAML_SIZE).read_to_end(&mut content)?;
+        return Ok(Some(content));
+    }
+    Ok(None)
+}
+#[cfg(test)]
+mod tests {
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+    fn chart_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+    #[test]
+    fn test_read_chart_metadata() {
+        let archive = chart_archive(&[
+            (
+                "nginx/charts/common/Chart.yaml",
+                "apiVersion: v2\nname: common\nversion: 2.0.0\n",
+            ),
+            (
+                "nginx/Chart.yaml",
+                "apiVersion: v2\nname: nginx\nversion: 1.2.3\ndescription: A web server\n",
+            ),
+            ("nginx/values.yaml", "replicaCount: 1\n"),
+        ]);
+        let metadata = read_chart_metadata(&archive).unwrap();
+        assert_eq!(metadata.name, "nginx");
+        assert_eq!(metadata.version, "1.2.3");
+        assert_eq!(metadata.description.as_deref(), Some("A web server"));
+
+        let missing = chart_archive(&[("nginx/values.yaml", "replicaCount: 1\n")]);
+        assert!(read_chart_metadata(&missing).is_err());
+
+        let invalid_version = chart_archive(&[(
+            "nginx/Chart.yaml",
+            "apiVersion: v2\nname: nginx\nversion: latest\n",
+        )]);
+        assert!(read_chart_metadata(&invalid_version).is_err());
+
+        assert!(read_chart_metadata(b"not a chart").is_err());
+    }
+}
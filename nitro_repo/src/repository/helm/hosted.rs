@@ -0,0 +1,482 @@
+use super::{
+    HelmRepositoryError,
+    chart::read_chart_metadata,
+    types::{
+        CHARTS_DIRECTORY, ChartVersion, HelmPath, INDEX_FILE, IndexFile, chart_path,
+        compare_chart_versions, provenance_path, validate_chart_name,
+    },
+    upload::ChartUpload,
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        utils::{RepositoryExt, json_response},
+    },
+};
+use axum::response::Response;
+use chrono::Utc;
+use derive_more::derive::Deref;
+use http::{StatusCode, header::CONTENT_TYPE};
+use nr_core::{
+    database::entities::{
+        project::{
+            DBProject, NewProject, NewProjectMember,
+            versions::{DBProjectVersion, NewVersion},
+        },
+        repository::DBRepository,
+    },
+    repository::{
+        Visibility,
+        config::{RepositoryConfigType, project::ProjectConfigType},
+        project::{Author, ProjectSource, ReleaseType, VersionData},
+    },
+    storage::StoragePath,
+    user::permissions::RepositoryActions,
+};
+use nr_storage::{DynStorage, FileContent, Storage};
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+use tracing::{debug, error, info, instrument, warn};
+
+#[derive(derive_more::Debug)]
+pub struct HelmHostedInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: uuid::Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    /// Held while charts are changed and `index.yaml` is regenerated
+    #[debug(skip)]
+    pub index_lock: tokio::sync::Mutex<()>,
+}
+/// A Helm chart repository that hosts its own charts
+///
+/// Charts are saved at `charts/{name}/{name}-{version}.tgz`. Projects are keyed by the chart name.
+/// The database is the source of truth. `index.yaml` is generated from the versions of every project
+#[derive(Debug, Clone, Deref)]
+pub struct HelmHosted(Arc<HelmHostedInner>);
+impl HelmHosted {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(HelmHostedInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+            index_lock: tokio::sync::Mutex::new(()),
+        })))
+    }
+    /// Reads the index entries saved with the versions of the project
+    async fn get_chart_versions(
+        &self,
+        project: &DBProject,
+    ) -> Result<Vec<ChartVersion>, HelmRepositoryError> {
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        let mut charts = Vec::with_capacity(versions.len());
+        for version in versions {
+            let Some(extra) = version.extra.0.extra else {
+                warn!(?version, "Invalid Helm Chart");
+                continue;
+            };
+            match serde_json::from_value::<ChartVersion>(extra) {
+                Ok(chart) => charts.push(chart),
+                Err(err) => {
+                    warn!(?err, ?version.version, "Invalid Helm Chart");
+                }
+            }
+        }
+        Ok(charts)
+    }
+    async fn get_all_charts(&self) -> Result<IndexFile, HelmRepositoryError> {
+        let projects = DBProject::get_all_for_repository(self.id, self.site.as_ref()).await?;
+        let mut charts = Vec::new();
+        for project in projects {
+            charts.extend(self.get_chart_versions(&project).await?);
+        }
+        Ok(IndexFile::new(charts))
+    }
+    /// Generates `index.yaml` from the database and saves it.
+    ///
+    /// The caller must hold the index lock
+    #[instrument]
+    async fn regenerate_index(&self) -> Result<String, HelmRepositoryError> {
+        let index = self.get_all_charts().await?;
+        let yaml = serde_yaml::to_string(&index)?;
+        self.storage
+            .save_file(
+                self.id,
+                FileContent::Content(yaml.clone().into_bytes()),
+                &StoragePath::from(INDEX_FILE),
+            )
+            .await?;
+        debug!(charts = index.entries.len(), "Regenerated index.yaml");
+        Ok(yaml)
+    }
+    /// Serves the saved `index.yaml`. It is generated if the repository does not have one yet
+    async fn handle_index(&self) -> Result<RepoResponse, HelmRepositoryError> {
+        let index_path = StoragePath::from(INDEX_FILE);
+        if let Some(file) = self.storage.open_file(self.id, &index_path).await? {
+            return Ok(RepoResponse::from(Some(file)));
+        }
+        let yaml = {
+            let _index_guard = self.index_lock.lock().await;
+            self.regenerate_index().await?
+        };
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/x-yaml")
+            .body(yaml.into())
+            .unwrap()
+            .into())
+    }
+    /// `api/charts/{name}`. Every version of the chart. Newest first
+    async fn handle_api_chart(&self, name: &str) -> Result<RepoResponse, HelmRepositoryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("Chart {} not found", name),
+            ));
+        };
+        let mut charts = self.get_chart_versions(&project).await?;
+        charts.sort_by(|a, b| compare_chart_versions(&b.metadata.version, &a.metadata.version));
+        json_response(StatusCode::OK, &charts)
+    }
+    /// `api/charts/{name}/{version}`
+    async fn handle_api_chart_version(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        let chart_version = match self.get_project_from_key(name).await? {
+            Some(project) => self.get_project_version(project.id, version).await?,
+            None => None,
+        };
+        let chart = chart_version
+            .and_then(|chart_version| chart_version.extra.0.extra)
+            .map(serde_json::from_value::<ChartVersion>)
+            .transpose()?;
+        match chart {
+            Some(chart) => json_response(StatusCode::OK, &chart),
+            None => Err(HelmRepositoryError::ChartNotFound {
+                name: name.to_owned(),
+                version: version.to_owned(),
+            }),
+        }
+    }
+    /// `POST api/charts`. Uploads a chart and optionally its provenance file
+    #[instrument]
+    async fn handle_upload(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        let content_type = request
+            .parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        let body = request.body.body_as_bytes().await?;
+        let upload = ChartUpload::read(&content_type, body).await?;
+        let metadata = read_chart_metadata(&upload.chart)?;
+        let name = metadata.name.clone();
+        let version = metadata.version.clone();
+
+        let _index_guard = self.index_lock.lock().await;
+        let project = self.get_project_from_key(&name).await?;
+        if let Some(project) = &project {
+            if self
+                .get_project_version(project.id, &version)
+                .await?
+                .is_some()
+            {
+                return Err(HelmRepositoryError::ChartAlreadyExists { name, version });
+            }
+        }
+
+        let chart_path = chart_path(&name, &version);
+        let digest = format!("{:x}", Sha256::digest(&upload.chart));
+        self.storage
+            .save_file(self.id, FileContent::Bytes(upload.chart), &chart_path)
+            .await?;
+        if let Some(prov) = upload.prov {
+            self.storage
+                .save_file(
+                    self.id,
+                    FileContent::Bytes(prov),
+                    &provenance_path(&name, &version),
+                )
+                .await?;
+        }
+
+        let project = match project {
+            Some(project) => {
+                if project.description != metadata.description || project.tags != metadata.keywords
+                {
+                    DBProject::update_description_and_tags(
+                        project.id,
+                        metadata.description.as_deref(),
+                        &metadata.keywords,
+                        self.site.as_ref(),
+                    )
+                    .await?;
+                }
+                project
+            }
+            None => {
+                let project = NewProject {
+                    scope: None,
+                    project_key: name.clone(),
+                    name: name.clone(),
+                    latest_release: None,
+                    latest_pre_release: None,
+                    description: metadata.description.clone(),
+                    tags: metadata.keywords.clone(),
+                    repository: self.id,
+                    storage_path: format!("{}/", CHARTS_DIRECTORY),
+                }
+                .insert(self.site.as_ref())
+                .await?;
+                NewProjectMember::new_owner(user.id, project.id)
+                    .insert_no_return(self.site.as_ref())
+                    .await?;
+                info!(?project, "Created new project");
+                project
+            }
+        };
+        let authors = metadata
+            .maintainers
+            .iter()
+            .map(|maintainer| Author {
+                name: Some(maintainer.name.clone()),
+                email: maintainer.email.clone(),
+                website: maintainer.url.clone(),
+            })
+            .collect();
+        let source = metadata.sources.first().map(|url| ProjectSource::Git {
+            url: url.clone(),
+            branch: None,
+            commit: None,
+        });
+        let chart = ChartVersion {
+            urls: vec![chart_path.to_string()],
+            created: Utc::now(),
+            digest,
+            metadata,
+        };
+        NewVersion {
+            project_id: project.id,
+            version: version.clone(),
+            release_type: ReleaseType::release_type_from_version(&version),
+            version_path: chart_path.to_string(),
+            publisher: Some(user.id),
+            version_page: None,
+            extra: VersionData {
+                website: chart.metadata.home.clone(),
+                authors,
+                description: chart.metadata.description.clone(),
+                source,
+                extra: Some(serde_json::to_value(&chart)?),
+                ..Default::default()
+            },
+        }
+        .insert(self.site.as_ref())
+        .await?;
+        self.regenerate_index().await?;
+        info!(?name, ?version, "Uploaded chart");
+        json_response(StatusCode::CREATED, &serde_json::json!({ "saved": true }))
+    }
+    /// `DELETE api/charts/{name}/{version}`.
+    ///
+    /// The project is removed once its last version is gone
+    #[instrument]
+    async fn handle_delete_chart(
+        &self,
+        request: &RepositoryRequest,
+        name: &str,
+        version: &str,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        if request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+            .is_none()
+        {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        }
+        let not_found = || HelmRepositoryError::ChartNotFound {
+            name: name.to_owned(),
+            version: version.to_owned(),
+        };
+        let _index_guard = self.index_lock.lock().await;
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Err(not_found());
+        };
+        let Some(chart_version) = self.get_project_version(project.id, version).await? else {
+            return Err(not_found());
+        };
+        DBProjectVersion::delete_by_id(chart_version.id, self.site.as_ref()).await?;
+        self.storage
+            .delete_file(self.id, &chart_path(name, version))
+            .await?;
+        self.storage
+            .delete_file(self.id, &provenance_path(name, version))
+            .await?;
+
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        if versions.is_empty() {
+            DBProject::delete_by_id(project.id, self.site.as_ref()).await?;
+            info!(?name, "Deleted project");
+        } else if project.latest_release.as_deref() == Some(version) {
+            let latest = versions
+                .iter()
+                .filter(|version| version.release_type.is_stable())
+                .max_by(|a, b| compare_chart_versions(&a.version, &b.version))
+                .map(|version| version.version.as_str());
+            DBProject::update_latest_release(project.id, latest, self.site.as_ref()).await?;
+        }
+        self.regenerate_index().await?;
+        info!(?name, ?version, "Deleted chart");
+        json_response(StatusCode::OK, &serde_json::json!({ "deleted": true }))
+    }
+}
+impl RepositoryExt for HelmHosted {}
+impl Repository for HelmHosted {
+    type Error = HelmRepositoryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "helm"
+    }
+    fn full_type(&self) -> &'static str {
+        "helm/hosted"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![ProjectConfigType::get_type_static()]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "helm/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        let path_as_string = request.path.to_string();
+        debug!(?path_as_string, "Handling Helm GET request");
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match HelmPath::parse(&path_as_string) {
+            Some(HelmPath::Index) => {
+                if let Some(err) = self.indexing_check(&request.authentication).await? {
+                    return Ok(err);
+                }
+                self.handle_index().await
+            }
+            Some(HelmPath::ApiCharts) => {
+                if let Some(err) = self.indexing_check(&request.authentication).await? {
+                    return Ok(err);
+                }
+                let index = self.get_all_charts().await?;
+                json_response(StatusCode::OK, &index.entries)
+            }
+            Some(HelmPath::ApiChart { name }) => self.handle_api_chart(&name).await,
+            Some(HelmPath::ApiChartVersion { name, version }) => {
+                self.handle_api_chart_version(&name, &version).await
+            }
+            Some(HelmPath::ChartFile) => {
+                let file = self.storage.open_file(self.id, &request.path).await?;
+                Ok(RepoResponse::from(file))
+            }
+            None => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        let path_as_string = request.path.to_string();
+        match HelmPath::parse(&path_as_string) {
+            Some(HelmPath::ApiCharts) => self.handle_upload(request).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_delete(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, HelmRepositoryError> {
+        let path_as_string = request.path.to_string();
+        match HelmPath::parse(&path_as_string) {
+            Some(HelmPath::ApiChartVersion { name, version }) => {
+                validate_chart_name(&name)?;
+                self.handle_delete_chart(&request, &name, &version).await
+            }
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
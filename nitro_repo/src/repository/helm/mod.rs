@@ -0,0 +1,167 @@
+//! Helm Chart Repository Implementation
+//!
+//! Charts are installed with `helm repo add` and uploaded with the ChartMuseum API. `index.yaml` is regenerated on every upload or delete.
+//!
+//! Documentation for chart repositories: https://helm.sh/docs/topics/chart_repository/
+
+use std::borrow::Cow;
+
+use ahash::HashMap;
+use futures::future::BoxFuture;
+use hosted::HelmHosted;
+use http::header::CONTENT_TYPE;
+use nr_core::{
+    database::entities::repository::DBRepository,
+    repository::config::{RepositoryConfigType, project::ProjectConfigType},
+};
+use nr_macros::DynRepositoryHandler;
+use nr_storage::DynStorage;
+use tracing::debug;
+use types::InvalidChart;
+
+pub mod chart;
+pub mod hosted;
+pub mod types;
+pub mod upload;
+use crate::{
+    app::authentication::AuthenticationError,
+    error::{BadRequestErrors, IntoErrorResponse},
+};
+
+pub use super::prelude::*;
+use super::{DynRepository, NewRepository, RepositoryType, RepositoryTypeDescription};
+
+#[derive(Debug, Clone, DynRepositoryHandler)]
+#[repository_handler(error=HelmRepositoryError)]
+pub enum HelmRepository {
+    Hosted(HelmHosted),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HelmRepositoryError {
+    #[error(transparent)]
+    InvalidChart(#[from] InvalidChart),
+    #[error("Invalid upload. {0}")]
+    InvalidUpload(Cow<'static, str>),
+    #[error("{name}-{version} already exists")]
+    ChartAlreadyExists { name: String, version: String },
+    #[error("{name}-{version} not found")]
+    ChartNotFound { name: String, version: String },
+    #[error("{0}")]
+    Other(Box<dyn IntoErrorResponse>),
+}
+impl From<HelmRepositoryError> for RepositoryHandlerError {
+    fn from(err: HelmRepositoryError) -> Self {
+        RepositoryHandlerError::Other(Box::new(err))
+    }
+}
+impl From<multer::Error> for HelmRepositoryError {
+    fn from(err: multer::Error) -> Self {
+        HelmRepositoryError::InvalidUpload(Cow::Owned(err.to_string()))
+    }
+}
+macro_rules! impl_from_error_for_other {
+    ($t:ty) => {
+        impl From<$t> for HelmRepositoryError {
+            fn from(e: $t) -> Self {
+                HelmRepositoryError::Other(Box::new(e))
+            }
+        }
+    };
+}
+impl_from_error_for_other!(BadRequestErrors);
+impl_from_error_for_other!(sqlx::Error);
+impl_from_error_for_other!(serde_json::Error);
+impl_from_error_for_other!(serde_yaml::Error);
+impl_from_error_for_other!(std::io::Error);
+impl_from_error_for_other!(AuthenticationError);
+impl_from_error_for_other!(RepositoryHandlerError);
+impl_from_error_for_other!(nr_storage::StorageError);
+
+impl IntoErrorResponse for HelmRepositoryError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
+
+impl From<HelmRepositoryError> for DynRepositoryHandlerError {
+    fn from(err: HelmRepositoryError) -> Self {
+        DynRepositoryHandlerError(Box::new(err))
+    }
+}
+/// Errors are returned as `{"error": "..."}` like ChartMuseum. helm cm-push prints the message
+impl IntoResponse for HelmRepositoryError {
+    fn into_response(self) -> Response {
+        match self {
+            HelmRepositoryError::Other(other) => other.into_response_boxed(),
+            error => {
+                let status = match &error {
+                    HelmRepositoryError::ChartAlreadyExists { .. } => StatusCode::CONFLICT,
+                    HelmRepositoryError::ChartNotFound { .. } => StatusCode::NOT_FOUND,
+                    bad_request => {
+                        debug!("Bad Request: {:?}", bad_request);
+                        StatusCode::BAD_REQUEST
+                    }
+                };
+                let body = serde_json::json!({ "error": error.to_string() });
+                Response::builder()
+                    .status(status)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.to_string().into())
+                    .unwrap()
+            }
+        }
+    }
+}
+#[derive(Debug, Default)]
+pub struct HelmRepositoryType;
+
+impl RepositoryType for HelmRepositoryType {
+    fn get_type(&self) -> &'static str {
+        "helm"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![ProjectConfigType::get_type_static()]
+    }
+
+    fn get_description(&self) -> RepositoryTypeDescription {
+        RepositoryTypeDescription {
+            type_name: "helm",
+            name: "Helm",
+            description: "A Helm Chart Repository. Supports helm and the ChartMuseum API",
+            documentation_url: Some("https://nitro-repo.kingtux.dev/repositoryTypes/helm/"),
+            is_stable: false,
+            required_configs: vec![],
+        }
+    }
+
+    fn create_new(
+        &self,
+        name: String,
+        uuid: uuid::Uuid,
+        configs: HashMap<String, serde_json::Value>,
+        storage: nr_storage::DynStorage,
+    ) -> BoxFuture<'static, Result<NewRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            Ok(NewRepository {
+                name,
+                uuid,
+                repository_type: "helm".to_string(),
+                configs,
+            })
+        })
+    }
+
+    fn load_repo(
+        &self,
+        repo: DBRepository,
+        storage: DynStorage,
+        website: NitroRepo,
+    ) -> BoxFuture<'static, Result<DynRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let hosted = HelmHosted::load(website, storage, repo).await?;
+            Ok(HelmRepository::Hosted(hosted).into())
+        })
+    }
+}
@@ -0,0 +1,327 @@
+//! Chart.yaml, index.yaml, and the paths of a Helm chart repository
+//!
+//! Documentation for chart repositories: https://helm.sh/docs/topics/chart_repository/
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use chrono::{DateTime, Utc};
+use nr_core::storage::StoragePath;
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use serde_json::Value;
+use thiserror::Error;
+/// The index of every chart in the repository. Served at `index.yaml`
+pub const INDEX_FILE: &str = "index.yaml";
+/// Chart archives are saved in `charts/{name}/`
+pub const CHARTS_DIRECTORY: &str = "charts";
+
+#[derive(Debug, Error)]
+pub enum InvalidChart {
+    #[error("Invalid chart name {0}. Names can only contain letters, digits, `-`, `_`, and `.`")]
+    Name(String),
+    #[error("Invalid chart version {0}. Versions must be semantic versions")]
+    Version(String),
+    #[error("Unsupported chart apiVersion {0}. Expected v1 or v2")]
+    ApiVersion(String),
+    #[error("Invalid chart archive. {0}")]
+    Archive(String),
+}
+/// A maintainer of the chart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChartMaintainer {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+/// The contents of `Chart.yaml`
+///
+/// https://helm.sh/docs/topics/charts/#the-chartyaml-file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartMetadata {
+    pub api_version: String,
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_scalar")]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kube_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `application` or `library`
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub chart_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// Kept as is. Dependencies have many optional fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintainers: Vec<ChartMaintainer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub app_version: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+/// YAML reads unquoted versions such as `appVersion: 1.25` as numbers
+fn deserialize_scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match deserialize_optional_scalar(deserializer)? {
+        Some(value) => Ok(value),
+        None => Err(D::Error::custom("Expected a string. Found null")),
+    }
+}
+fn deserialize_optional_scalar<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(value) => Ok(Some(value)),
+        Value::Number(value) => Ok(Some(value.to_string())),
+        other => Err(D::Error::custom(format!(
+            "Expected a string. Found {}",
+            other
+        ))),
+    }
+}
+impl ChartMetadata {
+    pub fn validate(&self) -> Result<(), InvalidChart> {
+        if !matches!(self.api_version.as_str(), "v1" | "v2") {
+            return Err(InvalidChart::ApiVersion(self.api_version.clone()));
+        }
+        validate_chart_name(&self.name)?;
+        if parse_chart_version(&self.version).is_none() {
+            return Err(InvalidChart::Version(self.version.clone()));
+        }
+        Ok(())
+    }
+    /// `{name}-{version}.tgz`
+    pub fn file_name(&self) -> String {
+        chart_file_name(&self.name, &self.version)
+    }
+}
+pub fn validate_chart_name(name: &str) -> Result<(), InvalidChart> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidChart::Name(name.to_owned()))
+    }
+}
+/// Helm accepts versions with a `v` prefix. Such as `v1.2.3`
+pub fn parse_chart_version(version: &str) -> Option<semver::Version> {
+    semver::Version::parse(version.strip_prefix('v').unwrap_or(version)).ok()
+}
+/// Orders versions by semantic version. Versions that can not be parsed are ordered first
+pub fn compare_chart_versions(a: &str, b: &str) -> Ordering {
+    match (parse_chart_version(a), parse_chart_version(b)) {
+        (Some(a), Some(b)) => a.cmp_precedence(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+pub fn chart_file_name(name: &str, version: &str) -> String {
+    format!("{}-{}.tgz", name, version)
+}
+/// `charts/{name}/{name}-{version}.tgz`
+///
+/// Charts are namespaced by name. `a` `1.0.0-1.0.0` and `a-1.0.0` `1.0.0` have the same file name
+pub fn chart_path(name: &str, version: &str) -> StoragePath {
+    StoragePath::from(format!(
+        "{}/{}/{}",
+        CHARTS_DIRECTORY,
+        name,
+        chart_file_name(name, version)
+    ))
+}
+/// `charts/{name}/{name}-{version}.tgz.prov`
+pub fn provenance_path(name: &str, version: &str) -> StoragePath {
+    StoragePath::from(format!(
+        "{}/{}/{}.prov",
+        CHARTS_DIRECTORY,
+        name,
+        chart_file_name(name, version)
+    ))
+}
+/// An entry of index.yaml. Saved as the extra data of the project version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartVersion {
+    #[serde(flatten)]
+    pub metadata: ChartMetadata,
+    /// Relative to the repository. Such as `charts/{name}/{name}-{version}.tgz`
+    pub urls: Vec<String>,
+    pub created: DateTime<Utc>,
+    /// The hex encoded sha256 of the chart archive
+    pub digest: String,
+}
+/// The contents of `index.yaml`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexFile {
+    pub api_version: String,
+    pub entries: BTreeMap<String, Vec<ChartVersion>>,
+    pub generated: DateTime<Utc>,
+}
+impl IndexFile {
+    /// Groups the charts by name. The versions of each chart are sorted newest first
+    pub fn new(charts: impl IntoIterator<Item = ChartVersion>) -> Self {
+        let mut entries: BTreeMap<String, Vec<ChartVersion>> = BTreeMap::new();
+        for chart in charts {
+            entries
+                .entry(chart.metadata.name.clone())
+                .or_default()
+                .push(chart);
+        }
+        for versions in entries.values_mut() {
+            versions
+                .sort_by(|a, b| compare_chart_versions(&b.metadata.version, &a.metadata.version));
+        }
+        Self {
+            api_version: "v1".to_owned(),
+            entries,
+            generated: Utc::now(),
+        }
+    }
+}
+/// A request to the chart repository or the ChartMuseum API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelmPath {
+    /// `index.yaml`
+    Index,
+    /// `charts/{name}/{file}`. A chart archive or provenance file
+    ChartFile,
+    /// `api/charts`
+    ApiCharts,
+    /// `api/charts/{name}`
+    ApiChart { name: String },
+    /// `api/charts/{name}/{version}`
+    ApiChartVersion { name: String, version: String },
+}
+impl HelmPath {
+    pub fn parse(path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            [INDEX_FILE] => Some(HelmPath::Index),
+            [CHARTS_DIRECTORY, name, file]
+                if file.starts_with(&format!("{}-", name))
+                    && (file.ends_with(".tgz") || file.ends_with(".tgz.prov")) =>
+            {
+                Some(HelmPath::ChartFile)
+            }
+            ["api", "charts"] => Some(HelmPath::ApiCharts),
+            ["api", "charts", name] => Some(HelmPath::ApiChart {
+                name: (*name).to_owned(),
+            }),
+            ["api", "charts", name, version] => Some(HelmPath::ApiChartVersion {
+                name: (*name).to_owned(),
+                version: (*version).to_owned(),
+            }),
+            _ => None,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(HelmPath::parse("index.yaml"), Some(HelmPath::Index));
+        assert_eq!(
+            HelmPath::parse("charts/nginx/nginx-1.0.0.tgz"),
+            Some(HelmPath::ChartFile)
+        );
+        assert_eq!(
+            HelmPath::parse("charts/nginx/nginx-1.0.0.tgz.prov"),
+            Some(HelmPath::ChartFile)
+        );
+        assert_eq!(HelmPath::parse("charts/nginx-1.0.0.tgz"), None);
+        assert_eq!(HelmPath::parse("charts/other/nginx-1.0.0.tgz"), None);
+        assert_eq!(HelmPath::parse("api/charts"), Some(HelmPath::ApiCharts));
+        assert_eq!(
+            HelmPath::parse("api/charts/nginx/1.0.0"),
+            Some(HelmPath::ApiChartVersion {
+                name: "nginx".to_owned(),
+                version: "1.0.0".to_owned()
+            })
+        );
+        assert_eq!(HelmPath::parse("charts/nginx/nginx-1.0.0.zip"), None);
+        assert_eq!(HelmPath::parse("other"), None);
+    }
+    #[test]
+    fn test_validate_metadata() {
+        let chart: ChartMetadata = serde_yaml::from_str(
+            "apiVersion: v2\nname: nginx\nversion: 1.2.3\nappVersion: 1.25\nkeywords: [web]\n",
+        )
+        .unwrap();
+        assert!(chart.validate().is_ok());
+        assert_eq!(chart.app_version.as_deref(), Some("1.25"));
+        assert_eq!(chart.file_name(), "nginx-1.2.3.tgz");
+
+        assert_ne!(
+            chart_path("a", "1.0.0-1.0.0"),
+            chart_path("a-1.0.0", "1.0.0")
+        );
+
+        let mut invalid = chart.clone();
+        invalid.version = "latest".to_owned();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = chart.clone();
+        invalid.name = "../nginx".to_owned();
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = chart;
+        invalid.api_version = "v3".to_owned();
+        assert!(invalid.validate().is_err());
+    }
+    #[test]
+    fn test_index_file() {
+        let chart = |version: &str| ChartVersion {
+            metadata: ChartMetadata {
+                api_version: "v2".to_owned(),
+                name: "nginx".to_owned(),
+                version: version.to_owned(),
+                kube_version: None,
+                description: None,
+                chart_type: None,
+                keywords: vec![],
+                home: None,
+                sources: vec![],
+                dependencies: vec![],
+                maintainers: vec![],
+                icon: None,
+                app_version: None,
+                deprecated: false,
+                annotations: BTreeMap::new(),
+            },
+            urls: vec![format!("charts/nginx/nginx-{}.tgz", version)],
+            created: Utc::now(),
+            digest: String::new(),
+        };
+        let index = IndexFile::new([chart("1.2.0"), chart("1.10.0"), chart("1.9.0")]);
+        let versions: Vec<&str> = index.entries["nginx"]
+            .iter()
+            .map(|chart| chart.metadata.version.as_str())
+            .collect();
+        assert_eq!(versions, vec!["1.10.0", "1.9.0", "1.2.0"]);
+
+        let yaml = serde_yaml::to_string(&index).unwrap();
+        let parsed: IndexFile = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed, index);
+    }
+}
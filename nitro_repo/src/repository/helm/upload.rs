@@ -0,0 +1,44 @@
+//! The upload API of ChartMuseum. Used by `helm cm-push`
+//!
+//! The chart is either the raw body of the request or the `chart` field of a `multipart/form-data` form.
+//! The form can also contain a provenance file in the `prov` field.
+//! https://github.com/helm/chartmuseum#api
+use std::{borrow::Cow, convert::Infallible};
+
+use bytes::Bytes;
+
+use super::HelmRepositoryError;
+#[derive(Debug, Clone)]
+pub struct ChartUpload {
+    /// The chart archive
+    pub chart: Bytes,
+    /// The provenance file. Only sent with the form
+    pub prov: Option<Bytes>,
+}
+impl ChartUpload {
+    /// Reads the upload from the body of the request
+    pub async fn read(content_type: &str, body: Bytes) -> Result<Self, HelmRepositoryError> {
+        if !content_type.starts_with("multipart/form-data") {
+            return Ok(Self {
+                chart: body,
+                prov: None,
+            });
+        }
+        let boundary = multer::parse_boundary(content_type)?;
+        let stream = futures::stream::once(async move { Ok::<_, Infallible>(body) });
+        let mut multipart = multer::Multipart::new(stream, boundary);
+        let mut chart = None;
+        let mut prov = None;
+        while let Some(field) = multipart.next_field().await? {
+            match field.name() {
+                Some("chart") => chart = Some(field.bytes().await?),
+                Some("prov") => prov = Some(field.bytes().await?),
+                _ => {}
+            }
+        }
+        let chart = chart.ok_or(HelmRepositoryError::InvalidUpload(Cow::Borrowed(
+            "Missing the chart field",
+        )))?;
+        Ok(Self { chart, prov })
+    }
+}
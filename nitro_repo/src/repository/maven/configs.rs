@@ -6,15 +6,22 @@ use schemars::{JsonSchema, Schema, schema_for};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::proxy::MavenProxyConfig;
+use super::{group::MavenGroupConfig, proxy::MavenProxyConfig};
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "config")]
 pub enum MavenRepositoryConfig {
     Hosted,
     Proxy(MavenProxyConfig),
+    Group(MavenGroupConfig),
 }
 impl MavenRepositoryConfig {
+    pub fn validate(&self) -> Result<(), RepositoryConfigError> {
+        if let MavenRepositoryConfig::Proxy(proxy) = self {
+            proxy.validate()?;
+        }
+        Ok(())
+    }
     pub fn is_same_type(&self, other: &MavenRepositoryConfig) -> bool {
         matches!(
             (self, other),
@@ -23,6 +30,10 @@ impl MavenRepositoryConfig {
                     MavenRepositoryConfig::Proxy(_),
                     MavenRepositoryConfig::Proxy(_)
                 )
+                | (
+                    MavenRepositoryConfig::Group(_),
+                    MavenRepositoryConfig::Group(_)
+                )
         )
     }
 }
@@ -44,7 +55,24 @@ impl RepositoryConfigType for MavenRepositoryConfigType {
     }
     fn validate_config(&self, config: Value) -> Result<(), RepositoryConfigError> {
         let config: MavenRepositoryConfig = serde_json::from_value(config)?;
-        Ok(())
+        config.validate()
+    }
+    fn redact_secrets(&self, config: Value) -> Result<Value, RepositoryConfigError> {
+        let mut config: MavenRepositoryConfig = serde_json::from_value(config)?;
+        if let MavenRepositoryConfig::Proxy(proxy) = &mut config {
+            proxy.redact_secrets();
+        }
+        Ok(serde_json::to_value(config)?)
+    }
+    fn restore_secrets(&self, old: Value, new: Value) -> Result<Value, RepositoryConfigError> {
+        let old: MavenRepositoryConfig = serde_json::from_value(old)?;
+        let mut new: MavenRepositoryConfig = serde_json::from_value(new)?;
+        if let (MavenRepositoryConfig::Proxy(old), MavenRepositoryConfig::Proxy(new)) =
+            (&old, &mut new)
+        {
+            new.restore_secrets(old);
+        }
+        Ok(serde_json::to_value(new)?)
     }
     fn validate_change(&self, old: Value, new: Value) -> Result<(), RepositoryConfigError> {
         let new: MavenRepositoryConfig = serde_json::from_value(new)?;
@@ -55,7 +83,7 @@ impl RepositoryConfigType for MavenRepositoryConfigType {
                 "Cannot change the type of Maven Repository",
             ));
         }
-        Ok(())
+        new.validate()
     }
     fn default(&self) -> Result<Value, RepositoryConfigError> {
         let config = MavenRepositoryConfig::Hosted;
@@ -77,7 +105,9 @@ pub struct MavenPushRules {
     #[schemars(title = "Push Policy")]
     /// The push policy. Rather it allows snapshots, stages, or both
     pub push_policy: Policy,
-    /// If yanking is allowed
+    /// If yanking is allowed.
+    ///
+    /// Not enforced yet. Maven repositories do not have a way to delete or yank published artifacts
     #[schemars(title = "Yanking Allowed")]
     pub yanking_allowed: bool,
     /// If overwriting is allowed
@@ -0,0 +1,409 @@
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+
+use axum::body::Body;
+use derive_more::derive::Deref;
+use http::{
+    StatusCode,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::{
+        Visibility,
+        config::{RepositoryConfigType, repository_page::RepositoryPageType},
+    },
+    storage::StoragePath,
+    user::permissions::{HasPermissions, RepositoryActions},
+};
+use nr_storage::{
+    DynStorage, FileType, Storage, StorageFile, StorageFileMeta, generate_from_bytes,
+};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use super::{
+    MavenError, MavenRepository, MavenRepositoryConfig, MavenRepositoryConfigType,
+    REPOSITORY_TYPE_ID, RepoResponse, RepositoryRequest,
+    hosted::MavenHosted,
+    metadata::{MAVEN_METADATA_FILE, MavenMetadata},
+    proxy::MavenProxy,
+    utils::{MavenRepositoryExt, maven_checksum},
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        DynRepository, Repository, RepositoryAuthentication, RepositoryFactoryError,
+        utils::RepositoryExt,
+    },
+};
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MavenGroupConfig {
+    /// The repositories that make up the group.
+    ///
+    /// Requests check the members in order and return the first match.
+    #[schemars(with = "Vec<String>")]
+    pub members: Vec<Uuid>,
+}
+/// A repository that is a member of a group.
+///
+/// Groups can not contain other groups.
+#[derive(Debug, Clone)]
+pub enum MavenGroupMember {
+    Hosted(MavenHosted),
+    Proxy(MavenProxy),
+}
+impl MavenGroupMember {
+    pub fn id(&self) -> Uuid {
+        match self {
+            MavenGroupMember::Hosted(hosted) => hosted.id(),
+            MavenGroupMember::Proxy(proxy) => proxy.id(),
+        }
+    }
+    pub fn is_active(&self) -> bool {
+        match self {
+            MavenGroupMember::Hosted(hosted) => hosted.is_active(),
+            MavenGroupMember::Proxy(proxy) => proxy.is_active(),
+        }
+    }
+    pub fn visibility(&self) -> Visibility {
+        match self {
+            MavenGroupMember::Hosted(hosted) => hosted.visibility(),
+            MavenGroupMember::Proxy(proxy) => proxy.visibility(),
+        }
+    }
+    /// Opens the file from the member. Proxies will attempt to pull the file from upstream if it is not already cached
+    pub async fn open_file(&self, path: &StoragePath) -> Result<Option<StorageFile>, MavenError> {
+        match self {
+            MavenGroupMember::Hosted(hosted) => {
+                Ok(hosted.get_storage().open_file(hosted.id(), path).await?)
+            }
+            MavenGroupMember::Proxy(proxy) => {
+                if let Some(file) = proxy.get_storage().open_file(proxy.id(), path).await? {
+                    return proxy.revalidate_if_stale(path, file).await;
+                }
+                proxy.get_from_proxy(path.clone()).await
+            }
+        }
+    }
+    pub async fn get_file_information(
+        &self,
+        path: &StoragePath,
+    ) -> Result<Option<StorageFileMeta<FileType>>, MavenError> {
+        let (storage, id) = match self {
+            MavenGroupMember::Hosted(hosted) => (hosted.get_storage(), hosted.id()),
+            MavenGroupMember::Proxy(proxy) => (proxy.get_storage(), proxy.id()),
+        };
+        Ok(storage.get_file_information(id, path).await?)
+    }
+}
+#[derive(derive_more::Debug)]
+pub struct MavenGroupInner {
+    pub id: Uuid,
+    pub name: String,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    pub config: RwLock<MavenGroupConfig>,
+    #[debug(skip)]
+    pub storage: DynStorage,
+    #[debug(skip)]
+    pub site: NitroRepo,
+}
+#[derive(Debug, Clone, Deref)]
+pub struct MavenGroup(Arc<MavenGroupInner>);
+impl MavenRepositoryExt for MavenGroup {}
+impl RepositoryExt for MavenGroup {}
+impl MavenGroup {
+    pub async fn load(
+        repository: DBRepository,
+        storage: DynStorage,
+        site: NitroRepo,
+        group_config: MavenGroupConfig,
+    ) -> Result<Self, RepositoryFactoryError> {
+        let inner = MavenGroupInner {
+            id: repository.id,
+            name: repository.name.into(),
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            config: RwLock::new(group_config),
+            storage,
+            site,
+        };
+        Ok(Self(Arc::new(inner)))
+    }
+    /// Resolves the members of the group in order.
+    ///
+    /// Members that are missing, inactive, or not a Maven Hosted/Proxy repository are skipped.
+    /// Private members are skipped if the user can not read them.
+    pub async fn members(
+        &self,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Vec<MavenGroupMember>, MavenError> {
+        let member_ids = self.config.read().members.clone();
+        let mut members = Vec::with_capacity(member_ids.len());
+        for member_id in member_ids {
+            let member = match self.site.get_repository(member_id) {
+                Some(DynRepository::Maven(MavenRepository::Hosted(hosted))) => {
+                    MavenGroupMember::Hosted(hosted)
+                }
+                Some(DynRepository::Maven(MavenRepository::Proxy(proxy))) => {
+                    MavenGroupMember::Proxy(proxy)
+                }
+                Some(_) => {
+                    warn!(
+                        ?member_id,
+                        "Group member is not a Maven Hosted or Proxy repository"
+                    );
+                    continue;
+                }
+                None => {
+                    warn!(?member_id, "Group member does not exist");
+                    continue;
+                }
+            };
+            if !member.is_active() {
+                debug!(?member_id, "Skipping inactive group member");
+                continue;
+            }
+            if member.visibility().is_private()
+                && !authentication
+                    .has_action(RepositoryActions::Read, member_id, self.site.as_ref())
+                    .await?
+            {
+                debug!(?member_id, "Skipping group member the user can not read");
+                continue;
+            }
+            members.push(member);
+        }
+        Ok(members)
+    }
+    /// Reads the `maven-metadata.xml` from every member and merges them into one.
+    #[instrument(skip(self, authentication), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn merged_metadata(
+        &self,
+        path: &StoragePath,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Option<MavenMetadata>, MavenError> {
+        let mut merged: Option<MavenMetadata> = None;
+        for member in self.members(authentication).await? {
+            let file = match member.open_file(path).await {
+                Ok(Some(file)) => file,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(?err, member = ?member.id(), "Failed to read metadata from member");
+                    continue;
+                }
+            };
+            let Some((content, meta)) = file.file() else {
+                continue;
+            };
+            let bytes = content
+                .read_to_vec(meta.file_type.file_size as usize)
+                .await?;
+            let metadata = match MavenMetadata::from_bytes(&bytes) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    warn!(?err, member = ?member.id(), "Failed to parse metadata from member");
+                    continue;
+                }
+            };
+            match &mut merged {
+                Some(merged) => merged.merge(metadata),
+                None => merged = Some(metadata),
+            }
+        }
+        Ok(merged)
+    }
+    /// Handles requests for `maven-metadata.xml` and its checksums.
+    ///
+    /// Returns None if the path is not a metadata file.
+    async fn handle_metadata_request(
+        &self,
+        path: &StoragePath,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Option<RepoResponse>, MavenError> {
+        let path_as_string = path.to_string();
+        let (metadata_path, checksum) = if path_as_string.ends_with(MAVEN_METADATA_FILE) {
+            (path.clone(), None)
+        } else {
+            match path_as_string.rsplit_once('.') {
+                Some((metadata_path, algorithm))
+                    if metadata_path.ends_with(MAVEN_METADATA_FILE) =>
+                {
+                    (StoragePath::from(metadata_path), Some(algorithm.to_owned()))
+                }
+                _ => return Ok(None),
+            }
+        };
+        let Some(metadata) = self.merged_metadata(&metadata_path, authentication).await? else {
+            return Ok(Some(RepoResponse::from(Option::<StorageFile>::None)));
+        };
+        let metadata = metadata.to_xml_string()?;
+        let Some(algorithm) = checksum else {
+            let response = http::Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/xml")
+                .header(CONTENT_LENGTH, metadata.len())
+                .body(Body::from(metadata));
+            return Ok(Some(response.into()));
+        };
+        let hashes = generate_from_bytes(metadata.as_bytes());
+        let Some(checksum) = maven_checksum(&hashes, &algorithm) else {
+            return Ok(Some(RepoResponse::from(Option::<StorageFile>::None)));
+        };
+        let response = http::Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain")
+            .header(CONTENT_LENGTH, checksum.len())
+            .body(Body::from(checksum));
+        Ok(Some(response.into()))
+    }
+}
+impl Repository for MavenGroup {
+    type Error = MavenError;
+    #[inline(always)]
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+    #[inline(always)]
+    fn get_storage(&self) -> nr_storage::DynStorage {
+        self.0.storage.clone()
+    }
+    #[inline(always)]
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+    #[inline(always)]
+    fn get_type(&self) -> &'static str {
+        REPOSITORY_TYPE_ID
+    }
+    fn full_type(&self) -> &'static str {
+        "maven/group"
+    }
+    #[inline(always)]
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+    #[inline(always)]
+    fn id(&self) -> Uuid {
+        self.0.id
+    }
+    #[inline(always)]
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![
+            RepositoryPageType::get_type_static(),
+            MavenRepositoryConfigType::get_type_static(),
+        ]
+    }
+    #[instrument(fields(repository_type = "maven/group"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let Some(maven_config_db) = DBRepositoryConfig::<MavenRepositoryConfig>::get_config(
+            self.id,
+            MavenRepositoryConfigType::get_type_static(),
+            self.site.as_ref(),
+        )
+        .await?
+        else {
+            return Err(RepositoryFactoryError::MissingConfig(
+                MavenRepositoryConfigType::get_type_static(),
+            ));
+        };
+        match maven_config_db.value.0 {
+            MavenRepositoryConfig::Group(group_config) => {
+                let mut config = self.config.write();
+                *config = group_config;
+            }
+            _ => {
+                return Err(RepositoryFactoryError::InvalidConfig(
+                    MavenRepositoryConfigType::get_type_static(),
+                    "Expected Group Config".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        RepositoryRequest {
+            path,
+            authentication,
+            ..
+        }: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
+            return Ok(err);
+        }
+        if let Some(response) = self.handle_metadata_request(&path, &authentication).await? {
+            return Ok(response);
+        }
+        for member in self.members(&authentication).await? {
+            match member.open_file(&path).await {
+                Ok(Some(file)) => {
+                    debug!(member = ?member.id(), ?path, "Found file in group member");
+                    return self.indexing_check_file(file, &authentication).await;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(?err, member = ?member.id(), ?path, "Failed to get file from member");
+                }
+            }
+        }
+        Ok(RepoResponse::from(Option::<StorageFile>::None))
+    }
+    async fn handle_head(
+        &self,
+        RepositoryRequest {
+            path,
+            authentication,
+            ..
+        }: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
+            return Ok(err);
+        }
+        if let Some(response) = self.handle_metadata_request(&path, &authentication).await? {
+            return Ok(response);
+        }
+        for member in self.members(&authentication).await? {
+            match member.get_file_information(&path).await {
+                Ok(Some(file)) => {
+                    return self.indexing_check_file(file, &authentication).await;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(?err, member = ?member.id(), ?path, "Failed to get file from member");
+                }
+            }
+            if let MavenGroupMember::Proxy(proxy) = &member {
+                match proxy.head_from_proxy(&path).await {
+                    Ok(Some(response)) => return Ok(RepoResponse::Other(response)),
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!(?err, member = ?member.id(), ?path, "Failed to proxy HEAD request");
+                    }
+                }
+            }
+        }
+        Ok(RepoResponse::from(
+            Option::<StorageFileMeta<FileType>>::None,
+        ))
+    }
+}
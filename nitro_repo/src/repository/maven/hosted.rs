@@ -3,12 +3,18 @@ use std::sync::{
     atomic::{self, AtomicBool},
 };
 
+use chrono::Local;
 use derive_more::derive::Deref;
+use http::StatusCode;
 use maven_rs::pom::Pom;
 use nr_core::{
     database::entities::{
-        project::{DBProject, ProjectDBType, info::ProjectInfo, versions::DBProjectVersion},
+        project::{
+            DBProject, DBProjectMember, ProjectDBType, info::ProjectInfo,
+            versions::DBProjectVersion,
+        },
         repository::DBRepository,
+        stages::DBStage,
     },
     repository::{
         Visibility,
@@ -22,23 +28,32 @@ use nr_core::{
     storage::StoragePath,
     user::permissions::{HasPermissions, RepositoryActions},
 };
-use nr_storage::{DynStorage, Storage, StorageFile};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile, generate_from_bytes};
 use parking_lot::RwLock;
-use tracing::{debug, error, event, info, instrument};
+use tracing::{debug, error, event, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
     app::NitroRepo,
     repository::{
-        Repository, RepositoryFactoryError,
+        Repository, RepositoryAuthentication, RepositoryFactoryError, StagingManager,
         maven::{MavenRepositoryConfigType, configs::MavenPushRulesConfigType},
-        utils::RepositoryExt,
+        utils::{RepositoryExt, json_response},
     },
 };
 
 use super::{
-    MavenError, REPOSITORY_TYPE_ID, RepoResponse, RepositoryRequest, configs::MavenPushRules,
-    utils::MavenRepositoryExt,
+    MavenError, REPOSITORY_TYPE_ID, RepoResponse, RepositoryRequest,
+    configs::MavenPushRules,
+    get_release_type,
+    metadata::{MAVEN_METADATA_FILE, MavenMetadata, MetadataVersioning, MetadataVersions},
+    nitro_deploy::{
+        DeployFileHasher, NRMavenPublishSuccessFile, NRMavenPublishSuccessResponse,
+        NewNRMavenDeploy, NewNrMavenDeploySuccessResponse, NitroDeployPath, PUBLISH_ACTION,
+        StageVerificationError,
+    },
+    push_rules::{MavenUploadPath, PushRuleViolation, check_push_policy},
+    utils::{MavenRepositoryExt, maven_checksum},
 };
 #[derive(derive_more::Debug)]
 pub struct MavenHostedInner {
@@ -49,6 +64,8 @@ pub struct MavenHostedInner {
     pub push_rules: RwLock<MavenPushRules>,
     pub project: RwLock<ProjectConfig>,
     #[debug(skip)]
+    pub staging: StagingManager,
+    #[debug(skip)]
     pub storage: DynStorage,
     #[debug(skip)]
     pub site: NitroRepo,
@@ -83,6 +100,25 @@ impl MavenHosted {
                 return Ok(RepoResponse::require_nitro_deploy());
             }
         }
+        if let Some(upload) = MavenUploadPath::parse(&path) {
+            let files = if upload.is_metadata() {
+                vec![]
+            } else {
+                vec![path.clone()]
+            };
+            if let Err(violation) = self
+                .validate_push(
+                    &upload.project_key(),
+                    upload.version.as_deref(),
+                    &files,
+                    user_id,
+                )
+                .await?
+            {
+                info!(?violation, "Upload rejected by push rules");
+                return Ok(violation.into());
+            }
+        }
         let parent_path = path.clone().parent();
         if let Some(meta) = self
             .storage
@@ -109,7 +145,6 @@ impl MavenHosted {
 
         let body = body.body_as_bytes().await?;
         trace.metrics.project_write_bytes(body.len() as u64);
-        // TODO: Validate Against Push Rules
         let pom = if path.has_extension("pom") {
             let pom: Pom = self.parse_pom(body.to_vec())?;
             Some(pom)
@@ -156,11 +191,396 @@ impl MavenHosted {
             visibility: RwLock::new(repository.visibility),
             push_rules: RwLock::new(push_rules_db.value.0),
             project: RwLock::new(project_db.value.0),
+            staging: StagingManager::new(repository.id, site.clone()),
             storage,
             site,
         };
         Ok(Self(Arc::new(inner)))
     }
+    /// Checks the push rules and that the user can write to the repository.
+    ///
+    /// Returns the user id or the response explaining why the push is not allowed
+    async fn authorize_push(
+        &self,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Result<i32, RepoResponse>, MavenError> {
+        {
+            let push_rules = self.push_rules.read();
+            if push_rules.must_use_auth_token_for_push && !authentication.has_auth_token() {
+                info!("Repository requires an auth token for push");
+                return Ok(Err(RepoResponse::require_auth_token()));
+            }
+        }
+
+        let Some(user) = authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(Err(RepoResponse::unauthorized()));
+        };
+        if !user
+            .has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        {
+            info!(?self.id, ?user, "User does not have write permissions");
+            return Ok(Err(RepoResponse::forbidden()));
+        }
+        Ok(Ok(user.id))
+    }
+    /// Checks the push policy, project membership, and overwrite rules.
+    ///
+    /// `files` are the paths that will be written. SNAPSHOT files can always be overwritten
+    async fn validate_push(
+        &self,
+        project_key: &str,
+        version: Option<&str>,
+        files: &[StoragePath],
+        user_id: i32,
+    ) -> Result<Result<(), PushRuleViolation>, MavenError> {
+        let push_rules = self.push_rules.read().clone();
+        if let Some(version) = version {
+            if let Err(violation) = check_push_policy(&push_rules.push_policy, version) {
+                return Ok(Err(violation));
+            }
+        }
+        if push_rules.must_be_project_member {
+            let project =
+                DBProject::find_by_project_key(project_key, self.id, &self.site.database).await?;
+            if let Some(project) = project {
+                if !DBProjectMember::can_user_write(project.id, user_id, &self.site.database)
+                    .await?
+                {
+                    return Ok(Err(PushRuleViolation::NotProjectMember(
+                        project_key.to_owned(),
+                    )));
+                }
+            }
+        }
+        let is_snapshot = version.is_some_and(|version| get_release_type(version).is_snapshot());
+        if !push_rules.allow_overwrite && !is_snapshot {
+            for file in files {
+                if self.storage.file_exists(self.id, file).await? {
+                    return Ok(Err(PushRuleViolation::OverwriteNotAllowed(
+                        file.to_string(),
+                    )));
+                }
+            }
+        }
+        Ok(Ok(()))
+    }
+    /// `POST deploy` Creates a stage for the declared files
+    #[instrument(skip(self, request))]
+    async fn nitro_deploy_create(
+        &self,
+        user_id: i32,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        let deploy: NewNRMavenDeploy = request.body.body_as_json().await?;
+        deploy.validate()?;
+        let version_directory = deploy.version_directory();
+        let files: Vec<StoragePath> = deploy
+            .files
+            .iter()
+            .map(|file| version_directory.clone().push(&file.file_name))
+            .collect();
+        let project_key = format!("{}:{}", deploy.group_id, deploy.artifact_id);
+        if let Err(violation) = self
+            .validate_push(&project_key, Some(&deploy.version), &files, user_id)
+            .await?
+        {
+            info!(?violation, "Deploy rejected by push rules");
+            return Ok(violation.into());
+        }
+        let stage = self
+            .staging
+            .create_stage(serde_json::to_value(&deploy)?, user_id)
+            .await?;
+        info!(?stage.id, project = %deploy.artifact_directory(), "Created Nitro Deploy");
+        let response = NewNrMavenDeploySuccessResponse {
+            deploy_id: stage.id,
+        };
+        json_response(StatusCode::OK, &response)
+    }
+    /// Gets the stage and the deploy it was created with. Only the user that created the deploy can use it
+    async fn get_nitro_deploy(
+        &self,
+        id: Uuid,
+        user_id: i32,
+    ) -> Result<Result<(DBStage, NewNRMavenDeploy), RepoResponse>, MavenError> {
+        let Some(stage) = self.staging.get_stage(id).await? else {
+            return Ok(Err(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("Deploy {} not found", id),
+            )));
+        };
+        if stage.created_by != user_id {
+            return Ok(Err(RepoResponse::forbidden()));
+        }
+        let deploy = NewNRMavenDeploy::try_from(&stage)?;
+        Ok(Ok((stage, deploy)))
+    }
+    /// `PUT deploy/{id}/{file}` Adds a file to the stage after checking it matches the declared hashes
+    #[instrument(skip(self, request))]
+    async fn nitro_deploy_upload(
+        &self,
+        id: Uuid,
+        file_name: String,
+        user_id: i32,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        let (stage, deploy) = match self.get_nitro_deploy(id, user_id).await? {
+            Ok(ok) => ok,
+            Err(response) => return Ok(response),
+        };
+        if stage.is_closed() {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::CONFLICT,
+                format!("Deploy {} is closed", stage.id),
+            ));
+        }
+        let Some(file) = deploy.get_file(&file_name) else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                format!("{} was not declared in the deploy", file_name),
+            ));
+        };
+        if stage
+            .get_file_by_name(&file_name, &self.site.database)
+            .await?
+            .is_some()
+        {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::CONFLICT,
+                format!("{} was already uploaded", file_name),
+            ));
+        }
+        let RepositoryRequest { body, trace, .. } = request;
+        let body = body.body_as_bytes().await?;
+        trace.metrics.project_write_bytes(body.len() as u64);
+        if let Err(mismatch) = file.verify(&body) {
+            warn!(
+                ?mismatch,
+                "Uploaded file does not match the declared hashes"
+            );
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                mismatch.to_string(),
+            ));
+        }
+        self.staging.add_file(stage.id, file_name, body).await?;
+        Ok(RepoResponse::basic_text_response(StatusCode::CREATED, ""))
+    }
+    /// `POST deploy/{id}/publish`
+    #[instrument(skip(self))]
+    async fn nitro_deploy_publish(
+        &self,
+        id: Uuid,
+        user_id: i32,
+    ) -> Result<RepoResponse, MavenError> {
+        let (stage, deploy) = match self.get_nitro_deploy(id, user_id).await? {
+            Ok(ok) => ok,
+            Err(response) => return Ok(response),
+        };
+        match self.publish_stage(&stage, &deploy, user_id).await? {
+            Ok(response) => json_response(StatusCode::OK, &response),
+            Err(err) => {
+                warn!(?err, "Stage can not be published");
+                Ok(RepoResponse::basic_text_response(
+                    err.status_code(),
+                    err.to_string(),
+                ))
+            }
+        }
+    }
+    /// Checks that every declared file was uploaded and matches its hashes. Returns the parsed pom
+    pub async fn verify_stage(
+        &self,
+        stage: &DBStage,
+        deploy: &NewNRMavenDeploy,
+    ) -> Result<Result<Pom, StageVerificationError>, MavenError> {
+        let uploaded: Vec<String> = stage
+            .get_files(&self.site.database)
+            .await?
+            .into_iter()
+            .map(|file| file.file_name)
+            .collect();
+        let missing: Vec<String> = deploy
+            .files
+            .iter()
+            .filter(|file| !uploaded.contains(&file.file_name))
+            .map(|file| file.file_name.clone())
+            .collect();
+        if !missing.is_empty() {
+            return Ok(Err(StageVerificationError::MissingFiles(missing)));
+        }
+        for file in &deploy.files {
+            let content = self.staging.open_file(stage.id, &file.file_name).await?;
+            let hashes = DeployFileHasher::hash_reader(content).await?;
+            if let Err(mismatch) = file.verify_hashes(&hashes) {
+                return Ok(Err(mismatch.into()));
+            }
+        }
+        let Some(pom_file) = deploy.pom_file() else {
+            return Ok(Err(StageVerificationError::MissingPom));
+        };
+        let pom = self
+            .staging
+            .read_file(stage.id, &pom_file.file_name)
+            .await?;
+        Ok(Ok(self.parse_pom(pom)?))
+    }
+    /// Verifies every file and the push rules of this repository then moves the files into storage and indexes the project.
+    ///
+    /// The stage does not have to belong to this repository.
+    /// New files are saved before existing files are replaced. If a file fails to be saved only the files this publish created are removed
+    #[instrument(skip(self, deploy))]
+    pub async fn publish_stage(
+        &self,
+        stage: &DBStage,
+        deploy: &NewNRMavenDeploy,
+        publisher: i32,
+    ) -> Result<Result<NRMavenPublishSuccessResponse, StageVerificationError>, MavenError> {
+        // Everything is verified before anything is moved into storage
+        let pom = match self.verify_stage(stage, deploy).await? {
+            Ok(pom) => pom,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        let version_directory = deploy.version_directory();
+        let files: Vec<StoragePath> = deploy
+            .files
+            .iter()
+            .map(|file| version_directory.clone().push(&file.file_name))
+            .collect();
+        let project_key = format!("{}:{}", deploy.group_id, deploy.artifact_id);
+        if let Err(violation) = self
+            .validate_push(&project_key, Some(&deploy.version), &files, publisher)
+            .await?
+        {
+            info!(?violation, "Publish rejected by push rules");
+            return Ok(Err(violation.into()));
+        }
+        let stage_directory = self.staging.stage_directory(stage.id);
+        let mut new_files = Vec::with_capacity(deploy.files.len() * 3);
+        let mut replaced_files = Vec::new();
+        for file in &deploy.files {
+            let path = version_directory.clone().push(&file.file_name);
+            let mut file_and_checksums = vec![(
+                path.clone(),
+                FileContent::Path(stage_directory.join(&file.file_name)),
+            )];
+            // Maven clients expect the checksums next to the file
+            for (extension, checksum) in [("sha1", &file.sha1), ("md5", &file.md5)] {
+                let checksum_path = StoragePath::from(format!("{}.{}", path, extension).as_str());
+                file_and_checksums
+                    .push((checksum_path, FileContent::from(checksum.to_lowercase())));
+            }
+            for (path, content) in file_and_checksums {
+                if self.storage.file_exists(self.id, &path).await? {
+                    replaced_files.push((path, content));
+                } else {
+                    new_files.push((path, content));
+                }
+            }
+        }
+        let mut created = Vec::with_capacity(new_files.len());
+        for (path, content) in new_files.into_iter().chain(replaced_files) {
+            match self.storage.save_file(self.id, content, &path).await {
+                Ok((_, true)) => created.push(path),
+                Ok((_, false)) => {}
+                Err(err) => {
+                    error!(
+                        ?err,
+                        ?path,
+                        "Failed to move file into storage. Removing the files this publish created"
+                    );
+                    for created_path in created {
+                        if let Err(err) = self.storage.delete_file(self.id, &created_path).await {
+                            warn!(?err, ?created_path, "Failed to remove published file");
+                        }
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+        if let Err(err) = self.update_artifact_metadata(deploy).await {
+            warn!(?err, "Failed to update maven-metadata.xml");
+        }
+        let pom_path = deploy
+            .pom_file()
+            .map(|pom_file| version_directory.clone().push(&pom_file.file_name))
+            .unwrap_or_else(|| version_directory.clone());
+        self.post_pom_upload(pom_path, Some(publisher), pom).await;
+        self.staging.delete_stage(stage).await?;
+
+        let base_path = format!(
+            "/repositories/{}/{}",
+            self.storage.storage_config().storage_config.storage_name,
+            self.name
+        );
+        let files = deploy
+            .files
+            .iter()
+            .map(|file| NRMavenPublishSuccessFile {
+                name: file.file_name.clone(),
+                path: format!("{}/{}/{}", base_path, version_directory, file.file_name),
+            })
+            .collect();
+        info!(?stage.id, project = %deploy.artifact_directory(), "Published stage");
+        Ok(Ok(NRMavenPublishSuccessResponse { files }))
+    }
+    /// Adds the version to the artifact's `maven-metadata.xml`. Nitro Deploy clients do not upload it
+    async fn update_artifact_metadata(&self, deploy: &NewNRMavenDeploy) -> Result<(), MavenError> {
+        let metadata_path = deploy.artifact_directory().push(MAVEN_METADATA_FILE);
+        let mut metadata = match self.storage.open_file(self.id, &metadata_path).await? {
+            Some(file) => match file.file() {
+                Some((content, meta)) => {
+                    let bytes = content
+                        .read_to_vec(meta.file_type.file_size as usize)
+                        .await?;
+                    MavenMetadata::from_bytes(&bytes)?
+                }
+                None => MavenMetadata::default(),
+            },
+            None => MavenMetadata::default(),
+        };
+        let is_snapshot = deploy.version.ends_with("-SNAPSHOT");
+        metadata.merge(MavenMetadata {
+            group_id: Some(deploy.group_id.clone()),
+            artifact_id: Some(deploy.artifact_id.clone()),
+            versioning: Some(MetadataVersioning {
+                latest: Some(deploy.version.clone()),
+                release: (!is_snapshot).then(|| deploy.version.clone()),
+                versions: Some(MetadataVersions {
+                    version: vec![deploy.version.clone()],
+                }),
+                last_updated: Some(Local::now().format("%Y%m%d%H%M%S").to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let metadata = metadata.to_xml_string()?;
+        let hashes = generate_from_bytes(metadata.as_bytes());
+        self.storage
+            .save_file(
+                self.id,
+                FileContent::from(metadata.as_bytes()),
+                &metadata_path,
+            )
+            .await?;
+        for extension in ["sha1", "md5"] {
+            let Some(checksum) = maven_checksum(&hashes, extension) else {
+                continue;
+            };
+            let checksum_path =
+                StoragePath::from(format!("{}.{}", metadata_path, extension).as_str());
+            self.storage
+                .save_file(self.id, FileContent::from(checksum), &checksum_path)
+                .await?;
+        }
+        Ok(())
+    }
 }
 impl Repository for MavenHosted {
     type Error = MavenError;
@@ -249,7 +669,7 @@ impl Repository for MavenHosted {
             ..
         }: RepositoryRequest,
     ) -> Result<RepoResponse, MavenError> {
-        if let Some(err) = self.check_read(&authentication).await? {
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
             return Ok(err);
         }
         let visibility = self.visibility();
@@ -291,7 +711,7 @@ impl Repository for MavenHosted {
         }: RepositoryRequest,
     ) -> Result<RepoResponse, MavenError> {
         let visibility = self.visibility();
-        if let Some(err) = self.check_read(&authentication).await? {
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
             return Ok(err);
         }
         let file = self.storage.get_file_information(self.id, &path).await?;
@@ -299,39 +719,28 @@ impl Repository for MavenHosted {
     }
     async fn handle_put(&self, request: RepositoryRequest) -> Result<RepoResponse, MavenError> {
         info!("Handling PUT Request for Repository: {}", self.id);
-        {
-            let push_rules = self.push_rules.read();
-            if push_rules.must_use_auth_token_for_push && !request.authentication.has_auth_token() {
-                info!("Repository requires an auth token for push");
-                return Ok(RepoResponse::require_auth_token());
-            }
-        }
-
-        let Some(user) = request
-            .authentication
-            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
-            .await?
-        else {
-            info!("No acceptable user authentication provided");
-            return Ok(RepoResponse::unauthorized());
+        let user_id = match self.authorize_push(&request.authentication).await? {
+            Ok(user_id) => user_id,
+            Err(response) => return Ok(response),
         };
-        if !user
-            .has_action(RepositoryActions::Write, self.id, self.site.as_ref())
-            .await?
-        {
-            info!(?self.id, ?user, "User does not have write permissions");
-            return Ok(RepoResponse::forbidden());
-        }
 
         let Some(nitro_deploy_version) = request.get_nitro_repo_deploy_header()? else {
             return self.standard_maven_deploy(request).await;
         };
         info!(?nitro_deploy_version, "Handling Nitro Deploy Version");
-
-        Ok(RepoResponse::unsupported_method_response(
-            request.parts.method,
-            self.get_type(),
-        ))
+        match NitroDeployPath::parse(&request.path) {
+            Some(NitroDeployPath::Deploy {
+                id,
+                file: Some(file_name),
+            }) => {
+                self.nitro_deploy_upload(id, file_name, user_id, request)
+                    .await
+            }
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Nitro Deploy files must be sent to deploy/{deployId}/{fileName}",
+            )),
+        }
     }
     async fn handle_post(&self, request: RepositoryRequest) -> Result<RepoResponse, MavenError> {
         let Some(nitro_deploy_version) = request.get_nitro_repo_deploy_header()? else {
@@ -341,7 +750,51 @@ impl Repository for MavenHosted {
             ));
         };
         info!(?nitro_deploy_version, "Handling Nitro Deploy Version");
-        todo!()
+        let user_id = match self.authorize_push(&request.authentication).await? {
+            Ok(user_id) => user_id,
+            Err(response) => return Ok(response),
+        };
+        match NitroDeployPath::parse(&request.path) {
+            Some(NitroDeployPath::NewDeploy) => self.nitro_deploy_create(user_id, request).await,
+            Some(NitroDeployPath::Deploy {
+                id,
+                file: Some(action),
+            }) if action == PUBLISH_ACTION => self.nitro_deploy_publish(id, user_id).await,
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Unknown Nitro Deploy request",
+            )),
+        }
+    }
+    async fn handle_delete(&self, request: RepositoryRequest) -> Result<RepoResponse, MavenError> {
+        let Some(nitro_deploy_version) = request.get_nitro_repo_deploy_header()? else {
+            return Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            ));
+        };
+        info!(?nitro_deploy_version, "Handling Nitro Deploy Version");
+        let user_id = match self.authorize_push(&request.authentication).await? {
+            Ok(user_id) => user_id,
+            Err(response) => return Ok(response),
+        };
+        let Some(NitroDeployPath::Deploy { id, file: None }) =
+            NitroDeployPath::parse(&request.path)
+        else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Unknown Nitro Deploy request",
+            ));
+        };
+        let (stage, _) = match self.get_nitro_deploy(id, user_id).await? {
+            Ok(ok) => ok,
+            Err(response) => return Ok(response),
+        };
+        self.staging.delete_stage(&stage).await?;
+        Ok(RepoResponse::basic_text_response(
+            StatusCode::NO_CONTENT,
+            "",
+        ))
     }
     #[instrument(fields(repository_type = "maven/hosted"))]
     async fn resolve_project_and_version_for_path(
@@ -0,0 +1,226 @@
+//! Types for reading and writing `maven-metadata.xml` files.
+//!
+//! Only the fields that Nitro Repo needs to merge metadata from multiple repositories are modeled.
+use serde::{Deserialize, Serialize};
+
+use super::MavenError;
+use crate::error::OtherInternalError;
+
+pub static MAVEN_METADATA_FILE: &str = "maven-metadata.xml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "metadata", rename_all = "camelCase")]
+pub struct MavenMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versioning: Option<MetadataVersioning>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<MetadataPlugins>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataVersioning {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<MetadataSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versions: Option<MetadataVersions>,
+    /// Formatted as `yyyyMMddHHmmss` so it can be compared as a string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_versions: Option<MetadataSnapshotVersions>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataVersions {
+    #[serde(default)]
+    pub version: Vec<String>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataSnapshot {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_copy: Option<bool>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataSnapshotVersions {
+    #[serde(default)]
+    pub snapshot_version: Vec<MetadataSnapshotVersion>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataSnapshotVersion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classifier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataPlugins {
+    #[serde(default)]
+    pub plugin: Vec<MetadataPlugin>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataPlugin {
+    pub name: Option<String>,
+    pub prefix: String,
+    pub artifact_id: String,
+}
+impl MavenMetadata {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MavenError> {
+        let metadata = String::from_utf8_lossy(bytes);
+        let metadata = maven_rs::quick_xml::de::from_str(&metadata)?;
+        Ok(metadata)
+    }
+    pub fn to_xml_string(&self) -> Result<String, MavenError> {
+        let body = maven_rs::quick_xml::se::to_string(self).map_err(OtherInternalError::new)?;
+        Ok(format!(r#"<?xml version="1.0" encoding="UTF-8"?>{}"#, body))
+    }
+    /// Merges the other metadata into this one.
+    ///
+    /// Versions and plugins are combined. The values that describe the "current" state
+    /// (latest, release, snapshot) are taken from whichever metadata was updated most recently.
+    pub fn merge(&mut self, other: MavenMetadata) {
+        if self.group_id.is_none() {
+            self.group_id = other.group_id;
+        }
+        if self.artifact_id.is_none() {
+            self.artifact_id = other.artifact_id;
+        }
+        if self.version.is_none() {
+            self.version = other.version;
+        }
+        match (&mut self.versioning, other.versioning) {
+            (Some(versioning), Some(other)) => versioning.merge(other),
+            (versioning @ None, other) => *versioning = other,
+            _ => {}
+        }
+        match (&mut self.plugins, other.plugins) {
+            (Some(plugins), Some(other)) => {
+                for plugin in other.plugin {
+                    if !plugins.plugin.iter().any(|p| p.prefix == plugin.prefix) {
+                        plugins.plugin.push(plugin);
+                    }
+                }
+            }
+            (plugins @ None, other) => *plugins = other,
+            _ => {}
+        }
+    }
+}
+impl MetadataVersioning {
+    fn merge(&mut self, other: MetadataVersioning) {
+        let other_is_newer = other.last_updated > self.last_updated;
+        if other_is_newer {
+            self.last_updated = other.last_updated;
+        }
+        if other.latest.is_some() && (other_is_newer || self.latest.is_none()) {
+            self.latest = other.latest;
+        }
+        if other.release.is_some() && (other_is_newer || self.release.is_none()) {
+            self.release = other.release;
+        }
+        if other.snapshot.is_some() && (other_is_newer || self.snapshot.is_none()) {
+            self.snapshot = other.snapshot;
+        }
+        match (&mut self.versions, other.versions) {
+            (Some(versions), Some(other)) => {
+                for version in other.version {
+                    if !versions.version.contains(&version) {
+                        versions.version.push(version);
+                    }
+                }
+            }
+            (versions @ None, other) => *versions = other,
+            _ => {}
+        }
+        match (&mut self.snapshot_versions, other.snapshot_versions) {
+            (Some(snapshot_versions), Some(other)) => {
+                for snapshot_version in other.snapshot_version {
+                    let existing = snapshot_versions.snapshot_version.iter_mut().find(|v| {
+                        v.classifier == snapshot_version.classifier
+                            && v.extension == snapshot_version.extension
+                    });
+                    match existing {
+                        Some(existing) if snapshot_version.updated > existing.updated => {
+                            *existing = snapshot_version;
+                        }
+                        Some(_) => {}
+                        None => snapshot_versions.snapshot_version.push(snapshot_version),
+                    }
+                }
+            }
+            (snapshot_versions @ None, other) => *snapshot_versions = other,
+            _ => {}
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    #[test]
+    fn merge_versions() {
+        let mut first = MavenMetadata::from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+            <metadata>
+                <groupId>dev.kingtux</groupId>
+                <artifactId>tms</artifactId>
+                <versioning>
+                    <latest>1.0.0</latest>
+                    <release>1.0.0</release>
+                    <versions>
+                        <version>0.9.0</version>
+                        <version>1.0.0</version>
+                    </versions>
+                    <lastUpdated>20240101000000</lastUpdated>
+                </versioning>
+            </metadata>"#,
+        )
+        .unwrap();
+        let second = MavenMetadata::from_bytes(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+            <metadata>
+                <groupId>dev.kingtux</groupId>
+                <artifactId>tms</artifactId>
+                <versioning>
+                    <latest>1.1.0</latest>
+                    <release>1.1.0</release>
+                    <versions>
+                        <version>1.0.0</version>
+                        <version>1.1.0</version>
+                    </versions>
+                    <lastUpdated>20240201000000</lastUpdated>
+                </versioning>
+            </metadata>"#,
+        )
+        .unwrap();
+        first.merge(second);
+        let versioning = first.versioning.unwrap();
+        assert_eq!(versioning.latest.as_deref(), Some("1.1.0"));
+        assert_eq!(versioning.release.as_deref(), Some("1.1.0"));
+        assert_eq!(versioning.last_updated.as_deref(), Some("20240201000000"));
+        assert_eq!(
+            versioning.versions.unwrap().version,
+            vec!["0.9.0", "1.0.0", "1.1.0"]
+        );
+    }
+}
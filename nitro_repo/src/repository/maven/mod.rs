@@ -5,6 +5,7 @@ use ahash::HashMap;
 use axum::response::IntoResponse;
 pub use configs::*;
 use futures::future::BoxFuture;
+use group::MavenGroup;
 use hosted::MavenHosted;
 use nr_core::{
     builder_error,
@@ -20,9 +21,12 @@ use nr_storage::DynStorage;
 use proxy::MavenProxy;
 mod configs;
 use super::{DynRepository, Repository, RepositoryFactoryError, RepositoryType};
+pub mod group;
 pub mod hosted;
+pub mod metadata;
 pub mod nitro_deploy;
 pub mod proxy;
+pub mod push_rules;
 pub mod utils;
 pub static REPOSITORY_TYPE_ID: &str = "maven";
 #[derive(Debug, Default)]
@@ -105,6 +109,7 @@ impl RepositoryType for MavenRepositoryType {
 pub enum MavenRepository {
     Hosted(MavenHosted),
     Proxy(MavenProxy),
+    Group(MavenGroup),
 }
 impl MavenRepository {
     pub async fn load(
@@ -133,6 +138,10 @@ impl MavenRepository {
                 let proxy = MavenProxy::load(repo, storage, website, proxy_config).await?;
                 Ok(MavenRepository::Proxy(proxy))
             }
+            MavenRepositoryConfig::Group(group_config) => {
+                let group = MavenGroup::load(repo, storage, website, group_config).await?;
+                Ok(MavenRepository::Group(group))
+            }
         }
     }
 }
@@ -172,6 +181,7 @@ impl_from_error_for_other!(RepositoryHandlerError);
 impl_from_error_for_other!(nr_storage::StorageError);
 impl_from_error_for_other!(reqwest::Error);
 impl_from_error_for_other!(OtherInternalError);
+impl_from_error_for_other!(StagingManagerError);
 
 impl IntoErrorResponse for MavenError {
     fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
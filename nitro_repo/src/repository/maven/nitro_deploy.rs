@@ -1,7 +1,47 @@
-use nr_core::repository::project::VersionData;
+use http::StatusCode;
+use nr_core::{
+    database::entities::stages::DBStage,
+    repository::project::VersionData,
+    storage::{FileHashes, StoragePath, StoragePathComponent},
+};
+use nr_storage::FileHasher;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use utoipa::ToSchema;
 use uuid::Uuid;
+
+use super::{push_rules::PushRuleViolation, utils::maven_checksum};
+use crate::error::BadRequestErrors;
+/// The name of the action that publishes a deploy. `deploy/{id}/publish`
+pub const PUBLISH_ACTION: &str = "publish";
+/// A Nitro Deploy path relative to the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NitroDeployPath {
+    /// `deploy`
+    NewDeploy,
+    /// `deploy/{id}` or `deploy/{id}/{file}`
+    Deploy { id: Uuid, file: Option<String> },
+}
+impl NitroDeployPath {
+    /// Returns None if the path is not a Nitro Deploy path
+    pub fn parse(path: &StoragePath) -> Option<Self> {
+        let components: Vec<StoragePathComponent> = path.clone().into();
+        match components.as_slice() {
+            [deploy] if deploy == "deploy" => Some(Self::NewDeploy),
+            [deploy, id] if deploy == "deploy" => Some(Self::Deploy {
+                id: Uuid::parse_str(id.as_ref()).ok()?,
+                file: None,
+            }),
+            [deploy, id, file] if deploy == "deploy" => Some(Self::Deploy {
+                id: Uuid::parse_str(id.as_ref()).ok()?,
+                file: Some(file.to_string()),
+            }),
+            _ => None,
+        }
+    }
+}
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 pub enum MavenFileType {
     Pom,
@@ -29,6 +69,172 @@ pub struct NewNRMavenDeployFile {
     pub md5: String,
     pub maven_file_type: Option<MavenFileType>,
 }
+impl NewNRMavenDeploy {
+    /// Checks that a pom is included and that all names can be used as a path
+    pub fn validate(&self) -> Result<(), BadRequestErrors> {
+        for (name, value) in [
+            ("group_id", &self.group_id),
+            ("artifact_id", &self.artifact_id),
+            ("version", &self.version),
+        ] {
+            if !is_valid_path_part(value) {
+                return Err(BadRequestErrors::Other(format!(
+                    "Invalid {}: {:?}",
+                    name, value
+                )));
+            }
+        }
+        for (index, file) in self.files.iter().enumerate() {
+            if !is_valid_path_part(&file.file_name) {
+                return Err(BadRequestErrors::Other(format!(
+                    "Invalid file name: {:?}",
+                    file.file_name
+                )));
+            }
+            if self.files[..index]
+                .iter()
+                .any(|other| other.file_name == file.file_name)
+            {
+                return Err(BadRequestErrors::Other(format!(
+                    "Duplicate file: {}",
+                    file.file_name
+                )));
+            }
+        }
+        if self.pom_file().is_none() {
+            return Err(BadRequestErrors::Other("A pom file is required".to_owned()));
+        }
+        Ok(())
+    }
+    /// `{groupId as path}/{artifactId}`
+    pub fn artifact_directory(&self) -> StoragePath {
+        let mut path = StoragePath::from(self.group_id.replace('.', "/").as_str());
+        path.push_mut(&self.artifact_id);
+        path
+    }
+    /// `{groupId as path}/{artifactId}/{version}`
+    pub fn version_directory(&self) -> StoragePath {
+        self.artifact_directory().push(&self.version)
+    }
+    pub fn get_file(&self, file_name: &str) -> Option<&NewNRMavenDeployFile> {
+        self.files.iter().find(|file| file.file_name == file_name)
+    }
+    pub fn pom_file(&self) -> Option<&NewNRMavenDeployFile> {
+        self.files.iter().find(|file| {
+            file.maven_file_type == Some(MavenFileType::Pom) || file.file_name.ends_with(".pom")
+        })
+    }
+}
+fn is_valid_path_part(value: &str) -> bool {
+    !value.is_empty() && value != "." && value != ".." && !value.contains(['/', '\\'])
+}
+impl TryFrom<&DBStage> for NewNRMavenDeploy {
+    type Error = serde_json::Error;
+    fn try_from(stage: &DBStage) -> Result<Self, Self::Error> {
+        serde_json::from_value(stage.stage_state.0.clone())
+    }
+}
+/// Why a stage can not be closed or published
+#[derive(Debug, Error)]
+pub enum StageVerificationError {
+    #[error("Missing files: {}", .0.join(", "))]
+    MissingFiles(Vec<String>),
+    #[error(transparent)]
+    HashMismatch(#[from] HashMismatch),
+    #[error("A pom file is required")]
+    MissingPom,
+    /// The target repository rejects the deploy. Push rules are checked again when the stage is published
+    #[error(transparent)]
+    PushRule(#[from] PushRuleViolation),
+}
+impl StageVerificationError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            StageVerificationError::PushRule(violation) => violation.status_code(),
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+#[derive(Debug, Error)]
+#[error("{algorithm} of {file_name} does not match. Expected {expected} got {actual}")]
+pub struct HashMismatch {
+    pub file_name: String,
+    pub algorithm: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+/// Hashes a deploy file as it is read. Nitro Deploy declares sha512 in addition to the [FileHashes]
+#[derive(Debug, Clone, Default)]
+pub struct DeployFileHasher {
+    hasher: FileHasher,
+    sha512: Sha512,
+}
+impl DeployFileHasher {
+    pub fn update(&mut self, buffer: &[u8]) {
+        self.hasher.update(buffer);
+        self.sha512.update(buffer);
+    }
+    /// Reads the content in chunks. The content is never fully in memory
+    pub async fn hash_reader(
+        mut reader: impl AsyncRead + Unpin,
+    ) -> Result<DeployFileHashes, std::io::Error> {
+        let mut hasher = Self::default();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize())
+    }
+    pub fn finalize(self) -> DeployFileHashes {
+        DeployFileHashes {
+            hashes: self.hasher.finalize(),
+            sha512: self
+                .sha512
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
+#[derive(Debug, Clone)]
+pub struct DeployFileHashes {
+    pub hashes: FileHashes,
+    /// Hex encoded
+    pub sha512: String,
+}
+impl NewNRMavenDeployFile {
+    /// Compares the content against the declared hashes. Hashes are hex encoded
+    pub fn verify(&self, content: &[u8]) -> Result<(), HashMismatch> {
+        let mut hasher = DeployFileHasher::default();
+        hasher.update(content);
+        self.verify_hashes(&hasher.finalize())
+    }
+    /// Compares hashes generated with [DeployFileHasher] against the declared hashes
+    pub fn verify_hashes(&self, hashes: &DeployFileHashes) -> Result<(), HashMismatch> {
+        let actual_hashes = [
+            ("sha512", &self.sha512, Some(hashes.sha512.clone())),
+            ("sha1", &self.sha1, maven_checksum(&hashes.hashes, "sha1")),
+            ("md5", &self.md5, maven_checksum(&hashes.hashes, "md5")),
+        ];
+        for (algorithm, expected, actual) in actual_hashes {
+            let actual = actual.unwrap_or_default();
+            if !expected.eq_ignore_ascii_case(&actual) {
+                return Err(HashMismatch {
+                    file_name: self.file_name.clone(),
+                    algorithm,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct NewNrMavenDeploySuccessResponse {
     pub deploy_id: Uuid,
@@ -43,3 +249,50 @@ pub struct NRMavenPublishSuccessFile {
     pub name: String,
     pub path: String,
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_deploy_path() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            NitroDeployPath::parse(&StoragePath::from("deploy")),
+            Some(NitroDeployPath::NewDeploy)
+        );
+        assert_eq!(
+            NitroDeployPath::parse(&StoragePath::from(format!("deploy/{}", id).as_str())),
+            Some(NitroDeployPath::Deploy { id, file: None })
+        );
+        assert_eq!(
+            NitroDeployPath::parse(&StoragePath::from(
+                format!("deploy/{}/publish", id).as_str()
+            )),
+            Some(NitroDeployPath::Deploy {
+                id,
+                file: Some(PUBLISH_ACTION.to_owned())
+            })
+        );
+        assert_eq!(
+            NitroDeployPath::parse(&StoragePath::from("deploy/not-an-id")),
+            None
+        );
+        assert_eq!(
+            NitroDeployPath::parse(&StoragePath::from("dev/kingtux/tms")),
+            None
+        );
+    }
+    #[test]
+    fn verify_hashes() {
+        let mut file = NewNRMavenDeployFile {
+            file_name: "tms-1.0.0.jar".to_owned(),
+            sha512: "9B71D224BD62F3785D96D46AD3EA3D73319BFBC2890CAADAE2DFF72519673CA72323C3D99BA5C11D7C7ACC6E14B8C5DA0C4663475C2E5C3ADEF46F73BCDEC043".to_owned(),
+            sha1: "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d".to_owned(),
+            md5: "5d41402abc4b2a76b9719d911017c592".to_owned(),
+            maven_file_type: Some(MavenFileType::Jar),
+        };
+        assert!(file.verify(b"hello").is_ok());
+        file.md5 = "00000000000000000000000000000000".to_owned();
+        let mismatch = file.verify(b"hello").unwrap_err();
+        assert_eq!(mismatch.algorithm, "md5");
+    }
+}
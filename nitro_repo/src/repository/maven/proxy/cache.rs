@@ -0,0 +1,185 @@
+use std::time::Instant;
+
+use ahash::{HashMap, HashMapExt};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use http::{
+    HeaderMap,
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use nr_core::storage::StoragePath;
+use nr_storage::meta::RepositoryMeta;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::repository::maven::metadata::MAVEN_METADATA_FILE;
+
+const ETAG_META_KEY: &str = "proxy.etag";
+const LAST_MODIFIED_META_KEY: &str = "proxy.last_modified";
+const CHECKED_AT_META_KEY: &str = "proxy.checked_at";
+/// How long cached files are served before they are revalidated with the upstream.
+///
+/// All values are in seconds. If a value is null the file is never revalidated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MavenProxyCacheConfig {
+    /// Release artifacts. These should never change upstream
+    #[schemars(title = "Release TTL")]
+    pub release_ttl: Option<u64>,
+    /// Files inside of a SNAPSHOT version
+    #[schemars(title = "Snapshot TTL")]
+    pub snapshot_ttl: Option<u64>,
+    /// `maven-metadata.xml` and its checksums
+    #[schemars(title = "Metadata TTL")]
+    pub metadata_ttl: Option<u64>,
+    /// How long to remember that no route had a file. Null disables the negative cache
+    #[schemars(title = "Not Found TTL")]
+    pub not_found_ttl: Option<u64>,
+}
+impl Default for MavenProxyCacheConfig {
+    fn default() -> Self {
+        Self {
+            release_ttl: None,
+            snapshot_ttl: Some(60 * 60),
+            metadata_ttl: Some(30 * 60),
+            not_found_ttl: Some(5 * 60),
+        }
+    }
+}
+impl MavenProxyCacheConfig {
+    pub fn ttl_for(&self, path: &StoragePath) -> Option<Duration> {
+        let path = path.to_string();
+        let ttl = if path.contains(MAVEN_METADATA_FILE) {
+            self.metadata_ttl
+        } else if path.contains("-SNAPSHOT") {
+            self.snapshot_ttl
+        } else {
+            self.release_ttl
+        }?;
+        Some(Duration::seconds(ttl as i64))
+    }
+    pub fn not_found_ttl(&self) -> Option<std::time::Duration> {
+        self.not_found_ttl.map(std::time::Duration::from_secs)
+    }
+}
+/// Information about where a cached file came from. Stored inside the [RepositoryMeta] of the file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyCacheMeta {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The last time the file was downloaded or revalidated
+    pub checked_at: Option<DateTime<FixedOffset>>,
+}
+impl ProxyCacheMeta {
+    pub fn from_response_headers(headers: &HeaderMap) -> Self {
+        let header_as_string = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header_as_string(ETAG),
+            last_modified: header_as_string(LAST_MODIFIED),
+            checked_at: Some(Local::now().fixed_offset()),
+        }
+    }
+    pub fn is_stale(&self, ttl: Duration, file_modified: DateTime<FixedOffset>) -> bool {
+        let checked_at = self.checked_at.unwrap_or(file_modified);
+        Local::now().fixed_offset() - checked_at > ttl
+    }
+    pub fn mark_checked(&mut self) {
+        self.checked_at = Some(Local::now().fixed_offset());
+    }
+    /// Headers to make a conditional request to the upstream
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self.etag.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = self.last_modified.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+        headers
+    }
+    pub fn write_to(&self, meta: &mut RepositoryMeta) {
+        if let Some(etag) = &self.etag {
+            meta.insert(ETAG_META_KEY, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            meta.insert(LAST_MODIFIED_META_KEY, last_modified);
+        }
+        if let Some(checked_at) = &self.checked_at {
+            meta.insert(CHECKED_AT_META_KEY, checked_at.to_rfc3339());
+        }
+    }
+}
+impl From<&RepositoryMeta> for ProxyCacheMeta {
+    fn from(meta: &RepositoryMeta) -> Self {
+        Self {
+            etag: meta.get(ETAG_META_KEY).map(str::to_owned),
+            last_modified: meta.get(LAST_MODIFIED_META_KEY).map(str::to_owned),
+            checked_at: meta
+                .get(CHECKED_AT_META_KEY)
+                .and_then(|value| DateTime::parse_from_rfc3339(value).ok()),
+        }
+    }
+}
+/// A bounded in memory cache of paths that no route had.
+///
+/// Prevents repeated requests for missing files from being sent to every route.
+#[derive(Debug)]
+pub struct NegativeCache {
+    entries: Mutex<HashMap<String, Instant>>,
+    max_entries: usize,
+}
+impl Default for NegativeCache {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+impl NegativeCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_entries,
+        }
+    }
+    /// Returns true if the path was recently not found
+    pub fn contains(&self, path: &StoragePath) -> bool {
+        let path = path.to_string();
+        let mut entries = self.entries.lock();
+        match entries.get(&path) {
+            Some(expires) if *expires > Instant::now() => true,
+            Some(_) => {
+                entries.remove(&path);
+                false
+            }
+            None => false,
+        }
+    }
+    pub fn insert(&self, path: &StoragePath, ttl: std::time::Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock();
+        if entries.len() >= self.max_entries {
+            entries.retain(|_, expires| *expires > now);
+        }
+        if entries.len() >= self.max_entries {
+            // Still full. Drop the entry closest to expiring
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, expires)| **expires)
+                .map(|(path, _)| path.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(path.to_string(), now + ttl);
+    }
+    pub fn remove(&self, path: &StoragePath) {
+        self.entries.lock().remove(&path.to_string());
+    }
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
@@ -0,0 +1,188 @@
+use nr_core::storage::FileHashes;
+use nr_storage::FileHasher;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+
+use super::MavenProxyRepositoryRoute;
+use crate::repository::maven::utils::maven_checksum;
+/// Extensions of files that are signatures or checksums of another file. These are never verified
+pub static CHECKSUM_EXTENSIONS: &[&str] = &["md5", "sha1", "sha256", "sha512", "asc"];
+/// The checksums that are checked against the upstream. In order of preference
+static VERIFY_ALGORITHMS: &[&str] = &["sha256", "sha1"];
+/// What to do when a downloaded file does not match the upstream checksum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ChecksumPolicy {
+    /// Files that do not match or do not have a checksum upstream are not cached
+    Strict,
+    /// Files that do not match are not cached. Files without a checksum upstream are logged but still cached
+    #[default]
+    Warn,
+    /// Checksums are not checked
+    Off,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumVerification {
+    Valid {
+        algorithm: &'static str,
+        checksum: String,
+    },
+    Mismatch {
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// The upstream does not have a checksum file
+    Missing,
+    /// The policy is off or the file is a checksum itself
+    Skipped,
+}
+impl ChecksumPolicy {
+    /// Checks the result against the policy. Returns true if the file should be cached
+    pub fn allows(&self, verification: &ChecksumVerification, url: &str) -> bool {
+        match (self, verification) {
+            (ChecksumPolicy::Off, _)
+            | (_, ChecksumVerification::Valid { .. })
+            | (_, ChecksumVerification::Skipped) => true,
+            (ChecksumPolicy::Strict, ChecksumVerification::Mismatch { .. }) => {
+                error!(
+                    ?verification,
+                    ?url,
+                    "Checksum mismatch. Refusing to cache file"
+                );
+                false
+            }
+            (ChecksumPolicy::Strict, ChecksumVerification::Missing) => {
+                error!(?url, "Upstream has no checksum. Refusing to cache file");
+                false
+            }
+            (ChecksumPolicy::Warn, ChecksumVerification::Mismatch { .. }) => {
+                error!(
+                    ?verification,
+                    ?url,
+                    "Checksum mismatch. Refusing to cache file"
+                );
+                false
+            }
+            (ChecksumPolicy::Warn, ChecksumVerification::Missing) => {
+                warn!(?url, "Upstream has no checksum");
+                true
+            }
+        }
+    }
+}
+pub fn is_checksum_file(path: &str) -> bool {
+    path.rsplit_once('.')
+        .is_some_and(|(_, extension)| CHECKSUM_EXTENSIONS.contains(&extension))
+}
+/// Checksum files can contain just the hash or the hash followed by the file name
+pub fn parse_checksum_file(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .next()
+        .map(|checksum| checksum.to_lowercase())
+}
+/// The hasher the download is fed into. None if the policy does not require the file to be verified
+pub fn download_hasher(policy: ChecksumPolicy, url: &str) -> Option<FileHasher> {
+    if policy == ChecksumPolicy::Off || is_checksum_file(url) {
+        None
+    } else {
+        Some(FileHasher::default())
+    }
+}
+/// Verifies a downloaded file with the hashes generated while it was downloaded.
+///
+/// Skipped if the file was not hashed. See [download_hasher]
+pub async fn verify_download(
+    http_client: &reqwest::Client,
+    route: &MavenProxyRepositoryRoute,
+    url: &str,
+    hashes: Option<&FileHashes>,
+) -> ChecksumVerification {
+    let Some(hashes) = hashes else {
+        return ChecksumVerification::Skipped;
+    };
+    verify_against_upstream(http_client, route, url, hashes).await
+}
+/// Downloads the checksum sidecar files from the route and compares them against the bytes
+pub async fn verify_against_upstream(
+    http_client: &reqwest::Client,
+    route: &MavenProxyRepositoryRoute,
+    url: &str,
+    hashes: &FileHashes,
+) -> ChecksumVerification {
+    for algorithm in VERIFY_ALGORITHMS {
+        let checksum_url = format!("{}.{}", url, algorithm);
+        let response = match route.get(http_client, &checksum_url).send().await {
+            Ok(ok) if ok.status().is_success() => ok,
+            Ok(ok) => {
+                debug!(?checksum_url, status = ?ok.status(), "No checksum file");
+                continue;
+            }
+            Err(err) => {
+                warn!(?err, ?checksum_url, "Failed to download checksum file");
+                continue;
+            }
+        };
+        let Some(expected) = response
+            .text()
+            .await
+            .ok()
+            .as_deref()
+            .and_then(parse_checksum_file)
+        else {
+            continue;
+        };
+        let Some(actual) = maven_checksum(hashes, algorithm) else {
+            continue;
+        };
+        if expected == actual {
+            return ChecksumVerification::Valid {
+                algorithm,
+                checksum: expected,
+            };
+        }
+        return ChecksumVerification::Mismatch {
+            algorithm,
+            expected,
+            actual,
+        };
+    }
+    ChecksumVerification::Missing
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_checksum_file() {
+        assert_eq!(
+            parse_checksum_file("D41D8CD98F00B204E9800998ECF8427E  tms-1.0.0.jar\n").as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert_eq!(parse_checksum_file("   "), None);
+        assert!(is_checksum_file("dev/kingtux/tms-1.0.0.jar.sha1"));
+        assert!(!is_checksum_file("dev/kingtux/tms-1.0.0.jar"));
+    }
+    #[test]
+    fn test_download_hasher() {
+        assert!(download_hasher(ChecksumPolicy::Off, "dev/kingtux/tms-1.0.0.jar").is_none());
+        assert!(
+            download_hasher(ChecksumPolicy::Strict, "dev/kingtux/tms-1.0.0.jar.sha1").is_none()
+        );
+        assert!(download_hasher(ChecksumPolicy::Warn, "dev/kingtux/tms-1.0.0.jar").is_some());
+    }
+    #[test]
+    fn test_policy_allows() {
+        let url = "dev/kingtux/tms-1.0.0.jar";
+        let mismatch = ChecksumVerification::Mismatch {
+            algorithm: "sha256",
+            expected: "a".to_owned(),
+            actual: "b".to_owned(),
+        };
+        assert_eq!(ChecksumPolicy::default(), ChecksumPolicy::Warn);
+        assert!(!ChecksumPolicy::Warn.allows(&mismatch, url));
+        assert!(ChecksumPolicy::Warn.allows(&ChecksumVerification::Missing, url));
+        assert!(!ChecksumPolicy::Strict.allows(&ChecksumVerification::Missing, url));
+        assert!(ChecksumPolicy::Off.allows(&mismatch, url));
+    }
+}
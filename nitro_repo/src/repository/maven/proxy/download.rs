@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use ahash::{HashMap, HashMapExt};
+use axum::{body::Body, response::Response};
+use bytes::Bytes;
+use futures::StreamExt;
+use http::{HeaderMap, StatusCode};
+use nr_core::storage::{FileHashes, StoragePath};
+use nr_storage::FileHasher;
+use parking_lot::Mutex;
+use tempfile::NamedTempFile;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot, watch},
+};
+use tracing::debug;
+
+use super::MavenError;
+use crate::error::OtherInternalError;
+/// Downloads from the upstream that are currently running. Keyed by the path.
+///
+/// Used so concurrent requests for the same uncached path only trigger one upstream download.
+#[derive(Debug, Default)]
+pub struct InFlightDownloads {
+    downloads: Arc<Mutex<HashMap<String, watch::Receiver<()>>>>,
+}
+pub enum InFlight {
+    /// No download was running. The download is tracked until the guard is dropped
+    Leader(InFlightGuard),
+    /// Another request is already downloading the path
+    Follower(InFlightDownload),
+}
+impl InFlightDownloads {
+    pub fn start(&self, path: &StoragePath) -> InFlight {
+        let key = path.to_string();
+        let mut downloads = self.downloads.lock();
+        if let Some(download) = downloads.get(&key) {
+            return InFlight::Follower(InFlightDownload(download.clone()));
+        }
+        let (sender, receiver) = watch::channel(());
+        downloads.insert(key.clone(), receiver);
+        InFlight::Leader(InFlightGuard {
+            downloads: self.downloads.clone(),
+            key,
+            _sender: sender,
+        })
+    }
+}
+/// Removes the download once the leader is done. Dropping the sender wakes up all followers
+#[derive(Debug)]
+pub struct InFlightGuard {
+    downloads: Arc<Mutex<HashMap<String, watch::Receiver<()>>>>,
+    key: String,
+    _sender: watch::Sender<()>,
+}
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.downloads.lock().remove(&self.key);
+    }
+}
+#[derive(Debug)]
+pub struct InFlightDownload(watch::Receiver<()>);
+impl InFlightDownload {
+    /// Waits for the leader to finish. The result of the download should be read from storage
+    pub async fn wait(mut self) {
+        // Errors once the sender is dropped. Which is the only thing we are waiting for
+        let _ = self.0.changed().await;
+    }
+}
+/// Sends the upstream body to the client while it is being written to storage
+#[derive(Debug)]
+pub struct DownloadTee {
+    started: Option<oneshot::Sender<HeaderMap>>,
+    body: mpsc::Sender<Result<Bytes, std::io::Error>>,
+}
+/// The client side of a [DownloadTee]
+#[derive(Debug)]
+pub struct TeeReceiver {
+    pub started: oneshot::Receiver<HeaderMap>,
+    body: mpsc::Receiver<Result<Bytes, std::io::Error>>,
+}
+impl DownloadTee {
+    pub fn new() -> (Self, TeeReceiver) {
+        let (started_sender, started) = oneshot::channel();
+        let (body_sender, body) = mpsc::channel(16);
+        (
+            Self {
+                started: Some(started_sender),
+                body: body_sender,
+            },
+            TeeReceiver { started, body },
+        )
+    }
+    /// Tells the client the download has started and which headers to respond with
+    pub fn start(&mut self, headers: HeaderMap) {
+        if let Some(started) = self.started.take() {
+            let _ = started.send(headers);
+        }
+    }
+    /// Forwards a chunk to the client. If the client has disconnected the chunk is dropped
+    async fn send(&self, chunk: Result<Bytes, std::io::Error>) {
+        if self.body.send(chunk).await.is_err() {
+            debug!("Client disconnected. Continuing download");
+        }
+    }
+}
+impl TeeReceiver {
+    pub fn into_response(self, headers: HeaderMap) -> Result<Response, MavenError> {
+        let stream = futures::stream::unfold(self.body, |mut body| async move {
+            body.recv().await.map(|chunk| (chunk, body))
+        });
+        let mut builder = Response::builder().status(StatusCode::OK);
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .body(Body::from_stream(stream))
+            .map_err(OtherInternalError::new)?;
+        Ok(response)
+    }
+}
+/// A file downloaded from the upstream
+#[derive(Debug)]
+pub struct DownloadedFile {
+    pub file: NamedTempFile,
+    /// The hashes of the file. None if no hasher was provided
+    pub hashes: Option<FileHashes>,
+}
+/// Streams the response body into a temporary file. Each chunk is also sent to the tee and the hasher if they are provided.
+///
+/// If the download fails the client receives an error so it does not treat the partial body as complete.
+pub async fn download_to_temp_file(
+    response: reqwest::Response,
+    tee: Option<&DownloadTee>,
+    mut hasher: Option<FileHasher>,
+) -> Result<DownloadedFile, MavenError> {
+    let temp_file = NamedTempFile::new()?;
+    let mut file = tokio::fs::File::from_std(temp_file.reopen()?);
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(ok) => ok,
+            Err(err) => {
+                if let Some(tee) = tee {
+                    tee.send(Err(std::io::Error::other(err.to_string()))).await;
+                }
+                return Err(err.into());
+            }
+        };
+        if let Err(err) = file.write_all(&chunk).await {
+            if let Some(tee) = tee {
+                tee.send(Err(std::io::Error::other(err.to_string()))).await;
+            }
+            return Err(err.into());
+        }
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        if let Some(tee) = tee {
+            tee.send(Ok(chunk)).await;
+        }
+    }
+    file.flush().await?;
+    Ok(DownloadedFile {
+        file: temp_file,
+        hashes: hasher.map(FileHasher::finalize),
+    })
+}
@@ -0,0 +1,821 @@
+use std::{
+    ops::Deref,
+    sync::{
+        Arc,
+        atomic::{self, AtomicBool},
+    },
+};
+
+use axum::{body::Body, response::Response};
+use bytes::Bytes;
+use http::{
+    HeaderMap, HeaderName, StatusCode,
+    header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+};
+use maven_rs::pom::Pom;
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::{
+        Visibility,
+        config::{
+            RepositoryConfigType as _, get_repository_config_or_default,
+            project::{ProjectConfig, ProjectConfigType},
+            repository_page::RepositoryPageType,
+        },
+        proxy_url::{InvalidProxyHeader, ProxyCredentials, ProxyRoute, ProxyURL},
+    },
+    storage::StoragePath,
+};
+use nr_storage::{DynStorage, FileContent, FileType, Storage, StorageFile, StorageFileMeta};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    app::NitroRepo,
+    error::OtherInternalError,
+    repository::{
+        Repository,
+        utils::{RepositoryExt, http_client},
+    },
+};
+
+use super::{
+    MavenError, MavenRepositoryConfig, MavenRepositoryConfigType, REPOSITORY_TYPE_ID, RepoResponse,
+    RepositoryRequest, repo_type::RepositoryFactoryError, utils::MavenRepositoryExt,
+};
+mod cache;
+mod checksum;
+mod download;
+mod routing;
+pub use cache::*;
+pub use checksum::*;
+pub use download::*;
+pub use routing::*;
+/// The result of requesting a file from the upstream routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpstreamResponse {
+    /// The file was downloaded and saved to storage
+    Saved,
+    /// The upstream confirmed the cached file is still valid
+    NotModified,
+    /// No route had the file
+    NotFound,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MavenProxyConfig {
+    pub routes: Vec<MavenProxyRepositoryRoute>,
+    #[serde(default)]
+    pub cache: MavenProxyCacheConfig,
+    #[serde(default)]
+    pub route_affinity: RouteAffinityConfig,
+    /// Verify downloaded files against the upstream `.sha256`/`.sha1` files
+    #[serde(default)]
+    pub checksum_policy: ChecksumPolicy,
+}
+impl MavenProxyConfig {
+    pub fn sort(&mut self) {
+        self.routes.sort_by(|a, b| match (a.priority, b.priority) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+    }
+    pub fn validate(&self) -> Result<(), InvalidProxyHeader> {
+        ProxyCredentials::validate_routes(&self.routes)
+    }
+    pub fn redact_secrets(&mut self) {
+        ProxyCredentials::redact_routes(&mut self.routes);
+    }
+    pub fn restore_secrets(&mut self, old: &MavenProxyConfig) {
+        ProxyCredentials::restore_redacted_routes(&mut self.routes, &old.routes);
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MavenProxyRepositoryRoute {
+    pub url: ProxyURL,
+    pub name: Option<String>,
+    /// If Null then it will be the lowest priority
+    pub priority: Option<i32>,
+    /// Credentials and headers sent to the upstream
+    #[serde(default)]
+    pub credentials: ProxyCredentials,
+    /// If not empty only paths matching one of these patterns are sent to this route. `*` matches anything
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Paths matching one of these patterns are never sent to this route
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+impl ProxyRoute for MavenProxyRepositoryRoute {
+    fn url(&self) -> &ProxyURL {
+        &self.url
+    }
+    fn credentials(&self) -> &ProxyCredentials {
+        &self.credentials
+    }
+    fn credentials_mut(&mut self) -> &mut ProxyCredentials {
+        &mut self.credentials
+    }
+}
+impl MavenProxyRepositoryRoute {
+    /// Checks the include and exclude patterns
+    pub fn allows_path(&self, path: &StoragePath) -> bool {
+        let path = path.to_string();
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| path_matches_pattern(pattern, &path))
+        {
+            return false;
+        }
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| path_matches_pattern(pattern, &path))
+    }
+    /// Creates a GET request with the route's credentials
+    pub fn get(
+        &self,
+        client: &reqwest::Client,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        self.with_credentials(client.get(url))
+    }
+    /// Creates a HEAD request with the route's credentials
+    pub fn head(
+        &self,
+        client: &reqwest::Client,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        self.with_credentials(client.head(url))
+    }
+    fn with_credentials(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.credentials.header_map() {
+            Ok(headers) => request.headers(headers),
+            Err(err) => {
+                warn!(?err, route = ?self.name, "Invalid credentials for route");
+                request
+            }
+        }
+    }
+}
+/// Headers from an upstream HEAD response that are returned to the client
+static PROXIED_HEAD_HEADERS: &[HeaderName] = &[CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED];
+/// The headers from an upstream response that are returned to the client
+fn proxied_headers(headers: &HeaderMap) -> HeaderMap {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            PROXIED_HEAD_HEADERS.contains(name) || name.as_str().starts_with("x-checksum")
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+fn project_download_files(pom: &Pom) -> Result<Vec<String>, MavenError> {
+    let version = pom
+        .get_version()
+        .ok_or(MavenError::MissingFromPom("version"))?;
+    Ok(vec![
+        format!("{}-{}.jar", pom.artifact_id, version),
+        format!("{}-{}-sources.jar", pom.artifact_id, version),
+        format!("{}-{}-javadoc.jar", pom.artifact_id, version),
+    ])
+}
+#[derive(Debug)]
+pub struct MavenProxyInner {
+    pub storage: DynStorage,
+    pub site: NitroRepo,
+    pub id: Uuid,
+    pub name: String,
+    pub visibility: RwLock<Visibility>,
+    pub active: AtomicBool,
+    pub project: RwLock<ProjectConfig>,
+    pub config: RwLock<MavenProxyConfig>,
+    pub negative_cache: NegativeCache,
+    pub route_affinity: RouteAffinityCache,
+    pub downloads: InFlightDownloads,
+}
+#[derive(Debug, Clone)]
+pub struct MavenProxy(Arc<MavenProxyInner>);
+impl Deref for MavenProxy {
+    type Target = MavenProxyInner;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl MavenProxy {
+    pub async fn load(
+        repository: DBRepository,
+        storage: DynStorage,
+        site: NitroRepo,
+        proxy_config: MavenProxyConfig,
+    ) -> Result<Self, RepositoryFactoryError> {
+        let project_config_db =
+            get_repository_config_or_default::<ProjectConfigType, ProjectConfig>(
+                repository.id,
+                site.as_ref(),
+            )
+            .await?;
+        let inner = MavenProxyInner {
+            id: repository.id,
+            name: repository.name.into(),
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            config: RwLock::new(proxy_config),
+            negative_cache: NegativeCache::default(),
+            route_affinity: RouteAffinityCache::default(),
+            downloads: InFlightDownloads::default(),
+            project: RwLock::new(project_config_db.value.0),
+            storage,
+            site,
+        };
+        Ok(Self(Arc::new(inner)))
+    }
+    async fn save_bytes(
+        &self,
+        bytes: Bytes,
+        to: &StoragePath,
+    ) -> Result<(), nr_storage::StorageError> {
+        self.storage
+            .save_file(self.id, FileContent::Bytes(bytes), to)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn proxy_project_download(
+        &self,
+        path: StoragePath,
+        proxy_config: MavenProxyRepositoryRoute,
+        pom: Bytes,
+    ) -> Result<(), MavenError> {
+        let pom = self.parse_pom(pom.to_vec())?;
+        let version_dir = path.parent();
+        let http_client = http_client();
+        let checksum_policy = self.config.read().checksum_policy;
+
+        for file in project_download_files(&pom)? {
+            debug!(?file, "Downloading file");
+            let mut path = version_dir.clone();
+            path.push_mut(&file);
+            let url = format!("{}/{}", proxy_config.url, path);
+            match proxy_config.get(&http_client, &url).send().await {
+                Ok(ok) => {
+                    if ok.status().is_success() {
+                        let hasher = download_hasher(checksum_policy, &url);
+                        let download = download_to_temp_file(ok, None, hasher).await?;
+                        let content = FileContent::Path(download.file.path().to_path_buf());
+                        let verification = verify_download(
+                            &http_client,
+                            &proxy_config,
+                            &url,
+                            download.hashes.as_ref(),
+                        )
+                        .await;
+                        if !checksum_policy.allows(&verification, &url) {
+                            continue;
+                        }
+                        self.storage.save_file(self.id, content, &path).await?;
+                        self.save_verified_checksum(&path, &verification).await?;
+                    } else {
+                        warn!(?url, ?file, ?ok, "Failed to download file");
+                    }
+                }
+                Err(err) => {
+                    warn!(?url, ?file, ?err, "Failed to download file");
+                }
+            }
+        }
+        // TODO: Trigger project indexing
+        Ok(())
+    }
+    #[instrument(skip(self), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn get_from_proxy(
+        &self,
+        path: StoragePath,
+    ) -> Result<Option<StorageFile>, MavenError> {
+        if self.negative_cache.contains(&path) {
+            debug!(?path, "Path was recently not found upstream");
+            return Ok(None);
+        }
+        let _guard = match self.downloads.start(&path) {
+            InFlight::Leader(guard) => guard,
+            InFlight::Follower(download) => {
+                debug!(?path, "Waiting for in flight download");
+                download.wait().await;
+                return Ok(self.storage.open_file(self.id, &path).await?);
+            }
+        };
+        match self.request_from_upstream(&path, None, None).await? {
+            UpstreamResponse::Saved => Ok(self.storage.open_file(self.id, &path).await?),
+            UpstreamResponse::NotModified => Ok(None),
+            UpstreamResponse::NotFound => {
+                self.remember_not_found(&path);
+                Ok(None)
+            }
+        }
+    }
+    /// Streams the file from the upstream to the client while it is being saved to storage.
+    ///
+    /// With [ChecksumPolicy::Strict] the file has to be verified before it is served. So it is downloaded first.
+    #[instrument(skip(self), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn stream_from_proxy(&self, path: StoragePath) -> Result<RepoResponse, MavenError> {
+        if self.config.read().checksum_policy == ChecksumPolicy::Strict {
+            return Ok(self.get_from_proxy(path).await?.into());
+        }
+        if self.negative_cache.contains(&path) {
+            debug!(?path, "Path was recently not found upstream");
+            return Ok(Option::<StorageFile>::None.into());
+        }
+        let guard = match self.downloads.start(&path) {
+            InFlight::Leader(guard) => guard,
+            InFlight::Follower(download) => {
+                debug!(?path, "Waiting for in flight download");
+                download.wait().await;
+                return Ok(self.storage.open_file(self.id, &path).await?.into());
+            }
+        };
+        let (tee, mut receiver) = DownloadTee::new();
+        let this = self.clone();
+        let download_path = path.clone();
+        // Spawned so the download is finished even if the client disconnects
+        let download = tokio::spawn(async move {
+            let _guard = guard;
+            this.request_from_upstream(&download_path, None, Some(tee))
+                .await
+        });
+        if let Ok(headers) = (&mut receiver.started).await {
+            return Ok(RepoResponse::Other(receiver.into_response(headers)?));
+        }
+        // The download ended without sending anything to the client
+        match download.await.map_err(OtherInternalError::new)?? {
+            UpstreamResponse::NotFound => {
+                self.remember_not_found(&path);
+                Ok(Option::<StorageFile>::None.into())
+            }
+            _ => Ok(self.storage.open_file(self.id, &path).await?.into()),
+        }
+    }
+    /// The routes that are allowed to serve the path.
+    ///
+    /// If a route recently served the same groupId it is moved to the front.
+    async fn routes_for_path(&self, path: &StoragePath) -> Vec<MavenProxyRepositoryRoute> {
+        let (mut routes, affinity_config) = {
+            let config = self.config.read();
+            let routes: Vec<_> = config
+                .routes
+                .iter()
+                .filter(|route| route.allows_path(path))
+                .cloned()
+                .collect();
+            (routes, config.route_affinity.clone())
+        };
+        if !affinity_config.enabled {
+            return routes;
+        }
+        let Some(group) = group_directory(path) else {
+            return routes;
+        };
+        let preferred = match self.route_affinity.get(&group) {
+            Some(route) => Some(route),
+            None if affinity_config.persist => self
+                .storage
+                .get_repository_meta(self.id, &StoragePath::from(group.as_str()))
+                .await
+                .ok()
+                .flatten()
+                .and_then(|meta| meta.get(ROUTE_AFFINITY_META_KEY).map(str::to_owned))
+                .and_then(|route| ProxyURL::try_from(route).ok()),
+            None => None,
+        };
+        if let Some(preferred) = preferred {
+            if let Some(index) = routes.iter().position(|route| route.url == preferred) {
+                debug!(?group, ?preferred, "Using route affinity");
+                let route = routes.remove(index);
+                routes.insert(0, route);
+            }
+        }
+        routes
+    }
+    /// Records that the route served a file for the path's groupId
+    async fn remember_route(&self, path: &StoragePath, route: &MavenProxyRepositoryRoute) {
+        let affinity_config = self.config.read().route_affinity.clone();
+        if !affinity_config.enabled {
+            return;
+        }
+        let Some(group) = group_directory(path) else {
+            return;
+        };
+        if self.route_affinity.get(&group).as_ref() == Some(&route.url) {
+            return;
+        }
+        self.route_affinity.insert(
+            group.clone(),
+            route.url.clone(),
+            affinity_config.max_entries,
+        );
+        if !affinity_config.persist {
+            return;
+        }
+        let group_path = StoragePath::from(group.as_str());
+        let result = async {
+            let mut meta = self
+                .storage
+                .get_repository_meta(self.id, &group_path)
+                .await?
+                .unwrap_or_default();
+            meta.insert(ROUTE_AFFINITY_META_KEY, route.url.to_string());
+            self.storage
+                .put_repository_meta(self.id, &group_path, meta)
+                .await
+        }
+        .await;
+        if let Err(err) = result {
+            warn!(?err, ?group, "Failed to persist route affinity");
+        }
+    }
+    fn remember_not_found(&self, path: &StoragePath) {
+        if let Some(ttl) = self.config.read().cache.not_found_ttl() {
+            self.negative_cache.insert(path, ttl);
+        }
+    }
+    /// Sends a HEAD request to each route and returns the headers of the first success.
+    ///
+    /// The file is not downloaded.
+    #[instrument(skip(self), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn head_from_proxy(
+        &self,
+        path: &StoragePath,
+    ) -> Result<Option<Response>, MavenError> {
+        if self.negative_cache.contains(path) {
+            debug!(?path, "Path was recently not found upstream");
+            return Ok(None);
+        }
+        let http_client = http_client();
+        for route in self.routes_for_path(path).await {
+            let url = match route.url.add_storage_path(path.clone()) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    error!(?err, ?route.url, "Failed to parse URL");
+                    continue;
+                }
+            };
+            let response = match route.head(&http_client, url.clone()).send().await {
+                Ok(ok) => ok,
+                Err(err) => {
+                    error!(?err, %url, "Failed to send request");
+                    continue;
+                }
+            };
+            if !response.status().is_success() {
+                debug!(?response, %url, "Upstream does not have file");
+                continue;
+            }
+            self.remember_route(path, &route).await;
+            let mut builder = Response::builder().status(StatusCode::OK);
+            for (name, value) in &proxied_headers(response.headers()) {
+                builder = builder.header(name, value);
+            }
+            let response = builder
+                .body(Body::empty())
+                .map_err(OtherInternalError::new)?;
+            return Ok(Some(response));
+        }
+        self.remember_not_found(path);
+        Ok(None)
+    }
+    /// Requests the file from each route until one responds successfully.
+    ///
+    /// If `cached` is provided a conditional request is made.
+    /// If `tee` is provided the body is sent to it while the file is being downloaded.
+    #[instrument(skip(self, tee), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    async fn request_from_upstream(
+        &self,
+        path: &StoragePath,
+        cached: Option<&ProxyCacheMeta>,
+        mut tee: Option<DownloadTee>,
+    ) -> Result<UpstreamResponse, MavenError> {
+        let http_client = http_client();
+        for route in self.routes_for_path(path).await {
+            let mut path_as_string = path.to_string();
+            if path_as_string.starts_with("/") {
+                path_as_string = path_as_string[1..].into();
+            }
+            let url_string = format!("{}/{}", route.url, path_as_string);
+            debug!(?url_string, "Proxying request");
+            let url = match url::Url::parse(&url_string) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    error!(?err, ?url_string, "Failed to parse URL");
+                    continue;
+                }
+            };
+            let mut request = route.get(&http_client, url);
+            if let Some(cached) = cached {
+                request = request.headers(cached.conditional_headers());
+            }
+            let response = match request.send().await {
+                Ok(ok) => ok,
+                Err(err) => {
+                    error!(?err, ?url_string, "Failed to send request");
+                    continue;
+                }
+            };
+            if response.status() == StatusCode::NOT_MODIFIED {
+                debug!(?url_string, "Upstream file has not been modified");
+                self.remember_route(path, &route).await;
+                return Ok(UpstreamResponse::NotModified);
+            } else if response.status().is_success() {
+                let cache_meta = ProxyCacheMeta::from_response_headers(response.headers());
+                if let Some(tee) = tee.as_mut() {
+                    tee.start(proxied_headers(response.headers()));
+                }
+                let checksum_policy = self.config.read().checksum_policy;
+                let hasher = download_hasher(checksum_policy, &url_string);
+                let download = download_to_temp_file(response, tee.as_ref(), hasher).await?;
+                // Closes the client's body. The rest does not need to wait on the client
+                drop(tee.take());
+                let content = FileContent::Path(download.file.path().to_path_buf());
+                let verification =
+                    verify_download(&http_client, &route, &url_string, download.hashes.as_ref())
+                        .await;
+                if !checksum_policy.allows(&verification, &url_string) {
+                    continue;
+                }
+                if path_as_string.ends_with(".pom") {
+                    let self_clone = self.clone();
+                    let path = path.clone();
+                    let pom = Bytes::from(tokio::fs::read(download.file.path()).await?);
+                    let route = route.clone();
+                    tokio::spawn(async move {
+                        if let Err(error) =
+                            self_clone.proxy_project_download(path, route, pom).await
+                        {
+                            error!(?error, "Failed to download project files");
+                        };
+                    });
+                }
+                self.storage.save_file(self.id, content, path).await?;
+                self.negative_cache.remove(path);
+                self.update_cache_meta(path, |meta| *meta = cache_meta)
+                    .await?;
+                self.save_verified_checksum(path, &verification).await?;
+                self.remember_route(path, &route).await;
+                return Ok(UpstreamResponse::Saved);
+            } else {
+                warn!(?response, ?url_string, "Failed to proxy request");
+            }
+        }
+        Ok(UpstreamResponse::NotFound)
+    }
+    /// Saves the checksum that was used to verify the file next to it. So clients requesting the checksum do not go to the upstream
+    async fn save_verified_checksum(
+        &self,
+        path: &StoragePath,
+        verification: &ChecksumVerification,
+    ) -> Result<(), MavenError> {
+        let ChecksumVerification::Valid {
+            algorithm,
+            checksum,
+        } = verification
+        else {
+            return Ok(());
+        };
+        let checksum_path = StoragePath::from(format!("{}.{}", path, algorithm));
+        self.save_bytes(Bytes::from(checksum.clone()), &checksum_path)
+            .await?;
+        Ok(())
+    }
+    async fn update_cache_meta(
+        &self,
+        path: &StoragePath,
+        update: impl FnOnce(&mut ProxyCacheMeta),
+    ) -> Result<(), MavenError> {
+        let mut repository_meta = self
+            .storage
+            .get_repository_meta(self.id, path)
+            .await?
+            .unwrap_or_default();
+        let mut cache_meta = ProxyCacheMeta::from(&repository_meta);
+        update(&mut cache_meta);
+        cache_meta.write_to(&mut repository_meta);
+        self.storage
+            .put_repository_meta(self.id, path, repository_meta)
+            .await?;
+        Ok(())
+    }
+    /// If the cached file is older than the configured TTL it is revalidated with the upstream.
+    ///
+    /// The cached file is kept if the upstream can not be reached.
+    #[instrument(skip(self, file), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn revalidate_if_stale(
+        &self,
+        path: &StoragePath,
+        file: StorageFile,
+    ) -> Result<Option<StorageFile>, MavenError> {
+        let StorageFile::File { meta, .. } = &file else {
+            return Ok(Some(file));
+        };
+        let Some(ttl) = self.config.read().cache.ttl_for(path) else {
+            return Ok(Some(file));
+        };
+        let cache_meta = self
+            .storage
+            .get_repository_meta(self.id, path)
+            .await?
+            .map(|meta| ProxyCacheMeta::from(&meta))
+            .unwrap_or_default();
+        if !cache_meta.is_stale(ttl, *meta.modified()) {
+            return Ok(Some(file));
+        }
+        debug!(?path, "Cached file is stale. Revalidating");
+        drop(file);
+        let _guard = match self.downloads.start(path) {
+            InFlight::Leader(guard) => guard,
+            InFlight::Follower(download) => {
+                debug!(?path, "Waiting for in flight revalidation");
+                download.wait().await;
+                return Ok(self.storage.open_file(self.id, path).await?);
+            }
+        };
+        match self
+            .request_from_upstream(path, Some(&cache_meta), None)
+            .await
+        {
+            Ok(UpstreamResponse::Saved) => {}
+            Ok(UpstreamResponse::NotModified) => {
+                self.update_cache_meta(path, ProxyCacheMeta::mark_checked)
+                    .await?;
+            }
+            Ok(UpstreamResponse::NotFound) => {
+                warn!(
+                    ?path,
+                    "Upstream did not return the file. Serving cached file"
+                );
+            }
+            Err(err) => {
+                warn!(
+                    ?err,
+                    ?path,
+                    "Failed to revalidate file. Serving cached file"
+                );
+            }
+        }
+        Ok(self.storage.open_file(self.id, path).await?)
+    }
+}
+
+impl Repository for MavenProxy {
+    type Error = MavenError;
+    fn get_storage(&self) -> nr_storage::DynStorage {
+        self.0.storage.clone()
+    }
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn get_type(&self) -> &'static str {
+        REPOSITORY_TYPE_ID
+    }
+    fn full_type(&self) -> &'static str {
+        "maven/proxy"
+    }
+    fn config_types(&self) -> Vec<&str> {
+        vec![
+            RepositoryPageType::get_type_static(),
+            ProjectConfigType::get_type_static(),
+            MavenRepositoryConfigType::get_type_static(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    fn is_active(&self) -> bool {
+        self.0.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "maven/proxy"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let project_config_db =
+            get_repository_config_or_default::<ProjectConfigType, ProjectConfig>(
+                self.id,
+                self.site.as_ref(),
+            )
+            .await?;
+        let Some(maven_config_db) = DBRepositoryConfig::<MavenRepositoryConfig>::get_config(
+            self.id,
+            MavenRepositoryConfigType::get_type_static(),
+            self.site.as_ref(),
+        )
+        .await?
+        else {
+            return Err(RepositoryFactoryError::MissingConfig(
+                MavenRepositoryConfigType::get_type_static(),
+            ));
+        };
+        {
+            let mut project_config = self.project.write();
+            *project_config = project_config_db.value.0;
+        }
+        self.negative_cache.clear();
+        self.route_affinity.clear();
+        {
+            match maven_config_db.value.0 {
+                MavenRepositoryConfig::Proxy(proxy_config) => {
+                    let mut maven_config = self.config.write();
+                    *maven_config = proxy_config;
+                }
+                _ => {
+                    return Err(RepositoryFactoryError::InvalidConfig(
+                        MavenRepositoryConfigType::get_type_static(),
+                        "Expected Proxy Config".into(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        RepositoryRequest {
+            parts,
+            path,
+            authentication,
+            ..
+        }: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
+            return Ok(err);
+        }
+        let visibility = self.visibility();
+        let Some(file) = self.0.storage.open_file(self.id, &path).await? else {
+            debug!(?path, "File not found in storage. Proxying request");
+            return match self.stream_from_proxy(path).await {
+                Ok(ok) => Ok(ok),
+                Err(err) => {
+                    warn!(?err, "Failed to proxy request");
+                    Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body(format!("Failed to proxy request: {}", err).into())
+                        .into())
+                }
+            };
+        };
+        let file = self.revalidate_if_stale(&path, file).await?;
+        return self.indexing_check_option(file, &authentication).await;
+    }
+    async fn handle_head(
+        &self,
+        RepositoryRequest {
+            parts,
+            path,
+            authentication,
+            ..
+        }: RepositoryRequest,
+    ) -> Result<RepoResponse, MavenError> {
+        let visibility = self.visibility();
+        if let Some(err) = self.check_read(&authentication, "Basic").await? {
+            return Ok(err);
+        }
+        if let Some(file) = self.storage.get_file_information(self.id, &path).await? {
+            return self.indexing_check_file(file, &authentication).await;
+        }
+        debug!(?path, "File not found in storage. Proxying HEAD request");
+        match self.head_from_proxy(&path).await {
+            Ok(Some(response)) => Ok(RepoResponse::Other(response)),
+            Ok(None) => Ok(RepoResponse::from(
+                Option::<StorageFileMeta<FileType>>::None,
+            )),
+            Err(err) => {
+                warn!(?err, "Failed to proxy HEAD request");
+                Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .body(format!("Failed to proxy request: {}", err).into())
+                    .into())
+            }
+        }
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+}
+impl MavenRepositoryExt for MavenProxy {}
+impl RepositoryExt for MavenProxy {}
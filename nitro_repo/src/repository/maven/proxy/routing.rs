@@ -0,0 +1,169 @@
+use std::time::Instant;
+
+use ahash::{HashMap, HashMapExt};
+use nr_core::{repository::proxy_url::ProxyURL, storage::StoragePath};
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::repository::maven::metadata::MAVEN_METADATA_FILE;
+/// The key used to persist the route inside of the [nr_storage::meta::RepositoryMeta] of the groupId directory
+pub const ROUTE_AFFINITY_META_KEY: &str = "proxy.route";
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RouteAffinityConfig {
+    /// Remember which route served a groupId and try that route first next time
+    pub enabled: bool,
+    /// The max number of groupIds remembered in memory
+    pub max_entries: usize,
+    /// Save the route to the groupId directory so it is remembered after a restart
+    pub persist: bool,
+}
+impl Default for RouteAffinityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1000,
+            persist: false,
+        }
+    }
+}
+#[derive(Debug)]
+struct RouteAffinityEntry {
+    route: ProxyURL,
+    last_used: Instant,
+}
+/// A bounded in memory map of groupId directories to the route that last served them.
+///
+/// When full the least recently used entry is removed.
+#[derive(Debug, Default)]
+pub struct RouteAffinityCache {
+    entries: Mutex<HashMap<String, RouteAffinityEntry>>,
+}
+impl RouteAffinityCache {
+    pub fn get(&self, group: &str) -> Option<ProxyURL> {
+        let mut entries = self.entries.lock();
+        let entry = entries.get_mut(group)?;
+        entry.last_used = Instant::now();
+        Some(entry.route.clone())
+    }
+    pub fn insert(&self, group: String, route: ProxyURL, max_entries: usize) {
+        let mut entries = self.entries.lock();
+        if !entries.contains_key(&group) && entries.len() >= max_entries {
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(group, _)| group.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            group,
+            RouteAffinityEntry {
+                route,
+                last_used: Instant::now(),
+            },
+        );
+    }
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+/// Gets the groupId directory from a Maven path.
+///
+/// `dev/kingtux/tms/1.0.0/tms-1.0.0.pom` and `dev/kingtux/tms/maven-metadata.xml` both return `dev/kingtux/`
+pub fn group_directory(path: &StoragePath) -> Option<String> {
+    let path = path.to_string();
+    let components: Vec<&str> = path.split('/').filter(|v| !v.is_empty()).collect();
+    let file_name = components.last()?;
+    let is_artifact_metadata = file_name.starts_with(MAVEN_METADATA_FILE)
+        && components
+            .len()
+            .checked_sub(2)
+            .and_then(|index| components.get(index))
+            .is_some_and(|parent| !parent.ends_with("-SNAPSHOT"));
+    // Artifact level metadata is directly inside of the artifact directory. Everything else is inside of a version directory
+    let artifact_and_below = if is_artifact_metadata { 2 } else { 3 };
+    let group_length = components.len().checked_sub(artifact_and_below)?;
+    if group_length == 0 {
+        return None;
+    }
+    let mut group = components[..group_length].join("/");
+    group.push('/');
+    Some(group)
+}
+/// Checks if the path matches the pattern. `*` matches any number of characters including `/`.
+///
+/// Leading slashes are ignored.
+pub fn path_matches_pattern(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches('/');
+    let path = path.trim_start_matches('/');
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return path.is_empty();
+    };
+    let Some(mut remaining) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcards. Must be an exact match
+        return remaining.is_empty();
+    };
+    for part in middle {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_directory() {
+        let cases = [
+            ("dev/kingtux/tms/1.0.0/tms-1.0.0.pom", Some("dev/kingtux/")),
+            ("dev/kingtux/tms/maven-metadata.xml", Some("dev/kingtux/")),
+            (
+                "dev/kingtux/tms/maven-metadata.xml.sha1",
+                Some("dev/kingtux/"),
+            ),
+            (
+                "dev/kingtux/tms/1.0.0-SNAPSHOT/maven-metadata.xml",
+                Some("dev/kingtux/"),
+            ),
+            ("tms/1.0.0/tms-1.0.0.pom", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                group_directory(&StoragePath::from(path)).as_deref(),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+    #[test]
+    fn test_path_matches_pattern() {
+        assert!(path_matches_pattern(
+            "dev/kingtux/*",
+            "/dev/kingtux/tms/1.0.0/tms-1.0.0.pom"
+        ));
+        assert!(path_matches_pattern(
+            "*/tms/*",
+            "dev/kingtux/tms/1.0.0/a.jar"
+        ));
+        assert!(path_matches_pattern("*.pom", "dev/kingtux/tms/1.0.0/a.pom"));
+        assert!(!path_matches_pattern(
+            "*.pom",
+            "dev/kingtux/tms/1.0.0/a.jar"
+        ));
+        assert!(!path_matches_pattern("dev/kingtux/*", "com/google/a.jar"));
+        assert!(path_matches_pattern("dev/kingtux", "dev/kingtux"));
+        assert!(!path_matches_pattern("dev/kingtux", "dev/kingtux/a"));
+    }
+}
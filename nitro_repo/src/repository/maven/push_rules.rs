@@ -0,0 +1,138 @@
+use http::StatusCode;
+use nr_core::{repository::Policy, storage::StoragePath};
+use thiserror::Error;
+
+use super::{RepoResponse, get_release_type, metadata::MAVEN_METADATA_FILE};
+/// Why an upload was rejected by the [super::MavenPushRules].
+///
+/// The message is the response body. Maven prints it to the console
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PushRuleViolation {
+    #[error("This repository only accepts release versions. {0} is a SNAPSHOT")]
+    SnapshotNotAllowed(String),
+    #[error("This repository only accepts SNAPSHOT versions. {0} is a release")]
+    ReleaseNotAllowed(String),
+    #[error("{0} already exists and this repository does not allow overwriting releases")]
+    OverwriteNotAllowed(String),
+    #[error("You must be a member of {0} with write access to push to it")]
+    NotProjectMember(String),
+}
+impl PushRuleViolation {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            PushRuleViolation::SnapshotNotAllowed(_) | PushRuleViolation::ReleaseNotAllowed(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            PushRuleViolation::OverwriteNotAllowed(_) => StatusCode::CONFLICT,
+            PushRuleViolation::NotProjectMember(_) => StatusCode::FORBIDDEN,
+        }
+    }
+}
+impl From<PushRuleViolation> for RepoResponse {
+    fn from(violation: PushRuleViolation) -> Self {
+        RepoResponse::basic_text_response(violation.status_code(), violation.to_string())
+    }
+}
+/// Checks the version against the push policy of the repository
+pub fn check_push_policy(policy: &Policy, version: &str) -> Result<(), PushRuleViolation> {
+    let is_snapshot = get_release_type(version).is_snapshot();
+    match policy {
+        Policy::Release if is_snapshot => {
+            Err(PushRuleViolation::SnapshotNotAllowed(version.to_owned()))
+        }
+        Policy::Snapshot if !is_snapshot => {
+            Err(PushRuleViolation::ReleaseNotAllowed(version.to_owned()))
+        }
+        _ => Ok(()),
+    }
+}
+/// The parts of a path uploaded by a standard Maven deploy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MavenUploadPath {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// None if the file is the `maven-metadata.xml` of the artifact
+    pub version: Option<String>,
+    pub file_name: String,
+}
+impl MavenUploadPath {
+    /// Parses `dev/kingtux/tms/1.0.0/tms-1.0.0.jar` or `dev/kingtux/tms/maven-metadata.xml`
+    pub fn parse(path: &StoragePath) -> Option<Self> {
+        let path = path.to_string();
+        let components: Vec<&str> = path.split('/').filter(|v| !v.is_empty()).collect();
+        let (file_name, parents) = components.split_last()?;
+        let (parent, _) = parents.split_last()?;
+        let is_artifact_metadata =
+            file_name.starts_with(MAVEN_METADATA_FILE) && !get_release_type(parent).is_snapshot();
+        let (version, artifact_and_group) = if is_artifact_metadata {
+            (None, parents)
+        } else {
+            let (version, artifact_and_group) = parents.split_last()?;
+            (Some(version.to_string()), artifact_and_group)
+        };
+        let (artifact_id, group) = artifact_and_group.split_last()?;
+        if group.is_empty() {
+            return None;
+        }
+        Some(Self {
+            group_id: group.join("."),
+            artifact_id: artifact_id.to_string(),
+            version,
+            file_name: file_name.to_string(),
+        })
+    }
+    pub fn project_key(&self) -> String {
+        format!("{}:{}", self.group_id, self.artifact_id)
+    }
+    /// Metadata is rewritten on every deploy so it can always be overwritten
+    pub fn is_metadata(&self) -> bool {
+        self.file_name.starts_with(MAVEN_METADATA_FILE)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_parse_upload_path() {
+        let upload =
+            MavenUploadPath::parse(&StoragePath::from("dev/kingtux/tms/1.0.0/tms-1.0.0.jar"))
+                .unwrap();
+        assert_eq!(upload.project_key(), "dev.kingtux:tms");
+        assert_eq!(upload.version.as_deref(), Some("1.0.0"));
+        assert!(!upload.is_metadata());
+
+        let upload = MavenUploadPath::parse(&StoragePath::from(
+            "dev/kingtux/tms/maven-metadata.xml.sha1",
+        ))
+        .unwrap();
+        assert_eq!(upload.project_key(), "dev.kingtux:tms");
+        assert_eq!(upload.version, None);
+        assert!(upload.is_metadata());
+
+        let upload = MavenUploadPath::parse(&StoragePath::from(
+            "dev/kingtux/tms/1.0.0-SNAPSHOT/maven-metadata.xml",
+        ))
+        .unwrap();
+        assert_eq!(upload.version.as_deref(), Some("1.0.0-SNAPSHOT"));
+
+        assert_eq!(
+            MavenUploadPath::parse(&StoragePath::from("tms/1.0.0/tms-1.0.0.jar")),
+            None
+        );
+    }
+    #[test]
+    fn test_check_push_policy() {
+        assert!(check_push_policy(&Policy::Mixed, "1.0.0-SNAPSHOT").is_ok());
+        assert!(check_push_policy(&Policy::Release, "1.0.0").is_ok());
+        assert_eq!(
+            check_push_policy(&Policy::Release, "1.0.0-SNAPSHOT"),
+            Err(PushRuleViolation::SnapshotNotAllowed(
+                "1.0.0-SNAPSHOT".to_owned()
+            ))
+        );
+        assert_eq!(
+            check_push_policy(&Policy::Snapshot, "1.0.0"),
+            Err(PushRuleViolation::ReleaseNotAllowed("1.0.0".to_owned()))
+        );
+    }
+}
@@ -8,41 +8,23 @@ use nr_core::{
         versions::NewVersionBuilder,
     },
     repository::project::{ReleaseType, VersionData, VersionDataBuilder},
-    storage::{FileTypeCheck, StoragePath},
+    storage::{FileHashes, FileTypeCheck, StoragePath},
     user::permissions::{HasPermissions, RepositoryActions},
+    utils::base64_utils,
 };
 
 use nr_storage::Storage;
 use tracing::{Level, error, event, info, instrument, trace};
 use uuid::Uuid;
 
-use super::{MavenError, RepoResponse, RepositoryAuthentication, RepositoryHandlerError};
-use crate::{error::BadRequestErrors, repository::Repository};
+use super::{MavenError, RepoResponse, RepositoryAuthentication};
+use crate::{error::BadRequestErrors, repository::utils::RepositoryExt};
 
 /// Utilities for Maven Repositories
-pub trait MavenRepositoryExt: Repository + Debug {
-    /// Checks if the user has the correct permissions to read the repository
-    ///
-    /// If not authenticated at all, it will return a `WWW-Authenticate` header with the `Basic` scheme (Maven gets confused otherwise)
-    async fn check_read(
-        &self,
-        authentication: &RepositoryAuthentication,
-    ) -> Result<Option<RepoResponse>, RepositoryHandlerError> {
-        if self.visibility().is_private() {
-            if authentication.is_no_identification() {
-                return Ok(Some(RepoResponse::www_authenticate("Basic")));
-            } else if !(authentication
-                .has_action(RepositoryActions::Read, self.id(), self.site().as_ref())
-                .await?)
-            {
-                return Ok(Some(RepoResponse::forbidden()));
-            }
-        }
-        Ok(None)
-    }
+pub trait MavenRepositoryExt: RepositoryExt + Debug {
     /// Checks if the user has the correct permissions to read the repository
     /// If the repository is hidden and the requested file is a directory, then the user must have the read permission
-    async fn indexing_check<T: FileTypeCheck>(
+    async fn indexing_check_file<T: FileTypeCheck>(
         &self,
         file_response: T,
         authentication: &RepositoryAuthentication,
@@ -63,7 +45,7 @@ pub trait MavenRepositoryExt: Repository + Debug {
         Ok(RepoResponse::from(file_response))
     }
 
-    /// Same as [indexing_check] but for an Option
+    /// Same as [indexing_check_file] but for an Option
     async fn indexing_check_option<T: FileTypeCheck>(
         &self,
         file_response: Option<T>,
@@ -74,7 +56,8 @@ pub trait MavenRepositoryExt: Repository + Debug {
         RepoResponse: From<Option<T>>,
     {
         if let Some(file_response) = file_response {
-            self.indexing_check(file_response, authentication).await
+            self.indexing_check_file(file_response, authentication)
+                .await
         } else {
             Ok(RepoResponse::from(None))
         }
@@ -265,3 +248,16 @@ pub fn pom_to_version_extra_data(pom: Pom) -> Result<VersionData, MavenError> {
         .build()?;
     Ok(extra)
 }
+/// Maven checksum files are hex encoded. [FileHashes] are stored base64 encoded.
+///
+/// Returns None if the algorithm is not supported
+pub fn maven_checksum(hashes: &FileHashes, algorithm: &str) -> Option<String> {
+    let hash = match algorithm {
+        "md5" => hashes.md5.as_ref(),
+        "sha1" => hashes.sha1.as_ref(),
+        "sha256" => hashes.sha2_256.as_ref(),
+        _ => None,
+    }?;
+    let hash = base64_utils::decode(hash).ok()?;
+    Some(hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
@@ -21,9 +21,14 @@ mod staging;
 pub use staging::*;
 mod repo_http;
 pub use repo_http::*;
+pub mod cargo;
 pub mod commands;
+pub mod docker;
+pub mod go;
+pub mod helm;
 pub mod maven;
 pub mod npm;
+pub mod python;
 mod repo_type;
 pub use repo_type::*;
 use uuid::Uuid;
@@ -150,4 +155,9 @@ pub trait Repository: Send + Sync + Clone + Debug {
 pub enum DynRepository {
     Maven(maven::MavenRepository),
     NPM(npm::NPMRegistry),
+    Cargo(cargo::CargoRegistry),
+    Python(python::PythonRepository),
+    Docker(docker::DockerRegistry),
+    Go(go::GoRepository),
+    Helm(helm::HelmRepository),
 }
@@ -0,0 +1,460 @@
+//! `npm audit`
+//!
+//! Advisories are stored in the `advisories` table and are shared by every NPM repository.
+//!
+//! - npm 7+ uses [BULK_ADVISORY_PATH]
+//! - npm 6 and older use [QUICK_AUDIT_PATH]
+use std::io::Read;
+
+use ahash::{HashMap, HashMapExt};
+use flate2::read::GzDecoder;
+use http::{StatusCode, header::CONTENT_ENCODING};
+use nr_core::database::entities::advisories::{AdvisorySeverity, DBAdvisory};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tracing::{debug, instrument, warn};
+
+use super::NPMRegistryError;
+use crate::{
+    app::NitroRepo,
+    error::BadRequestErrors,
+    repository::{RepoResponse, RepositoryRequest, utils::json_response},
+};
+pub const BULK_ADVISORY_PATH: &str = "-/npm/v1/security/advisories/bulk";
+pub const QUICK_AUDIT_PATH: &str = "-/npm/v1/security/audits/quick";
+/// The ecosystem advisories are stored under
+const NPM_ECOSYSTEM: &str = "npm";
+/// The largest audit request that is accepted. After it is decompressed
+const MAX_AUDIT_BODY_SIZE: u64 = 32 * 1024 * 1024;
+/// Checks if the version is inside of a npm range. Such as `>=1.0.0 <1.2.3 || >=2.0.0 <2.0.4`
+///
+/// Ranges that can not be parsed match every version. So a bad advisory is reported instead of hidden
+pub fn npm_range_matches(range: &str, version: &str) -> bool {
+    let Ok(version) = Version::parse(version.trim().trim_start_matches('v')) else {
+        debug!(?version, "Invalid version");
+        return false;
+    };
+    for part in range.split("||") {
+        match npm_comparators_to_req(part) {
+            Some(req) => {
+                if req.matches(&version) {
+                    return true;
+                }
+            }
+            None => {
+                warn!(?range, "Unable to parse advisory range");
+                return true;
+            }
+        }
+    }
+    false
+}
+/// Converts a set of space separated npm comparators into a [VersionReq]
+fn npm_comparators_to_req(comparators: &str) -> Option<VersionReq> {
+    let tokens: Vec<&str> = comparators.split_whitespace().collect();
+    let mut requirements = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = tokens[index];
+        // Hyphen range. `1.0.0 - 2.0.0`
+        if tokens.get(index + 1) == Some(&"-") {
+            let upper = tokens.get(index + 2)?;
+            requirements.push(format!(">={}", token.trim_start_matches('v')));
+            requirements.push(format!("<={}", upper.trim_start_matches('v')));
+            index += 3;
+            continue;
+        }
+        // Operator separated from the version. `>= 1.0.0`
+        let token = if token
+            .chars()
+            .all(|c| matches!(c, '<' | '>' | '=' | '~' | '^'))
+        {
+            index += 1;
+            format!("{}{}", token, tokens.get(index)?)
+        } else {
+            token.to_owned()
+        };
+        index += 1;
+        let operator_length = token
+            .find(|c: char| !matches!(c, '<' | '>' | '=' | '~' | '^'))
+            .unwrap_or(token.len());
+        let (operator, version) = token.split_at(operator_length);
+        let version = version.trim_start_matches('v');
+        if version.is_empty() || version == "*" || version == "x" || version == "X" {
+            continue;
+        }
+        // npm treats a bare version as an exact match. The semver crate treats it as a caret
+        let operator = if operator.is_empty() && !version.contains(['x', 'X', '*']) {
+            "="
+        } else {
+            operator
+        };
+        requirements.push(format!("{}{}", operator, version));
+    }
+    if requirements.is_empty() {
+        return Some(VersionReq::STAR);
+    }
+    VersionReq::parse(&requirements.join(", ")).ok()
+}
+/// npm gzips the body of audit requests
+async fn read_audit_body<T: DeserializeOwned>(
+    request: RepositoryRequest,
+) -> Result<T, NPMRegistryError> {
+    let is_gzip = request
+        .parts
+        .headers
+        .get(CONTENT_ENCODING)
+        .map(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"))
+        .unwrap_or(false);
+    let body = request.body.body_as_bytes().await?;
+    let body = decode_audit_body(&body, is_gzip, MAX_AUDIT_BODY_SIZE)?;
+    Ok(serde_json::from_slice(&body).map_err(BadRequestErrors::from)?)
+}
+/// Decompresses the body if needed. Bodies larger than `limit` are rejected.
+///
+/// The gzip stream is never read past the limit. So a small request can not expand into an unbounded allocation
+fn decode_audit_body(body: &[u8], is_gzip: bool, limit: u64) -> Result<Vec<u8>, NPMRegistryError> {
+    let body = if is_gzip {
+        let mut decoded = Vec::new();
+        GzDecoder::new(body)
+            .take(limit + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|err| BadRequestErrors::Other(format!("Invalid gzip body: {}", err)))?;
+        decoded
+    } else {
+        body.to_vec()
+    };
+    if body.len() as u64 > limit {
+        return Err(NPMRegistryError::AuditBodyTooLarge(limit));
+    }
+    Ok(body)
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NPMBulkAdvisory {
+    pub id: i32,
+    pub url: String,
+    pub title: String,
+    pub severity: AdvisorySeverity,
+    pub vulnerable_versions: String,
+    pub cwe: Vec<String>,
+}
+impl From<DBAdvisory> for NPMBulkAdvisory {
+    fn from(advisory: DBAdvisory) -> Self {
+        Self {
+            id: advisory.id,
+            url: advisory.url.unwrap_or_default(),
+            title: advisory.title,
+            severity: advisory.severity,
+            vulnerable_versions: advisory.vulnerable_versions,
+            cwe: advisory.cwe,
+        }
+    }
+}
+/// Handles [BULK_ADVISORY_PATH]
+///
+/// The body is `{"package": ["1.0.0", "1.0.1"]}`. Responds with the advisories affecting any of the versions
+#[instrument(skip(site, request))]
+pub async fn bulk_advisories(
+    site: &NitroRepo,
+    request: RepositoryRequest,
+) -> Result<RepoResponse, NPMRegistryError> {
+    let packages: HashMap<String, Vec<String>> = read_audit_body(request).await?;
+    let names: Vec<String> = packages.keys().cloned().collect();
+    let advisories = DBAdvisory::get_for_packages(NPM_ECOSYSTEM, &names, site.as_ref()).await?;
+    let mut response: HashMap<String, Vec<NPMBulkAdvisory>> = HashMap::new();
+    for advisory in advisories {
+        let Some(versions) = packages.get(&advisory.package_name) else {
+            continue;
+        };
+        if !versions
+            .iter()
+            .any(|version| npm_range_matches(&advisory.vulnerable_versions, version))
+        {
+            continue;
+        }
+        response
+            .entry(advisory.package_name.clone())
+            .or_default()
+            .push(advisory.into());
+    }
+    json_response(StatusCode::OK, &response)
+}
+/// The dependency tree npm 6 sends to [QUICK_AUDIT_PATH]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuickAuditRequest {
+    pub name: Option<String>,
+    pub dependencies: HashMap<String, QuickAuditDependency>,
+}
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuickAuditDependency {
+    pub version: String,
+    pub dev: bool,
+    pub optional: bool,
+    pub dependencies: HashMap<String, QuickAuditDependency>,
+}
+/// A package in the dependency tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    /// Such as `a>b>c`
+    pub path: String,
+    pub dev: bool,
+    pub optional: bool,
+}
+impl QuickAuditRequest {
+    /// Flattens the dependency tree
+    pub fn installed_packages(&self) -> Vec<InstalledPackage> {
+        fn walk(
+            dependencies: &HashMap<String, QuickAuditDependency>,
+            parent: Option<&str>,
+            packages: &mut Vec<InstalledPackage>,
+        ) {
+            for (name, dependency) in dependencies {
+                let path = match parent {
+                    Some(parent) => format!("{}>{}", parent, name),
+                    None => name.clone(),
+                };
+                walk(&dependency.dependencies, Some(&path), packages);
+                packages.push(InstalledPackage {
+                    name: name.clone(),
+                    version: dependency.version.clone(),
+                    path,
+                    dev: dependency.dev,
+                    optional: dependency.optional,
+                });
+            }
+        }
+        let mut packages = Vec::new();
+        walk(&self.dependencies, None, &mut packages);
+        packages
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickAuditResponse {
+    pub actions: Vec<Value>,
+    pub advisories: HashMap<String, QuickAuditAdvisory>,
+    pub muted: Vec<Value>,
+    pub metadata: QuickAuditMetadata,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickAuditAdvisory {
+    pub id: i32,
+    pub title: String,
+    pub module_name: String,
+    pub severity: AdvisorySeverity,
+    pub vulnerable_versions: String,
+    pub patched_versions: String,
+    pub overview: String,
+    pub recommendation: String,
+    pub url: String,
+    pub cwe: Vec<String>,
+    pub findings: Vec<QuickAuditFinding>,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickAuditFinding {
+    pub version: String,
+    pub paths: Vec<String>,
+}
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickAuditMetadata {
+    pub vulnerabilities: HashMap<AdvisorySeverity, usize>,
+    pub dependencies: usize,
+    pub dev_dependencies: usize,
+    pub optional_dependencies: usize,
+    pub total_dependencies: usize,
+}
+impl QuickAuditAdvisory {
+    fn new(advisory: DBAdvisory) -> Self {
+        let recommendation = match &advisory.patched_versions {
+            Some(patched) => format!("Upgrade to version {}", patched),
+            None => "None".to_owned(),
+        };
+        Self {
+            id: advisory.id,
+            title: advisory.title,
+            module_name: advisory.package_name,
+            severity: advisory.severity,
+            vulnerable_versions: advisory.vulnerable_versions,
+            // npm 6 expects a range. `<0.0.0` matches nothing
+            patched_versions: advisory
+                .patched_versions
+                .unwrap_or_else(|| "<0.0.0".to_owned()),
+            overview: advisory.overview.unwrap_or_default(),
+            recommendation,
+            url: advisory.url.unwrap_or_default(),
+            cwe: advisory.cwe,
+            findings: vec![],
+        }
+    }
+    fn add_finding(&mut self, package: &InstalledPackage) {
+        match self
+            .findings
+            .iter_mut()
+            .find(|finding| finding.version == package.version)
+        {
+            Some(finding) => finding.paths.push(package.path.clone()),
+            None => self.findings.push(QuickAuditFinding {
+                version: package.version.clone(),
+                paths: vec![package.path.clone()],
+            }),
+        }
+    }
+}
+/// Handles [QUICK_AUDIT_PATH]
+#[instrument(skip(site, request))]
+pub async fn quick_audit(
+    site: &NitroRepo,
+    request: RepositoryRequest,
+) -> Result<RepoResponse, NPMRegistryError> {
+    let audit_request: QuickAuditRequest = read_audit_body(request).await?;
+    let installed = audit_request.installed_packages();
+    let mut names: Vec<String> = installed
+        .iter()
+        .map(|package| package.name.clone())
+        .collect();
+    names.sort();
+    names.dedup();
+    let advisories = DBAdvisory::get_for_packages(NPM_ECOSYSTEM, &names, site.as_ref()).await?;
+
+    let mut metadata = QuickAuditMetadata {
+        vulnerabilities: [
+            AdvisorySeverity::Info,
+            AdvisorySeverity::Low,
+            AdvisorySeverity::Moderate,
+            AdvisorySeverity::High,
+            AdvisorySeverity::Critical,
+        ]
+        .into_iter()
+        .map(|severity| (severity, 0))
+        .collect(),
+        total_dependencies: installed.len(),
+        ..Default::default()
+    };
+    for package in &installed {
+        if package.dev {
+            metadata.dev_dependencies += 1;
+        } else {
+            metadata.dependencies += 1;
+        }
+        if package.optional {
+            metadata.optional_dependencies += 1;
+        }
+    }
+    let mut response_advisories = HashMap::new();
+    for advisory in advisories {
+        let severity = advisory.severity;
+        let mut quick_advisory: Option<QuickAuditAdvisory> = None;
+        for package in installed.iter().filter(|package| {
+            package.name == advisory.package_name
+                && npm_range_matches(&advisory.vulnerable_versions, &package.version)
+        }) {
+            quick_advisory
+                .get_or_insert_with(|| QuickAuditAdvisory::new(advisory.clone()))
+                .add_finding(package);
+            *metadata.vulnerabilities.entry(severity).or_default() += 1;
+        }
+        if let Some(quick_advisory) = quick_advisory {
+            response_advisories.insert(quick_advisory.id.to_string(), quick_advisory);
+        }
+    }
+    json_response(
+        StatusCode::OK,
+        &QuickAuditResponse {
+            actions: vec![],
+            advisories: response_advisories,
+            muted: vec![],
+            metadata,
+        },
+    )
+}
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_decode_audit_body() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'a'; 1024]).unwrap();
+        let gzip = encoder.finish().unwrap();
+
+        assert_eq!(decode_audit_body(&gzip, true, 1024).unwrap().len(), 1024);
+        assert!(matches!(
+            decode_audit_body(&gzip, true, 1023),
+            Err(NPMRegistryError::AuditBodyTooLarge(1023))
+        ));
+        assert!(matches!(
+            decode_audit_body(&[b'a'; 16], false, 8),
+            Err(NPMRegistryError::AuditBodyTooLarge(8))
+        ));
+        assert!(decode_audit_body(b"not gzip", true, 1024).is_err());
+    }
+    #[test]
+    fn test_npm_range_matches() {
+        let tests = [
+            ("<1.2.3", "1.2.2", true),
+            ("<1.2.3", "1.2.3", false),
+            (">=1.0.0 <1.2.3", "0.9.0", false),
+            (">=1.0.0 <1.2.3", "1.1.0", true),
+            (">= 1.0.0 < 1.2.3", "1.1.0", true),
+            ("<1.0.0 || >=2.0.0 <2.0.4", "2.0.3", true),
+            ("<1.0.0 || >=2.0.0 <2.0.4", "1.5.0", false),
+            ("1.0.0 - 1.4.0", "1.4.0", true),
+            ("1.0.0 - 1.4.0", "1.4.1", false),
+            ("1.2.3", "1.2.4", false),
+            ("1.x", "1.9.0", true),
+            ("*", "5.0.0", true),
+            ("<1.2.3", "not-a-version", false),
+        ];
+        for (range, version, expected) in tests {
+            assert_eq!(
+                npm_range_matches(range, version),
+                expected,
+                "{} matches {}",
+                range,
+                version
+            );
+        }
+    }
+    #[test]
+    fn test_installed_packages() {
+        let request: QuickAuditRequest = serde_json::from_value(json!({
+            "name": "my-app",
+            "version": "1.0.0",
+            "requires": { "a": "^1.0.0" },
+            "dependencies": {
+                "a": {
+                    "version": "1.0.0",
+                    "requires": { "b": "^2.0.0" },
+                    "dependencies": {
+                        "b": { "version": "2.0.0" }
+                    }
+                },
+                "c": { "version": "3.0.0", "dev": true }
+            }
+        }))
+        .unwrap();
+        let mut installed = request.installed_packages();
+        installed.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<_> = installed
+            .iter()
+            .map(|package| (package.path.as_str(), package.version.as_str(), package.dev))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("a", "1.0.0", false),
+                ("a>b", "2.0.0", false),
+                ("c", "3.0.0", true),
+            ]
+        );
+    }
+}
@@ -3,10 +3,38 @@ use schemars::{JsonSchema, schema_for};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::{group::NPMGroupConfig, proxy::NPMProxyConfig};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "config")]
 pub enum NPMRegistryConfig {
     Hosted,
+    Proxy(NPMProxyConfig),
+    Group(NPMGroupConfig),
+}
+impl NPMRegistryConfig {
+    pub fn validate(&self) -> Result<(), RepositoryConfigError> {
+        match self {
+            NPMRegistryConfig::Proxy(proxy) => {
+                proxy.validate()?;
+            }
+            NPMRegistryConfig::Group(group) => {
+                group
+                    .validate()
+                    .map_err(RepositoryConfigError::InvalidConfig)?;
+            }
+            NPMRegistryConfig::Hosted => {}
+        }
+        Ok(())
+    }
+    pub fn is_same_type(&self, other: &NPMRegistryConfig) -> bool {
+        matches!(
+            (self, other),
+            (NPMRegistryConfig::Hosted, NPMRegistryConfig::Hosted)
+                | (NPMRegistryConfig::Proxy(_), NPMRegistryConfig::Proxy(_))
+                | (NPMRegistryConfig::Group(_), NPMRegistryConfig::Group(_))
+        )
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -27,10 +55,33 @@ impl RepositoryConfigType for NPMRegistryConfigType {
     }
     fn validate_config(&self, config: Value) -> Result<(), RepositoryConfigError> {
         let config: NPMRegistryConfig = serde_json::from_value(config)?;
-        Ok(())
+        config.validate()
+    }
+    fn redact_secrets(&self, config: Value) -> Result<Value, RepositoryConfigError> {
+        let mut config: NPMRegistryConfig = serde_json::from_value(config)?;
+        if let NPMRegistryConfig::Proxy(proxy) = &mut config {
+            proxy.redact_secrets();
+        }
+        Ok(serde_json::to_value(config)?)
+    }
+    fn restore_secrets(&self, old: Value, new: Value) -> Result<Value, RepositoryConfigError> {
+        let old: NPMRegistryConfig = serde_json::from_value(old)?;
+        let mut new: NPMRegistryConfig = serde_json::from_value(new)?;
+        if let (NPMRegistryConfig::Proxy(old), NPMRegistryConfig::Proxy(new)) = (&old, &mut new) {
+            new.restore_secrets(old);
+        }
+        Ok(serde_json::to_value(new)?)
     }
     fn validate_change(&self, old: Value, new: Value) -> Result<(), RepositoryConfigError> {
-        Ok(())
+        let new: NPMRegistryConfig = serde_json::from_value(new)?;
+        let old: NPMRegistryConfig = serde_json::from_value(old)?;
+        if !old.is_same_type(&new) {
+            return Err(RepositoryConfigError::InvalidChange(
+                "npm",
+                "Cannot change the type of NPM Registry",
+            ));
+        }
+        new.validate()
     }
     fn default(&self) -> Result<Value, RepositoryConfigError> {
         let config = NPMRegistryConfig::Hosted;
@@ -45,3 +96,51 @@ impl RepositoryConfigType for NPMRegistryConfigType {
         }
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct NPMPublishRules {
+    /// If versions and packages can be unpublished
+    #[schemars(title = "Unpublish Allowed")]
+    pub unpublish_allowed: bool,
+}
+impl Default for NPMPublishRules {
+    fn default() -> Self {
+        Self {
+            unpublish_allowed: true,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NPMPublishRulesConfigType;
+impl RepositoryConfigType for NPMPublishRulesConfigType {
+    fn get_type(&self) -> &'static str {
+        Self::get_type_static()
+    }
+
+    fn get_type_static() -> &'static str
+    where
+        Self: Sized,
+    {
+        "npm_publish_rules"
+    }
+    fn get_description(&self) -> ConfigDescription {
+        ConfigDescription {
+            name: "Publish Rules",
+            description: Some("Rules for publishing to a NPM registry"),
+            documentation_link: None,
+            ..Default::default()
+        }
+    }
+    fn validate_config(&self, config: Value) -> Result<(), RepositoryConfigError> {
+        let _config: NPMPublishRules = serde_json::from_value(config)?;
+        Ok(())
+    }
+
+    fn default(&self) -> Result<Value, RepositoryConfigError> {
+        Ok(serde_json::to_value(NPMPublishRules::default())?)
+    }
+
+    fn schema(&self) -> Option<schemars::Schema> {
+        Some(schema_for!(NPMPublishRules))
+    }
+}
@@ -0,0 +1,487 @@
+//! A virtual NPM Registry that combines other registries.
+//!
+//! Packuments from every member are merged in order. Scopes can be pinned to specific members
+//! so private packages are never resolved from a public upstream
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+
+use axum::response::IntoResponse;
+use derive_more::derive::Deref;
+use http::StatusCode;
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::{Visibility, config::RepositoryConfigType},
+    user::permissions::{HasPermissions, RepositoryActions},
+};
+use nr_storage::{DynStorage, StorageFile};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use super::{
+    NPMRegistry, NPMRegistryConfig, NPMRegistryConfigType, NPMRegistryError, audit,
+    hosted::NPMHostedRegistry,
+    proxy::NPMProxyRegistry,
+    types::request::GetPath,
+    utils::{
+        NpmRegistryExt, find_version, package_not_found, rewrite_tarball_urls, version_not_found,
+    },
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        DynRepository, RepoResponse, Repository, RepositoryAuthentication, RepositoryFactoryError,
+        RepositoryRequest,
+        utils::{RepositoryExt, json_response, registry_url},
+    },
+};
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NPMGroupConfig {
+    /// The registries that make up the group.
+    ///
+    /// Packuments are merged in order. If two members have the same version the first member wins.
+    #[schemars(with = "Vec<String>")]
+    pub members: Vec<Uuid>,
+    /// Packages in these scopes are only resolved from the members of the scope
+    #[serde(default)]
+    pub scopes: Vec<NPMGroupScope>,
+}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NPMGroupScope {
+    /// The scope. Such as `@acme`
+    pub scope: String,
+    /// The registries the scope is resolved from. In order
+    #[schemars(with = "Vec<String>")]
+    pub members: Vec<Uuid>,
+}
+impl NPMGroupScope {
+    pub fn matches(&self, scope: &str) -> bool {
+        self.scope.trim_start_matches('@') == scope
+    }
+}
+impl NPMGroupConfig {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self
+            .scopes
+            .iter()
+            .any(|scope| scope.scope.trim_start_matches('@').is_empty())
+        {
+            return Err("Group scope can not be empty");
+        }
+        Ok(())
+    }
+    /// The members a package is resolved from.
+    ///
+    /// Scoped packages with a matching scope rule only use the members of the rule
+    pub fn members_for(&self, package: &str) -> &[Uuid] {
+        if let Some(scope) = package_scope(package) {
+            if let Some(rule) = self.scopes.iter().find(|rule| rule.matches(scope)) {
+                return &rule.members;
+            }
+        }
+        &self.members
+    }
+}
+/// The scope of the package without the `@`. None if the package is not scoped
+pub fn package_scope(package: &str) -> Option<&str> {
+    package
+        .strip_prefix('@')
+        .and_then(|package| package.split_once('/'))
+        .map(|(scope, _)| scope)
+}
+/// Merges the packument into the merged packument.
+///
+/// Versions, dist-tags, and times already in the merged packument are kept
+pub fn merge_packument(merged: &mut Value, packument: Value) {
+    let Value::Object(packument) = packument else {
+        return;
+    };
+    let Some(merged) = merged.as_object_mut() else {
+        return;
+    };
+    for (key, value) in packument {
+        match (merged.get_mut(&key), value) {
+            (Some(Value::Object(existing)), Value::Object(value))
+                if matches!(key.as_str(), "versions" | "dist-tags" | "time") =>
+            {
+                for (key, value) in value {
+                    existing.entry(key).or_insert(value);
+                }
+            }
+            (Some(_), _) => {}
+            (None, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+}
+/// A registry that is a member of a group.
+///
+/// Groups can not contain other groups.
+#[derive(Debug, Clone)]
+pub enum NPMGroupMember {
+    Hosted(NPMHostedRegistry),
+    Proxy(NPMProxyRegistry),
+}
+impl NPMGroupMember {
+    pub fn id(&self) -> Uuid {
+        match self {
+            NPMGroupMember::Hosted(hosted) => hosted.id(),
+            NPMGroupMember::Proxy(proxy) => proxy.id(),
+        }
+    }
+    pub fn is_active(&self) -> bool {
+        match self {
+            NPMGroupMember::Hosted(hosted) => hosted.is_active(),
+            NPMGroupMember::Proxy(proxy) => proxy.is_active(),
+        }
+    }
+    pub fn visibility(&self) -> Visibility {
+        match self {
+            NPMGroupMember::Hosted(hosted) => hosted.visibility(),
+            NPMGroupMember::Proxy(proxy) => proxy.visibility(),
+        }
+    }
+    pub async fn get_packument(&self, name: &str) -> Result<Option<Value>, NPMRegistryError> {
+        match self {
+            NPMGroupMember::Hosted(hosted) => match hosted.get_packument(name).await? {
+                Some(packument) => Ok(Some(serde_json::to_value(packument)?)),
+                None => Ok(None),
+            },
+            NPMGroupMember::Proxy(proxy) => proxy.get_packument(name).await,
+        }
+    }
+    /// Opens the tarball from the member. Proxies will download the tarball if it is not cached
+    pub async fn get_tarball(
+        &self,
+        name: &str,
+        version: &str,
+        file: &str,
+    ) -> Result<Option<StorageFile>, NPMRegistryError> {
+        match self {
+            NPMGroupMember::Hosted(hosted) => hosted.get_tarball(name, version, file).await,
+            NPMGroupMember::Proxy(proxy) => proxy.get_tarball(name, file).await,
+        }
+    }
+}
+#[derive(derive_more::Debug)]
+pub struct NPMGroupInner {
+    pub id: Uuid,
+    pub name: String,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    pub config: RwLock<NPMGroupConfig>,
+    #[debug(skip)]
+    pub storage: DynStorage,
+    #[debug(skip)]
+    pub site: NitroRepo,
+}
+#[derive(Debug, Clone, Deref)]
+pub struct NPMGroupRegistry(Arc<NPMGroupInner>);
+impl NPMGroupRegistry {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+        config: NPMGroupConfig,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(NPMGroupInner {
+            id: repository.id,
+            name: repository.name.into(),
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            config: RwLock::new(config),
+            storage,
+            site,
+        })))
+    }
+    fn registry_url(&self, request: &RepositoryRequest) -> String {
+        registry_url(
+            &self.site,
+            &request.parts,
+            &self.storage.storage_config().storage_config.storage_name,
+            &self.name,
+        )
+    }
+    /// Resolves the members the package can be resolved from. In order.
+    ///
+    /// Members that are missing, inactive, or not a NPM Hosted/Proxy registry are skipped.
+    /// Private members are skipped if the user does not have the read permission for them.
+    pub async fn members(
+        &self,
+        package: &str,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Vec<NPMGroupMember>, NPMRegistryError> {
+        let member_ids = self.config.read().members_for(package).to_vec();
+        let mut members = Vec::with_capacity(member_ids.len());
+        for member_id in member_ids {
+            let member = match self.site.get_repository(member_id) {
+                Some(DynRepository::NPM(NPMRegistry::Hosted(hosted))) => {
+                    NPMGroupMember::Hosted(hosted)
+                }
+                Some(DynRepository::NPM(NPMRegistry::Proxy(proxy))) => NPMGroupMember::Proxy(proxy),
+                Some(_) => {
+                    warn!(
+                        ?member_id,
+                        "Group member is not a NPM Hosted or Proxy registry"
+                    );
+                    continue;
+                }
+                None => {
+                    warn!(?member_id, "Group member does not exist");
+                    continue;
+                }
+            };
+            if !member.is_active() {
+                debug!(?member_id, "Skipping inactive group member");
+                continue;
+            }
+            if member.visibility().is_private()
+                && !authentication
+                    .has_action(RepositoryActions::Read, member_id, self.site.as_ref())
+                    .await?
+            {
+                debug!(?member_id, "Skipping group member the user can not read");
+                continue;
+            }
+            members.push(member);
+        }
+        Ok(members)
+    }
+    /// Reads the packument from every member and merges them into one.
+    #[instrument(skip(self, authentication), fields(nr.repository.id = %self.id, nr.repository.name = %self.name))]
+    pub async fn merged_packument(
+        &self,
+        name: &str,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Option<Value>, NPMRegistryError> {
+        let mut merged: Option<Value> = None;
+        for member in self.members(name, authentication).await? {
+            let packument = match member.get_packument(name).await {
+                Ok(Some(packument)) => packument,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(?err, member = ?member.id(), "Failed to get packument from member");
+                    continue;
+                }
+            };
+            match &mut merged {
+                Some(merged) => merge_packument(merged, packument),
+                None => merged = Some(packument),
+            }
+        }
+        Ok(merged)
+    }
+}
+impl NpmRegistryExt for NPMGroupRegistry {}
+impl RepositoryExt for NPMGroupRegistry {}
+impl Repository for NPMGroupRegistry {
+    type Error = NPMRegistryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "npm"
+    }
+    fn full_type(&self) -> &'static str {
+        "npm/group"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![NPMRegistryConfigType::get_type_static()]
+    }
+
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "npm/group"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let Some(npm_config_db) = DBRepositoryConfig::<NPMRegistryConfig>::get_config(
+            self.id,
+            NPMRegistryConfigType::get_type_static(),
+            self.site.as_ref(),
+        )
+        .await?
+        else {
+            return Err(RepositoryFactoryError::MissingConfig(
+                NPMRegistryConfigType::get_type_static(),
+            ));
+        };
+        match npm_config_db.value.0 {
+            NPMRegistryConfig::Group(group_config) => {
+                *self.config.write() = group_config;
+                Ok(())
+            }
+            _ => Err(RepositoryFactoryError::InvalidConfig(
+                NPMRegistryConfigType::get_type_static(),
+                "Expected Group Config".into(),
+            )),
+        }
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        let get_path = match GetPath::try_from(request.path.clone()) {
+            Ok(ok) => ok,
+            Err(err) => return Ok(err.into_response().into()),
+        };
+        match get_path {
+            GetPath::GetPackageInfo { name } => {
+                let Some(mut packument) = self
+                    .merged_packument(&name, &request.authentication)
+                    .await?
+                else {
+                    return Ok(package_not_found(&name));
+                };
+                rewrite_tarball_urls(&mut packument, &self.registry_url(&request), &name);
+                json_response(StatusCode::OK, &packument)
+            }
+            GetPath::VersionInfo { name, version } => {
+                let Some(mut packument) = self
+                    .merged_packument(&name, &request.authentication)
+                    .await?
+                else {
+                    return Ok(package_not_found(&name));
+                };
+                rewrite_tarball_urls(&mut packument, &self.registry_url(&request), &name);
+                match find_version(&packument, &version) {
+                    Some(version) => json_response(StatusCode::OK, version),
+                    None => Ok(version_not_found(&name, &version)),
+                }
+            }
+            GetPath::GetTar {
+                name,
+                version,
+                file,
+            } => {
+                for member in self.members(&name, &request.authentication).await? {
+                    match member.get_tarball(&name, &version, &file).await {
+                        Ok(Some(file)) => {
+                            debug!(member = ?member.id(), ?file, "Found tarball in group member");
+                            return Ok(RepoResponse::from(Some(file)));
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            warn!(
+                                ?err,
+                                member = ?member.id(),
+                                ?file,
+                                "Failed to get tarball from member"
+                            );
+                        }
+                    }
+                }
+                Ok(RepoResponse::from(Option::<StorageFile>::None))
+            }
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match request.path.to_string().as_str() {
+            audit::BULK_ADVISORY_PATH => audit::bulk_advisories(&self.site, request).await,
+            audit::QUICK_AUDIT_PATH => audit::quick_audit(&self.site, request).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_members_for() {
+        let hosted = Uuid::new_v4();
+        let proxy = Uuid::new_v4();
+        let config = NPMGroupConfig {
+            members: vec![hosted, proxy],
+            scopes: vec![NPMGroupScope {
+                scope: "@acme".to_owned(),
+                members: vec![hosted],
+            }],
+        };
+        assert_eq!(config.members_for("@acme/utils"), &[hosted]);
+        assert_eq!(config.members_for("@other/utils"), &[hosted, proxy]);
+        assert_eq!(config.members_for("acme"), &[hosted, proxy]);
+    }
+    #[test]
+    fn test_merge_packument() {
+        let mut merged = json!({
+            "name": "mylib",
+            "dist-tags": { "latest": "2.0.0" },
+            "versions": { "2.0.0": { "version": "2.0.0", "from": "hosted" } },
+            "time": { "2.0.0": "2024-01-01T00:00:00.000Z" }
+        });
+        merge_packument(
+            &mut merged,
+            json!({
+                "name": "mylib",
+                "description": "From the proxy",
+                "dist-tags": { "latest": "1.0.0", "next": "3.0.0-beta" },
+                "versions": {
+                    "1.0.0": { "version": "1.0.0" },
+                    "2.0.0": { "version": "2.0.0", "from": "proxy" }
+                },
+                "time": { "1.0.0": "2023-01-01T00:00:00.000Z" }
+            }),
+        );
+        assert_eq!(merged["dist-tags"]["latest"], "2.0.0");
+        assert_eq!(merged["dist-tags"]["next"], "3.0.0-beta");
+        assert_eq!(merged["versions"]["2.0.0"]["from"], "hosted");
+        assert_eq!(merged["versions"]["1.0.0"]["version"], "1.0.0");
+        assert_eq!(merged["time"]["1.0.0"], "2023-01-01T00:00:00.000Z");
+        assert_eq!(merged["description"], "From the proxy");
+    }
+}
@@ -1,29 +1,64 @@
+use super::audit;
+use super::integrity;
+use super::login::web_login::{self, WebLoginSessions};
 use super::types::{
     NPM_COMMAND_HEADER, NpmRegistryPackageResponse,
-    request::{GetPath, InvalidNPMCommand, NPMCommand, PublishVersion},
+    abbreviated::{
+        ABBREVIATED_CONTENT_TYPE, AbbreviatedPackument, AbbreviatedPackumentCache,
+        accepts_abbreviated,
+    },
+    request::{
+        DistTagsPath, GetPath, InvalidNPMCommand, NPMCommand, PublishVersion, strip_revision,
+    },
+    search::{
+        NPMSearchObject, NPMSearchPackage, NPMSearchRequest, NPMSearchResponse, NPMSearchScore,
+        search_score,
+    },
 };
-use super::utils::{NpmRegistryExt, npm_time};
+use super::utils::{NpmRegistryExt, npm_time, package_not_found, version_not_found};
 use crate::{
     app::{NitroRepo, responses::no_content_response},
     repository::{
         RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
-        npm::{NPMRegistryConfigType, NPMRegistryError, types::PublishRequest},
-        utils::RepositoryExt,
+        npm::{
+            NPMPublishRules, NPMPublishRulesConfigType, NPMRegistryConfigType, NPMRegistryError,
+            types::PublishRequest,
+        },
+        utils::{RepositoryExt, json_response},
     },
 };
 use ahash::{HashMap, HashMapExt};
 use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use chrono::Local;
 use derive_more::derive::Deref;
-use http::{StatusCode, header::CONTENT_TYPE};
+use http::{
+    StatusCode,
+    header::{CONTENT_TYPE, VARY},
+};
 use nr_core::{
-    database::entities::{project::versions::DBProjectVersion, repository::DBRepository},
-    repository::config::RepositoryConfigType,
+    database::entities::{
+        project::{
+            DBProject, dist_tags::DBProjectDistTag, update::UpdateProjectVersion,
+            versions::DBProjectVersion,
+        },
+        repository::DBRepository,
+    },
+    repository::{
+        Visibility,
+        config::{RepositoryConfigType, get_repository_config_or_default},
+    },
     storage::StoragePath,
-    user::permissions::RepositoryActions,
+    user::permissions::{HasPermissions, RepositoryActions},
 };
-use nr_storage::{DynStorage, FileContent, Storage};
-use std::sync::Arc;
-use tracing::{debug, info, instrument, warn};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile};
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+use tracing::{debug, error, info, instrument, warn};
 
 #[derive(derive_more::Debug)]
 pub struct NpmRegistryInner {
@@ -32,6 +67,11 @@ pub struct NpmRegistryInner {
     pub storage: DynStorage,
     pub id: uuid::Uuid,
     pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    pub publish_rules: RwLock<NPMPublishRules>,
+    pub web_logins: WebLoginSessions,
+    pub abbreviated_packuments: AbbreviatedPackumentCache,
 }
 #[derive(Debug, Clone, Deref)]
 pub struct NPMHostedRegistry(Arc<NpmRegistryInner>);
@@ -41,13 +81,431 @@ impl NPMHostedRegistry {
         storage: DynStorage,
         repository: DBRepository,
     ) -> Result<Self, RepositoryFactoryError> {
+        let publish_rules = get_repository_config_or_default::<
+            NPMPublishRulesConfigType,
+            NPMPublishRules,
+        >(repository.id, site.as_ref())
+        .await?;
         Ok(Self(Arc::new(NpmRegistryInner {
             site,
             storage,
             id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
             repository,
+            publish_rules: RwLock::new(publish_rules.value.0),
+            web_logins: WebLoginSessions::default(),
+            abbreviated_packuments: AbbreviatedPackumentCache::default(),
         })))
     }
+    /// Builds the packument from the project and its versions
+    #[instrument]
+    pub async fn get_packument(
+        &self,
+        name: &str,
+    ) -> Result<Option<NpmRegistryPackageResponse>, NPMRegistryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(None);
+        };
+        debug!(?project, "Got project");
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        let dist_tags = self.get_dist_tags(&project).await?;
+        let mut times = HashMap::new();
+        times.insert(
+            "created".to_owned(),
+            npm_time::format_date_time(&project.created_at),
+        );
+        times.insert(
+            "modified".to_owned(),
+            npm_time::format_date_time(&project.updated_at),
+        );
+        let mut versions_map = HashMap::new();
+        for version in versions {
+            times.insert(
+                version.version.clone(),
+                npm_time::format_date_time(&version.created_at),
+            );
+            debug!(?version, "Got Version");
+            if let Some(extra) = version.extra.0.extra {
+                let extra: PublishVersion = match serde_json::from_value(extra) {
+                    Ok(ok) => ok,
+                    Err(err) => {
+                        warn!(?err, "Invalid NPM Project");
+                        continue;
+                    }
+                };
+                versions_map.insert(version.version.clone(), extra);
+            } else {
+                warn!(?version, "Invalid NPM Project");
+            }
+        }
+        Ok(Some(NpmRegistryPackageResponse {
+            id: project.project_key.clone(),
+            name: project.name.clone(),
+            description: project.description.clone(),
+            dist_tags,
+            versions: versions_map,
+            time: times,
+        }))
+    }
+    /// The rendered abbreviated packument. Served from the cache if it has already been built
+    #[instrument]
+    pub async fn get_abbreviated_packument(
+        &self,
+        name: &str,
+    ) -> Result<Option<Bytes>, NPMRegistryError> {
+        if let Some(cached) = self.abbreviated_packuments.get(name) {
+            debug!(?name, "Using cached abbreviated packument");
+            return Ok(Some(cached));
+        }
+        // Read before the packument. So a publish that happens while building it is not hidden by the cache
+        let generation = self.abbreviated_packuments.generation();
+        let Some(packument) = self.get_packument(name).await? else {
+            return Ok(None);
+        };
+        let abbreviated = AbbreviatedPackument::from(&packument);
+        let abbreviated = Bytes::from(serde_json::to_vec(&abbreviated)?);
+        self.abbreviated_packuments
+            .insert(name, abbreviated.clone(), generation);
+        Ok(Some(abbreviated))
+    }
+    /// The dist-tags of the project.
+    ///
+    /// Projects published before dist-tags were stored use the latest release as `latest`
+    pub async fn get_dist_tags(
+        &self,
+        project: &DBProject,
+    ) -> Result<HashMap<String, String>, NPMRegistryError> {
+        let mut dist_tags = HashMap::new();
+        if let Some(latest) = &project.latest_release {
+            dist_tags.insert("latest".to_owned(), latest.clone());
+        }
+        for tag in DBProjectDistTag::get_all_for_project(project.id, self.site.as_ref()).await? {
+            dist_tags.insert(tag.tag, tag.version);
+        }
+        Ok(dist_tags)
+    }
+    /// Finds the version. The version can also be a dist-tag such as `latest`
+    async fn find_version_or_tag(
+        &self,
+        project: &DBProject,
+        version: &str,
+    ) -> Result<Option<DBProjectVersion>, NPMRegistryError> {
+        if let Some(version) = self.get_project_version(project.id, version).await? {
+            return Ok(Some(version));
+        }
+        let dist_tags = self.get_dist_tags(project).await?;
+        let Some(tagged) = dist_tags.get(version) else {
+            return Ok(None);
+        };
+        Ok(self.get_project_version(project.id, tagged).await?)
+    }
+    /// `npm dist-tag ls`
+    async fn handle_dist_tags_list(&self, name: &str) -> Result<RepoResponse, NPMRegistryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(package_not_found(name));
+        };
+        json_response(StatusCode::OK, &self.get_dist_tags(&project).await?)
+    }
+    /// `npm dist-tag add {name}@{version} {tag}`. The body is the version as a JSON string
+    #[instrument]
+    async fn handle_dist_tag_add(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        let Some(DistTagsPath {
+            name,
+            tag: Some(tag),
+        }) = DistTagsPath::parse(&request.path.to_string())
+        else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Expected -/package/{name}/dist-tags/{tag}",
+            ));
+        };
+        if semver::Version::parse(&tag).is_ok() {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                format!("Tag {} can not be a version number", tag),
+            ));
+        }
+        let body = request.body.body_as_string().await?;
+        let version: String = serde_json::from_str(&body)?;
+        let Some(project) = self.get_project_from_key(&name).await? else {
+            return Ok(package_not_found(&name));
+        };
+        if self
+            .get_project_version(project.id, &version)
+            .await?
+            .is_none()
+        {
+            return Ok(version_not_found(&name, &version));
+        }
+        DBProjectDistTag::set_tag(project.id, &tag, &version, self.site.as_ref()).await?;
+        self.abbreviated_packuments.invalidate(&name);
+        info!(?name, ?tag, ?version, "Set dist-tag");
+        json_response(StatusCode::OK, &self.get_dist_tags(&project).await?)
+    }
+    /// `npm dist-tag rm {name} {tag}`. The `latest` tag can not be removed
+    #[instrument]
+    async fn handle_dist_tag_remove(
+        &self,
+        name: &str,
+        tag: &str,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if tag == "latest" {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "The latest tag can not be removed",
+            ));
+        }
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(package_not_found(name));
+        };
+        if !DBProjectDistTag::delete_tag(project.id, tag, self.site.as_ref()).await? {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("Tag {} not found in project {}", tag, name),
+            ));
+        }
+        self.abbreviated_packuments.invalidate(name);
+        info!(?name, ?tag, "Removed dist-tag");
+        json_response(StatusCode::OK, &self.get_dist_tags(&project).await?)
+    }
+    /// Opens a tarball of the version. Returns None if the project or version does not exist
+    #[instrument]
+    pub async fn get_tarball(
+        &self,
+        name: &str,
+        version: &str,
+        file: &str,
+    ) -> Result<Option<StorageFile>, NPMRegistryError> {
+        let Some(project) = self.get_project_from_key(name).await? else {
+            return Ok(None);
+        };
+        debug!(?project, ?version, "Getting version");
+        let Some(version) = self.get_project_version(project.id, version).await? else {
+            return Ok(None);
+        };
+        let mut storage_path = StoragePath::from(version.version_path.as_str());
+        storage_path.push_mut(file);
+        debug!(?storage_path, "Getting file");
+        Ok(self.storage.open_file(self.id, &storage_path).await?)
+    }
+    fn unpublish_not_allowed() -> RepoResponse {
+        RepoResponse::basic_text_response(
+            StatusCode::FORBIDDEN,
+            "Unpublishing is not allowed in this repository",
+        )
+    }
+    /// Deletes the files of the version and removes it from the project.
+    ///
+    /// Dist-tags pointing at the version are removed
+    #[instrument]
+    async fn unpublish_version(
+        &self,
+        project: &DBProject,
+        version: &DBProjectVersion,
+    ) -> Result<(), NPMRegistryError> {
+        let version_path = StoragePath::from(version.version_path.as_str());
+        self.storage.delete_file(self.id, &version_path).await?;
+        DBProjectVersion::delete_by_id(version.id, self.site.as_ref()).await?;
+        DBProjectDistTag::delete_tags_for_version(project.id, &version.version, self.site.as_ref())
+            .await?;
+        self.abbreviated_packuments.invalidate(&project.project_key);
+        info!(?project.project_key, ?version.version, "Unpublished version");
+        Ok(())
+    }
+    /// Deletes every file of the project and the project itself
+    #[instrument]
+    async fn unpublish_project(&self, project: &DBProject) -> Result<(), NPMRegistryError> {
+        let project_path = StoragePath::from(project.storage_path.as_str());
+        self.storage.delete_file(self.id, &project_path).await?;
+        DBProject::delete_by_id(project.id, self.site.as_ref()).await?;
+        self.abbreviated_packuments.invalidate(&project.project_key);
+        info!(?project.project_key, "Unpublished project");
+        Ok(())
+    }
+    /// Called after versions are unpublished.
+    ///
+    /// The project is removed once its last version is gone. Otherwise `latest` is moved to the newest version if it was removed
+    async fn after_unpublish(&self, project: &DBProject) -> Result<(), NPMRegistryError> {
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        let Some(newest) = versions.iter().max_by_key(|version| version.created_at) else {
+            return self.unpublish_project(project).await;
+        };
+        let latest = match DBProjectDistTag::get_tag(project.id, "latest", self.site.as_ref())
+            .await?
+        {
+            Some(latest) => latest.version,
+            None => {
+                DBProjectDistTag::set_tag(project.id, "latest", &newest.version, self.site.as_ref())
+                    .await?
+                    .version
+            }
+        };
+        if let Some(latest_release) = &project.latest_release {
+            if !versions
+                .iter()
+                .any(|version| &version.version == latest_release)
+            {
+                DBProject::update_latest_release(project.id, Some(&latest), self.site.as_ref())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+    /// `npm unpublish {name}@{version}` sends the packument without the version.
+    ///
+    /// Every version missing from the packument is unpublished
+    #[instrument]
+    async fn handle_unpublish_versions(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if !self.publish_rules.read().unpublish_allowed {
+            return Ok(Self::unpublish_not_allowed());
+        }
+        let path = request.path.to_string();
+        let Ok(GetPath::GetPackageInfo { name }) =
+            GetPath::try_from(StoragePath::from(strip_revision(&path)))
+        else {
+            return Err(NPMRegistryError::InvalidGetRequest);
+        };
+        let body = request.body.body_as_string().await?;
+        let packument: Value = serde_json::from_str(&body)?;
+        let Some(remaining) = packument.get("versions").and_then(Value::as_object) else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Missing versions",
+            ));
+        };
+        let Some(project) = self.get_project_from_key(&name).await? else {
+            return Ok(package_not_found(&name));
+        };
+        let versions = DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        for version in versions {
+            if !remaining.contains_key(&version.version) {
+                self.unpublish_version(&project, &version).await?;
+            }
+        }
+        self.after_unpublish(&project).await?;
+        Ok(no_content_response().into())
+    }
+    /// `npm deprecate {name}@{range} {message}` sends the packument with `deprecated` set on the versions.
+    ///
+    /// An empty message removes the deprecation. Everything else in the packument is ignored
+    #[instrument]
+    async fn handle_deprecate(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        let path = request.path.to_string();
+        let Ok(GetPath::GetPackageInfo { name }) =
+            GetPath::try_from(StoragePath::from(strip_revision(&path)))
+        else {
+            return Err(NPMRegistryError::InvalidGetRequest);
+        };
+        let body = request.body.body_as_string().await?;
+        let packument: Value = serde_json::from_str(&body)?;
+        let Some(versions) = packument.get("versions").and_then(Value::as_object) else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::BAD_REQUEST,
+                "Missing versions",
+            ));
+        };
+        let Some(project) = self.get_project_from_key(&name).await? else {
+            return Ok(package_not_found(&name));
+        };
+        for (version, data) in versions {
+            let Some(message) = data.get("deprecated").and_then(Value::as_str) else {
+                continue;
+            };
+            let Some(db_version) = self.get_project_version(project.id, version).await? else {
+                continue;
+            };
+            let mut version_data = db_version.extra.0.clone();
+            let Some(Value::Object(extra)) = &mut version_data.extra else {
+                warn!(?version, "Invalid NPM Project");
+                continue;
+            };
+            let current = extra.get("deprecated").and_then(Value::as_str);
+            if current.unwrap_or_default() == message {
+                continue;
+            }
+            if message.is_empty() {
+                extra.remove("deprecated");
+            } else {
+                extra.insert("deprecated".to_owned(), Value::String(message.to_owned()));
+            }
+            UpdateProjectVersion {
+                extra: Some(version_data),
+                ..Default::default()
+            }
+            .update(db_version.id, self.site.as_ref())
+            .await?;
+            self.abbreviated_packuments.invalidate(&name);
+            info!(?name, ?version, ?message, "Updated deprecation");
+        }
+        Ok(no_content_response().into())
+    }
+    /// Handles `/-/v1/search`. Searches the projects of this repository
+    #[instrument]
+    async fn handle_search(
+        &self,
+        request: &RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        let search_request = NPMSearchRequest::from_query(request.parts.uri.query());
+        let search = search_request.project_search();
+        let (projects, total) = search
+            .search(
+                self.id,
+                search_request.size,
+                search_request.from,
+                self.site.as_ref(),
+            )
+            .await?;
+        let mut objects = Vec::with_capacity(projects.len());
+        for project in projects {
+            let Some(version) = self
+                .get_dist_tags(&project)
+                .await?
+                .remove("latest")
+                .or_else(|| project.latest_release.clone())
+            else {
+                debug!(?project.project_key, "Project has no release. Skipping");
+                continue;
+            };
+            let score = search_score(&search, &project.project_key);
+            let scope = project
+                .scope
+                .as_deref()
+                .map(|scope| scope.trim_start_matches('@').to_owned())
+                .unwrap_or_else(|| "unscoped".to_owned());
+            objects.push(NPMSearchObject {
+                package: NPMSearchPackage {
+                    name: project.project_key,
+                    scope,
+                    version,
+                    description: project.description,
+                    keywords: project.tags,
+                    date: npm_time::format_date_time(&project.updated_at),
+                    links: HashMap::new(),
+                    maintainers: vec![],
+                },
+                score: NPMSearchScore::new(score),
+                search_score: score,
+            });
+        }
+        json_response(
+            StatusCode::OK,
+            &NPMSearchResponse {
+                objects,
+                total,
+                time: npm_time::format_date_time(&Local::now().fixed_offset()),
+            },
+        )
+    }
     #[instrument]
     async fn handle_publish(
         &self,
@@ -80,16 +538,60 @@ impl NPMHostedRegistry {
                 &self.repository.name,
             )?;
         }
+        // Verify every tarball before anything is saved
+        let mut tarballs = Vec::with_capacity(attachments.len());
+        for (file, attachment) in attachments.into_iter() {
+            info!(?file, ?attachment, "Verifying Attachment");
+            let attachment_data = attachment.read_data()?;
+            integrity::verify_integrity(&file, &data.dist, &attachment_data)?;
+            integrity::verify_package_json(
+                &file,
+                &data.name.to_string(),
+                &data.version,
+                &attachment_data,
+            )?;
+            tarballs.push((file, attachment_data));
+        }
+        // `npm publish --tag next` sends `{"next": version}`. Without a tag npm sends `latest`
+        let mut dist_tags: HashMap<String, String> = other
+            .get("dist-tags")
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        dist_tags.retain(|tag, tag_version| {
+            if *tag_version != version {
+                warn!(
+                    ?tag,
+                    ?tag_version,
+                    "Ignoring dist-tag for a different version"
+                );
+                return false;
+            }
+            true
+        });
         let project_path = StoragePath::from(name.clone());
         let project = self.get_or_create_project(&project_path, &data).await?;
+        let current_latest = self.get_dist_tags(&project).await?.remove("latest");
         let mut version_path = project_path.clone();
         version_path.push_mut(&version);
 
         self.create_or_update_version(user.id, &version_path, &project, &data)
             .await?;
+        if !dist_tags.contains_key("latest") {
+            // Keep latest where it was. The first version of a project is always latest
+            dist_tags.insert(
+                "latest".to_owned(),
+                current_latest.unwrap_or_else(|| version.clone()),
+            );
+        }
+        for (tag, tag_version) in &dist_tags {
+            DBProjectDistTag::set_tag(project.id, tag, tag_version, self.site.as_ref()).await?;
+        }
+        self.abbreviated_packuments.invalidate(&project.project_key);
 
-        for (file, attachment) in attachments.into_iter() {
-            info!(?file, ?attachment, "Saving Attachment");
+        for (file, attachment_data) in tarballs {
+            info!(?file, "Saving Attachment");
             let mut path = version_path.clone();
             if file.starts_with("@") && file.contains("/") {
                 let split = file.split("/").collect::<Vec<&str>>();
@@ -97,7 +599,6 @@ impl NPMHostedRegistry {
             } else {
                 path.push_mut(&file);
             }
-            let attachment_data = attachment.read_data()?;
             let storage = self.get_storage();
             storage
                 .save_file(self.id, FileContent::Content(attachment_data), &path)
@@ -126,7 +627,10 @@ impl Repository for NPMHostedRegistry {
     }
 
     fn config_types(&self) -> Vec<&str> {
-        vec![NPMRegistryConfigType::get_type_static()]
+        vec![
+            NPMRegistryConfigType::get_type_static(),
+            NPMPublishRulesConfigType::get_type_static(),
+        ]
     }
 
     fn name(&self) -> String {
@@ -137,12 +641,32 @@ impl Repository for NPMHostedRegistry {
         self.id
     }
 
-    fn visibility(&self) -> nr_core::repository::Visibility {
-        nr_core::repository::Visibility::Public
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
     }
 
     fn is_active(&self) -> bool {
-        true
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "npm/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let publish_rules = get_repository_config_or_default::<
+            NPMPublishRulesConfigType,
+            NPMPublishRules,
+        >(self.id, self.site.as_ref())
+        .await?;
+        *self.publish_rules.write() = publish_rules.value.0;
+        self.abbreviated_packuments.clear();
+        Ok(())
     }
     async fn handle_get(
         &self,
@@ -151,70 +675,41 @@ impl Repository for NPMHostedRegistry {
         let headers = request.headers();
         let path_as_string = request.path.to_string();
         debug!(?headers, ?path_as_string, "Handling NPM GET request");
+        if let Some(session) = path_as_string.strip_prefix(web_login::DONE_PATH) {
+            return web_login::login_done(self, session).await;
+        }
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        if let Some(DistTagsPath { name, .. }) = DistTagsPath::parse(&path_as_string) {
+            return self.handle_dist_tags_list(&name).await;
+        }
         let get_path = match GetPath::try_from(request.path.clone()) {
             Ok(ok) => ok,
             Err(err) => return Ok(err.into_response().into()),
         };
         match get_path {
-            GetPath::GetPackageInfo { name } => {
-                let Some(project) = self.get_project_from_key(&name).await? else {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(format!("Project {} not found in repository", name).into())
-                        .into());
+            GetPath::GetPackageInfo { name } if accepts_abbreviated(request.headers()) => {
+                let Some(packument) = self.get_abbreviated_packument(&name).await? else {
+                    return Ok(package_not_found(&name));
                 };
-                debug!(?project, "Got project");
-                let versions =
-                    DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
-                let mut dist_tags = HashMap::new();
-                let mut times = HashMap::new();
-                times.insert(
-                    "created".to_owned(),
-                    npm_time::format_date_time(&project.created_at),
-                );
-                times.insert(
-                    "modified".to_owned(),
-                    npm_time::format_date_time(&project.updated_at),
-                );
-                if let Some(latest) = project.latest_release {
-                    dist_tags.insert("latest".to_string(), latest);
-                }
-                let mut versions_map = HashMap::new();
-                for version in versions {
-                    times.insert(
-                        version.version.clone(),
-                        npm_time::format_date_time(&version.created_at),
-                    );
-                    debug!(?version, "Got Version");
-                    if let Some(extra) = version.extra.0.extra {
-                        let extra: PublishVersion = match serde_json::from_value(extra) {
-                            Ok(ok) => ok,
-                            Err(err) => {
-                                warn!("Invalid NPM Project");
-                                continue;
-                            }
-                        };
-                        versions_map.insert(version.version.clone(), extra);
-                    } else {
-                        warn!(?version, "Invalid NPM Project");
-                    }
-                }
-                let project_response = NpmRegistryPackageResponse {
-                    id: project.project_key.clone(),
-                    name: project.name.clone(),
-                    description: project.description.clone(),
-                    dist_tags,
-                    versions: versions_map,
-                    time: times,
-                };
-                debug!(?project_response, "Returning Project");
-                let as_string = serde_json::to_string(&project_response).unwrap();
                 Ok(Response::builder()
                     .status(StatusCode::OK)
-                    .header(CONTENT_TYPE, "application/json")
-                    .body(as_string.into())
+                    .header(CONTENT_TYPE, ABBREVIATED_CONTENT_TYPE)
+                    .header(VARY, "Accept")
+                    .body(packument.into())
                     .into())
             }
+            GetPath::GetPackageInfo { name } => {
+                let Some(project_response) = self.get_packument(&name).await? else {
+                    return Ok(package_not_found(&name));
+                };
+                debug!(?project_response, "Returning Project");
+                json_response(StatusCode::OK, &project_response)
+            }
             GetPath::VersionInfo { name, version } => {
                 let Some(project) = self.get_project_from_key(&name).await? else {
                     return Ok(Response::builder()
@@ -223,11 +718,8 @@ impl Repository for NPMHostedRegistry {
                         .into());
                 };
                 debug!(?project, ?version, "Getting version");
-                let Some(version) = self.get_project_version(project.id, &version).await? else {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(format!("Version {} not found in project {}", version, name).into())
-                        .into());
+                let Some(version) = self.find_version_or_tag(&project, &version).await? else {
+                    return Ok(version_not_found(&name, &version));
                 };
                 debug!(?version, "Got Version");
                 if let Some(extra) = version.extra.0.extra {
@@ -249,27 +741,15 @@ impl Repository for NPMHostedRegistry {
                 version,
                 file,
             } => {
-                let Some(project) = self.get_project_from_key(&name).await? else {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(format!("Project {} not found in repository", name).into())
-                        .into());
-                };
-                debug!(?project, ?version, "Getting version");
-                let Some(version) = self.get_project_version(project.id, &version).await? else {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(format!("Version {} not found in project {}", version, name).into())
-                        .into());
-                };
-                debug!(?version, "Got Version");
-                let mut storage_path = StoragePath::from(version.version_path.as_str());
-                storage_path.push_mut(&file);
-                debug!(?storage_path, "Getting file");
-                let storage = self.get_storage();
-                let file = storage.open_file(self.id, &storage_path).await?;
+                let file = self.get_tarball(&name, &version, &file).await?;
                 Ok(RepoResponse::from(file))
             }
+            GetPath::Search => {
+                if let Some(err) = self.indexing_check(&request.authentication).await? {
+                    return Ok(err);
+                }
+                self.handle_search(&request).await
+            }
             _ => Ok(Response::builder()
                 .status(StatusCode::NOT_FOUND)
                 .body("Not Found".into())
@@ -289,7 +769,7 @@ impl Repository for NPMHostedRegistry {
         if path_as_string.starts_with(r#"-/user/org.couchdb.user:"#) {
             return super::login::couch_db::perform_login(self, request).await;
         } else if path_as_string.eq("-/v1/login") {
-            return super::login::web_login::perform_login(self, request).await;
+            return web_login::perform_login(self, request).await;
         }
         let Some(user) = request
             .authentication
@@ -311,6 +791,83 @@ impl Repository for NPMHostedRegistry {
 
         match command_header {
             NPMCommand::Publish => self.handle_publish(request).await,
+            NPMCommand::DistTag => self.handle_dist_tag_add(request).await,
+            NPMCommand::Unpublish => self.handle_unpublish_versions(request).await,
+            NPMCommand::Deprecate => self.handle_deprecate(request).await,
+        }
+    }
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        let path_as_string = request.path.to_string();
+        if path_as_string == "-/v1/login" {
+            return web_login::perform_login(self, request).await;
+        }
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match path_as_string.as_str() {
+            audit::BULK_ADVISORY_PATH => audit::bulk_advisories(&self.site, request).await,
+            audit::QUICK_AUDIT_PATH => audit::quick_audit(&self.site, request).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+    async fn handle_delete(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        let path_as_string = request.path.to_string();
+        if !request
+            .authentication
+            .has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        }
+        if let Some(DistTagsPath { name, tag }) = DistTagsPath::parse(&path_as_string) {
+            let Some(tag) = tag else {
+                return Ok(RepoResponse::unsupported_method_response(
+                    request.parts.method,
+                    self.get_type(),
+                ));
+            };
+            return self.handle_dist_tag_remove(&name, &tag).await;
+        }
+        if !self.publish_rules.read().unpublish_allowed {
+            return Ok(Self::unpublish_not_allowed());
+        }
+        match GetPath::try_from(StoragePath::from(strip_revision(&path_as_string)))? {
+            // `npm unpublish {name} --force`
+            GetPath::GetPackageInfo { name } => {
+                let Some(project) = self.get_project_from_key(&name).await? else {
+                    return Ok(package_not_found(&name));
+                };
+                self.unpublish_project(&project).await?;
+                Ok(no_content_response().into())
+            }
+            // Sent after the packument without the version. The version is normally already removed
+            GetPath::GetTar { name, version, .. } => {
+                let Some(project) = self.get_project_from_key(&name).await? else {
+                    return Ok(no_content_response().into());
+                };
+                if let Some(version) = self.get_project_version(project.id, &version).await? {
+                    self.unpublish_version(&project, &version).await?;
+                    self.after_unpublish(&project).await?;
+                }
+                Ok(no_content_response().into())
+            }
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
         }
     }
 }
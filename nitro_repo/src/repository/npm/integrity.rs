@@ -0,0 +1,258 @@
+//! Verifies the tarballs sent with `npm publish`
+//!
+//! - `dist.integrity` is a [Subresource Integrity](https://w3c.github.io/webappsec-subresource-integrity/) string. Such as `sha512-{base64}`
+//! - `dist.shasum` is the hex encoded sha1 of the tarball
+//! - `package/package.json` inside of the tarball must have the published name and version
+use std::{borrow::Cow, io::Read};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tracing::{debug, instrument};
+
+use super::{NPMRegistryError, types::request::PublishDist};
+/// The largest package.json that will be read from a tarball
+const MAX_PACKAGE_JSON_SIZE: u64 = 1024 * 1024;
+/// SRI hash algorithms. Ordered from weakest to strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum IntegrityAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+impl IntegrityAlgorithm {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "sha384" => Some(Self::Sha384),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1 => Sha1::digest(data).to_vec(),
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+/// Checks the data against a SRI string.
+///
+/// Only the strongest algorithm in the string is used. The data must match one of its hashes.
+/// Returns None if the string does not contain a supported algorithm
+fn integrity_matches(integrity: &str, data: &[u8]) -> Option<bool> {
+    let hashes: Vec<(IntegrityAlgorithm, &str)> = integrity
+        .split_whitespace()
+        .filter_map(|hash| {
+            let (algorithm, value) = hash.split_once('-')?;
+            // Options such as `sha512-{base64}?foo` are ignored
+            let value = value.split('?').next().unwrap_or_default();
+            Some((IntegrityAlgorithm::from_prefix(algorithm)?, value))
+        })
+        .collect();
+    let strongest = hashes.iter().map(|(algorithm, _)| *algorithm).max()?;
+    let digest = strongest.digest(data);
+    Some(
+        hashes
+            .iter()
+            .filter(|(algorithm, _)| *algorithm == strongest)
+            .any(|(_, value)| STANDARD.decode(value).is_ok_and(|value| value == digest)),
+    )
+}
+/// Verifies `dist.integrity` and `dist.shasum` against the decoded tarball.
+///
+/// Older clients do not send `dist.integrity`. So it is only checked if it is set
+#[instrument(skip(data))]
+pub fn verify_integrity(
+    attachment: &str,
+    dist: &PublishDist,
+    data: &[u8],
+) -> Result<(), NPMRegistryError> {
+    if !dist.integrity.is_empty() {
+        match integrity_matches(&dist.integrity, data) {
+            Some(true) => {}
+            Some(false) => {
+                return Err(NPMRegistryError::IntegrityMismatch {
+                    attachment: attachment.to_owned(),
+                    field: "dist.integrity",
+                });
+            }
+            None => {
+                return Err(NPMRegistryError::InvalidTarballContents {
+                    attachment: attachment.to_owned(),
+                    error: Cow::Owned(format!(
+                        "Unsupported integrity {}. Expected sha512",
+                        dist.integrity
+                    )),
+                });
+            }
+        }
+    }
+    let shasum = format!("{:x}", Sha1::digest(data));
+    if !dist.shasum.eq_ignore_ascii_case(&shasum) {
+        debug!(?shasum, expected = ?dist.shasum, "Shasum mismatch");
+        return Err(NPMRegistryError::IntegrityMismatch {
+            attachment: attachment.to_owned(),
+            field: "dist.shasum",
+        });
+    }
+    Ok(())
+}
+#[derive(Debug, Deserialize)]
+struct TarballPackageJson {
+    name: String,
+    version: String,
+}
+/// Reads the `package.json` at the root of the package inside the tarball.
+///
+/// npm puts everything inside of a `package` directory. Other tools use different names, so any top level directory is accepted
+fn read_package_json(data: &[u8]) -> Result<Option<TarballPackageJson>, std::io::Error> {
+    let mut archive = tar::Archive::new(GzDecoder::new(data));
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let is_package_json = {
+            let path = entry.path()?;
+            let mut components = path.components();
+            match (components.next(), components.next(), components.next()) {
+                (Some(_), Some(file), None) => file.as_os_str() == "package.json",
+                _ => false,
+            }
+        };
+        if !is_package_json {
+            continue;
+        }
+        let mut content = Vec::new();
+        entry
+            .take(MAX_PACKAGE_JSON_SIZE)
+            .read_to_end(&mut content)?;
+        return serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(std::io::Error::other);
+    }
+    Ok(None)
+}
+/// Checks that the `package.json` inside the tarball has the published name and version
+#[instrument(skip(data))]
+pub fn verify_package_json(
+    attachment: &str,
+    name: &str,
+    version: &str,
+    data: &[u8],
+) -> Result<(), NPMRegistryError> {
+    let package_json = match read_package_json(data) {
+        Ok(Some(package_json)) => package_json,
+        Ok(None) => {
+            return Err(NPMRegistryError::InvalidTarballContents {
+                attachment: attachment.to_owned(),
+                error: Cow::Borrowed("Missing package.json"),
+            });
+        }
+        Err(err) => {
+            return Err(NPMRegistryError::InvalidTarballContents {
+                attachment: attachment.to_owned(),
+                error: Cow::Owned(err.to_string()),
+            });
+        }
+    };
+    if package_json.name != name || package_json.version != version {
+        return Err(NPMRegistryError::PackageJsonMismatch {
+            expected: format!("{}@{}", name, version),
+            found: format!("{}@{}", package_json.name, package_json.version),
+        });
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use ahash::HashMap;
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+    fn tarball(package_json: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in [
+            ("package/index.js", "module.exports = {}"),
+            ("package/package.json", package_json),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+    fn dist_for(data: &[u8]) -> PublishDist {
+        PublishDist {
+            integrity: format!("sha512-{}", STANDARD.encode(Sha512::digest(data))),
+            shasum: format!("{:x}", Sha1::digest(data)),
+            tarball: "http://localhost/repositories/test/npm/mylib/-/mylib-1.0.0.tgz".to_owned(),
+            other: HashMap::default(),
+        }
+    }
+    #[test]
+    fn test_verify_integrity() {
+        let data = tarball(r#"{"name": "mylib", "version": "1.0.0"}"#);
+        let dist = dist_for(&data);
+        assert!(verify_integrity("mylib-1.0.0.tgz", &dist, &data).is_ok());
+
+        let other = tarball(r#"{"name": "mylib", "version": "1.0.1"}"#);
+        assert!(matches!(
+            verify_integrity("mylib-1.0.0.tgz", &dist, &other),
+            Err(NPMRegistryError::IntegrityMismatch {
+                field: "dist.integrity",
+                ..
+            })
+        ));
+
+        let mut dist_without_integrity = dist_for(&other);
+        dist_without_integrity.integrity = String::new();
+        dist_without_integrity.shasum = dist.shasum.clone();
+        assert!(matches!(
+            verify_integrity("mylib-1.0.0.tgz", &dist_without_integrity, &other),
+            Err(NPMRegistryError::IntegrityMismatch {
+                field: "dist.shasum",
+                ..
+            })
+        ));
+    }
+    #[test]
+    fn test_integrity_uses_strongest_algorithm() {
+        let data = b"mylib";
+        let sha1 = format!("sha1-{}", STANDARD.encode(Sha1::digest(data)));
+        let sha512 = format!("sha512-{}", STANDARD.encode(Sha512::digest(data)));
+        assert_eq!(integrity_matches(&sha1, data), Some(true));
+        assert_eq!(
+            integrity_matches(&format!("{} {}", sha1, sha512), data),
+            Some(true)
+        );
+        // The sha1 matches but the stronger sha512 does not
+        let bad_sha512 = format!("sha512-{}", STANDARD.encode(Sha512::digest(b"other")));
+        assert_eq!(
+            integrity_matches(&format!("{} {}", sha1, bad_sha512), data),
+            Some(false)
+        );
+        assert_eq!(integrity_matches("md5-abc", data), None);
+    }
+    #[test]
+    fn test_verify_package_json() {
+        let data = tarball(r#"{"name": "@acme/mylib", "version": "1.0.0"}"#);
+        assert!(verify_package_json("mylib-1.0.0.tgz", "@acme/mylib", "1.0.0", &data).is_ok());
+        assert!(matches!(
+            verify_package_json("mylib-1.0.0.tgz", "@acme/mylib", "2.0.0", &data),
+            Err(NPMRegistryError::PackageJsonMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_package_json("mylib-1.0.0.tgz", "@acme/mylib", "1.0.0", b"not a tarball"),
+            Err(NPMRegistryError::InvalidTarballContents { .. })
+        ));
+    }
+}
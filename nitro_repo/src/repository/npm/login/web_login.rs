@@ -1,20 +1,275 @@
+//! `npm login --auth-type=web`
+//!
+//! 1. npm sends POST `/-/v1/login`. The response contains a `loginUrl` and a `doneUrl`
+//! 2. npm opens the `loginUrl` in the browser. The user approves the login with their Nitro Repo session
+//! 3. npm polls the `doneUrl`. Until the login is approved it responds with `202 Accepted`.
+//!    Once approved the token is returned and the session is removed
+//!
+//! The `doneUrl` has its own secret. It is never sent to the browser, so the `loginUrl` can not be used to collect the token
+use std::fmt::Debug;
+
+use ahash::{HashMap, HashMapExt};
+use axum::{body::Body, response::Response};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use http::{
+    StatusCode,
+    header::{CONTENT_TYPE, RETRY_AFTER},
+};
+use nr_core::database::entities::user::auth_token::generate_token;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument, warn};
 
 use crate::repository::{
-    RepoResponse, RepositoryRequest,
-    npm::{NPMRegistryError, utils::NpmRegistryExt},
+    RepoResponse, Repository, RepositoryRequest,
+    npm::{NPMRegistryError, hosted::NPMHostedRegistry},
+    utils::{registry_url, site_base_url},
 };
 
-use super::LoginResponse;
+use super::couch_db::CouchDBLoginResponse;
+/// The path npm polls to check if the login has been approved
+pub const DONE_PATH: &str = "-/v1/done/";
+/// How long the user has to approve the login in the browser. In minutes
+const SESSION_TTL: i64 = 10;
+/// How long npm should wait before polling again. In seconds
+const RETRY_AFTER_SECONDS: &str = "2";
+/// The max number of logins that can be pending at once.
+///
+/// Logins can be started without authenticating. So the number has to be limited
+const MAX_PENDING_LOGINS: usize = 1024;
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WebLoginResponse {
     pub done_url: String,
     pub login_url: String,
 }
+#[derive(Clone)]
+pub enum WebLoginState {
+    /// Waiting for the user to approve the login in the browser
+    Pending,
+    /// The token that will be given to npm on the next poll
+    Approved { token: String },
+}
+impl Debug for WebLoginState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebLoginState::Pending => f.write_str("Pending"),
+            WebLoginState::Approved { .. } => f
+                .debug_struct("Approved")
+                .field("token", &"********")
+                .finish(),
+        }
+    }
+}
+#[derive(derive_more::Debug, Clone)]
+pub struct WebLoginSession {
+    /// Used as the source of the token. Such as `NPM CLI (npm/10.8.2 node/v20.17.0)`
+    pub source: String,
+    /// The secret in the `doneUrl`. Only npm knows it
+    #[debug(skip)]
+    pub done_id: String,
+    pub created: DateTime<FixedOffset>,
+    pub state: WebLoginState,
+}
+impl WebLoginSession {
+    pub fn is_expired(&self) -> bool {
+        Local::now().fixed_offset() - self.created > Duration::minutes(SESSION_TTL)
+    }
+}
+/// The ids of a new login
+#[derive(Clone)]
+pub struct NewWebLogin {
+    /// Used in the `loginUrl`. Opened in the browser
+    pub login_id: String,
+    /// Used in the `doneUrl`. Polled by npm
+    pub done_id: String,
+}
+/// Web logins that have been started but not finished. Keyed by the login id
+///
+/// Sessions are kept in memory. Restarting Nitro Repo cancels any pending logins
+#[derive(Debug, Default)]
+pub struct WebLoginSessions(Mutex<HashMap<String, WebLoginSession>>);
+impl WebLoginSessions {
+    fn remove_expired(sessions: &mut HashMap<String, WebLoginSession>) {
+        sessions.retain(|_, session| !session.is_expired());
+    }
+    /// Starts a new login. Returns None if [MAX_PENDING_LOGINS] logins are already pending
+    pub fn create(&self, source: String) -> Option<NewWebLogin> {
+        let mut sessions = self.0.lock();
+        Self::remove_expired(&mut sessions);
+        if sessions.len() >= MAX_PENDING_LOGINS {
+            return None;
+        }
+        let login = NewWebLogin {
+            login_id: generate_token(),
+            done_id: generate_token(),
+        };
+        sessions.insert(
+            login.login_id.clone(),
+            WebLoginSession {
+                source,
+                done_id: login.done_id.clone(),
+                created: Local::now().fixed_offset(),
+                state: WebLoginState::Pending,
+            },
+        );
+        Some(login)
+    }
+    pub fn get(&self, id: &str) -> Option<WebLoginSession> {
+        let mut sessions = self.0.lock();
+        Self::remove_expired(&mut sessions);
+        sessions.get(id).cloned()
+    }
+    /// Marks the session as approved. Returns false if the session does not exist or was already approved
+    pub fn approve(&self, id: &str, token: String) -> bool {
+        let mut sessions = self.0.lock();
+        Self::remove_expired(&mut sessions);
+        match sessions.get_mut(id) {
+            Some(session) if matches!(session.state, WebLoginState::Pending) => {
+                session.state = WebLoginState::Approved { token };
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Returns the state of the session with the done id. Approved sessions are removed so the token is only given out once
+    pub fn poll(&self, done_id: &str) -> Option<WebLoginState> {
+        let mut sessions = self.0.lock();
+        Self::remove_expired(&mut sessions);
+        let (login_id, session) = sessions
+            .iter()
+            .find(|(_, session)| session.done_id == done_id)?;
+        let state = session.state.clone();
+        if let WebLoginState::Approved { .. } = state {
+            let login_id = login_id.clone();
+            sessions.remove(&login_id);
+        }
+        Some(state)
+    }
+}
+/// Handles `/-/v1/login`.
+///
+/// Responds with the page the user approves the login on and the URL npm polls
+#[instrument(name = "npm_web_login")]
 pub async fn perform_login(
-    repository: &impl NpmRegistryExt,
+    repository: &NPMHostedRegistry,
     request: RepositoryRequest,
 ) -> Result<RepoResponse, NPMRegistryError> {
-    // TODO: Implement Web Login
-    Ok(LoginResponse::UnsupportedLogin.into())
+    let Some(source) = request
+        .user_agent_as_string()?
+        .map(|header| format!("NPM CLI ({})", header))
+    else {
+        return Ok(RepoResponse::forbidden());
+    };
+    let Some(login) = repository.web_logins.create(source) else {
+        warn!(repository = ?repository.id(), "Too many pending web logins");
+        return Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(RETRY_AFTER, (SESSION_TTL * 60).to_string())
+            .body(Body::from("Too many pending logins. Try again later"))
+            .unwrap()
+            .into());
+    };
+    let site = repository.site();
+    let storage_name = repository
+        .get_storage()
+        .storage_config()
+        .storage_config
+        .storage_name
+        .clone();
+    let response = WebLoginResponse {
+        login_url: format!(
+            "{}/npm/login/{}/{}",
+            site_base_url(&site, &request.parts),
+            repository.id(),
+            login.login_id
+        ),
+        done_url: format!(
+            "{}/{}{}",
+            registry_url(&site, &request.parts, &storage_name, &repository.name()),
+            DONE_PATH,
+            login.done_id
+        ),
+    };
+    info!(repository = ?repository.id(), "Started web login");
+    let body = serde_json::to_string(&response)?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+        .into())
+}
+/// Handles `/-/v1/done/{done_id}`.
+///
+/// Responds with `202 Accepted` until the login is approved
+#[instrument(name = "npm_web_login_done", skip(done_id))]
+pub async fn login_done(
+    repository: &NPMHostedRegistry,
+    done_id: &str,
+) -> Result<RepoResponse, NPMRegistryError> {
+    match repository.web_logins.poll(done_id) {
+        Some(WebLoginState::Pending) => {
+            debug!("Web login is still pending");
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .header(RETRY_AFTER, RETRY_AFTER_SECONDS)
+                .body(Body::empty())
+                .unwrap()
+                .into())
+        }
+        Some(WebLoginState::Approved { token }) => {
+            let body = serde_json::to_string(&CouchDBLoginResponse::from(token))?;
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+                .into())
+        }
+        None => Ok(RepoResponse::basic_text_response(
+            StatusCode::NOT_FOUND,
+            "Login session not found or expired",
+        )),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_web_login_sessions() {
+        let sessions = WebLoginSessions::default();
+        let login = sessions.create("NPM CLI (test)".to_owned()).unwrap();
+        assert_ne!(login.login_id, login.done_id);
+        assert!(matches!(
+            sessions.poll(&login.done_id),
+            Some(WebLoginState::Pending)
+        ));
+        // The login id is opened in the browser. It can not be used to collect the token
+        assert!(sessions.poll(&login.login_id).is_none());
+        assert!(sessions.approve(&login.login_id, "token".to_owned()));
+        assert!(!sessions.approve(&login.login_id, "other".to_owned()));
+        assert!(sessions.poll(&login.login_id).is_none());
+        match sessions.poll(&login.done_id) {
+            Some(WebLoginState::Approved { token }) => assert_eq!(token, "token"),
+            state => panic!("Expected approved session. Got {:?}", state),
+        }
+        assert!(sessions.poll(&login.done_id).is_none());
+        assert!(sessions.get(&login.login_id).is_none());
+        assert!(!sessions.approve("missing", "token".to_owned()));
+    }
+    #[test]
+    fn test_web_login_sessions_limit() {
+        let sessions = WebLoginSessions::default();
+        let logins: Vec<NewWebLogin> = (0..MAX_PENDING_LOGINS)
+            .map(|_| sessions.create("NPM CLI (test)".to_owned()).unwrap())
+            .collect();
+        assert!(sessions.create("NPM CLI (test)".to_owned()).is_none());
+
+        // Finished logins make room for new ones
+        assert!(sessions.approve(&logins[0].login_id, "token".to_owned()));
+        assert!(sessions.poll(&logins[0].done_id).is_some());
+        assert!(sessions.create("NPM CLI (test)".to_owned()).is_some());
+        assert!(sessions.create("NPM CLI (test)".to_owned()).is_none());
+    }
 }
@@ -9,15 +9,21 @@ use ahash::HashMap;
 use base64::DecodeError;
 use config::RepositoryConfigType;
 use futures::future::BoxFuture;
+use group::NPMGroupRegistry;
 use hosted::NPMHostedRegistry;
 use nr_core::database::entities::repository::{DBRepository, DBRepositoryConfig};
 use nr_macros::DynRepositoryHandler;
 use nr_storage::DynStorage;
+use proxy::NPMProxyRegistry;
 use tracing::debug;
 use types::InvalidNPMPackageName;
 
+pub mod audit;
+pub mod group;
 pub mod hosted;
+pub mod integrity;
 pub mod login;
+pub mod proxy;
 pub mod types;
 pub mod utils;
 use crate::{
@@ -34,6 +40,8 @@ pub use configs::*;
 #[repository_handler(error=NPMRegistryError)]
 pub enum NPMRegistry {
     Hosted(hosted::NPMHostedRegistry),
+    Proxy(NPMProxyRegistry),
+    Group(NPMGroupRegistry),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -57,6 +65,20 @@ pub enum NPMRegistryError {
     InvalidPackageAttachment(DecodeError),
     #[error("Only one release or attachment can be uploaded at a time")]
     OnlyOneReleaseOrAttachmentAtATime,
+    #[error("Attachment {attachment} does not match the {field} of the version")]
+    IntegrityMismatch {
+        attachment: String,
+        field: &'static str,
+    },
+    #[error("Invalid tarball {attachment}. Error: {error}")]
+    InvalidTarballContents {
+        attachment: String,
+        error: Cow<'static, str>,
+    },
+    #[error("The package.json in the tarball is for {found}. Expected {expected}")]
+    PackageJsonMismatch { expected: String, found: String },
+    #[error("The audit request is larger than {0} bytes")]
+    AuditBodyTooLarge(u64),
     #[error("{0}")]
     Other(Box<dyn IntoErrorResponse>),
 }
@@ -81,6 +103,7 @@ impl_from_error_for_other!(std::io::Error);
 impl_from_error_for_other!(AuthenticationError);
 impl_from_error_for_other!(RepositoryHandlerError);
 impl_from_error_for_other!(nr_storage::StorageError);
+impl_from_error_for_other!(reqwest::Error);
 
 impl IntoErrorResponse for NPMRegistryError {
     fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
@@ -101,6 +124,10 @@ impl IntoResponse for NPMRegistryError {
                 .status(StatusCode::NOT_FOUND)
                 .body("Invalid GET request".into())
                 .unwrap(),
+            too_large @ NPMRegistryError::AuditBodyTooLarge(_) => Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(too_large.to_string().into())
+                .unwrap(),
             NPMRegistryError::Other(other) => other.into_response_boxed(),
             bad_request => {
                 debug!("Bad Request: {:?}", bad_request);
@@ -121,7 +148,10 @@ impl RepositoryType for NpmRegistryType {
     }
 
     fn config_types(&self) -> Vec<&str> {
-        vec![NPMRegistryConfigType::get_type_static()]
+        vec![
+            NPMRegistryConfigType::get_type_static(),
+            NPMPublishRulesConfigType::get_type_static(),
+        ]
     }
 
     fn get_description(&self) -> RepositoryTypeDescription {
@@ -191,6 +221,16 @@ impl RepositoryType for NpmRegistryType {
                     let maven_hosted = NPMHostedRegistry::load(website, storage, repo).await?;
                     Ok(NPMRegistry::Hosted(maven_hosted).into())
                 }
+                NPMRegistryConfig::Proxy(proxy_config) => {
+                    let proxy =
+                        NPMProxyRegistry::load(website, storage, repo, proxy_config).await?;
+                    Ok(NPMRegistry::Proxy(proxy).into())
+                }
+                NPMRegistryConfig::Group(group_config) => {
+                    let group =
+                        NPMGroupRegistry::load(website, storage, repo, group_config).await?;
+                    Ok(NPMRegistry::Group(group).into())
+                }
             }
         })
     }
@@ -0,0 +1,401 @@
+//! A NPM Registry that proxies an upstream registry. Such as registry.npmjs.org
+//!
+//! Tarballs are cached in storage forever. Packuments are cached for [NPMProxyConfig::packument_ttl]
+use std::sync::{
+    Arc,
+    atomic::{self, AtomicBool},
+};
+
+use axum::response::IntoResponse;
+use bytes::Bytes;
+use chrono::{Duration, Local};
+use derive_more::derive::Deref;
+use http::{StatusCode, header::ACCEPT};
+use nr_core::{
+    database::entities::repository::{DBRepository, DBRepositoryConfig},
+    repository::{
+        Visibility,
+        config::RepositoryConfigType,
+        proxy_url::{InvalidProxyHeader, ProxyCredentials, ProxyRoute, ProxyURL},
+    },
+    storage::StoragePath,
+};
+use nr_storage::{DynStorage, FileContent, Storage, StorageFile};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use super::{
+    NPMRegistryConfig, NPMRegistryConfigType, NPMRegistryError, audit,
+    types::request::GetPath,
+    utils::{
+        NpmRegistryExt, find_version, package_not_found, rewrite_tarball_urls, version_not_found,
+    },
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        utils::{RepositoryExt, http_client, json_response, registry_url},
+    },
+};
+/// The public npm registry. Used when no routes are configured
+pub const NPMJS_REGISTRY: &str = "https://registry.npmjs.org";
+/// The file the upstream packument is cached in. Inside of the package directory
+const PACKUMENT_FILE: &str = "-/packument.json";
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NPMProxyRoute {
+    pub url: ProxyURL,
+    pub name: Option<String>,
+    /// Credentials and headers sent to the upstream
+    #[serde(default)]
+    pub credentials: ProxyCredentials,
+}
+impl ProxyRoute for NPMProxyRoute {
+    fn url(&self) -> &ProxyURL {
+        &self.url
+    }
+    fn credentials(&self) -> &ProxyCredentials {
+        &self.credentials
+    }
+    fn credentials_mut(&mut self) -> &mut ProxyCredentials {
+        &mut self.credentials
+    }
+}
+impl NPMProxyRoute {
+    pub fn npmjs() -> Self {
+        Self {
+            url: ProxyURL::try_from(NPMJS_REGISTRY.to_owned()).expect("Invalid npmjs URL"),
+            name: Some("npmjs".to_owned()),
+            credentials: ProxyCredentials::default(),
+        }
+    }
+    /// Creates a GET request with the route's credentials
+    pub fn get(
+        &self,
+        client: &reqwest::Client,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        let request = client.get(url);
+        match self.credentials.header_map() {
+            Ok(headers) => request.headers(headers),
+            Err(err) => {
+                warn!(?err, route = ?self.name, "Invalid credentials for route");
+                request
+            }
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NPMProxyConfig {
+    /// The upstream registries. Tried in order. If empty registry.npmjs.org is used
+    #[serde(default)]
+    pub routes: Vec<NPMProxyRoute>,
+    /// How long a packument is served from the cache before it is fetched again. In seconds
+    #[serde(default = "default_packument_ttl")]
+    #[schemars(title = "Packument TTL")]
+    pub packument_ttl: u64,
+}
+fn default_packument_ttl() -> u64 {
+    5 * 60
+}
+impl Default for NPMProxyConfig {
+    fn default() -> Self {
+        Self {
+            routes: vec![NPMProxyRoute::npmjs()],
+            packument_ttl: default_packument_ttl(),
+        }
+    }
+}
+impl NPMProxyConfig {
+    pub fn routes(&self) -> Vec<NPMProxyRoute> {
+        if self.routes.is_empty() {
+            vec![NPMProxyRoute::npmjs()]
+        } else {
+            self.routes.clone()
+        }
+    }
+    pub fn validate(&self) -> Result<(), InvalidProxyHeader> {
+        ProxyCredentials::validate_routes(&self.routes)
+    }
+    pub fn redact_secrets(&mut self) {
+        ProxyCredentials::redact_routes(&mut self.routes);
+    }
+    pub fn restore_secrets(&mut self, old: &NPMProxyConfig) {
+        ProxyCredentials::restore_redacted_routes(&mut self.routes, &old.routes);
+    }
+}
+#[derive(derive_more::Debug)]
+pub struct NPMProxyInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+    pub config: RwLock<NPMProxyConfig>,
+}
+#[derive(Debug, Clone, Deref)]
+pub struct NPMProxyRegistry(Arc<NPMProxyInner>);
+impl NPMProxyRegistry {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+        config: NPMProxyConfig,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(NPMProxyInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+            config: RwLock::new(config),
+        })))
+    }
+    fn registry_url(&self, request: &RepositoryRequest) -> String {
+        registry_url(
+            &self.site,
+            &request.parts,
+            &self.storage.storage_config().storage_config.storage_name,
+            &self.repository.name,
+        )
+    }
+    /// Requests the path from each route. Returns the body of the first successful response
+    #[instrument(skip(self))]
+    async fn request_from_upstream(&self, path: &str) -> Result<Option<Bytes>, NPMRegistryError> {
+        let http_client = http_client();
+        let routes = self.config.read().routes();
+        for route in routes {
+            let url = format!("{}/{}", route.url, path);
+            let response = match route
+                .get(&http_client, &url)
+                .header(ACCEPT, "application/json")
+                .send()
+                .await
+            {
+                Ok(ok) => ok,
+                Err(err) => {
+                    warn!(?err, ?url, "Failed to send request");
+                    continue;
+                }
+            };
+            if response.status().is_success() {
+                return Ok(Some(response.bytes().await?));
+            }
+            debug!(?url, status = ?response.status(), "Upstream does not have the path");
+        }
+        Ok(None)
+    }
+    /// Returns the upstream packument. Served from storage until it is older than the TTL.
+    ///
+    /// If no route returns the packument the cached packument is used
+    #[instrument(skip(self))]
+    pub async fn get_packument(&self, name: &str) -> Result<Option<Value>, NPMRegistryError> {
+        let path = StoragePath::from(format!("{}/{}", name, PACKUMENT_FILE));
+        let ttl = Duration::seconds(self.config.read().packument_ttl as i64);
+        let cached = match self.storage.open_file(self.id, &path).await? {
+            Some(file) => match file.file() {
+                Some((content, meta)) => {
+                    let is_stale = Local::now().fixed_offset() - *meta.modified() > ttl;
+                    let bytes = content
+                        .read_to_vec(meta.file_type.file_size as usize)
+                        .await?;
+                    if !is_stale {
+                        return Ok(Some(serde_json::from_slice(&bytes)?));
+                    }
+                    Some(bytes)
+                }
+                None => None,
+            },
+            None => None,
+        };
+        match self.request_from_upstream(name).await {
+            Ok(Some(bytes)) => {
+                let packument: Value = serde_json::from_slice(&bytes)?;
+                self.storage
+                    .save_file(self.id, FileContent::Bytes(bytes), &path)
+                    .await?;
+                Ok(Some(packument))
+            }
+            Ok(None) => match cached {
+                Some(cached) => {
+                    warn!(
+                        ?name,
+                        "No route returned the packument. Serving cached packument"
+                    );
+                    Ok(Some(serde_json::from_slice(&cached)?))
+                }
+                None => Ok(None),
+            },
+            Err(err) => {
+                let Some(cached) = cached else {
+                    return Err(err);
+                };
+                warn!(
+                    ?err,
+                    ?name,
+                    "Failed to refresh packument. Serving cached packument"
+                );
+                Ok(Some(serde_json::from_slice(&cached)?))
+            }
+        }
+    }
+    /// Opens the tarball from storage. If it is not cached it is downloaded from the upstream
+    #[instrument(skip(self))]
+    pub async fn get_tarball(
+        &self,
+        name: &str,
+        file: &str,
+    ) -> Result<Option<StorageFile>, NPMRegistryError> {
+        let upstream_path = format!("{}/-/{}", name, file);
+        let path = StoragePath::from(upstream_path.as_str());
+        if let Some(file) = self.storage.open_file(self.id, &path).await? {
+            return Ok(Some(file));
+        }
+        let Some(bytes) = self.request_from_upstream(&upstream_path).await? else {
+            debug!(?file, "Tarball not found upstream");
+            return Ok(None);
+        };
+        self.storage
+            .save_file(self.id, FileContent::Bytes(bytes), &path)
+            .await?;
+        Ok(self.storage.open_file(self.id, &path).await?)
+    }
+}
+impl NpmRegistryExt for NPMProxyRegistry {}
+impl RepositoryExt for NPMProxyRegistry {}
+impl Repository for NPMProxyRegistry {
+    type Error = NPMRegistryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "npm"
+    }
+    fn full_type(&self) -> &'static str {
+        "npm/proxy"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![NPMRegistryConfigType::get_type_static()]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "npm/proxy"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        let Some(npm_config_db) = DBRepositoryConfig::<NPMRegistryConfig>::get_config(
+            self.id,
+            NPMRegistryConfigType::get_type_static(),
+            self.site.as_ref(),
+        )
+        .await?
+        else {
+            return Err(RepositoryFactoryError::MissingConfig(
+                NPMRegistryConfigType::get_type_static(),
+            ));
+        };
+        match npm_config_db.value.0 {
+            NPMRegistryConfig::Proxy(proxy_config) => {
+                *self.config.write() = proxy_config;
+                Ok(())
+            }
+            _ => Err(RepositoryFactoryError::InvalidConfig(
+                NPMRegistryConfigType::get_type_static(),
+                "Expected Proxy Config".into(),
+            )),
+        }
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        let get_path = match GetPath::try_from(request.path.clone()) {
+            Ok(ok) => ok,
+            Err(err) => return Ok(err.into_response().into()),
+        };
+        match get_path {
+            GetPath::GetPackageInfo { name } => {
+                let Some(mut packument) = self.get_packument(&name).await? else {
+                    return Ok(package_not_found(&name));
+                };
+                rewrite_tarball_urls(&mut packument, &self.registry_url(&request), &name);
+                json_response(StatusCode::OK, &packument)
+            }
+            GetPath::VersionInfo { name, version } => {
+                let Some(mut packument) = self.get_packument(&name).await? else {
+                    return Ok(package_not_found(&name));
+                };
+                rewrite_tarball_urls(&mut packument, &self.registry_url(&request), &name);
+                match find_version(&packument, &version) {
+                    Some(version) => json_response(StatusCode::OK, version),
+                    None => Ok(version_not_found(&name, &version)),
+                }
+            }
+            GetPath::GetTar { name, file, .. } => {
+                Ok(RepoResponse::from(self.get_tarball(&name, &file).await?))
+            }
+            _ => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, NPMRegistryError> {
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match request.path.to_string().as_str() {
+            audit::BULK_ADVISORY_PATH => audit::bulk_advisories(&self.site, request).await,
+            audit::QUICK_AUDIT_PATH => audit::quick_audit(&self.site, request).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The abbreviated packument. Also called the "corgi" document.
+//!
+//! Package managers request it with `Accept: application/vnd.npm.install-v1+json`.
+//! It only contains what is needed to install the package
+//!
+//! https://github.com/npm/registry/blob/main/docs/responses/package-metadata.md#abbreviated-metadata-format
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ahash::{HashMap, HashMapExt};
+use bytes::Bytes;
+use http::{HeaderMap, header::ACCEPT};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    NpmRegistryPackageResponse,
+    request::{PublishDist, PublishVersion},
+};
+pub const ABBREVIATED_CONTENT_TYPE: &str = "application/vnd.npm.install-v1+json";
+/// The fields of a version that are kept in the abbreviated packument
+const ABBREVIATED_VERSION_FIELDS: &[&str] = &[
+    "deprecated",
+    "dependencies",
+    "optionalDependencies",
+    "devDependencies",
+    "bundleDependencies",
+    "peerDependencies",
+    "peerDependenciesMeta",
+    "acceptDependencies",
+    "bin",
+    "directories",
+    "engines",
+    "os",
+    "cpu",
+    "funding",
+    "license",
+    "_hasShrinkwrap",
+    "hasInstallScript",
+];
+/// The max number of packages kept in the [AbbreviatedPackumentCache]
+const MAX_CACHED_PACKAGES: usize = 1024;
+/// Scripts that are run when the package is installed
+const INSTALL_SCRIPTS: &[&str] = &["preinstall", "install", "postinstall"];
+/// Returns true if the client accepts the abbreviated packument.
+///
+/// npm sends `application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*`
+pub fn accepts_abbreviated(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .any(|media_type| {
+            let mut params = media_type.split(';').map(str::trim);
+            if params.next() != Some(ABBREVIATED_CONTENT_TYPE) {
+                return false;
+            }
+            // `q=0` means the client does not accept it
+            !params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|quality| quality.parse::<f32>().ok())
+                    .is_some_and(|quality| quality <= 0.0)
+            })
+        })
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbbreviatedPackument {
+    pub name: String,
+    pub modified: String,
+    #[serde(rename = "dist-tags")]
+    pub dist_tags: HashMap<String, String>,
+    pub versions: HashMap<String, AbbreviatedVersion>,
+}
+impl From<&NpmRegistryPackageResponse> for AbbreviatedPackument {
+    fn from(packument: &NpmRegistryPackageResponse) -> Self {
+        Self {
+            name: packument.name.clone(),
+            modified: packument.time.get("modified").cloned().unwrap_or_default(),
+            dist_tags: packument.dist_tags.clone(),
+            versions: packument
+                .versions
+                .iter()
+                .map(|(version, data)| (version.clone(), AbbreviatedVersion::from(data)))
+                .collect(),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbbreviatedVersion {
+    pub name: String,
+    pub version: String,
+    pub dist: PublishDist,
+    #[serde(flatten)]
+    pub fields: HashMap<String, Value>,
+}
+impl From<&PublishVersion> for AbbreviatedVersion {
+    fn from(version: &PublishVersion) -> Self {
+        let mut fields = HashMap::with_capacity(ABBREVIATED_VERSION_FIELDS.len());
+        for field in ABBREVIATED_VERSION_FIELDS {
+            if let Some(value) = version.extra.get(*field) {
+                fields.insert((*field).to_owned(), value.clone());
+            }
+        }
+        // Older clients do not set `hasInstallScript` when publishing
+        if !fields.contains_key("hasInstallScript") {
+            let has_install_script = version
+                .extra
+                .get("scripts")
+                .and_then(Value::as_object)
+                .is_some_and(|scripts| {
+                    INSTALL_SCRIPTS
+                        .iter()
+                        .any(|script| scripts.contains_key(*script))
+                });
+            if has_install_script {
+                fields.insert("hasInstallScript".to_owned(), Value::Bool(true));
+            }
+        }
+        Self {
+            name: version.name.to_string(),
+            version: version.version.clone(),
+            dist: version.dist.clone(),
+            fields,
+        }
+    }
+}
+/// The rendered abbreviated packuments. Keyed by the package name
+///
+/// Entries must be invalidated whenever the versions, dist-tags, or deprecations of the package change.
+/// Once full the cache is cleared
+#[derive(Debug, Default)]
+pub struct AbbreviatedPackumentCache {
+    packuments: RwLock<HashMap<String, Bytes>>,
+    /// Incremented on every invalidation. Only changed while holding the write lock of `packuments`
+    generation: AtomicU64,
+}
+impl AbbreviatedPackumentCache {
+    pub fn get(&self, name: &str) -> Option<Bytes> {
+        self.packuments.read().get(name).cloned()
+    }
+    /// Read before building a packument and passed to [AbbreviatedPackumentCache::insert]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+    /// Does nothing if the cache was invalidated after `generation` was read.
+    /// The packument might have been built from data that changed
+    pub fn insert(&self, name: &str, packument: Bytes, generation: u64) {
+        let mut cache = self.packuments.write();
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+        if cache.len() >= MAX_CACHED_PACKAGES && !cache.contains_key(name) {
+            cache.clear();
+        }
+        cache.insert(name.to_owned(), packument);
+    }
+    pub fn invalidate(&self, name: &str) {
+        let mut cache = self.packuments.write();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        cache.remove(name);
+    }
+    pub fn clear(&self) {
+        let mut cache = self.packuments.write();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        cache.clear();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_accepts_abbreviated() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_abbreviated(&headers));
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static(
+                "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*",
+            ),
+        );
+        assert!(accepts_abbreviated(&headers));
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.npm.install-v1+json;q=0, application/json"),
+        );
+        assert!(!accepts_abbreviated(&headers));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        assert!(!accepts_abbreviated(&headers));
+    }
+    #[test]
+    fn test_abbreviated_version() {
+        let version: PublishVersion = serde_json::from_value(json!({
+            "name": "mylib",
+            "version": "1.0.0",
+            "description": "Not needed to install",
+            "readme": "# mylib",
+            "scripts": { "postinstall": "node setup.js", "test": "jest" },
+            "dependencies": { "left-pad": "^1.0.0" },
+            "deprecated": "Use 2.0.0",
+            "dist": {
+                "integrity": "sha512-abc",
+                "shasum": "abc",
+                "tarball": "http://localhost/repositories/test/npm/mylib/-/mylib-1.0.0.tgz"
+            },
+            "_id": "mylib@1.0.0",
+            "_nodeVersion": "20.17.0",
+            "_npmVersion": "10.8.2"
+        }))
+        .unwrap();
+        let abbreviated = serde_json::to_value(AbbreviatedVersion::from(&version)).unwrap();
+        assert_eq!(
+            abbreviated,
+            json!({
+                "name": "mylib",
+                "version": "1.0.0",
+                "dependencies": { "left-pad": "^1.0.0" },
+                "deprecated": "Use 2.0.0",
+                "hasInstallScript": true,
+                "dist": {
+                    "integrity": "sha512-abc",
+                    "shasum": "abc",
+                    "tarball": "http://localhost/repositories/test/npm/mylib/-/mylib-1.0.0.tgz"
+                }
+            })
+        );
+    }
+    #[test]
+    fn test_cache_generation() {
+        let cache = AbbreviatedPackumentCache::default();
+        let generation = cache.generation();
+        cache.insert("mylib", Bytes::from_static(b"1"), generation);
+        assert_eq!(cache.get("mylib"), Some(Bytes::from_static(b"1")));
+
+        // Built before the package changed
+        let generation = cache.generation();
+        cache.invalidate("mylib");
+        cache.insert("mylib", Bytes::from_static(b"old"), generation);
+        assert_eq!(cache.get("mylib"), None);
+    }
+}
@@ -1,4 +1,6 @@
+pub mod abbreviated;
 pub mod request;
+pub mod search;
 
 use ahash::HashMap;
 use chrono::{DateTime, FixedOffset};
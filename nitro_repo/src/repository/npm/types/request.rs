@@ -22,6 +22,12 @@ use super::NPMPackageName;
 pub enum NPMCommand {
     #[strum(serialize = "publish")]
     Publish,
+    #[strum(serialize = "dist-tag")]
+    DistTag,
+    #[strum(serialize = "unpublish")]
+    Unpublish,
+    #[strum(serialize = "deprecate", serialize = "undeprecate")]
+    Deprecate,
 }
 impl TryFrom<&HeaderValue> for NPMCommand {
     type Error = InvalidNPMCommand;
@@ -119,6 +125,27 @@ impl PublishDist {
     }
 }
 impl PublishVersion {
+    /// The `description` field of the package.json
+    pub fn description(&self) -> Option<&str> {
+        self.extra
+            .get("description")
+            .and_then(Value::as_str)
+            .filter(|description| !description.is_empty())
+    }
+    /// The `keywords` field of the package.json
+    pub fn keywords(&self) -> Vec<String> {
+        self.extra
+            .get("keywords")
+            .and_then(Value::as_array)
+            .map(|keywords| {
+                keywords
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
     pub fn new_project(
         &self,
         save_path: String,
@@ -134,8 +161,8 @@ impl PublishVersion {
             repository: repository_id,
             latest_pre_release: None,
             latest_release: None,
-            description: None,
-            tags: vec![],
+            description: self.description().map(str::to_owned),
+            tags: self.keywords(),
         })
     }
     pub fn new_version(
@@ -183,6 +210,7 @@ impl GetPath {
     /// - `@{scope}/{package}` - Get package info
     /// - `@{scope}/{package}/{version}` - Get version info
     /// - `@{scope}/{package}/-/{scope}/{package}-{version}.tgz` - Get file
+    /// - `@{scope}/{package}/-/{package}-{version}.tgz` - Get file
     pub fn scoped_package_call(
         components: Vec<StoragePathComponent>,
     ) -> Result<Self, NPMRegistryError> {
@@ -199,6 +227,17 @@ impl GetPath {
             debug!(?name, ?version, "Version info");
             return Ok(GetPath::VersionInfo { name, version });
         }
+        if length == 4 && components[2] == "-" {
+            // The format used by registry.npmjs.org
+            let file = components[3].to_string();
+            let version =
+                extract_version_from_file(&file).ok_or(NPMRegistryError::InvalidGetRequest)?;
+            return Ok(GetPath::GetTar {
+                name,
+                version,
+                file,
+            });
+        }
         if length == 5 {
             let file = components[4].to_string();
             let version =
@@ -250,6 +289,9 @@ impl TryFrom<StoragePath> for GetPath {
 
     fn try_from(value: StoragePath) -> Result<Self, Self::Error> {
         let as_string = value.to_string();
+        if as_string == "-/v1/search" {
+            return Ok(GetPath::Search);
+        }
         let components: Vec<_> = value.into();
         if as_string.starts_with('@') {
             GetPath::scoped_package_call(components)
@@ -258,6 +300,37 @@ impl TryFrom<StoragePath> for GetPath {
         }
     }
 }
+/// `-/package/{name}/dist-tags` or `-/package/{name}/dist-tags/{tag}`
+///
+/// The `/` in a scoped name may be encoded as `%2f`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistTagsPath {
+    pub name: String,
+    pub tag: Option<String>,
+}
+impl DistTagsPath {
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path.strip_prefix("-/package/")?.trim_end_matches('/');
+        let (name, tag) = match path.strip_suffix("/dist-tags") {
+            Some(name) => (name, None),
+            None => {
+                let (name, tag) = path.rsplit_once('/')?;
+                (name.strip_suffix("/dist-tags")?, Some(tag.to_owned()))
+            }
+        };
+        if name.is_empty() {
+            return None;
+        }
+        let name = name.replace("%2f", "/").replace("%2F", "/");
+        Some(Self { name, tag })
+    }
+}
+/// Removes the `/-rev/{rev}` suffix npm adds when writing to a package.
+///
+/// Revisions are not tracked so the value is ignored
+pub fn strip_revision(path: &str) -> &str {
+    path.split_once("/-rev/").map_or(path, |(path, _)| path)
+}
 pub fn extract_version_from_file(file: &str) -> Option<String> {
     let parts: Vec<_> = file.split('-').collect();
     if let Some(version) = parts.last() {
@@ -271,7 +344,42 @@ pub fn extract_version_from_file(file: &str) -> Option<String> {
 pub mod tests {
     use nr_core::storage::StoragePath;
 
-    use super::GetPath;
+    use super::{DistTagsPath, GetPath, strip_revision};
+    #[test]
+    pub fn test_strip_revision() {
+        assert_eq!(strip_revision("mylib/-rev/3-abc"), "mylib");
+        assert_eq!(
+            strip_revision("@nr/mylib/-/mylib-1.0.0.tgz/-rev/undefined"),
+            "@nr/mylib/-/mylib-1.0.0.tgz"
+        );
+        assert_eq!(strip_revision("@nr/mylib"), "@nr/mylib");
+    }
+    #[test]
+    pub fn test_dist_tags_path() {
+        assert_eq!(
+            DistTagsPath::parse("-/package/mylib/dist-tags"),
+            Some(DistTagsPath {
+                name: "mylib".to_string(),
+                tag: None,
+            })
+        );
+        assert_eq!(
+            DistTagsPath::parse("-/package/@nr/mylib/dist-tags/beta"),
+            Some(DistTagsPath {
+                name: "@nr/mylib".to_string(),
+                tag: Some("beta".to_string()),
+            })
+        );
+        assert_eq!(
+            DistTagsPath::parse("-/package/@nr%2fmylib/dist-tags/next"),
+            Some(DistTagsPath {
+                name: "@nr/mylib".to_string(),
+                tag: Some("next".to_string()),
+            })
+        );
+        assert_eq!(DistTagsPath::parse("-/package/mylib/collaborators"), None);
+        assert_eq!(DistTagsPath::parse("mylib/-/mylib-1.0.0.tgz"), None);
+    }
     #[test]
     pub fn tests() {
         let tests = vec![
@@ -283,6 +391,14 @@ pub mod tests {
                     file: "mylib-1.0.0.tgz".to_string(),
                 },
             ),
+            (
+                StoragePath::from("@nr/mylib/-/mylib-1.0.0.tgz"),
+                GetPath::GetTar {
+                    name: "@nr/mylib".to_string(),
+                    version: "1.0.0".to_string(),
+                    file: "mylib-1.0.0.tgz".to_string(),
+                },
+            ),
             (
                 StoragePath::from("mylib/-/mylib-1.0.0.tgz"),
                 GetPath::GetTar {
@@ -312,6 +428,7 @@ pub mod tests {
                     file: "npm-check-updates-11.0.3.tgz".to_string(),
                 },
             ),
+            (StoragePath::from("-/v1/search"), GetPath::Search),
         ];
         for (path, expected) in tests {
             let get_path = GetPath::try_from(path).unwrap();
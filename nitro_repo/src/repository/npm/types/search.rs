@@ -0,0 +1,178 @@
+//! Types for `/-/v1/search`
+//!
+//! https://github.com/npm/registry/blob/main/docs/REGISTRY-API.md#get-v1search
+use ahash::HashMap;
+use nr_core::database::entities::project::search::ProjectSearchQuery;
+use serde::{Deserialize, Serialize};
+
+use super::Maintainers;
+/// The default number of results
+const DEFAULT_SIZE: i64 = 20;
+/// The max number of results npm allows in a single request
+const MAX_SIZE: i64 = 250;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NPMSearchRequest {
+    /// The full text search. Supports the `keywords:` and `scope:` qualifiers
+    pub text: String,
+    /// How many results to return
+    pub size: i64,
+    /// The offset to start returning results from
+    pub from: i64,
+}
+impl Default for NPMSearchRequest {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            size: DEFAULT_SIZE,
+            from: 0,
+        }
+    }
+}
+impl NPMSearchRequest {
+    /// Parses `text`, `size`, and `from` from the query string. Invalid numbers use the default
+    pub fn from_query(query: Option<&str>) -> Self {
+        let mut request = Self::default();
+        let Some(query) = query else {
+            return request;
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "text" => request.text = value.into_owned(),
+                "size" => {
+                    if let Ok(size) = value.parse::<i64>() {
+                        request.size = size.clamp(1, MAX_SIZE);
+                    }
+                }
+                "from" => {
+                    if let Ok(from) = value.parse::<i64>() {
+                        request.from = from.max(0);
+                    }
+                }
+                _ => {}
+            }
+        }
+        request
+    }
+    /// Splits the text into search terms and qualifiers.
+    ///
+    /// Qualifiers npm supports that Nitro Repo does not track, such as `author:`, are ignored
+    pub fn project_search(&self) -> ProjectSearchQuery {
+        let mut search = ProjectSearchQuery::default();
+        for term in self.text.split_whitespace() {
+            match term.split_once(':') {
+                Some(("keywords", keywords)) => search.tags.extend(
+                    keywords
+                        .split(',')
+                        .filter(|keyword| !keyword.is_empty())
+                        .map(str::to_owned),
+                ),
+                Some(("scope", scope)) if !scope.is_empty() => {
+                    search.scope = Some(format!("@{}", scope.trim_start_matches('@')));
+                }
+                Some((_, _)) => {}
+                None => search.terms.push(term.to_owned()),
+            }
+        }
+        search
+    }
+}
+/// How well the package matches the plain search terms. Between 0 and 1
+pub fn search_score(search: &ProjectSearchQuery, name: &str) -> f64 {
+    if search.terms.is_empty() {
+        return 1.0;
+    }
+    let name = name.to_lowercase();
+    let total: f64 = search
+        .terms
+        .iter()
+        .map(|term| {
+            let term = term.to_lowercase();
+            if name == term {
+                1.0
+            } else if name.contains(&term) {
+                0.75
+            } else {
+                // Matched the description or keywords
+                0.5
+            }
+        })
+        .sum();
+    total / search.terms.len() as f64
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NPMSearchResponse {
+    pub objects: Vec<NPMSearchObject>,
+    pub total: i64,
+    pub time: String,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NPMSearchObject {
+    pub package: NPMSearchPackage,
+    pub score: NPMSearchScore,
+    pub search_score: f64,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NPMSearchPackage {
+    pub name: String,
+    /// The scope without the `@`. `unscoped` if the package has no scope
+    pub scope: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub date: String,
+    pub links: HashMap<String, String>,
+    pub maintainers: Vec<Maintainers>,
+}
+/// Nitro Repo does not track quality, popularity, or maintenance. So they are always 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NPMSearchScore {
+    #[serde(rename = "final")]
+    pub final_score: f64,
+    pub detail: NPMSearchScoreDetail,
+}
+impl NPMSearchScore {
+    pub fn new(final_score: f64) -> Self {
+        Self {
+            final_score,
+            detail: NPMSearchScoreDetail {
+                quality: 1.0,
+                popularity: 1.0,
+                maintenance: 1.0,
+            },
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NPMSearchScoreDetail {
+    pub quality: f64,
+    pub popularity: f64,
+    pub maintenance: f64,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_search_request() {
+        let request = NPMSearchRequest::from_query(Some(
+            "text=%40acme%20keywords%3Aui%2Creact%20scope%3Aacme%20author%3Abob&size=500&from=-1",
+        ));
+        assert_eq!(request.size, MAX_SIZE);
+        assert_eq!(request.from, 0);
+        let search = request.project_search();
+        assert_eq!(search.terms, vec!["@acme".to_owned()]);
+        assert_eq!(search.tags, vec!["ui".to_owned(), "react".to_owned()]);
+        assert_eq!(search.scope.as_deref(), Some("@acme"));
+
+        let request = NPMSearchRequest::from_query(None);
+        assert_eq!(request, NPMSearchRequest::default());
+    }
+    #[test]
+    fn test_search_score() {
+        let request = NPMSearchRequest::from_query(Some("text=mylib"));
+        let search = request.project_search();
+        assert_eq!(search_score(&search, "mylib"), 1.0);
+        assert_eq!(search_score(&search, "@nr/mylib"), 0.75);
+        assert_eq!(search_score(&search, "other"), 0.5);
+    }
+}
@@ -1,10 +1,12 @@
+use http::StatusCode;
 use nr_core::{
     database::entities::project::{DBProject, ProjectDBType, versions::DBProjectVersion},
     storage::StoragePath,
 };
+use serde_json::Value;
 use tracing::{info, instrument};
 
-use crate::repository::Repository;
+use crate::repository::{RepoResponse, Repository};
 
 use super::{NPMRegistryError, types::request::PublishVersion};
 
@@ -15,6 +17,52 @@ pub mod npm_time {
         date_time.format("%Y-%m-%dT%H:%M:%S.%3fZ").to_string()
     }
 }
+/// Points every `dist.tarball` at this registry. The file name of the upstream tarball is kept
+pub fn rewrite_tarball_urls(packument: &mut Value, registry_url: &str, name: &str) {
+    let Some(versions) = packument.get_mut("versions").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for version in versions.values_mut() {
+        let Some(dist) = version.get_mut("dist").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let Some(file) = dist
+            .get("tarball")
+            .and_then(Value::as_str)
+            .and_then(|tarball| tarball.rsplit('/').next())
+            .map(str::to_owned)
+        else {
+            continue;
+        };
+        dist.insert(
+            "tarball".to_owned(),
+            Value::String(format!("{}/{}/-/{}", registry_url, name, file)),
+        );
+    }
+}
+/// Finds the version in a packument. The version can also be a dist-tag such as `latest`
+pub fn find_version<'a>(packument: &'a Value, version: &str) -> Option<&'a Value> {
+    let version = packument
+        .get("dist-tags")
+        .and_then(|tags| tags.get(version))
+        .and_then(Value::as_str)
+        .unwrap_or(version);
+    packument
+        .get("versions")
+        .and_then(|versions| versions.get(version))
+}
+pub fn package_not_found(name: &str) -> RepoResponse {
+    RepoResponse::basic_text_response(
+        StatusCode::NOT_FOUND,
+        format!("Project {} not found in repository", name),
+    )
+}
+pub fn version_not_found(name: &str, version: &str) -> RepoResponse {
+    RepoResponse::basic_text_response(
+        StatusCode::NOT_FOUND,
+        format!("Version {} not found in project {}", version, name),
+    )
+}
 pub trait NpmRegistryExt: Repository {
     #[instrument]
     async fn get_or_create_project(
@@ -29,7 +77,18 @@ pub trait NpmRegistryExt: Repository {
         )
         .await?
         {
-            // TODO: Update
+            // The search index uses the description and keywords of the latest publish
+            let description = release.description();
+            let keywords = release.keywords();
+            if project.description.as_deref() != description || project.tags != keywords {
+                DBProject::update_description_and_tags(
+                    project.id,
+                    description,
+                    &keywords,
+                    self.site().as_ref(),
+                )
+                .await?;
+            }
             return Ok(project);
         }
 
@@ -73,3 +132,33 @@ pub trait NpmRegistryExt: Repository {
         }
     }
 }
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    #[test]
+    fn test_rewrite_tarball_urls() {
+        let mut packument = json!({
+            "name": "@nr/mylib",
+            "versions": {
+                "1.0.0": {
+                    "dist": {
+                        "tarball": "https://registry.npmjs.org/@nr/mylib/-/mylib-1.0.0.tgz",
+                        "shasum": "abc"
+                    }
+                }
+            }
+        });
+        rewrite_tarball_urls(
+            &mut packument,
+            "http://localhost:6742/repositories/test/npm",
+            "@nr/mylib",
+        );
+        assert_eq!(
+            packument["versions"]["1.0.0"]["dist"]["tarball"],
+            "http://localhost:6742/repositories/test/npm/@nr/mylib/-/mylib-1.0.0.tgz"
+        );
+        assert_eq!(packument["versions"]["1.0.0"]["dist"]["shasum"], "abc");
+    }
+}
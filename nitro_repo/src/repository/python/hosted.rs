@@ -0,0 +1,476 @@
+use super::{
+    PythonRepositoryError,
+    types::{
+        PythonFile, PythonPath, PythonVersionFiles, SimpleFile, SimpleFormat, SimpleIndex,
+        SimpleIndexProject, SimpleMeta, SimpleProject, filename_project_name, normalize_name,
+        validate_project_name, validate_version,
+    },
+    upload::{UploadForm, verify_sha256},
+};
+use crate::{
+    app::NitroRepo,
+    repository::{
+        RepoResponse, Repository, RepositoryFactoryError, RepositoryRequest,
+        maven::get_release_type,
+        utils::{RepositoryExt, registry_url},
+    },
+};
+use ahash::{HashMap, HashMapExt};
+use axum::response::Response;
+use derive_more::derive::Deref;
+use http::{
+    StatusCode,
+    header::{CONTENT_TYPE, LOCATION, VARY},
+};
+use nr_core::{
+    database::entities::{
+        project::{
+            DBProject, DBProjectMember, NewProject, NewProjectMember,
+            update::UpdateProjectVersion,
+            versions::{DBProjectVersion, NewVersion},
+        },
+        repository::DBRepository,
+    },
+    repository::{
+        Visibility,
+        project::{Author, Licence, ProjectSource, VersionData},
+    },
+    storage::StoragePath,
+    user::{UserSafeData, permissions::RepositoryActions},
+};
+use nr_storage::{DynStorage, FileContent, Storage};
+use parking_lot::RwLock;
+use std::{
+    borrow::Cow,
+    sync::{
+        Arc,
+        atomic::{self, AtomicBool},
+    },
+};
+use tracing::{debug, error, info, instrument, warn};
+
+#[derive(derive_more::Debug)]
+pub struct PythonHostedInner {
+    #[debug(skip)]
+    pub site: NitroRepo,
+    pub storage: DynStorage,
+    pub id: uuid::Uuid,
+    pub repository: DBRepository,
+    pub active: AtomicBool,
+    pub visibility: RwLock<Visibility>,
+}
+/// A Python Package Index that hosts its own packages
+///
+/// Files are saved at `packages/{normalized name}/{version}/{filename}`.
+/// Projects are keyed by their normalized name
+#[derive(Debug, Clone, Deref)]
+pub struct PythonHosted(Arc<PythonHostedInner>);
+impl PythonHosted {
+    pub async fn load(
+        site: NitroRepo,
+        storage: DynStorage,
+        repository: DBRepository,
+    ) -> Result<Self, RepositoryFactoryError> {
+        Ok(Self(Arc::new(PythonHostedInner {
+            site,
+            storage,
+            id: repository.id,
+            active: AtomicBool::new(repository.active),
+            visibility: RwLock::new(repository.visibility),
+            repository,
+        })))
+    }
+    fn registry_url(&self, request: &RepositoryRequest) -> String {
+        registry_url(
+            &self.site,
+            &request.parts,
+            &self.storage.storage_config().storage_config.storage_name,
+            &self.repository.name,
+        )
+    }
+    fn simple_response(format: SimpleFormat, body: String) -> RepoResponse {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, format.content_type())
+            .header(VARY, "Accept")
+            .body(body.into())
+            .into()
+    }
+    /// `simple/`. Lists every project in the repository
+    #[instrument]
+    async fn handle_simple_index(
+        &self,
+        request: &RepositoryRequest,
+    ) -> Result<RepoResponse, PythonRepositoryError> {
+        let projects = DBProject::get_all_for_repository(self.id, self.site.as_ref()).await?;
+        let index = SimpleIndex {
+            meta: SimpleMeta::default(),
+            projects: projects
+                .into_iter()
+                .map(|project| SimpleIndexProject { name: project.name })
+                .collect(),
+        };
+        let format = SimpleFormat::from_headers(request.headers());
+        let body = match format {
+            SimpleFormat::Json => serde_json::to_string(&index)?,
+            SimpleFormat::Html => index.to_html(&format!("{}/simple", self.registry_url(request))),
+        };
+        Ok(Self::simple_response(format, body))
+    }
+    /// `simple/{project}/`. Names that are not normalized are redirected to the normalized name
+    #[instrument]
+    async fn handle_simple_project(
+        &self,
+        request: &RepositoryRequest,
+        name: &str,
+    ) -> Result<RepoResponse, PythonRepositoryError> {
+        let normalized = normalize_name(name);
+        let registry_url = self.registry_url(request);
+        if normalized != name {
+            return Ok(Response::builder()
+                .status(StatusCode::MOVED_PERMANENTLY)
+                .header(LOCATION, format!("{}/simple/{}/", registry_url, normalized))
+                .body(axum::body::Body::empty())
+                .into());
+        }
+        let Some(project) = self.get_project_from_key(&normalized).await? else {
+            return Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                format!("Project {} not found in repository", name),
+            ));
+        };
+        let mut versions =
+            DBProjectVersion::get_all_versions(project.id, self.site.as_ref()).await?;
+        versions.sort_by_key(|version| version.created_at);
+        let mut files = Vec::new();
+        for version in versions {
+            let Some(extra) = version.extra.0.extra else {
+                warn!(?version, "Invalid Python Project");
+                continue;
+            };
+            let version_files: PythonVersionFiles = match serde_json::from_value(extra) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    warn!(?err, ?version.version, "Invalid Python Project");
+                    continue;
+                }
+            };
+            let version_path = version.version_path.trim_end_matches('/');
+            for file in version_files.files {
+                let mut hashes = HashMap::with_capacity(1);
+                hashes.insert("sha256".to_owned(), file.sha256);
+                files.push(SimpleFile {
+                    url: format!("{}/{}/{}", registry_url, version_path, file.filename),
+                    filename: file.filename,
+                    hashes,
+                    requires_python: file.requires_python,
+                });
+            }
+        }
+        let simple_project = SimpleProject {
+            meta: SimpleMeta::default(),
+            name: project.name,
+            files,
+        };
+        let format = SimpleFormat::from_headers(request.headers());
+        let body = match format {
+            SimpleFormat::Json => serde_json::to_string(&simple_project)?,
+            SimpleFormat::Html => simple_project.to_html(),
+        };
+        Ok(Self::simple_response(format, body))
+    }
+    /// Admins and system managers can upload to every project. Everyone else must be a member of the project with write access
+    async fn can_upload_to_project(
+        &self,
+        user: &UserSafeData,
+        project: &DBProject,
+    ) -> Result<bool, PythonRepositoryError> {
+        if user.is_admin_or_system_manager() {
+            return Ok(true);
+        }
+        Ok(DBProjectMember::can_user_write(project.id, user.id, self.site.as_ref()).await?)
+    }
+    /// `twine upload`. One file is uploaded per request
+    #[instrument]
+    async fn handle_upload(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, PythonRepositoryError> {
+        let Some(user) = request
+            .authentication
+            .get_user_if_has_action(RepositoryActions::Write, self.id, self.site.as_ref())
+            .await?
+        else {
+            info!("No acceptable user authentication provided");
+            return Ok(RepoResponse::unauthorized());
+        };
+        let content_type = request
+            .parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        let body = request.body.body_as_bytes().await?;
+        let form = UploadForm::read(&content_type, body).await?;
+        if form.action != "file_upload" {
+            return Err(PythonRepositoryError::InvalidUpload(Cow::Owned(format!(
+                "Unsupported action {}",
+                form.action
+            ))));
+        }
+        validate_project_name(&form.name)?;
+        validate_version(&form.version)?;
+        let Some(file) = &form.content else {
+            return Err(PythonRepositoryError::InvalidUpload(Cow::Borrowed(
+                "Missing content",
+            )));
+        };
+        let normalized = normalize_name(&form.name);
+        let filename_matches = filename_project_name(&file.filename)
+            .is_some_and(|name| normalize_name(name) == normalized);
+        if !filename_matches || file.filename.contains('/') {
+            return Err(PythonRepositoryError::InvalidFilename {
+                filename: file.filename.clone(),
+                name: form.name.clone(),
+            });
+        }
+        let sha256 = verify_sha256(file, form.sha256_digest.as_deref())?;
+        let python_file = PythonFile {
+            filename: file.filename.clone(),
+            sha256,
+            requires_python: form.requires_python.clone(),
+            packagetype: form.filetype.clone(),
+            python_version: form.pyversion.clone(),
+            size: file.data.len() as u64,
+        };
+
+        let project = self.get_project_from_key(&normalized).await?;
+        if let Some(project) = &project {
+            if !self.can_upload_to_project(user, project).await? {
+                return Err(PythonRepositoryError::NotProjectMember(
+                    project.name.clone(),
+                ));
+            }
+        }
+        let existing_version = match &project {
+            Some(project) => self.get_project_version(project.id, &form.version).await?,
+            None => None,
+        };
+        let mut version_files = match &existing_version {
+            Some(version) => version
+                .extra
+                .0
+                .extra
+                .clone()
+                .map(serde_json::from_value::<PythonVersionFiles>)
+                .transpose()?
+                .unwrap_or_default(),
+            None => PythonVersionFiles::default(),
+        };
+        if version_files
+            .files
+            .iter()
+            .any(|existing| existing.filename == python_file.filename)
+        {
+            return Err(PythonRepositoryError::FileAlreadyExists(
+                python_file.filename,
+            ));
+        }
+
+        let project_path = StoragePath::from(format!("packages/{}", normalized));
+        let mut version_path = project_path.clone();
+        version_path.push_mut(&form.version);
+        let mut file_path = version_path.clone();
+        file_path.push_mut(&file.filename);
+        self.storage
+            .save_file(self.id, FileContent::Bytes(file.data.clone()), &file_path)
+            .await?;
+
+        let keywords = form.keywords();
+        let project = match project {
+            Some(project) => {
+                if project.description != form.summary || project.tags != keywords {
+                    DBProject::update_description_and_tags(
+                        project.id,
+                        form.summary.as_deref(),
+                        &keywords,
+                        self.site.as_ref(),
+                    )
+                    .await?;
+                }
+                project
+            }
+            None => {
+                let project = NewProject {
+                    scope: None,
+                    project_key: normalized.clone(),
+                    name: form.name.clone(),
+                    latest_release: None,
+                    latest_pre_release: None,
+                    description: form.summary.clone(),
+                    tags: keywords,
+                    repository: self.id,
+                    storage_path: project_path.to_string(),
+                }
+                .insert(self.site.as_ref())
+                .await?;
+                NewProjectMember::new_owner(user.id, project.id)
+                    .insert_no_return(self.site.as_ref())
+                    .await?;
+                info!(?project, "Created new project");
+                project
+            }
+        };
+        version_files.files.push(python_file);
+        let files_value = serde_json::to_value(&version_files)?;
+        match existing_version {
+            Some(version) => {
+                let mut version_data = version.extra.0;
+                version_data.extra = Some(files_value);
+                UpdateProjectVersion {
+                    extra: Some(version_data),
+                    ..Default::default()
+                }
+                .update(version.id, self.site.as_ref())
+                .await?;
+            }
+            None => {
+                let authors = if form.author.is_some() || form.author_email.is_some() {
+                    vec![Author {
+                        name: form.author.clone(),
+                        email: form.author_email.clone(),
+                        website: None,
+                    }]
+                } else {
+                    vec![]
+                };
+                let source = form
+                    .project_url("Source")
+                    .or_else(|| form.project_url("Repository"))
+                    .map(|url| ProjectSource::Git {
+                        url,
+                        branch: None,
+                        commit: None,
+                    });
+                NewVersion {
+                    project_id: project.id,
+                    version: form.version.clone(),
+                    release_type: get_release_type(&form.version),
+                    version_path: version_path.to_string(),
+                    publisher: Some(user.id),
+                    version_page: None,
+                    extra: VersionData {
+                        documentation_url: form.project_url("Documentation"),
+                        website: form.home_page.clone(),
+                        authors,
+                        description: form.summary.clone(),
+                        source,
+                        licence: form.license.clone().map(Licence::Simple),
+                        extra: Some(files_value),
+                    },
+                }
+                .insert(self.site.as_ref())
+                .await?;
+            }
+        }
+        info!(name = ?form.name, version = ?form.version, filename = ?file.filename, "Uploaded file");
+        Ok(RepoResponse::basic_text_response(StatusCode::OK, "OK"))
+    }
+}
+impl RepositoryExt for PythonHosted {}
+impl Repository for PythonHosted {
+    type Error = PythonRepositoryError;
+    fn get_storage(&self) -> DynStorage {
+        self.0.storage.clone()
+    }
+    fn site(&self) -> NitroRepo {
+        self.0.site.clone()
+    }
+
+    fn get_type(&self) -> &'static str {
+        "python"
+    }
+    fn full_type(&self) -> &'static str {
+        "python/hosted"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn name(&self) -> String {
+        self.0.repository.name.to_string()
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn visibility(&self) -> Visibility {
+        *self.visibility.read()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(atomic::Ordering::Relaxed)
+    }
+    #[instrument(fields(repository_type = "python/hosted"))]
+    async fn reload(&self) -> Result<(), RepositoryFactoryError> {
+        let Some(repository) = DBRepository::get_by_id(self.id, self.site.as_ref()).await? else {
+            error!("Failed to get repository");
+            self.0.active.store(false, atomic::Ordering::Relaxed);
+            return Ok(());
+        };
+        self.0
+            .active
+            .store(repository.active, atomic::Ordering::Relaxed);
+        *self.visibility.write() = repository.visibility;
+        Ok(())
+    }
+    async fn handle_get(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, PythonRepositoryError> {
+        let path_as_string = request.path.to_string();
+        debug!(?path_as_string, "Handling Python GET request");
+        if let Some(err) = self
+            .check_read(&request.authentication, &self.basic_challenge())
+            .await?
+        {
+            return Ok(err);
+        }
+        match PythonPath::parse(&path_as_string) {
+            Some(PythonPath::SimpleIndex) => {
+                if let Some(err) = self.indexing_check(&request.authentication).await? {
+                    return Ok(err);
+                }
+                self.handle_simple_index(&request).await
+            }
+            Some(PythonPath::SimpleProject { name }) => {
+                self.handle_simple_project(&request, &name).await
+            }
+            Some(PythonPath::File) => {
+                let file = self.storage.open_file(self.id, &request.path).await?;
+                Ok(RepoResponse::from(file))
+            }
+            None => Ok(RepoResponse::basic_text_response(
+                StatusCode::NOT_FOUND,
+                "Not Found",
+            )),
+        }
+    }
+    /// twine uploads to the root of the repository. `legacy/` is accepted for configs copied from PyPI
+    async fn handle_post(
+        &self,
+        request: RepositoryRequest,
+    ) -> Result<RepoResponse, PythonRepositoryError> {
+        let path_as_string = request.path.to_string();
+        match path_as_string.trim_matches('/') {
+            "" | "legacy" => self.handle_upload(request).await,
+            _ => Ok(RepoResponse::unsupported_method_response(
+                request.parts.method,
+                self.get_type(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Python Repository Implementation
+//!
+//! Packages are uploaded with `twine upload` and installed with pip using the Simple Repository API.
+//!
+//! Documentation for Python: https://packaging.python.org/en/latest/specifications/simple-repository-api/
+
+use std::borrow::Cow;
+
+use ahash::HashMap;
+use futures::future::BoxFuture;
+use hosted::PythonHosted;
+use nr_core::database::entities::repository::DBRepository;
+use nr_macros::DynRepositoryHandler;
+use nr_storage::DynStorage;
+use tracing::debug;
+use types::{InvalidProjectName, InvalidVersion};
+
+pub mod hosted;
+pub mod types;
+pub mod upload;
+use crate::{
+    app::authentication::AuthenticationError,
+    error::{BadRequestErrors, IntoErrorResponse},
+};
+
+pub use super::prelude::*;
+use super::{DynRepository, NewRepository, RepositoryType, RepositoryTypeDescription};
+
+#[derive(Debug, Clone, DynRepositoryHandler)]
+#[repository_handler(error=PythonRepositoryError)]
+pub enum PythonRepository {
+    Hosted(PythonHosted),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PythonRepositoryError {
+    #[error(transparent)]
+    InvalidProjectName(#[from] InvalidProjectName),
+    #[error(transparent)]
+    InvalidVersion(#[from] InvalidVersion),
+    #[error("Invalid upload. {0}")]
+    InvalidUpload(Cow<'static, str>),
+    #[error("File {filename} is not a wheel or sdist of {name}")]
+    InvalidFilename { filename: String, name: String },
+    #[error("File {filename} does not match the sha256_digest")]
+    DigestMismatch { filename: String },
+    #[error("File {0} already exists")]
+    FileAlreadyExists(String),
+    #[error("You are not a member of {0}")]
+    NotProjectMember(String),
+    #[error("{0}")]
+    Other(Box<dyn IntoErrorResponse>),
+}
+impl From<PythonRepositoryError> for RepositoryHandlerError {
+    fn from(err: PythonRepositoryError) -> Self {
+        RepositoryHandlerError::Other(Box::new(err))
+    }
+}
+impl From<multer::Error> for PythonRepositoryError {
+    fn from(err: multer::Error) -> Self {
+        PythonRepositoryError::InvalidUpload(Cow::Owned(err.to_string()))
+    }
+}
+macro_rules! impl_from_error_for_other {
+    ($t:ty) => {
+        impl From<$t> for PythonRepositoryError {
+            fn from(e: $t) -> Self {
+                PythonRepositoryError::Other(Box::new(e))
+            }
+        }
+    };
+}
+impl_from_error_for_other!(BadRequestErrors);
+impl_from_error_for_other!(sqlx::Error);
+impl_from_error_for_other!(serde_json::Error);
+impl_from_error_for_other!(std::io::Error);
+impl_from_error_for_other!(AuthenticationError);
+impl_from_error_for_other!(RepositoryHandlerError);
+impl_from_error_for_other!(nr_storage::StorageError);
+
+impl IntoErrorResponse for PythonRepositoryError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
+
+impl From<PythonRepositoryError> for DynRepositoryHandlerError {
+    fn from(err: PythonRepositoryError) -> Self {
+        DynRepositoryHandlerError(Box::new(err))
+    }
+}
+
+impl IntoResponse for PythonRepositoryError {
+    fn into_response(self) -> Response {
+        match self {
+            PythonRepositoryError::Other(other) => other.into_response_boxed(),
+            not_member @ PythonRepositoryError::NotProjectMember(_) => Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(not_member.to_string().into())
+                .unwrap(),
+            bad_request => {
+                debug!("Bad Request: {:?}", bad_request);
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(bad_request.to_string().into())
+                    .unwrap()
+            }
+        }
+    }
+}
+#[derive(Debug, Default)]
+pub struct PythonRepositoryType;
+
+impl RepositoryType for PythonRepositoryType {
+    fn get_type(&self) -> &'static str {
+        "python"
+    }
+
+    fn config_types(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn get_description(&self) -> RepositoryTypeDescription {
+        RepositoryTypeDescription {
+            type_name: "python",
+            name: "Python",
+            description: "A Python Package Index. Supports twine and pip",
+            documentation_url: Some("https://nitro-repo.kingtux.dev/repositoryTypes/python/"),
+            is_stable: false,
+            required_configs: vec![],
+        }
+    }
+
+    fn create_new(
+        &self,
+        name: String,
+        uuid: uuid::Uuid,
+        configs: HashMap<String, serde_json::Value>,
+        storage: nr_storage::DynStorage,
+    ) -> BoxFuture<'static, Result<NewRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            Ok(NewRepository {
+                name,
+                uuid,
+                repository_type: "python".to_string(),
+                configs,
+            })
+        })
+    }
+
+    fn load_repo(
+        &self,
+        repo: DBRepository,
+        storage: DynStorage,
+        website: NitroRepo,
+    ) -> BoxFuture<'static, Result<DynRepository, RepositoryFactoryError>> {
+        Box::pin(async move {
+            let hosted = PythonHosted::load(website, storage, repo).await?;
+            Ok(PythonRepository::Hosted(hosted).into())
+        })
+    }
+}
@@ -0,0 +1,411 @@
+//! Types for the Simple Repository API
+//!
+//! https://packaging.python.org/en/latest/specifications/simple-repository-api/
+use std::sync::LazyLock;
+
+use ahash::HashMap;
+use http::{HeaderMap, header::ACCEPT};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const SIMPLE_JSON_CONTENT_TYPE: &str = "application/vnd.pypi.simple.v1+json";
+pub const SIMPLE_HTML_CONTENT_TYPE: &str = "application/vnd.pypi.simple.v1+html";
+/// The version of the Simple API that is served
+const SIMPLE_API_VERSION: &str = "1.0";
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error(
+    "Invalid project name {0}. Names can only contain letters, numbers, `.`, `-`, and `_`. They must start and end with a letter or number"
+)]
+pub struct InvalidProjectName(pub String);
+/// Checks the name against [PEP 508](https://peps.python.org/pep-0508/#names)
+pub fn validate_project_name(name: &str) -> Result<(), InvalidProjectName> {
+    let valid = !name.is_empty()
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidProjectName(name.to_owned()))
+    }
+}
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Invalid version {0}. Versions must follow PEP 440")]
+pub struct InvalidVersion(pub String);
+/// Checks the version against [PEP 440](https://peps.python.org/pep-0440/#appendix-b-parsing-version-strings-with-regular-expressions)
+///
+/// Versions are used in the storage path. So this also keeps `/` and `..` out of the path
+pub fn validate_version(version: &str) -> Result<(), InvalidVersion> {
+    static VERSION_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(concat!(
+            r"(?i)^v?",
+            r"(?:[0-9]+!)?",
+            r"[0-9]+(?:\.[0-9]+)*",
+            r"(?:[-_.]?(?:a|b|c|rc|alpha|beta|pre|preview)[-_.]?[0-9]*)?",
+            r"(?:-[0-9]+|[-_.]?(?:post|rev|r)[-_.]?[0-9]*)?",
+            r"(?:[-_.]?dev[-_.]?[0-9]*)?",
+            r"(?:\+[a-z0-9]+(?:[-_.][a-z0-9]+)*)?$",
+        ))
+        .expect("Invalid version pattern")
+    });
+    if VERSION_PATTERN.is_match(version) {
+        Ok(())
+    } else {
+        Err(InvalidVersion(version.to_owned()))
+    }
+}
+/// Normalizes the name as defined by [PEP 503](https://peps.python.org/pep-0503/#normalized-names).
+///
+/// Runs of `-`, `_`, and `.` are replaced with a single `-` and the name is lowercased
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut last_was_separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !last_was_separator {
+                normalized.push('-');
+            }
+            last_was_separator = true;
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+            last_was_separator = false;
+        }
+    }
+    normalized
+}
+/// The project name in the file name of a wheel or sdist.
+///
+/// - `{name}-{version}(-{build})?-{python}-{abi}-{platform}.whl`
+/// - `{name}-{version}.tar.gz` or `{name}-{version}.zip`
+pub fn filename_project_name(filename: &str) -> Option<&str> {
+    if filename.ends_with(".whl") {
+        return filename.split_once('-').map(|(name, _)| name);
+    }
+    let without_extension = filename
+        .strip_suffix(".tar.gz")
+        .or_else(|| filename.strip_suffix(".zip"))?;
+    without_extension.rsplit_once('-').map(|(name, _)| name)
+}
+/// The format of a Simple API response. Picked with the `Accept` header as defined by [PEP 691](https://peps.python.org/pep-0691/)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimpleFormat {
+    Json,
+    Html,
+}
+impl SimpleFormat {
+    /// JSON is only used if the client prefers it. Clients that do not send `Accept` get HTML
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut json_quality = 0.0f32;
+        let mut html_quality = 0.0f32;
+        for media_type in headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|accept| accept.to_str().ok())
+            .flat_map(|accept| accept.split(','))
+        {
+            let mut params = media_type.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            match media_type {
+                SIMPLE_JSON_CONTENT_TYPE | "application/vnd.pypi.simple.latest+json" => {
+                    json_quality = json_quality.max(quality);
+                }
+                SIMPLE_HTML_CONTENT_TYPE
+                | "application/vnd.pypi.simple.latest+html"
+                | "text/html"
+                | "*/*" => {
+                    html_quality = html_quality.max(quality);
+                }
+                _ => {}
+            }
+        }
+        if json_quality > 0.0 && json_quality > html_quality {
+            Self::Json
+        } else {
+            Self::Html
+        }
+    }
+    /// HTML is served as `text/html`. Older versions of pip do not accept anything else
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => SIMPLE_JSON_CONTENT_TYPE,
+            Self::Html => "text/html",
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonPath {
+    /// `simple/`
+    SimpleIndex,
+    /// `simple/{project}/`. The name might not be normalized
+    SimpleProject { name: String },
+    /// `packages/{project}/{version}/{filename}`
+    File,
+}
+impl PythonPath {
+    pub fn parse(path: &str) -> Option<Self> {
+        let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        match parts.as_slice() {
+            ["simple"] => Some(Self::SimpleIndex),
+            ["simple", name] => Some(Self::SimpleProject {
+                name: (*name).to_owned(),
+            }),
+            ["packages", _, _, _] => Some(Self::File),
+            _ => None,
+        }
+    }
+}
+/// A file of a version. Saved in the `extra` of the version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PythonFile {
+    pub filename: String,
+    /// Hex encoded sha256 of the file
+    pub sha256: String,
+    #[serde(default)]
+    pub requires_python: Option<String>,
+    /// `bdist_wheel` or `sdist`
+    pub packagetype: String,
+    /// Such as `py3` or `source`
+    #[serde(default)]
+    pub python_version: Option<String>,
+    pub size: u64,
+}
+/// The `extra` of a version. Wheels and the sdist are uploaded one at a time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PythonVersionFiles {
+    #[serde(default)]
+    pub files: Vec<PythonFile>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleMeta {
+    #[serde(rename = "api-version")]
+    pub api_version: String,
+}
+impl Default for SimpleMeta {
+    fn default() -> Self {
+        Self {
+            api_version: SIMPLE_API_VERSION.to_owned(),
+        }
+    }
+}
+/// `simple/`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleIndex {
+    pub meta: SimpleMeta,
+    pub projects: Vec<SimpleIndexProject>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleIndexProject {
+    pub name: String,
+}
+/// `simple/{project}/`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleProject {
+    pub meta: SimpleMeta,
+    pub name: String,
+    pub files: Vec<SimpleFile>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimpleFile {
+    pub filename: String,
+    pub url: String,
+    pub hashes: HashMap<String, String>,
+    #[serde(
+        rename = "requires-python",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub requires_python: Option<String>,
+}
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+fn html_page(title: &str, links: String) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta name="pypi:repository-version" content="{SIMPLE_API_VERSION}">
+    <title>{title}</title>
+  </head>
+  <body>
+    <h1>{title}</h1>
+{links}  </body>
+</html>
+"#,
+        title = html_escape(title),
+    )
+}
+impl SimpleIndex {
+    /// [PEP 503](https://peps.python.org/pep-0503/). `simple_url` is the URL of `simple` without a trailing slash
+    pub fn to_html(&self, simple_url: &str) -> String {
+        let links: String = self
+            .projects
+            .iter()
+            .map(|project| {
+                let name = html_escape(&project.name);
+                let href = format!("{}/{}/", simple_url, normalize_name(&project.name));
+                format!("    <a href=\"{}\">{}</a><br/>\n", html_escape(&href), name)
+            })
+            .collect();
+        html_page("Simple index", links)
+    }
+}
+impl SimpleProject {
+    /// [PEP 503](https://peps.python.org/pep-0503/). The sha256 is added to the URL as a fragment
+    pub fn to_html(&self) -> String {
+        let links: String = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut href = file.url.clone();
+                if let Some(sha256) = file.hashes.get("sha256") {
+                    href.push_str("#sha256=");
+                    href.push_str(sha256);
+                }
+                let requires_python = file
+                    .requires_python
+                    .as_deref()
+                    .map(|requires_python| {
+                        format!(" data-requires-python=\"{}\"", html_escape(requires_python))
+                    })
+                    .unwrap_or_default();
+                format!(
+                    "    <a href=\"{}\"{}>{}</a><br/>\n",
+                    html_escape(&href),
+                    requires_python,
+                    html_escape(&file.filename)
+                )
+            })
+            .collect();
+        html_page(&format!("Links for {}", self.name), links)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Friendly-Bard"), "friendly-bard");
+        assert_eq!(normalize_name("FRIENDLY_BARD"), "friendly-bard");
+        assert_eq!(normalize_name("friendly.bard"), "friendly-bard");
+        assert_eq!(normalize_name("friendly--._bard"), "friendly-bard");
+    }
+    #[test]
+    fn test_validate_project_name() {
+        assert!(validate_project_name("my_package.utils").is_ok());
+        assert!(validate_project_name("").is_err());
+        assert!(validate_project_name("-package").is_err());
+        assert!(validate_project_name("package-").is_err());
+        assert!(validate_project_name("my package").is_err());
+    }
+    #[test]
+    fn test_validate_version() {
+        for version in [
+            "1.0.0",
+            "1.0",
+            "2!1.0",
+            "1.0a1",
+            "1.0.0rc2",
+            "1.0.post1",
+            "1.0-1",
+            "1.0.dev3",
+            "1.0+local.7",
+            "v1.0",
+            "1.0-Beta_2",
+        ] {
+            assert!(validate_version(version).is_ok(), "{version}");
+        }
+        for version in ["", "..", "1..0", "1.0/..", "latest", "1.0+", ".1.0"] {
+            assert!(validate_version(version).is_err(), "{version}");
+        }
+    }
+    #[test]
+    fn test_filename_project_name() {
+        assert_eq!(
+            filename_project_name("my_package-1.0.0-py3-none-any.whl"),
+            Some("my_package")
+        );
+        assert_eq!(
+            filename_project_name("my-package-1.0.0.tar.gz"),
+            Some("my-package")
+        );
+        assert_eq!(
+            filename_project_name("my-package-1.0.0.zip"),
+            Some("my-package")
+        );
+        assert_eq!(filename_project_name("my-package-1.0.0.exe"), None);
+    }
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(PythonPath::parse("simple/"), Some(PythonPath::SimpleIndex));
+        assert_eq!(
+            PythonPath::parse("simple/My_Package/"),
+            Some(PythonPath::SimpleProject {
+                name: "My_Package".to_owned()
+            })
+        );
+        assert_eq!(
+            PythonPath::parse("packages/my-package/1.0.0/my_package-1.0.0.tar.gz"),
+            Some(PythonPath::File)
+        );
+        assert_eq!(PythonPath::parse("packages/my-package"), None);
+    }
+    #[test]
+    fn test_simple_format() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(SimpleFormat::from_headers(&headers), SimpleFormat::Html);
+        // Sent by pip
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static(
+                "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html; q=0.1, text/html; q=0.01",
+            ),
+        );
+        assert_eq!(SimpleFormat::from_headers(&headers), SimpleFormat::Json);
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.pypi.simple.v1+json; q=0, text/html"),
+        );
+        assert_eq!(SimpleFormat::from_headers(&headers), SimpleFormat::Html);
+    }
+    #[test]
+    fn test_project_html() {
+        let mut hashes = HashMap::default();
+        hashes.insert("sha256".to_owned(), "abc".to_owned());
+        let project = SimpleProject {
+            meta: SimpleMeta::default(),
+            name: "my-package".to_owned(),
+            files: vec![SimpleFile {
+                filename: "my_package-1.0.0-py3-none-any.whl".to_owned(),
+                url: "http://localhost/packages/my-package/1.0.0/my_package-1.0.0-py3-none-any.whl"
+                    .to_owned(),
+                hashes,
+                requires_python: Some(">=3.8".to_owned()),
+            }],
+        };
+        let html = project.to_html();
+        assert!(html.contains(
+            r#"<a href="http://localhost/packages/my-package/1.0.0/my_package-1.0.0-py3-none-any.whl#sha256=abc" data-requires-python="&gt;=3.8">my_package-1.0.0-py3-none-any.whl</a>"#
+        ));
+    }
+}
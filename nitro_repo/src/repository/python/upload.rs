@@ -0,0 +1,195 @@
+//! The upload API used by `twine upload`
+//!
+//! The file and its metadata are sent as `multipart/form-data`.
+//! https://warehouse.pypa.io/api-reference/legacy.html#upload-api
+use std::{borrow::Cow, convert::Infallible};
+
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+use super::PythonRepositoryError;
+/// The largest text field that will be read. The file itself is not limited
+const MAX_FIELD_SIZE: usize = 1024 * 1024;
+#[derive(Debug, Clone, Default)]
+pub struct UploadForm {
+    /// Must be `file_upload`
+    pub action: String,
+    pub name: String,
+    pub version: String,
+    /// `bdist_wheel` or `sdist`
+    pub filetype: String,
+    pub pyversion: Option<String>,
+    pub summary: Option<String>,
+    pub home_page: Option<String>,
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    pub license: Option<String>,
+    pub keywords: Option<String>,
+    pub requires_python: Option<String>,
+    pub sha256_digest: Option<String>,
+    pub project_urls: Vec<String>,
+    pub content: Option<UploadFile>,
+}
+#[derive(Debug, Clone)]
+pub struct UploadFile {
+    pub filename: String,
+    pub data: Bytes,
+}
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+impl UploadForm {
+    /// Reads the form from the body of the request
+    pub async fn read(content_type: &str, body: Bytes) -> Result<Self, PythonRepositoryError> {
+        let boundary = multer::parse_boundary(content_type)?;
+        let stream = futures::stream::once(async move { Ok::<_, Infallible>(body) });
+        let mut multipart = multer::Multipart::new(stream, boundary);
+        let mut form = Self::default();
+        while let Some(field) = multipart.next_field().await? {
+            let Some(name) = field.name().map(str::to_owned) else {
+                continue;
+            };
+            if name == "content" {
+                let filename = field.file_name().map(str::to_owned).ok_or(
+                    PythonRepositoryError::InvalidUpload(Cow::Borrowed(
+                        "The content field is missing a file name",
+                    )),
+                )?;
+                let data = field.bytes().await?;
+                form.content = Some(UploadFile { filename, data });
+                continue;
+            }
+            let value = field.bytes().await?;
+            if value.len() > MAX_FIELD_SIZE {
+                return Err(PythonRepositoryError::InvalidUpload(Cow::Owned(format!(
+                    "Field {} is too large",
+                    name
+                ))));
+            }
+            let value = String::from_utf8_lossy(&value).into_owned();
+            match name.as_str() {
+                ":action" => form.action = value,
+                "name" => form.name = value.trim().to_owned(),
+                "version" => form.version = value.trim().to_owned(),
+                "filetype" => form.filetype = value,
+                "pyversion" => form.pyversion = non_empty(value),
+                "summary" => form.summary = non_empty(value),
+                "home_page" => form.home_page = non_empty(value),
+                "author" => form.author = non_empty(value),
+                "author_email" => form.author_email = non_empty(value),
+                "license" => form.license = non_empty(value),
+                "keywords" => form.keywords = non_empty(value),
+                "requires_python" => form.requires_python = non_empty(value),
+                "sha256_digest" => form.sha256_digest = non_empty(value),
+                "project_urls" => form.project_urls.extend(non_empty(value)),
+                _ => {}
+            }
+        }
+        Ok(form)
+    }
+    /// Keywords can be separated by commas or spaces
+    pub fn keywords(&self) -> Vec<String> {
+        self.keywords
+            .as_deref()
+            .unwrap_or_default()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|keyword| !keyword.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+    /// The URL of a `project_urls` entry. Entries are formatted as `{label}, {url}`
+    pub fn project_url(&self, label: &str) -> Option<String> {
+        self.project_urls.iter().find_map(|entry| {
+            let (entry_label, url) = entry.split_once(',')?;
+            entry_label
+                .trim()
+                .eq_ignore_ascii_case(label)
+                .then(|| url.trim().to_owned())
+        })
+    }
+}
+/// Returns the hex encoded sha256 of the file.
+///
+/// If the client sent `sha256_digest` it must match
+pub fn verify_sha256(
+    file: &UploadFile,
+    expected: Option<&str>,
+) -> Result<String, PythonRepositoryError> {
+    let sha256 = format!("{:x}", Sha256::digest(&file.data));
+    if let Some(expected) = expected {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            return Err(PythonRepositoryError::DigestMismatch {
+                filename: file.filename.clone(),
+            });
+        }
+    }
+    Ok(sha256)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn multipart_body(boundary: &str, fields: &[(&str, &str)], file: (&str, &[u8])) -> Bytes {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"content\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                file.0
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(file.1);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        Bytes::from(body)
+    }
+    #[tokio::test]
+    async fn test_read_upload_form() {
+        let data = b"not really a wheel";
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        let body = multipart_body(
+            "boundary",
+            &[
+                (":action", "file_upload"),
+                ("name", "my-package"),
+                ("version", "1.0.0"),
+                ("filetype", "bdist_wheel"),
+                ("pyversion", "py3"),
+                ("requires_python", ">=3.8"),
+                ("keywords", "a, b c"),
+                (
+                    "project_urls",
+                    "Source, https://github.com/example/my-package",
+                ),
+                ("sha256_digest", &sha256),
+            ],
+            ("my_package-1.0.0-py3-none-any.whl", data),
+        );
+        let form = UploadForm::read("multipart/form-data; boundary=boundary", body)
+            .await
+            .unwrap();
+        assert_eq!(form.action, "file_upload");
+        assert_eq!(form.name, "my-package");
+        assert_eq!(form.requires_python.as_deref(), Some(">=3.8"));
+        assert_eq!(form.keywords(), vec!["a", "b", "c"]);
+        assert_eq!(
+            form.project_url("source").as_deref(),
+            Some("https://github.com/example/my-package")
+        );
+        let file = form.content.unwrap();
+        assert_eq!(file.filename, "my_package-1.0.0-py3-none-any.whl");
+        assert_eq!(file.data.as_ref(), data);
+        assert_eq!(verify_sha256(&file, Some(&sha256)).unwrap(), sha256);
+        assert!(matches!(
+            verify_sha256(&file, Some("abc")),
+            Err(PythonRepositoryError::DigestMismatch { .. })
+        ));
+    }
+}
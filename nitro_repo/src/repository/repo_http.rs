@@ -54,6 +54,10 @@ impl RepositoryRequestBody {
         let bytes = body.to_bytes();
         Ok(bytes)
     }
+    /// Streams the body. For large bodies that should not be buffered
+    pub fn into_data_stream(self) -> axum::body::BodyDataStream {
+        self.0.into_data_stream()
+    }
     #[cfg(not(debug_assertions))]
     #[instrument]
     pub async fn body_as_json<T: for<'a> Deserialize<'a>>(
@@ -355,13 +359,12 @@ impl From<Option<StorageFileMeta<FileType>>> for RepoResponse {
         }
     }
 }
-#[allow(dead_code)]
 #[derive(Debug, Clone, Deserialize)]
 pub struct RepoRequestPath {
-    storage: String,
-    repository: String,
+    pub storage: String,
+    pub repository: String,
     #[serde(default)]
-    path: Option<StoragePath>,
+    pub path: Option<StoragePath>,
 }
 
 pub async fn handle_repo_request(
@@ -413,6 +416,7 @@ pub async fn handle_repo_request(
         let response = match method {
             Method::GET => repository.handle_get(request).await,
             Method::PUT => repository.handle_put(request).await,
+            Method::POST => repository.handle_post(request).await,
             Method::DELETE => repository.handle_delete(request).await,
             Method::PATCH => repository.handle_patch(request).await,
             Method::HEAD => repository.handle_head(request).await,
@@ -38,26 +38,7 @@ impl RepositoryAuthentication {
         repository_id: Uuid,
         database: &PgPool,
     ) -> Result<bool, AuthenticationError> {
-        match self {
-            RepositoryAuthentication::AuthToken(token, user)
-            | RepositoryAuthentication::Basic(Some(token), user) => {
-                debug!("Request has an Auth Token. Checking if it has access to the repository");
-                does_user_and_token_have_repository_action(
-                    user,
-                    token,
-                    action,
-                    repository_id,
-                    database,
-                )
-                .await
-                .map_err(AuthenticationError::from)
-            }
-            RepositoryAuthentication::Session(_, user)
-            | RepositoryAuthentication::Basic(None, user) => {
-                Ok(user.has_action(action, repository_id, database).await?)
-            }
-            _ => Ok(false),
-        }
+        Ok(self.has_action(action, repository_id, database).await?)
     }
     #[instrument]
     pub async fn get_user_if_has_action(
@@ -66,33 +47,10 @@ impl RepositoryAuthentication {
         repository_id: Uuid,
         database: &PgPool,
     ) -> Result<Option<&UserSafeData>, AuthenticationError> {
-        match self {
-            RepositoryAuthentication::AuthToken(token, user)
-            | RepositoryAuthentication::Basic(Some(token), user) => {
-                debug!("Request has an Auth Token. Checking if it has access to the repository");
-                if does_user_and_token_have_repository_action(
-                    user,
-                    token,
-                    action,
-                    repository_id,
-                    database,
-                )
-                .await?
-                {
-                    Ok(Some(user))
-                } else {
-                    Ok(None)
-                }
-            }
-            RepositoryAuthentication::Session(_, user)
-            | RepositoryAuthentication::Basic(None, user) => {
-                if user.has_action(action, repository_id, database).await? {
-                    Ok(Some(user))
-                } else {
-                    Ok(None)
-                }
-            }
-            _ => Ok(None),
+        if self.has_action(action, repository_id, database).await? {
+            Ok(self.get_user())
+        } else {
+            Ok(None)
         }
     }
 }
@@ -114,6 +72,27 @@ impl HasPermissions for RepositoryAuthentication {
             _ => None,
         }
     }
+    /// Requests with an Auth Token are limited to the scopes of the token
+    async fn has_action(
+        &self,
+        action: RepositoryActions,
+        repository: Uuid,
+        db: &PgPool,
+    ) -> Result<bool, sqlx::Error> {
+        match self {
+            RepositoryAuthentication::AuthToken(token, user)
+            | RepositoryAuthentication::Basic(Some(token), user) => {
+                debug!("Request has an Auth Token. Checking if it has access to the repository");
+                does_user_and_token_have_repository_action(user, token, action, repository, db)
+                    .await
+            }
+            RepositoryAuthentication::Session(_, user)
+            | RepositoryAuthentication::Basic(None, user) => {
+                user.has_action(action, repository, db).await
+            }
+            _ => Ok(false),
+        }
+    }
 }
 impl RepositoryAuthentication {
     pub fn get_user_id(&self) -> Option<i32> {
@@ -197,3 +176,79 @@ async fn get_by_auth_token(
         .ok_or(AuthenticationError::Unauthorized)?;
     Ok((token, user))
 }
+#[cfg(test)]
+mod tests {
+    use nr_core::{database::entities::user::auth_token::NewRepositoryToken, testing::TestCore};
+
+    use super::*;
+    async fn create_repository(name: &str, database: &PgPool) -> Uuid {
+        let storage: Uuid = sqlx::query_scalar(
+            r#"INSERT INTO storages (storage_type, name, config) VALUES ('Local', 'token_scope_test', '{}')
+            ON CONFLICT (name) DO UPDATE SET active = true RETURNING id"#,
+        )
+        .fetch_one(database)
+        .await
+        .unwrap();
+        sqlx::query_scalar(
+            r#"INSERT INTO repositories (storage_id, name, repository_type) VALUES ($1, $2, 'docker')
+            ON CONFLICT ON CONSTRAINT unique_repository_name DO UPDATE SET active = true RETURNING id"#,
+        )
+        .bind(storage)
+        .bind(name)
+        .fetch_one(database)
+        .await
+        .unwrap()
+    }
+    #[ignore = "Requires a database"]
+    #[tokio::test]
+    pub async fn test_token_scope_is_enforced() {
+        let (core, entry) =
+            TestCore::new(format!("{}::test_token_scope_is_enforced", module_path!()))
+                .await
+                .unwrap();
+        let user = core.get_test_user().await.unwrap().unwrap();
+        let repository_a = create_repository("token_scope_a", &core.db).await;
+        let repository_b = create_repository("token_scope_b", &core.db).await;
+        let (token_id, _) = NewRepositoryToken::new(
+            user.id,
+            "Token Scope Test".to_owned(),
+            repository_a,
+            vec![RepositoryActions::Read],
+        )
+        .insert(&core.db)
+        .await
+        .unwrap();
+        let token = AuthToken::get_by_id_and_user_id(token_id, user.id, &core.db)
+            .await
+            .unwrap()
+            .unwrap();
+        let authentication = RepositoryAuthentication::AuthToken(token, user.clone());
+        assert!(
+            authentication
+                .has_action(RepositoryActions::Read, repository_a, &core.db)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !authentication
+                .has_action(RepositoryActions::Write, repository_a, &core.db)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !authentication
+                .has_action(RepositoryActions::Read, repository_b, &core.db)
+                .await
+                .unwrap()
+        );
+        // Without the token the user can access both
+        let authentication = RepositoryAuthentication::Basic(None, user);
+        assert!(
+            authentication
+                .has_action(RepositoryActions::Read, repository_b, &core.db)
+                .await
+                .unwrap()
+        );
+        entry.set_success(&core.db).await.unwrap();
+    }
+}
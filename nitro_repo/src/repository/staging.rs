@@ -2,19 +2,29 @@ use std::{fmt::Debug, path::PathBuf, sync::Arc};
 
 use axum::response::{IntoResponse, Response};
 use bytes::Bytes;
-use chrono::Duration;
+use chrono::{Duration, Local};
 use derive_more::derive::Deref;
 use http::StatusCode;
 use nr_core::database::entities::stages::{
-    DBStage, NewDBStageBuilderError, NewDBStageFileBuilder, NewDBStageFileBuilderError,
+    DBStage, NewDBStageBuilder, NewDBStageBuilderError, NewDBStageFileBuilder,
+    NewDBStageFileBuilderError,
 };
 use redb::Result;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
-use tracing::{debug, error, instrument};
+use tokio::task::JoinHandle;
+use tracing::{
+    Level, debug, error,
+    field::{Empty, display},
+    info, instrument, span, warn,
+};
 use uuid::Uuid;
 
-use crate::app::{NitroRepo, config::get_current_directory};
+use crate::{
+    app::{NitroRepo, config::get_current_directory},
+    error::IntoErrorResponse,
+};
 #[derive(Debug, Error)]
 pub enum StagingManagerError {
     #[error("Database Error")]
@@ -36,21 +46,65 @@ impl IntoResponse for StagingManagerError {
             .unwrap()
     }
 }
+impl IntoErrorResponse for StagingManagerError {
+    fn into_response_boxed(self: Box<Self>) -> axum::response::Response {
+        self.into_response()
+    }
+}
 /// Stages are stored locally before being moved to the storage
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct StagingConfig {
     pub staging_dir: PathBuf,
+    /// How long a stage can stay open before it is deleted. Closed stages are kept until they are promoted or dropped
     #[serde(with = "nr_core::utils::duration_serde::as_seconds")]
     pub time_till_cleanup: Duration,
+    #[serde(with = "nr_core::utils::duration_serde::as_seconds")]
+    pub cleanup_interval: Duration,
 }
 impl Default for StagingConfig {
     fn default() -> Self {
         Self {
             staging_dir: get_current_directory().join("staging"),
             time_till_cleanup: Duration::hours(1),
+            cleanup_interval: Duration::minutes(10),
+        }
+    }
+}
+impl StagingConfig {
+    /// The local directory the files of the stage are stored in
+    pub fn stage_directory(&self, stage_id: Uuid) -> PathBuf {
+        self.staging_dir.join(stage_id.to_string())
+    }
+    /// [StagingConfig::time_till_cleanup]. The default is used if it is negative
+    pub fn time_till_cleanup(&self) -> std::time::Duration {
+        self.time_till_cleanup.to_std().unwrap_or_else(|_| {
+            warn!(
+                time_till_cleanup = ?self.time_till_cleanup,
+                "staging.time_till_cleanup is negative. Using the default"
+            );
+            default_duration(StagingConfig::default().time_till_cleanup)
+        })
+    }
+    /// [StagingConfig::cleanup_interval]. The default is used if it is not positive
+    pub fn cleanup_interval(&self) -> std::time::Duration {
+        match self.cleanup_interval.to_std() {
+            Ok(interval) if !interval.is_zero() => interval,
+            _ => {
+                warn!(
+                    cleanup_interval = ?self.cleanup_interval,
+                    "staging.cleanup_interval is not positive. Using the default"
+                );
+                default_duration(StagingConfig::default().cleanup_interval)
+            }
         }
     }
 }
+fn default_duration(duration: Duration) -> std::time::Duration {
+    duration
+        .to_std()
+        .expect("The default durations are positive")
+}
 pub struct StagingManagerInner {
     repository: Uuid,
     site: NitroRepo,
@@ -67,23 +121,39 @@ impl Debug for StagingManager {
 }
 
 impl StagingManager {
+    pub fn new(repository: Uuid, site: NitroRepo) -> Self {
+        Self(Arc::new(StagingManagerInner { repository, site }))
+    }
+    pub fn stage_directory(&self, stage_id: Uuid) -> PathBuf {
+        self.site.staging_config.stage_directory(stage_id)
+    }
+    pub async fn create_stage(
+        &self,
+        stage_state: Value,
+        created_by: i32,
+    ) -> Result<DBStage, StagingManagerError> {
+        let new_stage = NewDBStageBuilder::default()
+            .repository_id(self.repository)
+            .stage_state(stage_state)
+            .created_by(created_by)
+            .build()?;
+        let stage = new_stage.insert(&self.site.database).await?;
+        debug!(?stage, "Created stage");
+        Ok(stage)
+    }
     pub async fn get_stage(&self, id: Uuid) -> Result<Option<DBStage>, StagingManagerError> {
         let stage = DBStage::get_stage_by_id(id, self.repository, &self.site.database).await?;
         Ok(stage)
     }
     // This function will assume the stage exists.
-    #[instrument]
+    #[instrument(skip(file))]
     pub async fn add_file(
         &self,
         stage_id: Uuid,
         file_name: String,
         file: Bytes,
     ) -> Result<(), StagingManagerError> {
-        let staging_dir = self
-            .site
-            .staging_config
-            .staging_dir
-            .join(stage_id.to_string());
+        let staging_dir = self.stage_directory(stage_id);
         if !staging_dir.exists() {
             std::fs::create_dir_all(&staging_dir)?;
         }
@@ -99,4 +169,96 @@ impl StagingManager {
         debug!(?new_file, "File added to stage");
         Ok(())
     }
+    /// Reads a file that was added to the stage
+    pub async fn read_file(
+        &self,
+        stage_id: Uuid,
+        file_name: &str,
+    ) -> Result<Vec<u8>, StagingManagerError> {
+        let file_path = self.stage_directory(stage_id).join(file_name);
+        Ok(tokio::fs::read(file_path).await?)
+    }
+    /// Opens a file that was added to the stage. For reading large files without buffering them
+    pub async fn open_file(
+        &self,
+        stage_id: Uuid,
+        file_name: &str,
+    ) -> Result<tokio::fs::File, StagingManagerError> {
+        let file_path = self.stage_directory(stage_id).join(file_name);
+        Ok(tokio::fs::File::open(file_path).await?)
+    }
+    /// Deletes the local files and the database entry of the stage
+    #[instrument]
+    pub async fn delete_stage(&self, stage: &DBStage) -> Result<(), StagingManagerError> {
+        let staging_dir = self.stage_directory(stage.id);
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)?;
+        }
+        stage.delete_stage(&self.site.database).await?;
+        debug!(?stage.id, "Stage deleted");
+        Ok(())
+    }
+    pub fn start_cleaner(site: NitroRepo) -> Option<JoinHandle<()>> {
+        let how_often = site.staging_config.cleanup_interval();
+        debug!("Starting Stage Cleaner with interval: {:?}", how_often);
+        let result = tokio::spawn(StagingManager::cleaner_task(site, how_often));
+        Some(result)
+    }
+    pub async fn cleaner_task(site: NitroRepo, how_often: std::time::Duration) {
+        loop {
+            let span = span!(
+                Level::INFO,
+                "Stage Cleaner",
+                stages.removed = Empty,
+                stage.cleaner.error = Empty
+            );
+            let sleep_for = match StagingManager::clean_expired(&site).await {
+                Ok(value) => {
+                    info!(parent: &span, "Cleaned {} stages", value);
+                    span.record("stages.removed", value);
+                    how_often
+                }
+                Err(err) => {
+                    error!(parent: &span, "Failed to clean stages: {:?}", err);
+                    span.record("stage.cleaner.error", display(err));
+                    how_often / 2
+                }
+            };
+            tokio::time::sleep(sleep_for).await
+        }
+    }
+    /// Deletes open stages older than [StagingConfig::time_till_cleanup].
+    ///
+    /// Directories inside of the staging directory that no longer have a stage are also removed.
+    pub async fn clean_expired(site: &NitroRepo) -> Result<usize, StagingManagerError> {
+        let config = &site.staging_config;
+        let time_till_cleanup = config.time_till_cleanup();
+        let cutoff = Local::now().fixed_offset() - time_till_cleanup;
+        let stages = DBStage::get_open_stages_created_before(cutoff, &site.database).await?;
+        for stage in &stages {
+            debug!(?stage.id, ?stage.repository_id, "Removing expired stage");
+            let stage_directory = config.stage_directory(stage.id);
+            if stage_directory.exists() {
+                std::fs::remove_dir_all(stage_directory)?;
+            }
+            stage.delete_stage(&site.database).await?;
+        }
+        if !config.staging_dir.exists() {
+            return Ok(stages.len());
+        }
+        let cutoff = std::time::SystemTime::now() - time_till_cleanup;
+        for entry in std::fs::read_dir(&config.staging_dir)? {
+            let entry = entry?;
+            let Ok(stage_id) = Uuid::parse_str(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            let modified = entry.metadata()?.modified()?;
+            if modified > cutoff || DBStage::does_stage_exist(stage_id, &site.database).await? {
+                continue;
+            }
+            warn!(?stage_id, "Removing staging directory without a stage");
+            std::fs::remove_dir_all(entry.path())?;
+        }
+        Ok(stages.len())
+    }
 }
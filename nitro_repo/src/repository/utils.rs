@@ -1,13 +1,46 @@
+use std::sync::LazyLock;
+
+use http::{
+    Response, StatusCode,
+    header::{CONTENT_TYPE, HOST},
+    request::Parts,
+};
 use nr_core::{
     database::entities::project::{DBProject, ProjectDBType, versions::DBProjectVersion},
     repository::Visibility,
     user::permissions::{HasPermissions, RepositoryActions},
 };
+use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use super::{Repository, RepositoryHandlerError};
+use super::{RepoResponse, Repository, RepositoryAuthentication, RepositoryHandlerError};
+use crate::app::NitroRepo;
 
+/// Client used for all requests to upstream proxy routes
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .user_agent("Nitro Repo")
+        .build()
+        .expect("Failed to build HTTP Client")
+});
+/// Returns the shared HTTP client. Cloning it reuses the same connection pool
+pub fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.clone()
+}
+/// Serializes `value` into a JSON response with the given status
+pub fn json_response<E: From<serde_json::Error>>(
+    status: StatusCode,
+    value: &impl Serialize,
+) -> Result<RepoResponse, E> {
+    let body = serde_json::to_string(value)?;
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+        .into())
+}
 pub async fn can_read_repository<A: HasPermissions>(
     auth: &A,
     visibility: Visibility,
@@ -21,7 +54,84 @@ pub async fn can_read_repository<A: HasPermissions>(
             .await?),
     }
 }
+/// The URL of Nitro Repo. Such as `https://repo.example.com`
+///
+/// If the app url is not configured the Host header of the request is used
+pub fn site_base_url(site: &NitroRepo, parts: &Parts) -> String {
+    let (app_url, is_https) = {
+        let instance = site.instance.lock();
+        (instance.app_url.clone(), instance.is_https)
+    };
+    let scheme = if is_https { "https" } else { "http" };
+    if app_url.contains("://") {
+        app_url.trim_end_matches('/').to_owned()
+    } else if !app_url.is_empty() {
+        format!("{}://{}", scheme, app_url.trim_end_matches('/'))
+    } else {
+        let host = parts
+            .headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or("localhost");
+        format!("{}://{}", scheme, host)
+    }
+}
+/// The URL clients use to reach the repository. `{app_url}/repositories/{storage}/{repository}`
+pub fn registry_url(
+    site: &NitroRepo,
+    parts: &Parts,
+    storage_name: &str,
+    repository_name: &str,
+) -> String {
+    format!(
+        "{}/repositories/{}/{}",
+        site_base_url(site, parts),
+        storage_name,
+        repository_name
+    )
+}
 pub trait RepositoryExt: Repository {
+    /// Checks if the user has the correct permissions to read the repository
+    ///
+    /// If not authenticated at all, it will return a `WWW-Authenticate` header with the provided challenge.
+    /// Clients will then retry with their credentials instead of reporting a generic error
+    async fn check_read(
+        &self,
+        authentication: &RepositoryAuthentication,
+        challenge: &str,
+    ) -> Result<Option<RepoResponse>, RepositoryHandlerError> {
+        if self.visibility().is_private() {
+            if authentication.is_no_identification() {
+                return Ok(Some(RepoResponse::www_authenticate(challenge)));
+            } else if !(authentication
+                .has_action(RepositoryActions::Read, self.id(), self.site().as_ref())
+                .await?)
+            {
+                return Ok(Some(RepoResponse::forbidden()));
+            }
+        }
+        Ok(None)
+    }
+    /// The `Basic` challenge with the name of the repository as the realm
+    fn basic_challenge(&self) -> String {
+        format!(r#"Basic realm="{}""#, self.name())
+    }
+    /// Checks if the user can list the contents of the repository
+    ///
+    /// If the repository is hidden the user must have the read permission
+    async fn indexing_check(
+        &self,
+        authentication: &RepositoryAuthentication,
+    ) -> Result<Option<RepoResponse>, RepositoryHandlerError> {
+        if self.visibility().is_hidden()
+            && !authentication
+                .has_action(RepositoryActions::Read, self.id(), self.site().as_ref())
+                .await?
+        {
+            return Ok(Some(RepoResponse::indexing_not_allowed()));
+        }
+        Ok(None)
+    }
     async fn get_project_from_key(
         &self,
         project_key: &str,
@@ -41,7 +41,14 @@ impl TryFrom<String> for AuthorizationHeader {
     #[instrument(skip(value), name = "AuthorizationHeader::try_from")]
     fn try_from(value: String) -> Result<Self, Self::Error> {
         let parts: Vec<&str> = value.split(' ').collect();
-
+        // Cargo sends the token without a scheme
+        if let [token] = parts.as_slice() {
+            if !token.is_empty() {
+                return Ok(AuthorizationHeader::Bearer {
+                    token: (*token).to_owned(),
+                });
+            }
+        }
         if parts.len() != 2 {
             return Err(BadRequestErrors::InvalidAuthorizationHeader(
                 InvalidAuthorizationHeader::InvalidFormat,
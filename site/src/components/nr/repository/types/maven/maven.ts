@@ -26,18 +26,55 @@ export const MavenFrontendDefinition = {
     },
   ],
 } as FrontendRepositoryType;
+export type ProxyAuthentication =
+  | {
+      type: "Basic";
+      config: { username: string; password: string };
+    }
+  | {
+      type: "Bearer";
+      config: { token: string };
+    };
+export interface ProxyCredentials {
+  authentication?: ProxyAuthentication;
+  headers: Record<string, string>;
+}
 export interface MavenProxyRoute {
   url: string;
   name?: string;
+  credentials?: ProxyCredentials;
+  include?: string[];
+  exclude?: string[];
+}
+export interface MavenProxyCacheConfig {
+  release_ttl?: number;
+  snapshot_ttl?: number;
+  metadata_ttl?: number;
+  not_found_ttl?: number;
 }
 export interface MavenProxyConfigType {
   routes: MavenProxyRoute[];
+  cache?: MavenProxyCacheConfig;
+  route_affinity?: {
+    enabled: boolean;
+    max_entries: number;
+    persist: boolean;
+  };
+  checksum_policy?: "Strict" | "Warn" | "Off";
 }
 export function defaultProxy(): MavenProxyConfigType {
   return {
     routes: [],
   };
 }
+export interface MavenGroupConfigType {
+  members: string[];
+}
+export function defaultGroup(): MavenGroupConfigType {
+  return {
+    members: [],
+  };
+}
 export type MavenConfigType =
   | {
       type: "Hosted";
@@ -45,4 +82,8 @@ export type MavenConfigType =
   | {
       type: "Proxy";
       config: MavenProxyConfigType;
+    }
+  | {
+      type: "Group";
+      config: MavenGroupConfigType;
     };
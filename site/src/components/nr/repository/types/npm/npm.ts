@@ -1,5 +1,6 @@
 import { NpmIcon } from "vue3-simple-icons";
 import NPMProjectHelper from "./NPMProjectHelper.vue";
+import type { ProxyCredentials } from "../maven/maven";
 export const MavenFrontendDefinition = {
   name: "npm",
   properName: "npm",
@@ -16,18 +17,34 @@ export const MavenFrontendDefinition = {
     },
   ],
 };
-export interface MavenProxyRoute {
+export interface NPMProxyRoute {
   url: string;
   name?: string;
+  credentials?: ProxyCredentials;
 }
 export interface NPMProxyConfigType {
-  routes: MavenProxyRoute[];
+  routes: NPMProxyRoute[];
+  packument_ttl?: number;
 }
 export function defaultProxy(): NPMProxyConfigType {
   return {
     routes: [],
   };
 }
+export interface NPMGroupScope {
+  scope: string;
+  members: string[];
+}
+export interface NPMGroupConfigType {
+  members: string[];
+  scopes: NPMGroupScope[];
+}
+export function defaultGroup(): NPMGroupConfigType {
+  return {
+    members: [],
+    scopes: [],
+  };
+}
 export type NPMConfigType =
   | {
       type: "Hosted";
@@ -35,4 +52,8 @@ export type NPMConfigType =
   | {
       type: "Proxy";
       config: NPMProxyConfigType;
+    }
+  | {
+      type: "Group";
+      config: NPMGroupConfigType;
     };
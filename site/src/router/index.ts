@@ -3,6 +3,7 @@ import HomeView from "../views/HomeView.vue";
 import BrowseView from "@/views/BrowseView.vue";
 import LoginView from "@/views/LoginView.vue";
 import LogoutView from "@/views/LogoutView.vue";
+import NPMWebLoginView from "@/views/NPMWebLoginView.vue";
 
 import RepositoriesView from "@/views/RepositoriesView.vue";
 import type { Component } from "vue";
@@ -48,6 +49,14 @@ const routes = [
     name: "logout",
     component: LogoutView,
   },
+  {
+    path: "/npm/login/:repositoryId/:sessionId",
+    name: "npmWebLogin",
+    component: NPMWebLoginView,
+    meta: {
+      requiresAuth: true,
+    },
+  },
   {
     path: "/page/repositories",
     name: "repositories",